@@ -0,0 +1,115 @@
+// Adalight frames, as Prismatik, Hyperion and HyperHDR stream them
+
+use crate::host::StreamEvent;
+use crate::pio_programs::Rgb;
+
+/// What every frame starts with, before the LED count and its checksum
+pub const ADA_MAGIC: &[u8; 3] = b"Ada";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    /// Matched this many bytes of the magic
+    Magic(usize),
+    CountHigh,
+    CountLow(u8),
+    Checksum(u16),
+    /// Taking in pixel bytes, this many to go
+    Pixels(usize),
+}
+
+/// Picks Adalight frames out of a serial stream
+///
+/// A frame is `Ada`, the LED count less one as a big-endian 16-bit
+/// number, a checksum byte (the two count bytes XORed with 0x55), then
+/// three bytes of RGB for each LED. Frames for more LEDs than `N` have the
+/// rest dropped; shorter ones only set the first pixels.
+///
+/// The magic can turn up in the middle of anything, so [`push`](Self::push)
+/// only claims bytes from the count on: the three magic bytes also reach
+/// whatever else reads the stream, which should forget them once a frame
+/// starts.
+///
+/// ```ignore
+/// match adalight.push(byte) {
+///     StreamEvent::Pass => { /* not part of a frame */ }
+///     StreamEvent::Taken => {}
+///     StreamEvent::Frame(pixels) => host_frame.copy_from(pixels),
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct AdalightReader<const N: usize> {
+    state: State,
+    pixels: [Rgb; N],
+    /// Bytes of the frame so far
+    received: usize,
+    /// RGB of the pixel being received
+    partial: [u8; 3],
+}
+
+impl<const N: usize> AdalightReader<N> {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Magic(0),
+            pixels: [Rgb::BLACK; N],
+            received: 0,
+            partial: [0; 3],
+        }
+    }
+
+    /// Whether the magic has been seen and the rest of a frame is coming
+    pub fn in_frame(&self) -> bool {
+        !matches!(self.state, State::Magic(_))
+    }
+
+    pub fn push(&mut self, byte: u8) -> StreamEvent<'_> {
+        self.state = match self.state {
+            State::Magic(matched) => {
+                let matched = match byte {
+                    _ if byte == ADA_MAGIC[matched] => matched + 1,
+                    _ if byte == ADA_MAGIC[0] => 1,
+                    _ => 0,
+                };
+                self.state = if matched == ADA_MAGIC.len() {
+                    State::CountHigh
+                } else {
+                    State::Magic(matched)
+                };
+                return StreamEvent::Pass;
+            }
+            State::CountHigh => State::CountLow(byte),
+            State::CountLow(high) => State::Checksum(u16::from_be_bytes([high, byte])),
+            State::Checksum(count) => {
+                let [high, low] = count.to_be_bytes();
+                if byte != high ^ low ^ 0x55 {
+                    self.state = State::Magic(0);
+                    return StreamEvent::Pass;
+                }
+                self.received = 0;
+                State::Pixels((count as usize + 1) * 3)
+            }
+            State::Pixels(left) => {
+                self.partial[self.received % 3] = byte;
+                if self.received % 3 == 2 {
+                    if let Some(pixel) = self.pixels.get_mut(self.received / 3) {
+                        let [r, g, b] = self.partial;
+                        *pixel = Rgb::new(r, g, b);
+                    }
+                }
+                self.received += 1;
+                if left == 1 {
+                    self.state = State::Magic(0);
+                    let count = (self.received / 3).min(N);
+                    return StreamEvent::Frame(&self.pixels[..count]);
+                }
+                State::Pixels(left - 1)
+            }
+        };
+        StreamEvent::Taken
+    }
+}
+
+impl<const N: usize> Default for AdalightReader<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,157 @@
+// Ambient light sensing for automatic brightness
+
+use embedded_hal::adc::{Channel, OneShot};
+use embedded_hal::blocking::i2c::{Read, Write};
+
+/// How often [`AutoBrightness`] takes a reading
+pub const SAMPLE_MS: u32 = 50;
+
+/// Turns ambient light readings into a brightness level
+///
+/// Readings are averaged over a couple of seconds, so a passing shadow or a
+/// hand over the sensor doesn't make the strip pump. At or below `dark` the
+/// level is `min_level`, at or above `bright` it's full, and in between it
+/// goes linearly. Readings can be in any unit that rises with the light:
+/// ADC counts from a [`Photoresistor`], or lux from a [`Bh1750`].
+///
+/// ```ignore
+/// let mut auto = AutoBrightness::new(200, 3000);
+/// if auto.due(now_ms) {
+///     if let Some(light) = ldr.read(&mut adc) {
+///         engine.set_dimmer(auto.update(light));
+///     }
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct AutoBrightness {
+    pub dark: u16,
+    pub bright: u16,
+    /// Floor, so the strip stays visible in a dark room
+    pub min_level: u8,
+    /// Running average with 5 fraction bits
+    filtered: Option<u32>,
+    next_ms: u32,
+}
+
+impl AutoBrightness {
+    pub const fn new(dark: u16, bright: u16) -> Self {
+        Self {
+            dark,
+            bright,
+            min_level: 24,
+            filtered: None,
+            next_ms: 0,
+        }
+    }
+
+    pub fn min_level(mut self, level: u8) -> Self {
+        self.min_level = level;
+        self
+    }
+
+    /// Whether it's time for another reading
+    pub fn due(&mut self, now_ms: u32) -> bool {
+        if (now_ms.wrapping_sub(self.next_ms) as i32) < 0 {
+            return false;
+        }
+        self.next_ms = now_ms.wrapping_add(SAMPLE_MS);
+        true
+    }
+
+    /// Take in a reading; returns the level to dim to
+    pub fn update(&mut self, reading: u16) -> u8 {
+        let sample = (reading as u32) << 5;
+        let filtered = match self.filtered {
+            Some(f) => f - f / 32 + sample / 32,
+            // First reading goes straight through
+            None => sample,
+        };
+        self.filtered = Some(filtered);
+        self.level()
+    }
+
+    /// Level for the light seen so far, full before any readings
+    pub fn level(&self) -> u8 {
+        let Some(filtered) = self.filtered else {
+            return 255;
+        };
+        let light = filtered >> 5;
+        let (dark, bright) = (self.dark as u32, self.bright as u32);
+        if light <= dark {
+            return self.min_level;
+        }
+        if light >= bright {
+            return 255;
+        }
+        let span = (255 - self.min_level) as u32;
+        self.min_level + ((light - dark) * span / (bright - dark)) as u8
+    }
+}
+
+/// A photoresistor (LDR) in a divider on an ADC pin
+///
+/// Wire the LDR from 3V3 to the pin and a 10 kΩ resistor from the pin to
+/// AGND, so the reading rises with the light.
+pub struct Photoresistor<PIN> {
+    pin: PIN,
+}
+
+impl<PIN> Photoresistor<PIN> {
+    pub fn new(pin: PIN) -> Self {
+        Self { pin }
+    }
+
+    /// 12-bit reading, brighter is higher
+    pub fn read<ADC, A>(&mut self, adc: &mut A) -> Option<u16>
+    where
+        PIN: Channel<ADC>,
+        A: OneShot<ADC, u16, PIN>,
+    {
+        nb::block!(adc.read(&mut self.pin))
+            .ok()
+            .map(|v| v.min(4095))
+    }
+
+    pub fn free(self) -> PIN {
+        self.pin
+    }
+}
+
+/// BH1750 address with its ADDR pin low
+pub const BH1750_ADDR_LOW: u8 = 0x23;
+/// BH1750 address with its ADDR pin high
+pub const BH1750_ADDR_HIGH: u8 = 0x5C;
+
+const POWER_ON: u8 = 0x01;
+/// Continuous 1 lx resolution mode, a new reading every 120 ms
+const CONTINUOUS_HIGH_RES: u8 = 0x10;
+
+/// BH1750 digital light sensor on I2C, reading in lux
+pub struct Bh1750<I2C> {
+    i2c: I2C,
+    addr: u8,
+}
+
+impl<I2C, E> Bh1750<I2C>
+where
+    I2C: Read<Error = E> + Write<Error = E>,
+{
+    /// Power the sensor up and start continuous measurement
+    pub fn new(mut i2c: I2C, addr: u8) -> Result<Self, E> {
+        i2c.write(addr, &[POWER_ON])?;
+        i2c.write(addr, &[CONTINUOUS_HIGH_RES])?;
+        Ok(Self { i2c, addr })
+    }
+
+    /// Latest measurement in lux, saturating at 65535
+    pub fn read_lux(&mut self) -> Result<u16, E> {
+        let mut raw = [0; 2];
+        self.i2c.read(self.addr, &mut raw)?;
+        // The sensor counts 1.2 per lux
+        Ok((u16::from_be_bytes(raw) as u32 * 5 / 6) as u16)
+    }
+
+    pub fn free(self) -> I2C {
+        self.i2c
+    }
+}
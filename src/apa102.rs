@@ -0,0 +1,175 @@
+// APA102 / SK9822 (DotStar) driver: clocked output from a PIO state machine over DMA
+
+use crate::platform::hal::{
+    dma::SingleChannel,
+    gpio::{Function, Pin, PinId, PullType, ValidFunction},
+    pio::{
+        PIOBuilder, PIOExt, PinDir, Running, ShiftDirection, StateMachine, StateMachineIndex, Tx,
+        UninitStateMachine, PIO,
+    },
+};
+
+use crate::driver::{fixed_point_divisor, into_data_pin, DataPin, DmaStream, Frame};
+use crate::output::LedOutput;
+use crate::pio_programs::{self, Rgb};
+
+/// Default clock rate; APA102 copes with much faster, long strips may not
+pub const APA102_CLOCK_HZ: u32 = 4_000_000;
+
+/// Maximum of the 5-bit global brightness field
+pub const MAX_BRIGHTNESS: u8 = 31;
+
+/// Frame buffer words needed for `leds` pixels
+///
+/// A zero start frame, one word per LED, a zero frame for SK9822 to latch,
+/// then enough ones to push the data half a clock per LED down the strip.
+pub const fn frame_words(leds: usize) -> usize {
+    1 + leds + 1 + leds.div_ceil(64)
+}
+
+/// Configures a state machine for APA102 output on a clock and data pin
+///
+/// ```ignore
+/// let frame = cortex_m::singleton!(: [u32; frame_words(8)] = [0; frame_words(8)]).unwrap();
+/// let strip = Apa102Builder::new(&mut pio, sm0, pins.gpio14, pins.gpio15, sys_clk_hz)
+///     .build(dma.ch0, frame);
+/// ```
+pub struct Apa102Builder<'a, P: PIOExt, SM: StateMachineIndex> {
+    pio: &'a mut PIO<P>,
+    sm: UninitStateMachine<(P, SM)>,
+    clock: DataPin<P>,
+    data: DataPin<P>,
+    sys_clk_hz: u32,
+    clock_hz: u32,
+}
+
+impl<'a, P: PIOExt, SM: StateMachineIndex> Apa102Builder<'a, P, SM> {
+    pub fn new<CI, CF, CP, DI, DF, DP>(
+        pio: &'a mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock: Pin<CI, CF, CP>,
+        data: Pin<DI, DF, DP>,
+        sys_clk_hz: u32,
+    ) -> Self
+    where
+        CI: PinId + ValidFunction<P::PinFunction>,
+        CF: Function,
+        CP: PullType,
+        DI: PinId + ValidFunction<P::PinFunction>,
+        DF: Function,
+        DP: PullType,
+    {
+        Self {
+            pio,
+            sm,
+            clock: into_data_pin::<P, _, _, _>(clock),
+            data: into_data_pin::<P, _, _, _>(data),
+            sys_clk_hz,
+            clock_hz: APA102_CLOCK_HZ,
+        }
+    }
+
+    /// Change the clock rate from the 4 MHz default
+    pub fn clock_hz(mut self, clock_hz: u32) -> Self {
+        self.clock_hz = clock_hz;
+        self
+    }
+
+    /// Finish with DMA output from the static `frame` buffer on channel `ch`
+    ///
+    /// Size the buffer with [`frame_words`].
+    pub fn build<CH: SingleChannel, const W: usize>(
+        self,
+        ch: CH,
+        frame: Frame<W>,
+    ) -> Apa102<P, SM, CH, W> {
+        let clock_id = self.clock.id().num;
+        let data_id = self.data.id().num;
+        let installed = self.pio.install(&pio_programs::apa102()).unwrap();
+        let (div_int, div_frac) = fixed_point_divisor(self.sys_clk_hz, self.clock_hz * 2);
+
+        let (mut sm, _, tx) = PIOBuilder::from_program(installed)
+            .side_set_pin_base(clock_id)
+            .out_pins(data_id, 1)
+            .out_shift_direction(ShiftDirection::Left)
+            .autopull(true)
+            .pull_threshold(32)
+            .clock_divisor_fixed_point(div_int, div_frac)
+            .build(self.sm);
+        sm.set_pindirs([(clock_id, PinDir::Output), (data_id, PinDir::Output)]);
+
+        let max_leds = (0..W).rev().find(|&n| frame_words(n) <= W).unwrap_or(0);
+        Apa102 {
+            _sm: sm.start(),
+            _clock: self.clock,
+            _data: self.data,
+            max_leds,
+            brightness: MAX_BRIGHTNESS,
+            stream: DmaStream::new(ch, frame, tx),
+        }
+    }
+}
+
+/// APA102 / SK9822 strip driven by one PIO state machine, fed by one DMA channel
+pub struct Apa102<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const W: usize> {
+    _sm: StateMachine<(P, SM), Running>,
+    _clock: DataPin<P>,
+    _data: DataPin<P>,
+    max_leds: usize,
+    brightness: u8,
+    stream: DmaStream<CH, Tx<(P, SM)>, W>,
+}
+
+impl<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const W: usize> Apa102<P, SM, CH, W> {
+    /// Set the 5-bit hardware brightness sent with every pixel (0-31)
+    ///
+    /// This dims through the LEDs' own current control, so it doesn't cost
+    /// color resolution the way scaling the RGB values does.
+    pub fn set_brightness(&mut self, level: u8) {
+        self.brightness = level.min(MAX_BRIGHTNESS);
+    }
+
+    /// Whether a frame is still being clocked out
+    pub fn is_busy(&self) -> bool {
+        self.stream.is_busy()
+    }
+
+    /// Start sending `pixels` to the strip
+    ///
+    /// Waits for the previous frame first. Pixels that don't fit the frame
+    /// buffer are ignored.
+    pub fn write_frame(&mut self, pixels: &[Rgb]) {
+        self.write_pixels(pixels.iter().copied());
+    }
+
+    fn write_pixels(&mut self, pixels: impl Iterator<Item = Rgb>) {
+        let header = 0xE0 | self.brightness as u32;
+        let max_leds = self.max_leds;
+        self.stream.send(0, |frame| {
+            // Start frame
+            frame[0] = 0;
+            let mut leds = 0;
+            for (word, led) in frame[1..=max_leds].iter_mut().zip(pixels) {
+                *word = header << 24 | (led.b as u32) << 16 | (led.g as u32) << 8 | led.r as u32;
+                leds += 1;
+            }
+            // Black past the pixels given, as all ones would be full white
+            frame[leds + 1..=max_leds].fill(0xE000_0000);
+            // SK9822 latch frame, then end frame ones
+            frame[max_leds + 1] = 0;
+            frame[max_leds + 2..].fill(u32::MAX);
+        });
+    }
+}
+
+impl<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const W: usize> LedOutput
+    for Apa102<P, SM, CH, W>
+{
+    fn write_pixels(&mut self, pixels: impl Iterator<Item = Rgb>) {
+        self.write_pixels(pixels);
+    }
+
+    fn is_busy(&self) -> bool {
+        self.is_busy()
+    }
+}
@@ -0,0 +1,200 @@
+// Art-Net node: DMX over UDP, and the poll replies that make it show up in
+// consoles and Resolume
+
+use crate::dmx::{UniverseMap, DMX_CHANNELS, MAX_UNIVERSES};
+use crate::host::HostFrame;
+
+/// UDP port Art-Net is sent to; poll replies go back to it, broadcast
+pub const ARTNET_PORT: u16 = 6454;
+
+/// Senders repeat unchanged data at least this often, so a stream this
+/// quiet has stopped
+pub const ARTNET_TIMEOUT_MS: u32 = 4000;
+
+/// Length of an [`ArtNetNode::poll_reply`]
+pub const POLL_REPLY_LEN: usize = 239;
+
+const ARTNET_ID: &[u8; 8] = b"Art-Net\0";
+const PROTOCOL_VERSION: u16 = 14;
+const OP_POLL: u16 = 0x2000;
+const OP_POLL_REPLY: u16 = 0x2100;
+const OP_DMX: u16 = 0x5000;
+
+/// What [`ArtNetNode::receive`] made of a datagram
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum ArtNetEvent {
+    /// Not Art-Net, or DMX for universes that aren't the strip's
+    Ignored,
+    /// Pixels were written
+    Data,
+    /// A controller is looking for nodes: broadcast a
+    /// [`poll_reply`](ArtNetNode::poll_reply) for each port
+    Poll,
+}
+
+/// An Art-Net output node, one port for each universe the strip spans
+///
+/// Universes are Art-Net 4's 15-bit port addresses (net, sub-net and
+/// universe in one number, from 0), laid along the strip by `map`. Each
+/// port gets its own poll reply, told apart by the bind index, so a strip
+/// can span any number of universes. The latest DMX for a universe wins:
+/// there's no HTP or LTP merging of two controllers.
+///
+/// ```ignore
+/// match artnet.receive(datagram, now_ms, &host_frame) {
+///     ArtNetEvent::Data => current = host_effect,
+///     ArtNetEvent::Poll => {
+///         for port in 0..artnet.ports(NUM_LEDS) {
+///             broadcast(artnet.poll_reply(port, &mut reply));
+///         }
+///     }
+///     ArtNetEvent::Ignored => {}
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct ArtNetNode {
+    pub map: UniverseMap,
+    /// Shown in the console's node list, up to 17 bytes
+    pub short_name: &'static str,
+    /// Up to 63 bytes
+    pub long_name: &'static str,
+    /// The node's address, to be filled in once the network is up
+    pub ip: [u8; 4],
+    pub mac: [u8; 6],
+    /// Polls answered, for the node report
+    polls: u16,
+    last_ms: Option<u32>,
+    sequences: [Option<u8>; MAX_UNIVERSES],
+}
+
+impl ArtNetNode {
+    pub const fn new(map: UniverseMap) -> Self {
+        Self {
+            map,
+            short_name: "pico-display",
+            long_name: "pico-display WS2812 controller",
+            ip: [0; 4],
+            mac: [0; 6],
+            polls: 0,
+            last_ms: None,
+            sequences: [None; MAX_UNIVERSES],
+        }
+    }
+
+    pub fn short_name(mut self, name: &'static str) -> Self {
+        self.short_name = name;
+        self
+    }
+
+    pub fn long_name(mut self, name: &'static str) -> Self {
+        self.long_name = name;
+        self
+    }
+
+    /// Ports to report for a strip `len` pixels long, one per universe
+    pub fn ports(&self, len: usize) -> usize {
+        len.div_ceil(self.map.pixels_per_universe as usize)
+    }
+
+    /// Whether DMX has come in lately
+    pub fn is_streaming(&self, now_ms: u32) -> bool {
+        self.last_ms
+            .is_some_and(|ms| now_ms.wrapping_sub(ms) <= ARTNET_TIMEOUT_MS)
+    }
+
+    /// Take one UDP payload
+    pub fn receive<const N: usize>(
+        &mut self,
+        datagram: &[u8],
+        now_ms: u32,
+        frame: &HostFrame<N>,
+    ) -> ArtNetEvent {
+        if datagram.len() < 12
+            || &datagram[..8] != ARTNET_ID
+            || u16::from_be_bytes([datagram[10], datagram[11]]) < PROTOCOL_VERSION
+        {
+            return ArtNetEvent::Ignored;
+        }
+        match u16::from_le_bytes([datagram[8], datagram[9]]) {
+            OP_POLL => {
+                self.polls = self.polls.wrapping_add(1);
+                ArtNetEvent::Poll
+            }
+            OP_DMX if datagram.len() >= 18 => {
+                let sequence = datagram[12];
+                let universe = u16::from_le_bytes([datagram[14], datagram[15]]) & 0x7FFF;
+                let len = (u16::from_be_bytes([datagram[16], datagram[17]]) as usize)
+                    .min(DMX_CHANNELS)
+                    .min(datagram.len() - 18);
+                let Some(index) = self.map.index(universe, frame.len()) else {
+                    return ArtNetEvent::Ignored;
+                };
+                // Sequence 0 means the sender doesn't number its packets
+                if let Some(last) = self.sequences.get_mut(index).filter(|_| sequence != 0) {
+                    let behind = last.map(|last| sequence.wrapping_sub(last) as i8);
+                    if behind.is_some_and(|diff| diff <= 0 && diff > -20) {
+                        return ArtNetEvent::Ignored;
+                    }
+                    *last = Some(sequence);
+                }
+                self.last_ms = Some(now_ms);
+                self.map.write(universe, &datagram[18..18 + len], frame);
+                ArtNetEvent::Data
+            }
+            _ => ArtNetEvent::Ignored,
+        }
+    }
+
+    /// The ArtPollReply describing `port`, counting from 0
+    pub fn poll_reply<'a>(&self, port: usize, out: &'a mut [u8; POLL_REPLY_LEN]) -> &'a [u8] {
+        out.fill(0);
+        let universe = self.map.first_universe.wrapping_add(port as u16) & 0x7FFF;
+        out[..8].copy_from_slice(ARTNET_ID);
+        out[8..10].copy_from_slice(&OP_POLL_REPLY.to_le_bytes());
+        out[10..14].copy_from_slice(&self.ip);
+        out[14..16].copy_from_slice(&ARTNET_PORT.to_le_bytes());
+        // Firmware version
+        out[16..18].copy_from_slice(&1u16.to_be_bytes());
+        // Net and sub-net: port address bits 14-8 and 7-4
+        out[18] = (universe >> 8) as u8;
+        out[19] = (universe >> 4) as u8 & 0x0F;
+        // OEM code unknown, ESTA code from the prototyping range
+        out[20..22].copy_from_slice(&0x00FFu16.to_be_bytes());
+        out[24..26].copy_from_slice(&0x7FF0u16.to_le_bytes());
+        // Indicators normal, addresses set by the firmware
+        out[23] = 0xD0;
+        copy_name(&mut out[26..44], self.short_name);
+        copy_name(&mut out[44..108], self.long_name);
+        write_report(&mut out[108..172], self.polls);
+        // One output port, carrying DMX512, transmitting
+        out[172..174].copy_from_slice(&1u16.to_be_bytes());
+        out[174] = 0x80;
+        out[182] = 0x80;
+        out[190] = universe as u8 & 0x0F;
+        out[201..207].copy_from_slice(&self.mac);
+        out[207..211].copy_from_slice(&self.ip);
+        out[211] = (port + 1) as u8;
+        // Takes 15-bit port addresses
+        out[212] = 0x08;
+        out
+    }
+}
+
+/// A name, cut short to leave its terminating zero
+fn copy_name(field: &mut [u8], name: &str) {
+    let len = name.len().min(field.len() - 1);
+    field[..len].copy_from_slice(&name.as_bytes()[..len]);
+}
+
+/// The node report, "#0001 [polls] Power on", status 0001 being all well
+fn write_report(field: &mut [u8], polls: u16) {
+    field[..7].copy_from_slice(b"#0001 [");
+    let mut digits = [b'0'; 4];
+    let mut n = polls % 10000;
+    for digit in digits.iter_mut().rev() {
+        *digit = b'0' + (n % 10) as u8;
+        n /= 10;
+    }
+    field[7..11].copy_from_slice(&digits);
+    copy_name(&mut field[11..], "] Power on");
+}
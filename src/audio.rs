@@ -0,0 +1,302 @@
+// Audio input for sound-reactive effects: microphones and level tracking
+
+use core::cell::Cell;
+
+use embedded_hal::adc::{Channel, OneShot};
+use embedded_hal::blocking::delay::DelayUs;
+
+use crate::fft::{fft, hann_window, log2_8_8, magnitude};
+
+/// Frequency bands a [`SpectrumAnalyzer`] groups its bins into
+pub const BANDS: usize = 16;
+
+/// Quietest signal treated as full scale, so the level doesn't amplify
+/// silence into noise
+const MIN_RANGE: u16 = 1024;
+
+/// Peak-following envelope of an audio signal, with automatic gain
+///
+/// Feed it signed samples with `push` as they come in, then call `update`
+/// once per batch. The envelope jumps up to each new peak and falls back
+/// over `release_ms`; the level is the envelope relative to the loudest
+/// recently heard, which forgets over a few seconds, so quiet rooms and
+/// loud parties both use the whole range.
+#[derive(Copy, Clone, Debug)]
+pub struct Envelope {
+    /// Envelope below this is treated as silence
+    pub noise_floor: u16,
+    /// Milliseconds for the envelope to fall most of the way back
+    pub release_ms: u16,
+    /// Running mean of the input, 8 fraction bits, taken out as DC bias
+    dc: i32,
+    batch_peak: u16,
+    envelope: u16,
+    range: u16,
+    last_ms: Option<u32>,
+    level: u8,
+}
+
+impl Envelope {
+    pub const fn new() -> Self {
+        Self {
+            noise_floor: 256,
+            release_ms: 250,
+            dc: 0,
+            batch_peak: 0,
+            envelope: 0,
+            range: MIN_RANGE,
+            last_ms: None,
+            level: 0,
+        }
+    }
+
+    pub fn noise_floor(mut self, floor: u16) -> Self {
+        self.noise_floor = floor;
+        self
+    }
+
+    pub fn release_ms(mut self, ms: u16) -> Self {
+        self.release_ms = ms;
+        self
+    }
+
+    /// Take in one sample
+    pub fn push(&mut self, sample: i16) {
+        let x = (sample as i32) << 8;
+        self.dc += (x - self.dc) >> 10;
+        let ac = ((x - self.dc) >> 8).unsigned_abs().min(u16::MAX as u32) as u16;
+        self.batch_peak = self.batch_peak.max(ac);
+    }
+
+    /// Fold in the samples pushed since last time; returns the level, 0-255
+    pub fn update(&mut self, now_ms: u32) -> u8 {
+        let dt = match self.last_ms.replace(now_ms) {
+            Some(last) => now_ms.wrapping_sub(last),
+            None => 0,
+        };
+        let peak = core::mem::take(&mut self.batch_peak);
+
+        let release = self.release_ms.max(1) as u32;
+        let fall = (self.envelope as u32 * dt.min(release) / release) as u16;
+        self.envelope = (self.envelope - fall).max(peak);
+        // The gain forgets loud moments over about eight seconds
+        let forget = (self.range as u32 * dt.min(8192) / 8192) as u16;
+        self.range = (self.range - forget).max(self.envelope).max(MIN_RANGE);
+
+        let signal = self.envelope.saturating_sub(self.noise_floor) as u32;
+        let span = self.range.saturating_sub(self.noise_floor).max(1) as u32;
+        self.level = (signal * 255 / span).min(255) as u8;
+        self.level
+    }
+
+    /// Level as of the last `update`
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Splits audio into [`BANDS`] frequency bands with an `N`-point FFT
+///
+/// `push` slides new samples into the window, so with half a window per
+/// frame each transform overlaps the last. The lowest bands are one FFT bin
+/// wide and they widen toward the top, roughly following how we hear
+/// pitch. Band levels are logarithmic: `floor` and `ceiling` are the bin
+/// magnitudes, as 8.8 fixed-point base-2 logs, that map to 0 and 255.
+pub struct SpectrumAnalyzer<const N: usize> {
+    pub floor: u16,
+    pub ceiling: u16,
+    window: [i16; N],
+    bands: [u8; BANDS],
+}
+
+impl<const N: usize> SpectrumAnalyzer<N> {
+    pub const fn new() -> Self {
+        Self {
+            floor: 3 << 8,
+            ceiling: 12 << 8,
+            window: [0; N],
+            bands: [0; BANDS],
+        }
+    }
+
+    /// Slide `samples` in at the end of the window
+    pub fn push(&mut self, samples: &[i16]) {
+        let samples = &samples[samples.len().saturating_sub(N)..];
+        self.window.copy_within(samples.len().., 0);
+        self.window[N - samples.len()..].copy_from_slice(samples);
+    }
+
+    /// Transform the window; returns the band levels, 0-255
+    pub fn analyze(&mut self) -> &[u8; BANDS] {
+        let mut re = self.window;
+        let mut im = [0; N];
+        // Take out the microphone's bias, it would swamp the low bins
+        let mean = re.iter().map(|&s| s as i32).sum::<i32>() / N.max(1) as i32;
+        for s in re.iter_mut() {
+            *s = (*s as i32 - mean).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        }
+        hann_window(&mut re);
+        fft(&mut re, &mut im);
+
+        // Bin 0 is what's left of DC, the top half mirrors the bottom
+        let bins = N / 2;
+        let mut start = 1;
+        for (band, level) in self.bands.iter_mut().enumerate() {
+            let b = band + 1;
+            let end = (1 + (bins - 1) * b * b / (BANDS * BANDS))
+                .max(start + 1)
+                .min(bins);
+            let peak = (start..end)
+                .map(|bin| magnitude(re[bin], im[bin]))
+                .max()
+                .unwrap_or(0);
+            let log = log2_8_8(peak as u32).saturating_sub(self.floor) as u32;
+            let span = self.ceiling.saturating_sub(self.floor).max(1) as u32;
+            *level = (log * 255 / span).min(255) as u8;
+            start = end;
+        }
+        &self.bands
+    }
+
+    /// Band levels from the last `analyze`
+    pub fn bands(&self) -> &[u8; BANDS] {
+        &self.bands
+    }
+}
+
+impl<const N: usize> Default for SpectrumAnalyzer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the audio input has heard, shared with the effects drawing it
+///
+/// The main loop owns one and updates it from the microphone; effects hold
+/// a reference and read it while rendering.
+///
+/// ```ignore
+/// let audio = Audio::new();
+/// let mut vu = VuMeter::new(&audio);
+/// audio.set_level(envelope.update(now_ms));
+/// ```
+#[derive(Debug, Default)]
+pub struct Audio {
+    level: Cell<u8>,
+    bands: Cell<[u8; BANDS]>,
+    beats: Cell<u32>,
+    bpm: Cell<u16>,
+}
+
+impl Audio {
+    pub const fn new() -> Self {
+        Self {
+            level: Cell::new(0),
+            bands: Cell::new([0; BANDS]),
+            beats: Cell::new(0),
+            bpm: Cell::new(0),
+        }
+    }
+
+    /// Loudness, 0-255
+    pub fn level(&self) -> u8 {
+        self.level.get()
+    }
+
+    pub fn set_level(&self, level: u8) {
+        self.level.set(level);
+    }
+
+    /// Spectrum band levels, lowest frequencies first
+    pub fn bands(&self) -> [u8; BANDS] {
+        self.bands.get()
+    }
+
+    pub fn set_bands(&self, bands: &[u8; BANDS]) {
+        self.bands.set(*bands);
+    }
+
+    /// Beats heard (or, with [`TapTempo`](crate::beat::TapTempo), tapped)
+    /// so far; effects keep the count they last saw and act when it changes
+    pub fn beats(&self) -> u32 {
+        self.beats.get()
+    }
+
+    /// Count a beat
+    pub fn beat(&self) {
+        self.beats.set(self.beats.get().wrapping_add(1));
+    }
+
+    /// Estimated tempo, 0 until there's been a steady run of beats
+    pub fn bpm(&self) -> u16 {
+        self.bpm.get()
+    }
+
+    pub fn set_bpm(&self, bpm: u16) {
+        self.bpm.set(bpm);
+    }
+}
+
+/// An analog microphone module (MAX4466, MAX9814) on an ADC pin
+///
+/// These put out the audio on a bias of half their supply, so run them from
+/// 3V3. `sample` takes a quick burst of readings, enough to catch the peaks
+/// for a level meter; `capture` reads at a steady rate for a spectrum.
+pub struct AdcMic<PIN> {
+    pin: PIN,
+}
+
+impl<PIN> AdcMic<PIN> {
+    pub fn new(pin: PIN) -> Self {
+        Self { pin }
+    }
+
+    /// Take `count` readings straight into `envelope`
+    pub fn sample<ADC, A>(&mut self, adc: &mut A, envelope: &mut Envelope, count: usize)
+    where
+        PIN: Channel<ADC>,
+        A: OneShot<ADC, u16, PIN>,
+    {
+        for _ in 0..count {
+            if let Ok(raw) = nb::block!(adc.read(&mut self.pin)) {
+                envelope.push(to_sample(raw));
+            }
+        }
+    }
+
+    /// Fill `samples` with readings `period_us` apart
+    ///
+    /// Blocks for the whole buffer; the ADC's own conversion time adds a
+    /// couple of microseconds to each period.
+    pub fn capture<ADC, A, D>(
+        &mut self,
+        adc: &mut A,
+        delay: &mut D,
+        period_us: u32,
+        samples: &mut [i16],
+    ) where
+        PIN: Channel<ADC>,
+        A: OneShot<ADC, u16, PIN>,
+        D: DelayUs<u32>,
+    {
+        for s in samples.iter_mut() {
+            *s = nb::block!(adc.read(&mut self.pin)).map_or(0, to_sample);
+            delay.delay_us(period_us);
+        }
+    }
+
+    pub fn free(self) -> PIN {
+        self.pin
+    }
+}
+
+/// 12 bits centered on mid-scale, stretched to the full i16 range
+fn to_sample(raw: u16) -> i16 {
+    ((raw.min(4095) as i32 - 2048) << 4) as i16
+}
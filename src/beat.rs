@@ -0,0 +1,325 @@
+// Beat detection on the audio, tap tempo, and effects that move in time
+
+use crate::audio::Audio;
+use crate::effects::{Effect, Param, ParamInfo, ParamValue};
+use crate::palette::Palette16;
+use crate::pio_programs::Rgb;
+
+/// Closest two beats can be, 200 BPM
+pub const MIN_BEAT_MS: u32 = 300;
+
+/// Beat intervals are folded by octaves into this range, 80-160 BPM, so
+/// hits on every half or second beat still give the song's tempo
+const FOLD_MS: core::ops::Range<u32> = 375..750;
+
+/// Gaps longer than this start the tempo estimate over
+const LOST_MS: u32 = 2000;
+
+/// Consistent intervals needed before the tempo is reported
+const STEADY_BEATS: u8 = 4;
+
+/// Beat interval [`BeatSync`] assumes before a tempo is known, 120 BPM
+const DEFAULT_INTERVAL_MS: u32 = 500;
+
+/// Gaps between taps averaged into the tapped tempo
+const TAP_INTERVALS: usize = 3;
+
+/// A gap longer than this between taps starts a new run of them
+pub const TAP_TIMEOUT_MS: u32 = 2000;
+
+/// Onset detector with tempo tracking
+///
+/// Feed it the energy of the part of the spectrum the beat lives in (the
+/// bass bands, usually) once a frame. A beat is the energy jumping past
+/// `threshold` sixteenths of its running average, about a second long. The
+/// gaps between beats give the tempo, once a few of them agree.
+///
+/// ```ignore
+/// let bass = analyzer.bands()[..3].iter().copied().max().unwrap_or(0);
+/// if beats.update(bass, now_ms) {
+///     audio.beat();
+///     audio.set_bpm(beats.bpm());
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct BeatDetector {
+    pub threshold: u8,
+    /// Energies below this never count, so room noise doesn't beat
+    pub min_energy: u8,
+    /// Running average energy, 8 fraction bits
+    average: u32,
+    above: bool,
+    last_beat_ms: Option<u32>,
+    interval_ms: u32,
+    steady: u8,
+}
+
+impl BeatDetector {
+    pub const fn new() -> Self {
+        Self {
+            threshold: 24,
+            min_energy: 48,
+            average: 0,
+            above: false,
+            last_beat_ms: None,
+            interval_ms: 0,
+            steady: 0,
+        }
+    }
+
+    pub fn threshold(mut self, sixteenths: u8) -> Self {
+        self.threshold = sixteenths;
+        self
+    }
+
+    /// Take in this frame's energy; true on a beat
+    pub fn update(&mut self, energy: u8, now_ms: u32) -> bool {
+        let e = (energy as u32) << 8;
+        self.average = self.average - self.average / 32 + e / 32;
+        let over = energy >= self.min_energy && e * 16 > self.average * self.threshold as u32;
+        let onset = over && !self.above;
+        self.above = over;
+        if !onset {
+            return false;
+        }
+
+        if let Some(last) = self.last_beat_ms {
+            let gap = now_ms.wrapping_sub(last);
+            if gap < MIN_BEAT_MS {
+                return false;
+            }
+            self.track(gap);
+        }
+        self.last_beat_ms = Some(now_ms);
+        true
+    }
+
+    fn track(&mut self, gap: u32) {
+        if gap > LOST_MS {
+            self.interval_ms = 0;
+            self.steady = 0;
+            return;
+        }
+        let mut gap = gap;
+        while gap < FOLD_MS.start {
+            gap *= 2;
+        }
+        while gap >= FOLD_MS.end {
+            gap /= 2;
+        }
+        if self.interval_ms == 0 {
+            self.interval_ms = gap;
+            return;
+        }
+        // Within an eighth of the estimate counts as agreeing with it
+        if gap.abs_diff(self.interval_ms) <= self.interval_ms / 8 {
+            self.steady = self.steady.saturating_add(1);
+        } else {
+            self.steady = 0;
+        }
+        self.interval_ms = (self.interval_ms * 3 + gap) / 4;
+    }
+
+    /// Estimated tempo, 0 while unsure
+    pub fn bpm(&self) -> u16 {
+        if self.steady < STEADY_BEATS || self.interval_ms == 0 {
+            return 0;
+        }
+        (60_000 / self.interval_ms) as u16
+    }
+}
+
+impl Default for BeatDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A metronome set by tapping along
+///
+/// Each tap is a beat, and the gaps between the last few taps set the
+/// tempo the metronome carries on at. A lone tap, after a pause of more
+/// than [`TAP_TIMEOUT_MS`], keeps the tempo and moves the beat onto the
+/// tap. Poll it every loop and count a beat in [`Audio`] whenever it says
+/// so, and [`BeatSync`] effects follow the taps.
+///
+/// ```ignore
+/// if tap_pressed {
+///     tempo.tap(now_ms);
+/// }
+/// if tempo.poll(now_ms) {
+///     audio.beat();
+///     audio.set_bpm(tempo.bpm());
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TapTempo {
+    last_tap_ms: Option<u32>,
+    intervals: [u32; TAP_INTERVALS],
+    count: usize,
+    /// 0 until two taps have set a tempo
+    interval_ms: u32,
+    next_beat_ms: u32,
+    tapped: bool,
+}
+
+impl TapTempo {
+    pub const fn new() -> Self {
+        Self {
+            last_tap_ms: None,
+            intervals: [0; TAP_INTERVALS],
+            count: 0,
+            interval_ms: 0,
+            next_beat_ms: 0,
+            tapped: false,
+        }
+    }
+
+    /// A tap at `now_ms`; taps closer than [`MIN_BEAT_MS`] are ignored
+    pub fn tap(&mut self, now_ms: u32) {
+        if let Some(last) = self.last_tap_ms {
+            let gap = now_ms.wrapping_sub(last);
+            if gap < MIN_BEAT_MS {
+                return;
+            }
+            if gap <= TAP_TIMEOUT_MS {
+                self.intervals[self.count % TAP_INTERVALS] = gap;
+                self.count += 1;
+                let taken = self.count.min(TAP_INTERVALS);
+                self.interval_ms = self.intervals[..taken].iter().sum::<u32>() / taken as u32;
+            } else {
+                self.count = 0;
+            }
+        }
+        self.last_tap_ms = Some(now_ms);
+        self.next_beat_ms = now_ms.wrapping_add(self.interval_ms);
+        self.tapped = true;
+    }
+
+    /// Whether a beat falls due, on a tap or from the metronome
+    pub fn poll(&mut self, now_ms: u32) -> bool {
+        if core::mem::take(&mut self.tapped) {
+            return true;
+        }
+        if self.interval_ms == 0 || (now_ms.wrapping_sub(self.next_beat_ms) as i32) < 0 {
+            return false;
+        }
+        self.next_beat_ms = self.next_beat_ms.wrapping_add(self.interval_ms);
+        // Don't race to catch up on beats missed during a long stall
+        if (now_ms.wrapping_sub(self.next_beat_ms) as i32) >= 0 {
+            self.next_beat_ms = now_ms.wrapping_add(self.interval_ms);
+        }
+        true
+    }
+
+    /// Tapped tempo, 0 until there's been a pair of taps
+    pub fn bpm(&self) -> u16 {
+        match self.interval_ms {
+            0 => 0,
+            interval => (60_000 / interval) as u16,
+        }
+    }
+
+    /// Whether a tempo has been tapped in
+    pub fn is_set(&self) -> bool {
+        self.interval_ms != 0
+    }
+
+    /// Forget the tapped tempo and stop the metronome
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// Runs an effect in time with the beats in [`Audio`], heard or tapped
+///
+/// With `step_ms` set, the effect's clock moves `step_ms` per beat: it runs
+/// at the tempo between beats but never past the next step until the beat
+/// arrives, so a `TheaterChase` with `step_ms` of `1000 / speed` steps once
+/// per beat. In silence the effect holds still. With `colors` set, each
+/// beat also moves the effect's `Primary` color one entry along the
+/// palette. Parameters go through to the effect.
+///
+/// ```ignore
+/// let mut chase = TheaterChase::new(Rgb::RED, 3, 8);
+/// let mut synced = BeatSync::new(&audio, &mut chase).step_ms(125).colors(&PARTY);
+/// ```
+pub struct BeatSync<'a> {
+    audio: &'a Audio,
+    pub effect: &'a mut dyn Effect,
+    pub step_ms: u32,
+    pub colors: Option<&'static Palette16>,
+    seen: u32,
+    /// Engine time and effect time at the last beat
+    beat_t: u32,
+    local_beat: u32,
+    color: u8,
+}
+
+impl<'a> BeatSync<'a> {
+    pub fn new(audio: &'a Audio, effect: &'a mut dyn Effect) -> Self {
+        Self {
+            audio,
+            effect,
+            step_ms: 0,
+            colors: None,
+            seen: audio.beats(),
+            beat_t: 0,
+            local_beat: 0,
+            color: 0,
+        }
+    }
+
+    pub fn step_ms(mut self, ms: u32) -> Self {
+        self.step_ms = ms;
+        self
+    }
+
+    pub fn colors(mut self, palette: &'static Palette16) -> Self {
+        self.colors = Some(palette);
+        self
+    }
+}
+
+impl Effect for BeatSync<'_> {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        let beats = self.audio.beats();
+        if beats != self.seen {
+            self.seen = beats;
+            self.beat_t = t;
+            self.local_beat = self.local_beat.wrapping_add(self.step_ms);
+            if let Some(palette) = self.colors {
+                self.color = self.color.wrapping_add(16);
+                let color = palette.lookup(self.color);
+                self.effect
+                    .set_param(Param::Primary, ParamValue::Color(color));
+            }
+        }
+
+        let local = match self.step_ms {
+            0 => t,
+            step => {
+                let interval = match self.audio.bpm() {
+                    0 => DEFAULT_INTERVAL_MS,
+                    bpm => 60_000 / bpm as u32,
+                };
+                let since = t.wrapping_sub(self.beat_t) as u64;
+                let ahead = (step as u64 * since / interval as u64).min(step as u64 - 1);
+                self.local_beat.wrapping_add(ahead as u32)
+            }
+        };
+        self.effect.render(frame, local);
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        self.effect.params()
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        self.effect.param(param)
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        self.effect.set_param(param, value)
+    }
+}
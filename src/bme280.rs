@@ -0,0 +1,219 @@
+// BME280 temperature, humidity and pressure sensor over I2C
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+use crate::climate::Celsius;
+
+/// BME280 address with its SDO pin low
+pub const BME280_ADDR_LOW: u8 = 0x76;
+/// BME280 address with its SDO pin high
+pub const BME280_ADDR_HIGH: u8 = 0x77;
+
+/// What [`Bme280::chip_id`] reads on a BME280; a BMP280 reads 0x58
+pub const CHIP_ID: u8 = 0x60;
+
+/// Time between readings
+pub const SAMPLE_MS: u32 = 5000;
+
+const CALIB_00: u8 = 0x88;
+const CHIP_ID_REG: u8 = 0xD0;
+const CALIB_26: u8 = 0xE1;
+const CTRL_HUM: u8 = 0xF2;
+const CTRL_MEAS: u8 = 0xF4;
+const CONFIG: u8 = 0xF5;
+const PRESS_MSB: u8 = 0xF7;
+
+/// One measurement
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WeatherReading {
+    /// Millidegrees C, in steps of 10
+    pub temperature_mc: i32,
+    /// Relative humidity in tenths of a percent
+    pub humidity_permille: u16,
+    /// Air pressure in pascals
+    pub pressure_pa: u32,
+}
+
+impl defmt::Format for WeatherReading {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "{}, {}.{} %RH, {}.{} hPa",
+            Celsius(self.temperature_mc),
+            self.humidity_permille / 10,
+            self.humidity_permille % 10,
+            self.pressure_pa / 100,
+            self.pressure_pa % 100 / 10
+        );
+    }
+}
+
+/// Factory trimming, read once from the sensor
+#[derive(Copy, Clone, Debug, Default)]
+struct Calibration {
+    t1: u16,
+    t2: i16,
+    t3: i16,
+    p1: u16,
+    p2: i16,
+    p3: i16,
+    p4: i16,
+    p5: i16,
+    p6: i16,
+    p7: i16,
+    p8: i16,
+    p9: i16,
+    h1: u8,
+    h2: i16,
+    h3: u8,
+    h4: i16,
+    h5: i16,
+    h6: i8,
+}
+
+/// BME280 in normal mode, measuring once a second with a light IIR filter
+///
+/// `new` doesn't check what it's talking to; compare [`chip_id`](Self::chip_id)
+/// with [`CHIP_ID`] to tell it from a BMP280, which has no humidity sensor.
+///
+/// ```ignore
+/// let mut bme = Bme280::new(i2c, BME280_ADDR_LOW)?;
+/// let reading = bme.read()?;
+/// info!("{}", reading);
+/// ```
+pub struct Bme280<I2C> {
+    i2c: I2C,
+    addr: u8,
+    calibration: Calibration,
+    next_ms: u32,
+}
+
+impl<I2C, E> Bme280<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Read the calibration and start measuring
+    pub fn new(mut i2c: I2C, addr: u8) -> Result<Self, E> {
+        let mut a = [0; 26];
+        i2c.write_read(addr, &[CALIB_00], &mut a)?;
+        let mut b = [0; 7];
+        i2c.write_read(addr, &[CALIB_26], &mut b)?;
+
+        let u16_at = |i: usize| u16::from_le_bytes([a[i], a[i + 1]]);
+        let i16_at = |i: usize| i16::from_le_bytes([a[i], a[i + 1]]);
+        let calibration = Calibration {
+            t1: u16_at(0),
+            t2: i16_at(2),
+            t3: i16_at(4),
+            p1: u16_at(6),
+            p2: i16_at(8),
+            p3: i16_at(10),
+            p4: i16_at(12),
+            p5: i16_at(14),
+            p6: i16_at(16),
+            p7: i16_at(18),
+            p8: i16_at(20),
+            p9: i16_at(22),
+            h1: a[25],
+            h2: i16::from_le_bytes([b[0], b[1]]),
+            h3: b[2],
+            // Two 12-bit values sharing the middle byte's nibbles
+            h4: ((b[3] as i8 as i16) << 4) | (b[4] & 0x0F) as i16,
+            h5: ((b[5] as i8 as i16) << 4) | (b[4] >> 4) as i16,
+            h6: b[6] as i8,
+        };
+
+        // Humidity oversampling only takes effect with the CTRL_MEAS write
+        i2c.write(addr, &[CTRL_HUM, 0x01])?;
+        // 1 s standby, IIR filter coefficient 4
+        i2c.write(addr, &[CONFIG, 0xA8])?;
+        // Temperature and pressure oversampling x1, normal mode
+        i2c.write(addr, &[CTRL_MEAS, 0x27])?;
+
+        Ok(Self {
+            i2c,
+            addr,
+            calibration,
+            next_ms: 0,
+        })
+    }
+
+    pub fn chip_id(&mut self) -> Result<u8, E> {
+        let mut id = [0];
+        self.i2c.write_read(self.addr, &[CHIP_ID_REG], &mut id)?;
+        Ok(id[0])
+    }
+
+    /// Whether it's time for the next reading
+    pub fn due(&mut self, now_ms: u32) -> bool {
+        if (now_ms.wrapping_sub(self.next_ms) as i32) < 0 {
+            return false;
+        }
+        self.next_ms = now_ms.wrapping_add(SAMPLE_MS);
+        true
+    }
+
+    /// The latest measurement, compensated with the datasheet's integer
+    /// formulas
+    pub fn read(&mut self) -> Result<WeatherReading, E> {
+        let mut raw = [0; 8];
+        self.i2c.write_read(self.addr, &[PRESS_MSB], &mut raw)?;
+        let adc_p = (raw[0] as i32) << 12 | (raw[1] as i32) << 4 | (raw[2] as i32) >> 4;
+        let adc_t = (raw[3] as i32) << 12 | (raw[4] as i32) << 4 | (raw[5] as i32) >> 4;
+        let adc_h = (raw[6] as i32) << 8 | raw[7] as i32;
+
+        let c = &self.calibration;
+        let t_fine = {
+            let (t1, t2, t3) = (c.t1 as i32, c.t2 as i32, c.t3 as i32);
+            let var1 = (((adc_t >> 3) - (t1 << 1)) * t2) >> 11;
+            let var2 = (((((adc_t >> 4) - t1) * ((adc_t >> 4) - t1)) >> 12) * t3) >> 14;
+            var1 + var2
+        };
+        // Hundredths of a degree
+        let centi = (t_fine * 5 + 128) >> 8;
+
+        Ok(WeatherReading {
+            temperature_mc: centi * 10,
+            humidity_permille: humidity_permille(c, t_fine, adc_h),
+            pressure_pa: pressure_pa(c, t_fine, adc_p),
+        })
+    }
+
+    pub fn free(self) -> I2C {
+        self.i2c
+    }
+}
+
+fn pressure_pa(c: &Calibration, t_fine: i32, adc_p: i32) -> u32 {
+    let mut var1 = t_fine as i64 - 128_000;
+    let mut var2 = var1 * var1 * c.p6 as i64;
+    var2 += (var1 * c.p5 as i64) << 17;
+    var2 += (c.p4 as i64) << 35;
+    var1 = ((var1 * var1 * c.p3 as i64) >> 8) + ((var1 * c.p2 as i64) << 12);
+    var1 = (((1i64 << 47) + var1) * c.p1 as i64) >> 33;
+    if var1 == 0 {
+        // Uncalibrated, and it would divide by zero
+        return 0;
+    }
+    let mut p = 1_048_576 - adc_p as i64;
+    p = (((p << 31) - var2) * 3125) / var1;
+    let var1 = (c.p9 as i64 * (p >> 13) * (p >> 13)) >> 25;
+    let var2 = (c.p8 as i64 * p) >> 19;
+    // Pascals with 8 fraction bits
+    let p = ((p + var1 + var2) >> 8) + ((c.p7 as i64) << 4);
+    (p >> 8) as u32
+}
+
+fn humidity_permille(c: &Calibration, t_fine: i32, adc_h: i32) -> u16 {
+    // Worked in 64 bits, where the datasheet's 32-bit version sails close
+    let (adc_h, h1, h2, h3) = (adc_h as i64, c.h1 as i64, c.h2 as i64, c.h3 as i64);
+    let (h4, h5, h6) = (c.h4 as i64, c.h5 as i64, c.h6 as i64);
+    let x = t_fine as i64 - 76_800;
+    let x = (((adc_h << 14) - (h4 << 20) - h5 * x + 16_384) >> 15)
+        * (((((((x * h6) >> 10) * (((x * h3) >> 11) + 32_768)) >> 10) + 2_097_152) * h2 + 8192)
+            >> 14);
+    let x = x - (((((x >> 15) * (x >> 15)) >> 7) * h1) >> 4);
+    // Percent with 10 fraction bits
+    let rh = x.clamp(0, 419_430_400) >> 12;
+    ((rh * 10) >> 10) as u16
+}
@@ -0,0 +1,160 @@
+// Debounced push buttons with short, double and long press detection
+
+use embedded_hal::digital::v2::InputPin;
+
+/// How long the input must hold steady before a change counts
+pub const DEBOUNCE_MS: u32 = 20;
+
+/// Press length that counts as a long press
+pub const LONG_PRESS_MS: u32 = 600;
+
+/// Longest gap between the clicks of a double press
+pub const DOUBLE_PRESS_MS: u32 = 300;
+
+/// What a button did
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum ButtonEvent {
+    /// A single click, reported once the double press window has passed
+    Short,
+    /// Two clicks in quick succession
+    Double,
+    /// Held past [`LONG_PRESS_MS`], reported while still held
+    Long,
+}
+
+/// Debouncing and press detection, fed whether something is pressed
+///
+/// [`Button`] drives this from a GPIO; anything else that can be pressed,
+/// like a touch pad, can feed it directly.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Presses {
+    /// Raw state last seen, and since when
+    raw: bool,
+    raw_since: u32,
+    /// Debounced state
+    pressed: bool,
+    pressed_at: u32,
+    long_sent: bool,
+    /// Release time of a click that may yet become a double press
+    pending_click: Option<u32>,
+}
+
+impl Presses {
+    pub const fn new() -> Self {
+        Self {
+            raw: false,
+            raw_since: 0,
+            pressed: false,
+            pressed_at: 0,
+            long_sent: false,
+            pending_click: None,
+        }
+    }
+
+    /// Debounced state
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Whether it's down and has been for a long press, for adjusting
+    /// something for as long as it's held
+    pub fn is_held(&self) -> bool {
+        self.pressed && self.long_sent
+    }
+
+    /// Take in the raw state at `now_ms`
+    pub fn update(&mut self, raw: bool, now_ms: u32) -> Option<ButtonEvent> {
+        if raw != self.raw {
+            self.raw = raw;
+            self.raw_since = now_ms;
+        }
+
+        if raw != self.pressed && now_ms.wrapping_sub(self.raw_since) >= DEBOUNCE_MS {
+            self.pressed = raw;
+            if raw {
+                self.pressed_at = now_ms;
+                self.long_sent = false;
+            } else if !self.long_sent {
+                // A click: the second of a double press, or wait for one
+                if self.pending_click.take().is_some() {
+                    return Some(ButtonEvent::Double);
+                }
+                self.pending_click = Some(now_ms);
+            }
+        }
+
+        if self.pressed {
+            if !self.long_sent && now_ms.wrapping_sub(self.pressed_at) >= LONG_PRESS_MS {
+                self.long_sent = true;
+                self.pending_click = None;
+                return Some(ButtonEvent::Long);
+            }
+        } else if let Some(released) = self.pending_click {
+            if now_ms.wrapping_sub(released) >= DOUBLE_PRESS_MS {
+                self.pending_click = None;
+                return Some(ButtonEvent::Short);
+            }
+        }
+        None
+    }
+}
+
+/// One push button on a GPIO
+///
+/// Wired between the pin and ground with the pin pulled up by default; use
+/// `active_high` for buttons to 3.3V with a pull-down. Poll it from the main
+/// loop at least every [`DEBOUNCE_MS`] or so. For several buttons, make one
+/// `Button` each.
+///
+/// ```ignore
+/// let mut button = Button::new(pins.gpio20.into_pull_up_input());
+/// match button.poll(now_ms) {
+///     Some(ButtonEvent::Short) => next_effect(),
+///     Some(ButtonEvent::Long) => start_dimming(),
+///     _ => {}
+/// }
+/// ```
+pub struct Button<P> {
+    pin: P,
+    active_low: bool,
+    presses: Presses,
+}
+
+impl<P: InputPin> Button<P> {
+    pub fn new(pin: P) -> Self {
+        Self {
+            pin,
+            active_low: true,
+            presses: Presses::new(),
+        }
+    }
+
+    /// The pin reads high while pressed
+    pub fn active_high(mut self) -> Self {
+        self.active_low = false;
+        self
+    }
+
+    /// Debounced state
+    pub fn is_pressed(&self) -> bool {
+        self.presses.is_pressed()
+    }
+
+    /// See [`Presses::is_held`]
+    pub fn is_held(&self) -> bool {
+        self.presses.is_held()
+    }
+
+    pub fn poll(&mut self, now_ms: u32) -> Option<ButtonEvent> {
+        let level = if self.active_low {
+            self.pin.is_low()
+        } else {
+            self.pin.is_high()
+        };
+        self.presses.update(matches!(level, Ok(true)), now_ms)
+    }
+
+    pub fn free(self) -> P {
+        self.pin
+    }
+}
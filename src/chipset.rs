@@ -0,0 +1,177 @@
+// Per-chip timing parameters for single-wire (WS2812-style) pixels
+
+use crate::pio_programs::{BitCycles, ColorOrder};
+
+/// Bit timing of a single-wire pixel chip, from its datasheet
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Timing {
+    /// High time of a zero bit
+    pub t0h_ns: u32,
+    /// High time of a one bit
+    pub t1h_ns: u32,
+    /// Total bit period
+    pub period_ns: u32,
+    /// Low time that latches the frame
+    pub reset_us: u32,
+}
+
+/// Supported single-wire pixel chips
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Chipset {
+    /// WS2812B, also WS2811 wired for high-speed mode
+    Ws2812b,
+    /// WS2811 in low-speed (400 kHz) mode
+    Ws2811,
+    /// SK6812 RGB
+    Sk6812,
+    /// SK6812 RGBW, 32-bit GRBW pixels
+    Sk6812Rgbw,
+    /// WS2813 with its long reset latch
+    Ws2813,
+    /// WS2815, 12V with the WS2813's backup data line
+    Ws2815,
+    /// TM1814 RGBW
+    Tm1814,
+}
+
+impl Chipset {
+    pub const fn timing(self) -> Timing {
+        match self {
+            Chipset::Ws2812b => Timing {
+                t0h_ns: 400,
+                t1h_ns: 800,
+                period_ns: 1250,
+                reset_us: 50,
+            },
+            Chipset::Ws2811 => Timing {
+                t0h_ns: 500,
+                t1h_ns: 1200,
+                period_ns: 2500,
+                reset_us: 50,
+            },
+            Chipset::Sk6812 | Chipset::Sk6812Rgbw => Timing {
+                t0h_ns: 300,
+                t1h_ns: 600,
+                period_ns: 1250,
+                reset_us: 80,
+            },
+            Chipset::Ws2813 => Timing {
+                t0h_ns: 375,
+                t1h_ns: 875,
+                period_ns: 1250,
+                reset_us: 280,
+            },
+            Chipset::Ws2815 => Timing {
+                t0h_ns: 300,
+                t1h_ns: 750,
+                period_ns: 1250,
+                reset_us: 280,
+            },
+            Chipset::Tm1814 => Timing {
+                t0h_ns: 360,
+                t1h_ns: 720,
+                period_ns: 1250,
+                reset_us: 200,
+            },
+        }
+    }
+
+    /// Channel order the chip expects unless overridden on the builder
+    pub fn color_order(self) -> ColorOrder {
+        match self {
+            Chipset::Sk6812Rgbw => ColorOrder::Grbw,
+            Chipset::Tm1814 => ColorOrder::Wrgb,
+            _ => ColorOrder::Grb,
+        }
+    }
+
+    /// Whether the data line idles high and bits are sent as low pulses
+    pub fn inverted(self) -> bool {
+        self == Chipset::Tm1814
+    }
+
+    /// Data bits per pixel, which is also the PIO autopull threshold
+    pub fn bits_per_pixel(self) -> u8 {
+        self.color_order().bits_per_pixel()
+    }
+
+    pub const fn bit_rate_hz(self) -> u32 {
+        1_000_000_000 / self.timing().period_ns
+    }
+
+    /// PIO phase lengths that best match this chip's high times
+    ///
+    /// Tries every bit length the program can express and keeps the one with
+    /// the smallest total T0H + T1H error, preferring shorter bits (a slower
+    /// PIO clock) on ties.
+    pub fn bit_cycles(self) -> BitCycles {
+        let t = self.timing();
+        let mut best = BitCycles::WS2812;
+        let mut best_err = u32::MAX;
+
+        for total in 3..=24u32 {
+            let t1 = ((t.t0h_ns * total + t.period_ns / 2) / t.period_ns).clamp(1, 8);
+            let high = (t.t1h_ns * total + t.period_ns / 2) / t.period_ns;
+            let t2 = high.saturating_sub(t1).clamp(1, 8);
+            let Some(t3) = total.checked_sub(t1 + t2).filter(|t3| (1..=8).contains(t3)) else {
+                continue;
+            };
+
+            let t0h = t1 * t.period_ns / total;
+            let t1h = (t1 + t2) * t.period_ns / total;
+            let err = t0h.abs_diff(t.t0h_ns) + t1h.abs_diff(t.t1h_ns);
+            if err < best_err {
+                best_err = err;
+                best = BitCycles {
+                    t1: t1 as u8,
+                    t2: t2 as u8,
+                    t3: t3 as u8,
+                };
+            }
+        }
+
+        best
+    }
+}
+
+/// TM1814 constant-current setting, sent ahead of the pixels every frame
+///
+/// Each channel takes a 6-bit code: 0 is 6.5 mA, each step adds 0.5 mA, up
+/// to 38 mA at 63.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Tm1814Current {
+    pub w: u8,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Tm1814Current {
+    /// Highest code a channel accepts
+    pub const MAX: u8 = 63;
+
+    /// About 16.5 mA on every channel, a safe level for most strips
+    pub const DEFAULT: Tm1814Current = Tm1814Current::uniform(20);
+
+    pub const fn uniform(code: u8) -> Self {
+        Self {
+            w: code,
+            r: code,
+            g: code,
+            b: code,
+        }
+    }
+
+    /// The two configuration words: the WRGB codes, then their complement
+    pub fn words(self) -> [u32; 2] {
+        let word =
+            u32::from_be_bytes([self.w, self.r, self.g, self.b].map(|code| code.min(Self::MAX)));
+        [word, !word]
+    }
+}
+
+impl Default for Tm1814Current {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
@@ -0,0 +1,280 @@
+// Climate readings shared with the effects, the DHT22, and pressure trends
+
+use core::cell::Cell;
+
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+
+/// Time between DHT22 readings; the sensor can't measure any faster
+pub const SAMPLE_MS: u32 = 2000;
+
+/// Longest the sensor takes to move the line on to its next phase, in
+/// microseconds
+const TIMEOUT_US: u32 = 100;
+
+/// Why a DHT22 reading failed
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum DhtError {
+    /// Nothing answered the start signal
+    NoResponse,
+    /// The sensor stopped partway through
+    Timeout,
+    /// The data arrived garbled
+    Checksum,
+}
+
+/// Millidegrees C, logged as degrees to one decimal place
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Celsius(pub i32);
+
+impl defmt::Format for Celsius {
+    fn format(&self, f: defmt::Formatter) {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let tenths = self.0.unsigned_abs() / 100;
+        defmt::write!(f, "{}{}.{} °C", sign, tenths / 10, tenths % 10);
+    }
+}
+
+/// One DHT22 measurement
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClimateReading {
+    /// Millidegrees C, in steps of 100
+    pub temperature_mc: i32,
+    /// Relative humidity in tenths of a percent
+    pub humidity_permille: u16,
+}
+
+impl defmt::Format for ClimateReading {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "{}, {}.{} %RH",
+            Celsius(self.temperature_mc),
+            self.humidity_permille / 10,
+            self.humidity_permille % 10
+        );
+    }
+}
+
+/// A DHT22 (AM2302) on one GPIO
+///
+/// The pin must both drive low and read back, e.g. the HAL's open-drain
+/// `InOutPin`; the line needs a pull-up, which most breakout boards carry.
+/// A reading blocks for about 5 ms, with interrupts off while the sensor
+/// sends its 40 bits.
+///
+/// ```ignore
+/// let mut dht = Dht22::new(InOutPin::new(pins.gpio2.into_pull_up_input()));
+/// if dht.due(now_ms) {
+///     if let Ok(reading) = dht.read(&mut delay) {
+///         info!("{}", reading);
+///     }
+/// }
+/// ```
+pub struct Dht22<P> {
+    pin: P,
+    next_ms: u32,
+}
+
+impl<P: InputPin + OutputPin> Dht22<P> {
+    pub fn new(mut pin: P) -> Self {
+        let _ = pin.set_high();
+        Self {
+            pin,
+            // Give the sensor its first couple of seconds to settle
+            next_ms: SAMPLE_MS,
+        }
+    }
+
+    /// Whether it's time for the next reading
+    pub fn due(&mut self, now_ms: u32) -> bool {
+        if (now_ms.wrapping_sub(self.next_ms) as i32) < 0 {
+            return false;
+        }
+        self.next_ms = now_ms.wrapping_add(SAMPLE_MS);
+        true
+    }
+
+    /// Take a measurement
+    pub fn read<D: DelayUs<u32>>(&mut self, delay: &mut D) -> Result<ClimateReading, DhtError> {
+        // Start signal: at least 1 ms low, then let the pull-up take it
+        let _ = self.pin.set_low();
+        delay.delay_us(1_100);
+
+        let data = critical_section::with(|_| {
+            let _ = self.pin.set_high();
+            // The sensor answers 80 µs low, 80 µs high, then low for the
+            // first bit
+            self.wait_for(false, delay)
+                .map_err(|_| DhtError::NoResponse)?;
+            self.wait_for(true, delay)?;
+            self.wait_for(false, delay)?;
+
+            // Each bit is 50 µs low then high, for 26-28 µs for a 0 or
+            // 70 µs for a 1: look at the line partway in between
+            let mut data = [0u8; 5];
+            for bit in 0..40 {
+                self.wait_for(true, delay)?;
+                delay.delay_us(35);
+                if matches!(self.pin.is_high(), Ok(true)) {
+                    data[bit / 8] |= 0x80 >> (bit % 8);
+                    self.wait_for(false, delay)?;
+                }
+            }
+            Ok(data)
+        })?;
+
+        let sum = data[..4].iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        if sum != data[4] {
+            return Err(DhtError::Checksum);
+        }
+
+        // Temperature is sign and magnitude in tenths of a degree
+        let tenths = (u16::from_be_bytes([data[2] & 0x7F, data[3]]) as i32) * 100;
+        Ok(ClimateReading {
+            temperature_mc: if data[2] & 0x80 != 0 { -tenths } else { tenths },
+            humidity_permille: u16::from_be_bytes([data[0], data[1]]),
+        })
+    }
+
+    /// Spin until the line reads `high`
+    fn wait_for<D: DelayUs<u32>>(&mut self, high: bool, delay: &mut D) -> Result<(), DhtError> {
+        for _ in 0..TIMEOUT_US {
+            if matches!(self.pin.is_high(), Ok(level) if level == high) {
+                return Ok(());
+            }
+            delay.delay_us(1);
+        }
+        Err(DhtError::Timeout)
+    }
+
+    pub fn free(self) -> P {
+        self.pin
+    }
+}
+
+/// Pressure samples kept for [`PressureTrend`], one every
+/// [`TREND_STEP_MS`], covering three hours
+const TREND_STEPS: usize = 18;
+
+/// Time between the samples [`PressureTrend`] keeps
+pub const TREND_STEP_MS: u32 = 10 * 60 * 1000;
+
+/// Barometric tendency: how fast the air pressure is changing
+///
+/// Forecasters look at the change over three hours; until three hours of
+/// samples are in, the change so far is scaled up to match, once there's at
+/// least half an hour of it.
+#[derive(Copy, Clone, Debug)]
+pub struct PressureTrend {
+    /// Pascals, oldest at `head` once full
+    samples: [u32; TREND_STEPS + 1],
+    count: usize,
+    head: usize,
+    next_ms: u32,
+}
+
+impl PressureTrend {
+    pub const fn new() -> Self {
+        Self {
+            samples: [0; TREND_STEPS + 1],
+            count: 0,
+            head: 0,
+            next_ms: 0,
+        }
+    }
+
+    /// Take in a reading; only one per [`TREND_STEP_MS`] is kept
+    pub fn push(&mut self, now_ms: u32, pa: u32) {
+        if self.count > 0 && (now_ms.wrapping_sub(self.next_ms) as i32) < 0 {
+            return;
+        }
+        self.next_ms = now_ms.wrapping_add(TREND_STEP_MS);
+        let len = self.samples.len();
+        if self.count < len {
+            self.samples[(self.head + self.count) % len] = pa;
+            self.count += 1;
+        } else {
+            self.samples[self.head] = pa;
+            self.head = (self.head + 1) % len;
+        }
+    }
+
+    /// Change in pascals per three hours, rising positive
+    pub fn pa_per_3h(&self) -> Option<i32> {
+        let steps = self.count.checked_sub(1)?;
+        if steps < 3 {
+            return None;
+        }
+        let len = self.samples.len();
+        let oldest = self.samples[self.head] as i32;
+        let newest = self.samples[(self.head + steps) % len] as i32;
+        Some((newest - oldest) * TREND_STEPS as i32 / steps as i32)
+    }
+}
+
+impl Default for PressureTrend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The latest climate readings, shared with the effects showing them
+#[derive(Debug, Default)]
+pub struct Climate {
+    temperature_mc: Cell<Option<i32>>,
+    humidity_permille: Cell<Option<u16>>,
+    pressure_pa: Cell<Option<u32>>,
+    pressure_trend: Cell<Option<i32>>,
+}
+
+impl Climate {
+    pub const fn new() -> Self {
+        Self {
+            temperature_mc: Cell::new(None),
+            humidity_permille: Cell::new(None),
+            pressure_pa: Cell::new(None),
+            pressure_trend: Cell::new(None),
+        }
+    }
+
+    /// Millidegrees C, `None` before the first reading
+    pub fn temperature_mc(&self) -> Option<i32> {
+        self.temperature_mc.get()
+    }
+
+    /// Relative humidity in tenths of a percent, `None` before the first
+    /// reading or from sensors without one
+    pub fn humidity_permille(&self) -> Option<u16> {
+        self.humidity_permille.get()
+    }
+
+    /// Air pressure in pascals, from sensors that have it
+    pub fn pressure_pa(&self) -> Option<u32> {
+        self.pressure_pa.get()
+    }
+
+    /// See [`PressureTrend::pa_per_3h`]
+    pub fn pressure_trend(&self) -> Option<i32> {
+        self.pressure_trend.get()
+    }
+
+    pub fn set_temperature_mc(&self, mc: i32) {
+        self.temperature_mc.set(Some(mc));
+    }
+
+    pub fn set_humidity_permille(&self, permille: u16) {
+        self.humidity_permille.set(Some(permille));
+    }
+
+    pub fn set_pressure(&self, pa: u32, trend: Option<i32>) {
+        self.pressure_pa.set(Some(pa));
+        self.pressure_trend.set(trend);
+    }
+
+    /// Take in a whole DHT22 reading
+    pub fn update(&self, reading: ClimateReading) {
+        self.set_temperature_mc(reading.temperature_mc);
+        self.set_humidity_permille(reading.humidity_permille);
+    }
+}
@@ -0,0 +1,322 @@
+// Wall-clock time of day, kept on the millisecond timer between syncs
+
+/// Milliseconds in a day
+pub const DAY_MS: u32 = 86_400_000;
+
+/// A calendar date
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    pub fn is_leap_year(&self) -> bool {
+        let year = self.year;
+        year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+    }
+
+    fn days_in_month(&self) -> u8 {
+        match self.month {
+            2 if self.is_leap_year() => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    /// The date `days` after 1 January 1970
+    pub fn from_unix_days(days: u32) -> Self {
+        // Counted in 400-year eras from 1 March 0000, so leap days fall at
+        // the end of each year (Howard Hinnant's civil_from_days)
+        let z = days + 719_468;
+        let era = z / 146_097;
+        let day_of_era = z % 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u8;
+        let month = if month_from_march < 10 {
+            month_from_march + 3
+        } else {
+            month_from_march - 9
+        } as u8;
+        let year = (year_of_era + era * 400 + (month <= 2) as u32) as u16;
+        Self { year, month, day }
+    }
+
+    /// Days since 1 January 1970
+    pub fn unix_days(&self) -> u32 {
+        let year = self.year as u32 - (self.month <= 2) as u32;
+        let era = year / 400;
+        let year_of_era = year % 400;
+        let month = self.month as u32;
+        let day_of_year =
+            (153 * if month > 2 { month - 3 } else { month + 9 } + 2) / 5 + self.day as u32 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    /// Day of the week, 0 for Sunday
+    pub fn weekday(&self) -> u8 {
+        // 1 January 1970 was a Thursday
+        ((self.unix_days() + 4) % 7) as u8
+    }
+
+    /// The date `days` later
+    pub fn add_days(mut self, days: u32) -> Self {
+        for _ in 0..days {
+            if self.day < self.days_in_month() {
+                self.day += 1;
+            } else {
+                self.day = 1;
+                if self.month < 12 {
+                    self.month += 1;
+                } else {
+                    self.month = 1;
+                    self.year += 1;
+                }
+            }
+        }
+        self
+    }
+}
+
+/// Milliseconds since midnight
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeOfDay(pub u32);
+
+impl TimeOfDay {
+    pub const fn from_hms(hour: u8, minute: u8, second: u8) -> Self {
+        Self(((hour as u32 * 60 + minute as u32) * 60 + second as u32) * 1000)
+    }
+
+    pub fn hour(&self) -> u8 {
+        (self.0 / 3_600_000) as u8
+    }
+
+    pub fn minute(&self) -> u8 {
+        (self.0 / 60_000 % 60) as u8
+    }
+
+    pub fn second(&self) -> u8 {
+        (self.0 / 1000 % 60) as u8
+    }
+}
+
+impl defmt::Format for TimeOfDay {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "{=u8:02}:{=u8:02}:{=u8:02}",
+            self.hour(),
+            self.minute(),
+            self.second()
+        );
+    }
+}
+
+/// Which week of the month a summer time change falls in
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Week {
+    /// First to fourth
+    Nth(u8),
+    Last,
+}
+
+/// When summer time starts or ends: a Sunday, at an hour of standard local
+/// time
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Transition {
+    pub month: u8,
+    pub week: Week,
+    pub hour: u8,
+}
+
+impl Transition {
+    /// The day of the month it falls on in `year`
+    fn day(&self, year: u16) -> u8 {
+        let first = Date {
+            year,
+            month: self.month,
+            day: 1,
+        };
+        let first_sunday = 1 + (7 - first.weekday()) % 7;
+        match self.week {
+            Week::Nth(n) => first_sunday + 7 * (n.clamp(1, 4) - 1),
+            Week::Last => first_sunday + 7 * ((first.days_in_month() - first_sunday) / 7),
+        }
+    }
+
+    /// Whether the local standard `date` and `time` are at or after it
+    fn passed(&self, date: Date, time: TimeOfDay) -> bool {
+        let day = self.day(date.year);
+        (date.month, date.day, time) >= (self.month, day, TimeOfDay::from_hms(self.hour, 0, 0))
+    }
+}
+
+/// A summer time rule: clocks go forward `shift_min` from `start` until
+/// `end`; a start later in the year than the end is a southern summer
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Dst {
+    pub start: Transition,
+    pub end: Transition,
+    pub shift_min: i16,
+}
+
+impl Dst {
+    /// The United States and Canada: second Sunday in March at 2:00 to
+    /// the first in November at 2:00 summer time, 1:00 standard
+    pub const US: Dst = Dst {
+        start: Transition {
+            month: 3,
+            week: Week::Nth(2),
+            hour: 2,
+        },
+        end: Transition {
+            month: 11,
+            week: Week::Nth(1),
+            hour: 1,
+        },
+        shift_min: 60,
+    };
+
+    /// The European Union and the UK, changing at 1:00 UTC on the last
+    /// Sundays of March and October, for a zone `utc_offset_min` from UTC
+    pub const fn eu(utc_offset_min: i16) -> Dst {
+        let hour = (1 + utc_offset_min / 60) as u8;
+        Dst {
+            start: Transition {
+                month: 3,
+                week: Week::Last,
+                hour,
+            },
+            end: Transition {
+                month: 10,
+                week: Week::Last,
+                hour,
+            },
+            shift_min: 60,
+        }
+    }
+
+    /// Whether summer time is on at the local standard `date` and `time`
+    pub fn applies(&self, date: Date, time: TimeOfDay) -> bool {
+        let (started, ended) = (self.start.passed(date, time), self.end.passed(date, time));
+        if self.start.month < self.end.month {
+            started && !ended
+        } else {
+            started || !ended
+        }
+    }
+}
+
+/// The time of day, set from a time source now and then and run on the
+/// millisecond timer in between
+///
+/// Sources give UTC; `utc_offset_min` turns that into local time for
+/// [`time_of_day`](Self::time_of_day) and [`date`](Self::date), moved on
+/// through the summer by `dst` if the zone has summer time.
+///
+/// ```ignore
+/// let mut clock = WallClock::new().utc_offset_min(60).dst(Dst::eu(60));
+/// clock.set(now_ms, date, time);
+/// if let Some(time) = clock.time_of_day(now_ms) {
+///     info!("{}", time);
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct WallClock {
+    pub utc_offset_min: i16,
+    pub dst: Option<Dst>,
+    /// Timer reading, UTC date and time at the last sync
+    synced: Option<(u32, Date, TimeOfDay)>,
+}
+
+impl WallClock {
+    pub const fn new() -> Self {
+        Self {
+            utc_offset_min: 0,
+            dst: None,
+            synced: None,
+        }
+    }
+
+    pub fn utc_offset_min(mut self, minutes: i16) -> Self {
+        self.utc_offset_min = minutes;
+        self
+    }
+
+    pub fn dst(mut self, rule: Dst) -> Self {
+        self.dst = Some(rule);
+        self
+    }
+
+    /// Sync to a UTC date and time, as of `now_ms`
+    pub fn set(&mut self, now_ms: u32, date: Date, time: TimeOfDay) {
+        self.synced = Some((now_ms, date, time));
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.synced.is_some()
+    }
+
+    /// Local date and time at `now_ms`, `None` until the first sync
+    fn local(&self, now_ms: u32) -> Option<(Date, TimeOfDay)> {
+        let (date, time) = self.shifted(now_ms, self.utc_offset_min)?;
+        match self.dst {
+            Some(dst) if dst.applies(date, time) => {
+                self.shifted(now_ms, self.utc_offset_min + dst.shift_min)
+            }
+            _ => Some((date, time)),
+        }
+    }
+
+    /// Date and time at `now_ms`, `offset_min` from UTC
+    fn shifted(&self, now_ms: u32, offset_min: i16) -> Option<(Date, TimeOfDay)> {
+        let (at_ms, date, time) = self.synced?;
+        // Shift by a day's worth first so a negative offset can't go below 0
+        let offset = offset_min as i64 * 60_000;
+        let ms = time.0 as i64 + now_ms.wrapping_sub(at_ms) as i64 + offset + DAY_MS as i64;
+        let date = match (ms / DAY_MS as i64) as u32 {
+            // Still the day before, from a negative offset
+            0 => previous_day(date),
+            days => date.add_days(days - 1),
+        };
+        Some((date, TimeOfDay((ms % DAY_MS as i64) as u32)))
+    }
+
+    /// Local time of day at `now_ms`
+    pub fn time_of_day(&self, now_ms: u32) -> Option<TimeOfDay> {
+        self.local(now_ms).map(|(_, time)| time)
+    }
+
+    /// Local date at `now_ms`
+    pub fn date(&self, now_ms: u32) -> Option<Date> {
+        self.local(now_ms).map(|(date, _)| date)
+    }
+}
+
+fn previous_day(date: Date) -> Date {
+    if date.day > 1 {
+        return Date {
+            day: date.day - 1,
+            ..date
+        };
+    }
+    let (year, month) = match date.month {
+        1 => (date.year - 1, 12),
+        month => (date.year, month - 1),
+    };
+    let first = Date {
+        year,
+        month,
+        day: 1,
+    };
+    Date {
+        day: first.days_in_month(),
+        ..first
+    }
+}
@@ -0,0 +1,111 @@
+// Color spaces and color math on top of `Rgb`
+
+use crate::math::{qadd8, scale8};
+use crate::pio_programs::Rgb;
+
+/// A color as hue, saturation and value, each 0-255
+///
+/// Hue wraps: 0 is red, ~85 green, ~170 blue, then back to red, so
+/// animating a hue is just adding to it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Hsv {
+    pub h: u8,
+    pub s: u8,
+    pub v: u8,
+}
+
+impl Hsv {
+    pub const fn new(h: u8, s: u8, v: u8) -> Self {
+        Self { h, s, v }
+    }
+
+    /// Convert to RGB with integer math only
+    ///
+    /// The hue circle is split into six sectors of ~43 steps; within a
+    /// sector one channel ramps while the other two hold.
+    pub fn to_rgb(self) -> Rgb {
+        if self.s == 0 {
+            return Rgb::new(self.v, self.v, self.v);
+        }
+
+        let h = self.h as u16 * 6;
+        let sector = h >> 8;
+        // Position within the sector, 0-255
+        let f = h & 0xFF;
+        let v = self.v as u16;
+        let s = self.s as u16;
+
+        let p = (v * (255 - s) / 255) as u8;
+        let q = (v * (255 - s * f / 255) / 255) as u8;
+        let t = (v * (255 - s * (255 - f) / 255) / 255) as u8;
+        let v = self.v;
+
+        match sector {
+            0 => Rgb::new(v, t, p),
+            1 => Rgb::new(q, v, p),
+            2 => Rgb::new(p, v, t),
+            3 => Rgb::new(p, q, v),
+            4 => Rgb::new(t, p, v),
+            _ => Rgb::new(v, p, q),
+        }
+    }
+}
+
+impl From<Hsv> for Rgb {
+    fn from(c: Hsv) -> Self {
+        c.to_rgb()
+    }
+}
+
+/// Interpolate one channel, `t` of 255 being all `b`
+fn lerp8(a: u8, b: u8, t: u8) -> u8 {
+    let (a, b, t) = (a as i32, b as i32, t as i32);
+    (a + ((b - a) * t + 127 * (b - a).signum()) / 255) as u8
+}
+
+impl Rgb {
+    /// Interpolate toward `other`: 0 gives `self`, 255 gives `other`
+    pub fn lerp(self, other: Rgb, t: u8) -> Rgb {
+        Rgb::new(
+            lerp8(self.r, other.r, t),
+            lerp8(self.g, other.g, t),
+            lerp8(self.b, other.b, t),
+        )
+    }
+
+    /// Layer `other` on top with a screen blend
+    ///
+    /// Both colors show through and the result only gets lighter, without
+    /// the clipping of plain addition.
+    pub fn blend(self, other: Rgb) -> Rgb {
+        let screen = |a: u8, b: u8| 255 - ((255 - a as u16) * (255 - b as u16) / 255) as u8;
+        Rgb::new(
+            screen(self.r, other.r),
+            screen(self.g, other.g),
+            screen(self.b, other.b),
+        )
+    }
+
+    /// Add channel by channel, saturating at 255
+    pub fn saturating_add(self, other: Rgb) -> Rgb {
+        Rgb::new(
+            qadd8(self.r, other.r),
+            qadd8(self.g, other.g),
+            qadd8(self.b, other.b),
+        )
+    }
+
+    /// Scale every channel by `level`, 255 leaving the color unchanged
+    pub fn scale(self, level: u8) -> Rgb {
+        Rgb::new(
+            scale8(self.r, level),
+            scale8(self.g, level),
+            scale8(self.b, level),
+        )
+    }
+
+    /// Dim by `amount` out of 255, for trails that fade a little each frame
+    pub fn fade_to_black_by(self, amount: u8) -> Rgb {
+        self.scale(255 - amount)
+    }
+}
@@ -0,0 +1,285 @@
+// Line-based text commands from a host, e.g. over the USB serial port
+
+use crate::effects::{Param, ParamValue};
+use crate::pio_programs::{ColorOrder, Rgb};
+
+/// Longest command line kept, room for a JSON settings document; longer
+/// lines are thrown away
+pub const MAX_LINE: usize = 256;
+
+/// What `help` prints, one command a line
+pub const HELP: &str = "\
+pixel|p <n> <rrggbb>    set one pixel and show the host's pixels\r
+fill|f <rrggbb>         set every pixel\r
+brightness|b <0-255>    master brightness\r
+effect|e <n>            run effect n, counting from 0\r
+set <param> <value>     speed or intensity 0-255, primary or secondary rrggbb\r
+leds <n>                strip length, kept over a power cycle\r
+order <grb|...|chipset> channel order from the next power-up\r
+calibrate <rrggbb>      channel multipliers from the next power-up\r
+status|s                report the effect, brightness and strip\r
+save [n]                keep the settings over a power cycle, or in preset n\r
+preset <n>              recall preset n\r
+reboot                  restart the firmware\r
+bootsel <pin>           restart into the USB bootloader for new firmware\r
+quit|q                  leave the shell\r
+help|h|?                this list\r
+";
+
+/// One command from the host
+///
+/// ```text
+/// pixel 3 ff8000      set pixel 3 to orange (hex RGB, `#` optional)
+/// fill 000010         every pixel dim blue
+/// brightness 128      master brightness, 0-255
+/// effect 2            run effect 2, counting from 0
+/// set speed 200       a parameter of the running effect
+/// status              report the effect, brightness and strip
+/// ```
+///
+/// Words are separated by spaces or tabs, and the command name is matched
+/// regardless of case. The everyday commands also go by their first letter
+/// (see [`HELP`]); `set`, `leds`, `order`, `calibrate`, `save`, `preset`,
+/// `reboot` and `bootsel` have to be spelled out.
+#[derive(Copy, Clone, Debug, PartialEq, defmt::Format)]
+pub enum Command {
+    Pixel { index: usize, color: Rgb },
+    Fill(Rgb),
+    Brightness(u8),
+    Effect(usize),
+    Set { param: Param, value: ParamValue },
+    /// Strip length
+    Leds(usize),
+    /// Channel order, None for the chipset's, saved for the next power-up
+    Order(Option<ColorOrder>),
+    /// Per-channel multipliers, saved for the next power-up
+    Calibrate(Rgb),
+    Status,
+    /// Keep the settings, or with a number, the look in that preset
+    Save { preset: Option<usize> },
+    Preset(usize),
+    Reboot,
+    /// Reboot into the ROM's USB bootloader, if `pin` is the one the
+    /// firmware was built with
+    Bootsel { pin: usize },
+    Quit,
+    Help,
+}
+
+/// Why a line wasn't a command
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum CommandError {
+    Unknown,
+    MissingArgument,
+    BadArgument,
+    /// More arguments than the command takes
+    TooManyArguments,
+    /// The line was longer than [`MAX_LINE`]
+    TooLong,
+    /// A pixel or effect number past the end
+    OutOfRange,
+    /// A parameter the running effect doesn't have
+    NoSuchParam,
+    /// A command this build can't carry out
+    Unsupported,
+    /// The wrong PIN for a protected command
+    Denied,
+    /// A preset slot with nothing saved in it
+    EmptyPreset,
+}
+
+impl CommandError {
+    /// Short description for the reply to the host
+    pub fn message(&self) -> &'static str {
+        match self {
+            CommandError::Unknown => "unknown command",
+            CommandError::MissingArgument => "missing argument",
+            CommandError::BadArgument => "bad argument",
+            CommandError::TooManyArguments => "too many arguments",
+            CommandError::TooLong => "line too long",
+            CommandError::OutOfRange => "out of range",
+            CommandError::NoSuchParam => "no such param",
+            CommandError::Unsupported => "not supported",
+            CommandError::Denied => "denied",
+            CommandError::EmptyPreset => "empty preset",
+        }
+    }
+}
+
+impl Command {
+    /// Parse one line, without its line ending
+    pub fn parse(line: &[u8]) -> Result<Self, CommandError> {
+        let mut words = line
+            .split(|&b| b == b' ' || b == b'\t')
+            .filter(|word| !word.is_empty());
+        let name = words.next().ok_or(CommandError::Unknown)?;
+        let mut arg = || words.next().ok_or(CommandError::MissingArgument);
+        let is = |long: &[u8], short: &[u8]| {
+            name.eq_ignore_ascii_case(long) || name.eq_ignore_ascii_case(short)
+        };
+
+        let command = if is(b"pixel", b"p") {
+            let index = parse_decimal(arg()?)?;
+            Command::Pixel {
+                index,
+                color: parse_color(arg()?)?,
+            }
+        } else if is(b"fill", b"f") {
+            Command::Fill(parse_color(arg()?)?)
+        } else if is(b"brightness", b"b") {
+            Command::Brightness(parse_level(arg()?)?)
+        } else if is(b"effect", b"e") {
+            Command::Effect(parse_decimal(arg()?)?)
+        } else if name.eq_ignore_ascii_case(b"set") {
+            let param = Param::from_key(arg()?).ok_or(CommandError::NoSuchParam)?;
+            let value = match param {
+                Param::Speed | Param::Intensity => ParamValue::Level(parse_level(arg()?)?),
+                Param::Primary | Param::Secondary => ParamValue::Color(parse_color(arg()?)?),
+                // Palettes are picked with the controls, not by name
+                Param::Palette => return Err(CommandError::Unsupported),
+            };
+            Command::Set { param, value }
+        } else if name.eq_ignore_ascii_case(b"leds") {
+            Command::Leds(parse_decimal(arg()?)?)
+        } else if name.eq_ignore_ascii_case(b"order") {
+            let order = arg()?;
+            if order.eq_ignore_ascii_case(b"chipset") {
+                Command::Order(None)
+            } else {
+                Command::Order(Some(
+                    ColorOrder::from_name(order).ok_or(CommandError::BadArgument)?,
+                ))
+            }
+        } else if name.eq_ignore_ascii_case(b"calibrate") {
+            Command::Calibrate(parse_color(arg()?)?)
+        } else if is(b"status", b"s") {
+            Command::Status
+        } else if name.eq_ignore_ascii_case(b"save") {
+            Command::Save {
+                preset: arg().ok().map(parse_decimal).transpose()?,
+            }
+        } else if name.eq_ignore_ascii_case(b"preset") {
+            Command::Preset(parse_decimal(arg()?)?)
+        } else if name.eq_ignore_ascii_case(b"reboot") {
+            Command::Reboot
+        } else if name.eq_ignore_ascii_case(b"bootsel") {
+            Command::Bootsel {
+                pin: parse_decimal(arg()?)?,
+            }
+        } else if is(b"quit", b"q") {
+            Command::Quit
+        } else if is(b"help", b"h") || name == b"?" {
+            Command::Help
+        } else {
+            return Err(CommandError::Unknown);
+        };
+
+        if words.next().is_some() {
+            return Err(CommandError::TooManyArguments);
+        }
+        Ok(command)
+    }
+}
+
+pub(crate) fn parse_decimal(word: &[u8]) -> Result<usize, CommandError> {
+    if word.len() > 9 {
+        return Err(CommandError::OutOfRange);
+    }
+    word.iter().try_fold(0, |n, &d| match d {
+        b'0'..=b'9' => Ok(n * 10 + (d - b'0') as usize),
+        _ => Err(CommandError::BadArgument),
+    })
+}
+
+pub(crate) fn parse_level(word: &[u8]) -> Result<u8, CommandError> {
+    u8::try_from(parse_decimal(word)?).map_err(|_| CommandError::OutOfRange)
+}
+
+/// Six hex digits, `rrggbb`, with or without a leading `#`
+pub(crate) fn parse_color(word: &[u8]) -> Result<Rgb, CommandError> {
+    let hex = word.strip_prefix(b"#").unwrap_or(word);
+    if hex.len() != 6 {
+        return Err(CommandError::BadArgument);
+    }
+    let digit = |d: u8| (d as char).to_digit(16).ok_or(CommandError::BadArgument);
+    let byte = |i: usize| Ok((digit(hex[i])? << 4 | digit(hex[i + 1])?) as u8);
+    Ok(Rgb::new(byte(0)?, byte(2)?, byte(4)?))
+}
+
+/// Collects bytes into lines, ending at CR, LF or both
+///
+/// ```ignore
+/// for &byte in received {
+///     if let Some(line) = lines.push(byte) {
+///         let reply = line.and_then(Command::parse);
+///     }
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct LineReader {
+    buf: [u8; MAX_LINE],
+    len: usize,
+    overflow: bool,
+    /// The last byte was a CR, so an LF straight after is the same line end
+    after_cr: bool,
+}
+
+impl LineReader {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MAX_LINE],
+            len: 0,
+            overflow: false,
+            after_cr: false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Forget the line so far, when the bytes turned out to be something
+    /// else
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.overflow = false;
+        self.after_cr = false;
+    }
+
+    /// Take in one byte; returns the line when it ends one, or
+    /// [`CommandError::TooLong`] for a line that didn't fit. Backspace and
+    /// delete take back the last byte, and a blank line comes back empty.
+    pub fn push(&mut self, byte: u8) -> Option<Result<&[u8], CommandError>> {
+        let after_cr = core::mem::replace(&mut self.after_cr, byte == b'\r');
+        match byte {
+            b'\n' if after_cr => None,
+            b'\r' | b'\n' => {
+                let len = core::mem::take(&mut self.len);
+                if core::mem::take(&mut self.overflow) {
+                    Some(Err(CommandError::TooLong))
+                } else {
+                    Some(Ok(&self.buf[..len]))
+                }
+            }
+            0x08 | 0x7F => {
+                self.len = self.len.saturating_sub(1);
+                None
+            }
+            _ if self.len < MAX_LINE => {
+                self.buf[self.len] = byte;
+                self.len += 1;
+                None
+            }
+            _ => {
+                self.overflow = true;
+                None
+            }
+        }
+    }
+}
+
+impl Default for LineReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,359 @@
+// The USB drive's contents: a tiny FAT12 volume in RAM holding config.txt,
+// the settings as lines of text to edit in any editor
+
+use core::fmt;
+
+use crate::command::{parse_color, parse_decimal, parse_level, CommandError};
+use crate::effects::{Effect, ParamValue};
+use crate::json::{ConfigUpdate, JsonError, PARAMS};
+use crate::usb_msc::{BlockDevice, BLOCK_SIZE};
+
+/// The volume's size in blocks, 32 KiB; FAT12 needs but one FAT sector for
+/// it
+pub const SECTORS: usize = 64;
+
+/// RAM for the volume, a `static` so it stays put
+pub type Sectors = [[u8; BLOCK_SIZE]; SECTORS];
+
+/// Longest config.txt that's read back
+pub const MAX_CONFIG: usize = 1024;
+
+/// How long writes must have stopped before a file still mounted is read;
+/// hosts write a saved file's data, FAT and directory in bursts
+pub const SETTLE_MS: u32 = 2000;
+
+/// Boot sector, then one FAT, then the root directory, then clusters of a
+/// sector each, numbered from 2
+const FAT_SECTOR: usize = 1;
+const ROOT_SECTOR: usize = 2;
+const ROOT_SECTORS: usize = 4;
+const DIR_ENTRY: usize = 32;
+const DATA_SECTOR: usize = ROOT_SECTOR + ROOT_SECTORS;
+const FIRST_CLUSTER: usize = 2;
+const CLUSTERS: usize = SECTORS - DATA_SECTOR;
+/// FAT12 values from this up end a chain
+const END_OF_CHAIN: u16 = 0xFF8;
+
+const VOLUME_LABEL: &[u8; 11] = b"PICODISPLAY";
+const FILE_NAME: &[u8; 11] = b"CONFIG  TXT";
+
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+/// Long-name entries, which share the short name's directory
+const ATTR_LONG_NAME: u8 = 0x0F;
+/// The name and extension are shown in lower case, config.txt
+const NT_LOWER_CASE: u8 = 0x18;
+const DELETED: u8 = 0xE5;
+
+/// 2024-01-01, noon: there's no clock to stamp the file with
+const FAT_DATE: u16 = (2024 - 1980) << 9 | 1 << 5 | 1;
+const FAT_TIME: u16 = 12 << 11;
+
+/// A removable drive with config.txt on it
+///
+/// [`format`](Self::format) writes a fresh volume with the file holding
+/// whatever it's given, [`ConfigText`] for the running settings. The host
+/// then reads and writes the volume as it likes; [`poll`](Self::poll)
+/// finds config.txt again, by name in the root directory and through the
+/// FAT, so editors that save to a new file and rename it work as well as
+/// ones that write in place. The file's handed over once the host ejects
+/// the drive, or once writes have been quiet for [`SETTLE_MS`] for hosts
+/// that never do.
+///
+/// ```ignore
+/// drive.format(&ConfigText { /* the settings */ });
+/// if let Some(text) = drive.poll(now_ms) {
+///     let update = config_drive::parse(text)?;
+/// }
+/// ```
+pub struct ConfigDrive {
+    sectors: &'static mut Sectors,
+    present: bool,
+    /// The host wrote since the last poll
+    written: bool,
+    /// When the host wrote last, until the file's handed over
+    changed_ms: Option<u32>,
+    text: [u8; MAX_CONFIG],
+}
+
+impl ConfigDrive {
+    pub fn new(sectors: &'static mut Sectors) -> Self {
+        Self {
+            sectors,
+            present: true,
+            written: false,
+            changed_ms: None,
+            text: [0; MAX_CONFIG],
+        }
+    }
+
+    /// Whether the host has the drive; false from its eject until it loads
+    /// the drive again or reconnects
+    pub fn is_mounted(&self) -> bool {
+        self.present
+    }
+
+    /// Start over with a blank volume and config.txt holding `contents`,
+    /// cut off at the volume's size
+    pub fn format(&mut self, contents: &dyn fmt::Display) {
+        for sector in self.sectors.iter_mut() {
+            sector.fill(0);
+        }
+
+        let boot = &mut self.sectors[0];
+        boot[..11].copy_from_slice(b"\xEB\x3C\x90MSWIN4.1");
+        boot[11..13].copy_from_slice(&(BLOCK_SIZE as u16).to_le_bytes());
+        // A sector a cluster, one reserved sector and one FAT
+        boot[13..17].copy_from_slice(&[1, 1, 0, 1]);
+        boot[17..19]
+            .copy_from_slice(&((ROOT_SECTORS * BLOCK_SIZE / DIR_ENTRY) as u16).to_le_bytes());
+        boot[19..21].copy_from_slice(&(SECTORS as u16).to_le_bytes());
+        // Fixed media, one FAT sector, one sector a track on one head
+        boot[21..28].copy_from_slice(&[0xF8, 1, 0, 1, 0, 1, 0]);
+        // Drive number, extended boot signature, volume serial
+        boot[36..43].copy_from_slice(&[0x80, 0, 0x29, 0x88, 0x19, 0x40, 0x20]);
+        boot[43..54].copy_from_slice(VOLUME_LABEL);
+        boot[54..62].copy_from_slice(b"FAT12   ");
+        boot[510..].copy_from_slice(&[0x55, 0xAA]);
+
+        let data = self.sectors[DATA_SECTOR..].as_flattened_mut();
+        let mut out = Cursor { data, len: 0 };
+        let _ = fmt::write(&mut out, format_args!("{contents}"));
+        let size = out.len;
+        let clusters = size.div_ceil(BLOCK_SIZE);
+
+        let fat = &mut self.sectors[FAT_SECTOR];
+        set_fat(fat, 0, 0xFF8);
+        set_fat(fat, 1, 0xFFF);
+        for i in 0..clusters {
+            let cluster = FIRST_CLUSTER + i;
+            let next = if i + 1 == clusters {
+                0xFFF
+            } else {
+                cluster as u16 + 1
+            };
+            set_fat(fat, cluster, next);
+        }
+
+        let root = self.sectors[ROOT_SECTOR..DATA_SECTOR].as_flattened_mut();
+        root[..11].copy_from_slice(VOLUME_LABEL);
+        root[11] = ATTR_VOLUME_ID;
+        let file = &mut root[DIR_ENTRY..2 * DIR_ENTRY];
+        file[..11].copy_from_slice(FILE_NAME);
+        file[11] = ATTR_ARCHIVE;
+        file[12] = NT_LOWER_CASE;
+        for at in [14, 22] {
+            file[at..at + 2].copy_from_slice(&FAT_TIME.to_le_bytes());
+        }
+        for at in [16, 18, 24] {
+            file[at..at + 2].copy_from_slice(&FAT_DATE.to_le_bytes());
+        }
+        let start = if clusters > 0 {
+            FIRST_CLUSTER as u16
+        } else {
+            0
+        };
+        file[26..28].copy_from_slice(&start.to_le_bytes());
+        file[28..32].copy_from_slice(&(size as u32).to_le_bytes());
+
+        self.written = false;
+        self.changed_ms = None;
+    }
+
+    /// config.txt as the host left it, once it's done writing and only if
+    /// it wrote anything; None if it deleted the file
+    pub fn poll(&mut self, now_ms: u32) -> Option<&[u8]> {
+        if core::mem::take(&mut self.written) {
+            self.changed_ms = Some(now_ms);
+        }
+        let changed_ms = self.changed_ms?;
+        if self.present && now_ms.wrapping_sub(changed_ms) < SETTLE_MS {
+            return None;
+        }
+        self.changed_ms = None;
+        self.read_file()
+    }
+
+    fn read_file(&mut self) -> Option<&[u8]> {
+        let root = self.sectors[ROOT_SECTOR..DATA_SECTOR].as_flattened();
+        let entry = root
+            .chunks_exact(DIR_ENTRY)
+            .take_while(|entry| entry[0] != 0)
+            .filter(|entry| entry[0] != DELETED && entry[11] != ATTR_LONG_NAME)
+            .filter(|entry| entry[11] & (ATTR_VOLUME_ID | ATTR_DIRECTORY) == 0)
+            .find(|entry| entry[..11] == *FILE_NAME)?;
+        let mut cluster = u16::from_le_bytes([entry[26], entry[27]]) as usize;
+        let size = u32::from_le_bytes(entry[28..32].try_into().unwrap()) as usize;
+        let size = size.min(MAX_CONFIG);
+
+        let mut len = 0;
+        // A chain longer than the volume has clusters loops somewhere
+        for _ in 0..CLUSTERS {
+            if len == size || !(FIRST_CLUSTER..FIRST_CLUSTER + CLUSTERS).contains(&cluster) {
+                break;
+            }
+            let sector = &self.sectors[DATA_SECTOR + cluster - FIRST_CLUSTER];
+            let take = (size - len).min(BLOCK_SIZE);
+            self.text[len..len + take].copy_from_slice(&sector[..take]);
+            len += take;
+            let next = fat_entry(&self.sectors[FAT_SECTOR], cluster);
+            if next >= END_OF_CHAIN {
+                break;
+            }
+            cluster = next as usize;
+        }
+        Some(&self.text[..len])
+    }
+}
+
+impl BlockDevice for ConfigDrive {
+    fn blocks(&self) -> u32 {
+        SECTORS as u32
+    }
+
+    fn read_block(&mut self, lba: u32, block: &mut [u8; BLOCK_SIZE]) {
+        *block = self.sectors[lba as usize];
+    }
+
+    fn write_block(&mut self, lba: u32, block: &[u8; BLOCK_SIZE]) {
+        self.sectors[lba as usize] = *block;
+        self.written = true;
+    }
+
+    fn is_present(&self) -> bool {
+        self.present
+    }
+
+    fn set_present(&mut self, present: bool) {
+        self.present = present;
+    }
+}
+
+/// The 12-bit FAT entry for `cluster`, two to every three bytes
+fn fat_entry(fat: &[u8], cluster: usize) -> u16 {
+    let at = cluster * 3 / 2;
+    let pair = u16::from_le_bytes([fat[at], fat[at + 1]]);
+    if cluster.is_multiple_of(2) {
+        pair & 0xFFF
+    } else {
+        pair >> 4
+    }
+}
+
+fn set_fat(fat: &mut [u8], cluster: usize, value: u16) {
+    let at = cluster * 3 / 2;
+    let pair = u16::from_le_bytes([fat[at], fat[at + 1]]);
+    let pair = if cluster.is_multiple_of(2) {
+        pair & 0xF000 | value
+    } else {
+        pair & 0x000F | value << 4
+    };
+    fat[at..at + 2].copy_from_slice(&pair.to_le_bytes());
+}
+
+/// Writes text into the volume's data clusters, dropping what won't fit
+struct Cursor<'a> {
+    data: &'a mut [u8],
+    len: usize,
+}
+
+impl fmt::Write for Cursor<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let take = s.len().min(self.data.len() - self.len);
+        self.data[self.len..self.len + take].copy_from_slice(&s.as_bytes()[..take]);
+        self.len += take;
+        Ok(())
+    }
+}
+
+/// The running state as config.txt, written with `{}`
+///
+/// ```text
+/// # pico-display settings: edit, save, and eject the drive to apply them
+/// # effect counts from 0, of 7; leds is at most 60
+/// brightness = 128
+/// effect = 3
+/// leds = 60
+/// speed = 200
+/// primary = ff0000
+/// ```
+///
+/// The parameters are whichever of speed, intensity, primary and secondary
+/// the running effect has. Lines end CR LF, for Notepad.
+pub struct ConfigText<'a> {
+    pub brightness: u8,
+    pub effect: usize,
+    pub effects: usize,
+    pub leds: usize,
+    pub max_leds: usize,
+    pub running: &'a dyn Effect,
+}
+
+impl fmt::Display for ConfigText<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "# pico-display settings: edit, save, and eject the drive to apply them\r\n\
+             # effect counts from 0, of {}; leds is at most {}\r\n\
+             brightness = {}\r\neffect = {}\r\nleds = {}\r\n",
+            self.effects, self.max_leds, self.brightness, self.effect, self.leds
+        )?;
+        for param in PARAMS {
+            let name = param.key();
+            match self.running.param(param) {
+                Some(ParamValue::Level(level)) => write!(f, "{name} = {level}\r\n")?,
+                Some(ParamValue::Color(c)) => {
+                    write!(f, "{name} = {:02x}{:02x}{:02x}\r\n", c.r, c.g, c.b)?
+                }
+                Some(ParamValue::Palette(_)) | None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why config.txt wasn't taken, and on which line, counting from 1
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct ConfigError {
+    pub line: usize,
+    pub error: JsonError,
+}
+
+/// The changes in config.txt: `key = value` lines, blank lines and `#`
+/// comments between them
+///
+/// It takes the keys [`ConfigText`] writes, every one optional; values are
+/// checked as for a JSON document, whose [`ConfigUpdate`] this gives.
+pub fn parse(text: &[u8]) -> Result<ConfigUpdate, ConfigError> {
+    // Notepad used to start UTF-8 files with a byte order mark
+    let text = text.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(text);
+    let mut update = ConfigUpdate::default();
+    for (i, line) in text.split(|&b| b == b'\n').enumerate() {
+        let fail = |error| ConfigError { line: i + 1, error };
+        let argument = |e| match e {
+            CommandError::OutOfRange => fail(JsonError::OutOfRange),
+            _ => fail(JsonError::WrongType),
+        };
+        let line = line.trim_ascii();
+        if line.is_empty() || line[0] == b'#' {
+            continue;
+        }
+        let eq = line
+            .iter()
+            .position(|&b| b == b'=')
+            .ok_or(fail(JsonError::Syntax))?;
+        let (key, value) = (line[..eq].trim_ascii(), line[eq + 1..].trim_ascii());
+        match key {
+            b"brightness" => update.brightness = Some(parse_level(value).map_err(argument)?),
+            b"effect" => update.effect = Some(parse_decimal(value).map_err(argument)?),
+            b"leds" => update.leds = Some(parse_decimal(value).map_err(argument)?),
+            b"speed" => update.speed = Some(parse_level(value).map_err(argument)?),
+            b"intensity" => update.intensity = Some(parse_level(value).map_err(argument)?),
+            b"primary" => update.primary = Some(parse_color(value).map_err(argument)?),
+            b"secondary" => update.secondary = Some(parse_color(value).map_err(argument)?),
+            _ => return Err(fail(JsonError::UnknownKey)),
+        }
+    }
+    Ok(update)
+}
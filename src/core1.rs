@@ -0,0 +1,142 @@
+// Rendering on the RP2040's second core, so the strip's timing and the
+// I/O on core 0 don't wait on the effects
+
+use core::arch::asm;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use crate::effects::Effect;
+use crate::engine::RenderWorker;
+use crate::pio_programs::Rgb;
+use crate::platform::hal::multicore::{Multicore, Stack};
+use crate::platform::hal::pac;
+use crate::platform::hal::sio::SioFifo;
+
+/// Core 1's stack, in words; the effects render on it
+pub const STACK_WORDS: usize = 2048;
+
+/// A frame for core 1 to render; core 0 sends its address over the FIFO
+struct Job {
+    effect: *mut (dyn Effect + 'static),
+    frame: *mut [Rgb],
+    time_ms: u32,
+}
+
+/// Core 1, rendering an [`Engine`](crate::engine::Engine)'s frames while
+/// core 0 sends them
+///
+/// While a frame renders, the effect and whatever it reads, the shared
+/// audio, sensor and host pixel state, are core 1's; core 0 calls
+/// [`wait`](RenderWorker::wait) before it touches any of them. Between
+/// frames core 1 waits in RAM, so core 0 may write [`flash`](crate::flash)
+/// once it's waited.
+///
+/// ```ignore
+/// let mut core1 = Core1::spawn(&mut pac.PSM, &mut pac.PPB, sio.fifo);
+/// loop {
+///     core1.wait();
+///     // inputs and commands, touching the effects
+///     unsafe { engine.tick_on(&mut core1, &mut *effects[current], now_ms) };
+/// }
+/// ```
+pub struct Core1 {
+    fifo: SioFifo,
+    job: Option<Job>,
+    /// Core 1 hasn't said it's idle since it was last sent a job
+    busy: bool,
+}
+
+impl Core1 {
+    /// Start core 1 waiting for frames
+    ///
+    /// # Panics
+    ///
+    /// If it's called more than once, or core 1 doesn't start.
+    pub fn spawn(psm: &mut pac::PSM, ppb: &mut pac::PPB, mut fifo: SioFifo) -> Self {
+        let stack = cortex_m::singleton!(: Stack<STACK_WORDS> = Stack::new()).unwrap();
+        Multicore::new(psm, ppb, &mut fifo).cores()[1]
+            .spawn(&mut stack.mem, render_loop)
+            .unwrap();
+        // Busy until it first says it's idle, from RAM
+        Self {
+            fifo,
+            job: None,
+            busy: true,
+        }
+    }
+}
+
+impl RenderWorker for Core1 {
+    unsafe fn start(&mut self, effect: &mut dyn Effect, frame: &mut [Rgb], time_ms: u32) {
+        self.wait();
+        let effect =
+            core::mem::transmute::<*mut (dyn Effect + '_), *mut (dyn Effect + 'static)>(effect);
+        let job = self.job.insert(Job {
+            effect,
+            frame,
+            time_ms,
+        });
+        let address = job as *const Job as usize as u32;
+        // Everything core 0 wrote for the frame before core 1 hears of it
+        compiler_fence(Ordering::Release);
+        self.fifo.write_blocking(address);
+        self.busy = true;
+    }
+
+    fn wait(&mut self) {
+        if self.busy {
+            self.fifo.read_blocking();
+            compiler_fence(Ordering::Acquire);
+            self.busy = false;
+        }
+    }
+}
+
+/// Core 1 from start-up: say it's idle, render what's sent, again
+fn render_loop() {
+    loop {
+        // Safety: core 0 only sends the address of its `Job`, which and
+        // whose effect and frame it leaves alone until we're idle again
+        unsafe {
+            let job = &*(idle_until_job() as usize as *const Job);
+            (*job.effect).render(&mut *job.frame, job.time_ms);
+        }
+    }
+}
+
+/// Tell core 0 this core is idle, then wait for its next word
+///
+/// In RAM, as core 0 may write flash till it sends one, and in assembly,
+/// as an unoptimised build calls out to flash for register accesses.
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+fn idle_until_job() -> u32 {
+    let word;
+    // Safety: only core 1's end of the SIO FIFO, at 0xD000_0000, is used
+    unsafe {
+        asm!(
+            "movs r0, #0xD0",
+            "lsls r0, r0, #24",
+            // Room in FIFO_ST's RDY, bit 1, for the word saying so
+            "2:",
+            "ldr r1, [r0, #0x50]",
+            "lsrs r1, r1, #2",
+            "bcc 2b",
+            "movs r1, #0",
+            "str r1, [r0, #0x54]",
+            "sev",
+            // Then sleep till VLD, bit 0, says a word's come back
+            "3:",
+            "ldr r1, [r0, #0x50]",
+            "lsrs r1, r1, #1",
+            "bcs 4f",
+            "wfe",
+            "b 3b",
+            "4:",
+            "ldr r0, [r0, #0x58]",
+            out("r0") word,
+            out("r1") _,
+            options(nostack),
+        );
+    }
+    word
+}
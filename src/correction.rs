@@ -0,0 +1,185 @@
+// Output-stage corrections applied while packing pixels
+
+use crate::math::scale8;
+use crate::pio_programs::{Rgb, Rgbw};
+
+/// Gamma 2.8 curve: LED brightness is linear in duty cycle but the eye isn't,
+/// so linear values look washed out without it
+pub const GAMMA8: [u8; 256] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 5, 5, 5,
+    5, 6, 6, 6, 6, 7, 7, 7, 7, 8, 8, 8, 9, 9, 9, 10, 10, 10, 11, 11, 11, 12, 12, 13, 13, 13, 14,
+    14, 15, 15, 16, 16, 17, 17, 18, 18, 19, 19, 20, 20, 21, 21, 22, 22, 23, 24, 24, 25, 25, 26, 27,
+    27, 28, 29, 29, 30, 31, 32, 32, 33, 34, 35, 35, 36, 37, 38, 39, 39, 40, 41, 42, 43, 44, 45, 46,
+    47, 48, 49, 50, 50, 51, 52, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 66, 67, 68, 69, 70, 72,
+    73, 74, 75, 77, 78, 79, 81, 82, 83, 85, 86, 87, 89, 90, 92, 93, 95, 96, 98, 99, 101, 102, 104,
+    105, 107, 109, 110, 112, 114, 115, 117, 119, 120, 122, 124, 126, 127, 129, 131, 133, 135, 137,
+    138, 140, 142, 144, 146, 148, 150, 152, 154, 156, 158, 160, 162, 164, 167, 169, 171, 173, 175,
+    177, 180, 182, 184, 186, 189, 191, 193, 196, 198, 200, 203, 205, 208, 210, 213, 215, 218, 220,
+    223, 225, 228, 231, 233, 236, 239, 241, 244, 247, 249, 252, 255,
+];
+
+/// CIE 1931 lightness: the luminance that looks `index`/255 as bright
+pub const CIE8: [u8; 256] = [
+    0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 3, 3, 4,
+    4, 4, 4, 4, 4, 5, 5, 5, 5, 5, 6, 6, 6, 6, 6, 7, 7, 7, 7, 8, 8, 8, 8, 9, 9, 9, 10, 10, 10, 10,
+    11, 11, 11, 12, 12, 12, 13, 13, 13, 14, 14, 15, 15, 15, 16, 16, 17, 17, 17, 18, 18, 19, 19, 20,
+    20, 21, 21, 22, 22, 23, 23, 24, 24, 25, 25, 26, 26, 27, 28, 28, 29, 29, 30, 31, 31, 32, 32, 33,
+    34, 34, 35, 36, 37, 37, 38, 39, 39, 40, 41, 42, 43, 43, 44, 45, 46, 47, 47, 48, 49, 50, 51, 52,
+    53, 54, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 65, 66, 67, 68, 70, 71, 72, 73, 74, 75, 76,
+    77, 79, 80, 81, 82, 83, 85, 86, 87, 88, 90, 91, 92, 94, 95, 96, 98, 99, 100, 102, 103, 105,
+    106, 108, 109, 110, 112, 113, 115, 116, 118, 120, 121, 123, 124, 126, 128, 129, 131, 132, 134,
+    136, 138, 139, 141, 143, 145, 146, 148, 150, 152, 154, 155, 157, 159, 161, 163, 165, 167, 169,
+    171, 173, 175, 177, 179, 181, 183, 185, 187, 189, 191, 193, 196, 198, 200, 202, 204, 207, 209,
+    211, 214, 216, 218, 220, 223, 225, 228, 230, 232, 235, 237, 240, 242, 245, 247, 250, 252, 255,
+];
+
+/// Map a linear channel value through [`GAMMA8`]
+pub fn gamma(c: u8) -> u8 {
+    GAMMA8[c as usize]
+}
+
+/// How the global brightness setting maps to the scale applied to pixels
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum BrightnessCurve {
+    /// Use the setting as-is; fades look fast at the bright end
+    Linear,
+    /// Through [`GAMMA8`]
+    Gamma,
+    /// Through [`CIE8`], so equal steps look equally bright
+    Cie,
+}
+
+impl BrightnessCurve {
+    pub fn apply(self, level: u8) -> u8 {
+        match self {
+            BrightnessCurve::Linear => level,
+            BrightnessCurve::Gamma => GAMMA8[level as usize],
+            BrightnessCurve::Cie => CIE8[level as usize],
+        }
+    }
+}
+
+/// White-point presets, as per-channel multipliers that tint white toward
+/// the given color temperature
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum ColorTemperature {
+    /// Warm white, incandescent bulbs
+    K2700,
+    /// Neutral white
+    K4000,
+    /// Daylight
+    K6500,
+    /// No tint
+    Uncorrected,
+}
+
+impl ColorTemperature {
+    pub fn multipliers(self) -> Rgb {
+        match self {
+            ColorTemperature::K2700 => Rgb::new(255, 167, 87),
+            ColorTemperature::K4000 => Rgb::new(255, 206, 166),
+            ColorTemperature::K6500 => Rgb::new(255, 249, 253),
+            ColorTemperature::Uncorrected => Rgb::WHITE,
+        }
+    }
+}
+
+/// Typical calibration for 5050 WS2812 strips, whose blue and green run
+/// brighter than red
+pub const TYPICAL_LED_STRIP: Rgb = Rgb {
+    r: 255,
+    g: 176,
+    b: 240,
+};
+
+/// How an RGBW driver moves the gray part of plain RGB colors onto the
+/// white LED, which is brighter and cleaner per milliamp than mixing it
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum WhiteExtraction {
+    /// Leave the white LED to explicit RGBW colors
+    Off,
+    /// Move the smallest channel to white, treating the white LED as pure white
+    MinChannel,
+    /// Like `MinChannel`, with the white LED's own tint expressed as the RGB
+    /// color it matches, e.g. warm white LEDs at `Rgb::new(255, 190, 120)`
+    Calibrated(Rgb),
+}
+
+impl WhiteExtraction {
+    pub fn extract(self, led: Rgbw) -> Rgbw {
+        let white = match self {
+            WhiteExtraction::Off => return led,
+            WhiteExtraction::MinChannel => Rgb::WHITE,
+            WhiteExtraction::Calibrated(white) => white,
+        };
+        // How far the white LED can go before some channel runs out
+        let w = [(led.r, white.r), (led.g, white.g), (led.b, white.b)]
+            .iter()
+            .filter(|&&(_, white)| white > 0)
+            .map(|&(c, white)| c as u16 * 255 / white as u16)
+            .min()
+            .unwrap_or(0)
+            .min(255);
+        let take = |c: u8, white: u8| c - (w * white as u16 / 255) as u8;
+        Rgbw::new(
+            take(led.r, white.r),
+            take(led.g, white.g),
+            take(led.b, white.b),
+            led.w.saturating_add(w as u8),
+        )
+    }
+}
+
+/// Corrections a driver applies to every pixel on its way out, so effects
+/// can work in plain linear values
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Correction {
+    pub gamma: bool,
+    /// Global brightness, applied before gamma so dimming looks even
+    pub brightness: u8,
+    pub brightness_curve: BrightnessCurve,
+    /// Per-channel multipliers evening out this strip's LEDs, 255 for none
+    pub calibration: Rgb,
+    pub temperature: ColorTemperature,
+    /// Only used by drivers with a white channel
+    pub white: WhiteExtraction,
+}
+
+impl Correction {
+    /// Pixels go out exactly as given
+    pub const NONE: Correction = Correction {
+        gamma: false,
+        brightness: 255,
+        brightness_curve: BrightnessCurve::Linear,
+        calibration: Rgb::WHITE,
+        temperature: ColorTemperature::Uncorrected,
+        white: WhiteExtraction::Off,
+    };
+
+    pub fn apply(&self, led: Rgbw) -> Rgbw {
+        let tint = self.temperature.multipliers();
+        let brightness = self.brightness_curve.apply(self.brightness);
+        let channel = |c: u8, calibration: u8, tint: u8| {
+            let c = scale8(scale8(scale8(c, calibration), tint), brightness);
+            if self.gamma {
+                gamma(c)
+            } else {
+                c
+            }
+        };
+        // The white LED has its own color, only brightness and gamma apply
+        Rgbw::new(
+            channel(led.r, self.calibration.r, tint.r),
+            channel(led.g, self.calibration.g, tint.g),
+            channel(led.b, self.calibration.b, tint.b),
+            channel(led.w, 255, 255),
+        )
+    }
+}
+
+impl Default for Correction {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
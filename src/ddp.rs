@@ -0,0 +1,118 @@
+// DDP (Distributed Display Protocol), pixel data over UDP with next to no
+// overhead, as xLights, LedFx and WLED send it
+
+use crate::host::HostFrame;
+use crate::pio_programs::Rgb;
+
+/// UDP port DDP is sent to
+pub const DDP_PORT: u16 = 4048;
+
+/// A stream this quiet has stopped, as WLED times out realtime data
+pub const DDP_TIMEOUT_MS: u32 = 2500;
+
+const HEADER_LEN: usize = 10;
+const VERSION_MASK: u8 = 0xC0;
+const VERSION_1: u8 = 0x40;
+const FLAG_TIMECODE: u8 = 0x10;
+const FLAG_QUERY: u8 = 0x02;
+const FLAG_PUSH: u8 = 0x01;
+
+/// Destination IDs: the display itself, and every device
+const ID_DISPLAY: u8 = 1;
+const ID_ALL: u8 = 255;
+
+/// What [`DdpReceiver::receive`] made of a datagram
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum DdpEvent {
+    /// Not DDP pixel data for the strip
+    Ignored,
+    /// Pixels were written, with more of the frame to come
+    Data,
+    /// Pixels were written and the sender marked the frame complete
+    Push,
+}
+
+/// Takes DDP packets and writes their pixel data on the strip
+///
+/// Data is 8-bit RGB at a byte offset from the first pixel, so one frame
+/// can come in several packets, the last of them flagged push. The pixels
+/// go on the strip as they arrive rather than waiting for the push; at
+/// this strip's frame rate a frame split over packets very rarely shows
+/// half done. Types other than RGB (HSL, RGBW, grayscale, or 16-bit
+/// channels), queries and the config and status IDs are ignored.
+///
+/// ```ignore
+/// if ddp.receive(datagram, now_ms, &host_frame) != DdpEvent::Ignored {
+///     current = host_effect;
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DdpReceiver {
+    last_ms: Option<u32>,
+}
+
+impl DdpReceiver {
+    pub const fn new() -> Self {
+        Self { last_ms: None }
+    }
+
+    /// Whether data has come in lately
+    pub fn is_streaming(&self, now_ms: u32) -> bool {
+        self.last_ms
+            .is_some_and(|ms| now_ms.wrapping_sub(ms) <= DDP_TIMEOUT_MS)
+    }
+
+    /// Take one UDP payload
+    pub fn receive<const N: usize>(
+        &mut self,
+        datagram: &[u8],
+        now_ms: u32,
+        frame: &HostFrame<N>,
+    ) -> DdpEvent {
+        let Some(header) = datagram.get(..HEADER_LEN) else {
+            return DdpEvent::Ignored;
+        };
+        let flags = header[0];
+        // Pixel type in bits 5-3 (0 undefined, 1 RGB), bits per channel in
+        // 2-0 (0 undefined, 3 eight)
+        let (kind, size) = (header[2] >> 3 & 0x07, header[2] & 0x07);
+        if flags & VERSION_MASK != VERSION_1
+            || flags & FLAG_QUERY != 0
+            || !matches!(header[3], ID_DISPLAY | ID_ALL)
+            || kind > 1
+            || !matches!(size, 0 | 3)
+        {
+            return DdpEvent::Ignored;
+        }
+        let offset = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let len = u16::from_be_bytes([header[8], header[9]]) as usize;
+        let start = if flags & FLAG_TIMECODE != 0 {
+            HEADER_LEN + 4
+        } else {
+            HEADER_LEN
+        };
+        let Some(data) = datagram.get(start..start + len) else {
+            return DdpEvent::Ignored;
+        };
+
+        // Offsets count bytes, so a packet can start part way through a pixel
+        for (i, &byte) in data.iter().enumerate() {
+            let at = offset + i;
+            let Some(Rgb { r, g, b }) = frame.get(at / 3) else {
+                break;
+            };
+            let color = match at % 3 {
+                0 => Rgb::new(byte, g, b),
+                1 => Rgb::new(r, byte, b),
+                _ => Rgb::new(r, g, byte),
+            };
+            frame.set(at / 3, color);
+        }
+        self.last_ms = Some(now_ms);
+        if flags & FLAG_PUSH != 0 {
+            DdpEvent::Push
+        } else {
+            DdpEvent::Data
+        }
+    }
+}
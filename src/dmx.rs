@@ -0,0 +1,69 @@
+// DMX universes laid along the strip, for the network lighting protocols
+
+use crate::host::HostFrame;
+
+/// Channels in a universe
+pub const DMX_CHANNELS: usize = 512;
+
+/// Whole RGB pixels a universe carries
+pub const MAX_UNIVERSE_PIXELS: u16 = (DMX_CHANNELS / 3) as u16;
+
+/// Universes whose sequence numbers the receivers track; packets for
+/// universes past these are taken in any order
+pub const MAX_UNIVERSES: usize = 8;
+
+/// Which universes drive the strip, and how many pixels each covers
+///
+/// Universe `first_universe` starts at pixel 0 and each one after carries
+/// on where the last stopped, three channels (R, G, B) to a pixel. 170
+/// pixels a universe is what lighting software assumes; fewer leaves the
+/// spare channels at the end unused, to line universes up with the
+/// strip's segments.
+///
+/// ```ignore
+/// let map = UniverseMap::new(1).pixels_per_universe(150);
+/// map.write(universe, channels, &host_frame);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct UniverseMap {
+    pub first_universe: u16,
+    pub pixels_per_universe: u16,
+}
+
+impl UniverseMap {
+    pub const fn new(first_universe: u16) -> Self {
+        Self {
+            first_universe,
+            pixels_per_universe: MAX_UNIVERSE_PIXELS,
+        }
+    }
+
+    pub fn pixels_per_universe(mut self, pixels: u16) -> Self {
+        self.pixels_per_universe = pixels.clamp(1, MAX_UNIVERSE_PIXELS);
+        self
+    }
+
+    /// Which of ours `universe` is, counting from 0, if it lands on a strip
+    /// `len` pixels long
+    pub fn index(&self, universe: u16, len: usize) -> Option<usize> {
+        let index = universe.checked_sub(self.first_universe)? as usize;
+        (index * (self.pixels_per_universe as usize) < len).then_some(index)
+    }
+
+    /// Put a universe's channel data on its pixels; false if the universe
+    /// isn't one of the strip's
+    pub fn write<const N: usize>(
+        &self,
+        universe: u16,
+        channels: &[u8],
+        frame: &HostFrame<N>,
+    ) -> bool {
+        let Some(index) = self.index(universe, frame.len()) else {
+            return false;
+        };
+        let pixels = self.pixels_per_universe as usize;
+        let len = channels.len().min(pixels * 3);
+        frame.write_rgb(index * pixels, &channels[..len]);
+        true
+    }
+}
@@ -0,0 +1,805 @@
+// Non-blocking WS2812 driver built on a PIO state machine and a DMA channel
+
+use core::convert::Infallible;
+
+use crate::platform::hal::{
+    dma::{single_buffer, SingleChannel, WriteTarget},
+    gpio::{DynPinId, Function, Pin, PinId, PullNone, PullType, ValidFunction},
+    pac::{PIO0, PIO1, RESETS},
+    pio::{
+        InstalledProgram, PIOBuilder, PIOExt, PinDir, PioIRQ, Running, ShiftDirection,
+        StateMachine, StateMachineIndex, Tx, UninitStateMachine, PIO, SM0, SM1, SM2, SM3,
+    },
+};
+
+use embedded_dma::ReadBuffer;
+use smart_leds_trait::{SmartLedsWrite, RGB8};
+
+use crate::chipset::{Chipset, Tm1814Current};
+use crate::correction::{BrightnessCurve, ColorTemperature, Correction, WhiteExtraction};
+use crate::output::LedOutput;
+use crate::pio_programs::{self, BitCycles, ColorOrder, Rgb, Rgbw};
+
+/// WS2812 data rate
+pub const WS2812_BIT_RATE_HZ: u32 = 800_000;
+
+/// Slack on top of the reset time before the next frame starts
+const RESET_MARGIN_US: u32 = 20;
+
+/// Distinct chipset timings one PIO block can hold programs for
+const MAX_PROGRAMS: usize = 4;
+
+/// Fixed-point PIO clock divisor giving `bit_rate_hz` with the stock WS2812 program
+///
+/// Returns the integer and 1/256th fractional parts for
+/// `clock_divisor_fixed_point`. At 125 MHz and 800 kHz that's 14 + 52/256.
+pub fn clock_divisor(sys_clk_hz: u32, bit_rate_hz: u32) -> (u16, u8) {
+    fixed_point_divisor(
+        sys_clk_hz,
+        bit_rate_hz * pio_programs::WS2812_CYCLES_PER_BIT,
+    )
+}
+
+/// Fixed-point divisor running a state machine at `pio_clk_hz`
+pub fn fixed_point_divisor(sys_clk_hz: u32, pio_clk_hz: u32) -> (u16, u8) {
+    let pio_clk_hz = pio_clk_hz as u64;
+    let div_256 = (sys_clk_hz as u64 * 256 + pio_clk_hz / 2) / pio_clk_hz;
+    // The divisor is 16.8 bits wide and can't go below 1
+    let div_256 = div_256.clamp(256, (u16::MAX as u64) << 8 | 0xFF);
+    ((div_256 >> 8) as u16, div_256 as u8)
+}
+
+/// Cycles of `cortex_m::asm::delay` to wait once the FIFO is empty: the
+/// pixel still in the OSR, then the reset latch
+fn reset_cycles(sys_clk_hz: u32, chipset: Chipset, bits_per_pixel: u8) -> u32 {
+    let pixel_us = bits_per_pixel as u32 * 1_000_000 / chipset.bit_rate_hz();
+    sys_clk_hz / 1_000_000 * (pixel_us + chipset.timing().reset_us + RESET_MARGIN_US)
+}
+
+type RunningSm<P, SM> = StateMachine<(P, SM), Running>;
+
+/// Data pin handed over to the PIO block, kept by the driver so nothing else can claim it
+pub type DataPin<P> = Pin<DynPinId, <P as PIOExt>::PinFunction, PullNone>;
+
+/// Hand `pin` over to PIO block `P`
+pub(crate) fn into_data_pin<P, I, F, PT>(pin: Pin<I, F, PT>) -> DataPin<P>
+where
+    P: PIOExt,
+    I: PinId + ValidFunction<P::PinFunction>,
+    F: Function,
+    PT: PullType,
+{
+    pin.reconfigure::<P::PinFunction, PullNone>().into_dyn_pin()
+}
+
+/// A configured, running state machine with its FIFO and pin
+struct Started<P: PIOExt, SM: StateMachineIndex> {
+    sm: RunningSm<P, SM>,
+    tx: Tx<(P, SM)>,
+    pin: DataPin<P>,
+    backup: Option<DataPin<P>>,
+}
+
+/// Bit timing, polarity and mirroring a program was assembled for
+type ProgramKey = (BitCycles, bool, bool);
+
+/// A PIO block shared by WS2812-style strips on its state machines
+///
+/// Each of the four state machines can drive its own strip, with its own pin,
+/// chipset, length and frame buffer. The bit program for a chipset's timing
+/// is installed the first time a strip needs it and shared after that:
+///
+/// ```ignore
+/// let mut ws_pio = Ws2812Pio::new(pio, clocks.system_clock.freq().to_Hz());
+/// let a = ws_pio.strip(sm0, pins.gpio15).build(dma.ch0, frame_a);
+/// let b = ws_pio.strip(sm1, pins.gpio16).build(dma.ch1, frame_b);
+/// ```
+pub struct Ws2812Pio<P: PIOExt> {
+    pio: PIO<P>,
+    programs: [Option<(ProgramKey, InstalledProgram<P>)>; MAX_PROGRAMS],
+    sys_clk_hz: u32,
+}
+
+impl<P: PIOExt> Ws2812Pio<P> {
+    /// `sys_clk_hz` is the system clock the PIO block runs from, used to
+    /// derive the bit timing
+    pub fn new(pio: PIO<P>, sys_clk_hz: u32) -> Self {
+        Self {
+            pio,
+            programs: Default::default(),
+            sys_clk_hz,
+        }
+    }
+
+    /// Start configuring `sm` to drive a strip on `pin`
+    ///
+    /// The pin is switched to the PIO function and its number becomes the
+    /// side-set base, so the pin is only named once.
+    pub fn strip<SM, I, F, PT>(
+        &mut self,
+        sm: UninitStateMachine<(P, SM)>,
+        pin: Pin<I, F, PT>,
+    ) -> Ws2812Builder<'_, P, SM>
+    where
+        SM: StateMachineIndex,
+        I: PinId + ValidFunction<P::PinFunction>,
+        F: Function,
+        PT: PullType,
+    {
+        Ws2812Builder {
+            ws_pio: self,
+            sm,
+            pin: into_data_pin::<P, _, _, _>(pin),
+            backup: None,
+            chipset: Chipset::Ws2812b,
+            color_order: None,
+            tm1814_current: Tm1814Current::DEFAULT,
+        }
+    }
+
+    /// The bit program for `key`, installing it on first use
+    fn program(&mut self, key: ProgramKey) -> InstalledProgram<P> {
+        let (cycles, inverted, mirrored) = key;
+        // Safety: programs are never uninstalled, Ws2812Pio doesn't expose `uninstall`
+        if let Some((_, program)) = self.programs.iter().flatten().find(|(k, _)| *k == key) {
+            return unsafe { program.share() };
+        }
+        let program = self
+            .pio
+            .install(&pio_programs::ws2812_timed(cycles, inverted, mirrored))
+            .unwrap();
+        let slot = self
+            .programs
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .unwrap();
+        *slot = Some((key, unsafe { program.share() }));
+        program
+    }
+
+    /// The underlying PIO block, for installing other programs alongside
+    pub fn pio_mut(&mut self) -> &mut PIO<P> {
+        &mut self.pio
+    }
+}
+
+/// Configures one state machine for WS2812 output, see [`Ws2812Pio::strip`]
+pub struct Ws2812Builder<'a, P: PIOExt, SM: StateMachineIndex> {
+    ws_pio: &'a mut Ws2812Pio<P>,
+    sm: UninitStateMachine<(P, SM)>,
+    pin: DataPin<P>,
+    backup: Option<DataPin<P>>,
+    chipset: Chipset,
+    color_order: Option<ColorOrder>,
+    tm1814_current: Tm1814Current,
+}
+
+impl<P: PIOExt, SM: StateMachineIndex> Ws2812Builder<'_, P, SM> {
+    /// Set the strip's pixel chip, `Chipset::Ws2812b` by default
+    ///
+    /// The bit program, PIO clock divisor, bits per pixel and the wait
+    /// before the next frame all follow from the chipset's timing.
+    pub fn chipset(mut self, chipset: Chipset) -> Self {
+        self.chipset = chipset;
+        self
+    }
+
+    /// Override the chipset's channel order, e.g. `ColorOrder::Rgb` for
+    /// strips wired red first
+    ///
+    /// Four-channel orders send 32-bit pixels whatever the chipset.
+    pub fn color_order(mut self, order: ColorOrder) -> Self {
+        self.color_order = Some(order);
+        self
+    }
+
+    /// Send the same signal on `pin` too, for the backup data input (BIN)
+    /// of WS2813/WS2815 strips
+    ///
+    /// Feeding DIN and BIN together lets the strip keep working past a dead
+    /// first pixel. `pin` must be the GPIO right after the data pin, as
+    /// side-set drives consecutive pins.
+    pub fn backup_pin<I, F, PT>(mut self, pin: Pin<I, F, PT>) -> Self
+    where
+        I: PinId + ValidFunction<P::PinFunction>,
+        F: Function,
+        PT: PullType,
+    {
+        let pin = into_data_pin::<P, _, _, _>(pin);
+        assert_eq!(
+            pin.id().num,
+            self.pin.id().num + 1,
+            "backup pin must follow the data pin"
+        );
+        self.backup = Some(pin);
+        self
+    }
+
+    /// Set the constant current a `Chipset::Tm1814` strip is configured
+    /// with at the start of every frame
+    pub fn tm1814_current(mut self, current: Tm1814Current) -> Self {
+        self.tm1814_current = current;
+        self
+    }
+
+    fn order(&self) -> ColorOrder {
+        self.color_order.unwrap_or(self.chipset.color_order())
+    }
+
+    /// Words sent ahead of the pixels in every frame
+    fn preamble(&self) -> Option<[u32; 2]> {
+        (self.chipset == Chipset::Tm1814).then(|| self.tm1814_current.words())
+    }
+
+    /// Finish with DMA output from the static `frame` buffer on channel `ch`
+    ///
+    /// For `Chipset::Tm1814` the buffer needs two extra words for the
+    /// current configuration.
+    pub fn build<CH: SingleChannel, const N: usize>(
+        self,
+        ch: CH,
+        frame: Frame<N>,
+    ) -> Ws2812<P, SM, CH, N> {
+        let reset_cycles = reset_cycles(
+            self.ws_pio.sys_clk_hz,
+            self.chipset,
+            self.order().bits_per_pixel(),
+        );
+        let order = self.order();
+        let preamble = self.preamble();
+        let Started {
+            sm,
+            tx,
+            pin,
+            backup,
+        } = self.start();
+        Ws2812 {
+            _sm: sm,
+            _pin: pin,
+            _backup: backup,
+            reset_cycles,
+            encoder: Encoder::new(order),
+            preamble,
+            // Whatever the strip showed before, cleared by the first frame
+            shown: N,
+            stream: DmaStream::new(ch, frame, tx),
+        }
+    }
+
+    /// Finish with interrupt-driven output, see [`IrqWs2812`]
+    ///
+    /// As with `build`, `N` counts the TM1814 configuration words.
+    pub fn build_irq<const N: usize>(self) -> IrqWs2812<P, SM, N> {
+        let reset_cycles = reset_cycles(
+            self.ws_pio.sys_clk_hz,
+            self.chipset,
+            self.order().bits_per_pixel(),
+        );
+        let order = self.order();
+        let preamble = self.preamble();
+        let Started {
+            sm,
+            tx,
+            pin,
+            backup,
+        } = self.start();
+        IrqWs2812 {
+            _sm: sm,
+            _pin: pin,
+            _backup: backup,
+            reset_cycles,
+            encoder: Encoder::new(order),
+            preamble,
+            tx,
+            frame: [0; N],
+            len: 0,
+            shown: N,
+            next: 0,
+        }
+    }
+
+    /// Configure and start the state machine
+    fn start(self) -> Started<P, SM> {
+        let pin_id = self.pin.id().num;
+        let cycles = self.chipset.bit_cycles();
+        let (div_int, div_frac) = fixed_point_divisor(
+            self.ws_pio.sys_clk_hz,
+            self.chipset.bit_rate_hz() * cycles.total(),
+        );
+
+        let mirrored = self.backup.is_some();
+        let program = self
+            .ws_pio
+            .program((cycles, self.chipset.inverted(), mirrored));
+
+        let (mut sm, _, tx) = PIOBuilder::from_program(program)
+            .side_set_pin_base(pin_id)
+            .out_shift_direction(ShiftDirection::Left)
+            .autopull(true)
+            .pull_threshold(self.order().bits_per_pixel()) // Pull once per pixel
+            .clock_divisor_fixed_point(div_int, div_frac)
+            .build(self.sm);
+        sm.set_pindirs([(pin_id, PinDir::Output)]);
+        if mirrored {
+            sm.set_pindirs([(pin_id + 1, PinDir::Output)]);
+        }
+
+        Started {
+            sm: sm.start(),
+            tx,
+            pin: self.pin,
+            backup: self.backup,
+        }
+    }
+}
+
+pub(crate) type Buffer<W, const N: usize> = &'static mut [W; N];
+pub(crate) type Frame<const N: usize> = Buffer<u32, N>;
+
+/// A DMA destination that can tell when the last word has left the hardware
+pub(crate) trait Drain: WriteTarget<TransmittedWord: 'static> {
+    fn is_drained(&self) -> bool;
+}
+
+impl<P: PIOExt, SM: StateMachineIndex> Drain for Tx<(P, SM)> {
+    fn is_drained(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+/// The first `len` words of a static buffer, as a DMA source
+struct Prefix<W: 'static, const N: usize> {
+    frame: Buffer<W, N>,
+    len: usize,
+}
+
+// Safety: the words are the static buffer's, which the transfer owns while
+// it reads them, and `len` is at most its length
+unsafe impl<W: 'static, const N: usize> ReadBuffer for Prefix<W, N> {
+    type Word = W;
+
+    unsafe fn read_buffer(&self) -> (*const W, usize) {
+        (self.frame.as_ptr(), self.len)
+    }
+}
+
+enum State<CH: SingleChannel, TO: Drain, const N: usize> {
+    /// No transfer running, the channel, buffer and FIFO are ours
+    Idle {
+        ch: CH,
+        frame: Buffer<TO::TransmittedWord, N>,
+        tx: TO,
+    },
+    /// A frame is being streamed into the FIFO
+    Busy(single_buffer::Transfer<CH, Prefix<TO::TransmittedWord, N>, TO>),
+}
+
+/// A static word buffer streamed into a FIFO (a state machine's, usually) by one DMA channel
+pub(crate) struct DmaStream<CH: SingleChannel, TO: Drain, const N: usize> {
+    state: Option<State<CH, TO, N>>,
+}
+
+impl<CH: SingleChannel, TO: Drain, const N: usize> DmaStream<CH, TO, N> {
+    pub(crate) fn new(ch: CH, frame: Buffer<TO::TransmittedWord, N>, tx: TO) -> Self {
+        Self {
+            state: Some(State::Idle { ch, frame, tx }),
+        }
+    }
+
+    /// Whether a transfer is running or the FIFO is still draining
+    pub(crate) fn is_busy(&self) -> bool {
+        match &self.state {
+            Some(State::Busy(transfer)) => !transfer.is_done(),
+            Some(State::Idle { tx, .. }) => !tx.is_drained(),
+            None => false,
+        }
+    }
+
+    /// Wait for the previous buffer to drain plus `settle_cycles`, let `fill`
+    /// write the next one, then start streaming it
+    pub(crate) fn send(
+        &mut self,
+        settle_cycles: u32,
+        fill: impl FnOnce(&mut [TO::TransmittedWord; N]),
+    ) {
+        self.send_prefix(settle_cycles, |frame| {
+            fill(frame);
+            N
+        });
+    }
+
+    /// Like `send`, streaming only as many words as `fill` returns
+    pub(crate) fn send_prefix(
+        &mut self,
+        settle_cycles: u32,
+        fill: impl FnOnce(&mut [TO::TransmittedWord; N]) -> usize,
+    ) {
+        let (ch, frame, tx) = match self.state.take().unwrap() {
+            State::Idle { ch, frame, tx } => (ch, frame, tx),
+            State::Busy(transfer) => {
+                let (ch, prefix, tx) = transfer.wait();
+                (ch, prefix.frame, tx)
+            }
+        };
+
+        // DMA completion means the FIFO is loaded, not that the last word has been shifted out
+        while !tx.is_drained() {
+            cortex_m::asm::nop();
+        }
+        cortex_m::asm::delay(settle_cycles);
+
+        let len = fill(frame).min(N);
+        let transfer = single_buffer::Config::new(ch, Prefix { frame, len }, tx).start();
+        self.state = Some(State::Busy(transfer));
+    }
+}
+
+/// Turns pixels into FIFO words: white extraction, output corrections, then
+/// channel order
+#[derive(Copy, Clone)]
+struct Encoder {
+    order: ColorOrder,
+    correction: Correction,
+}
+
+impl Encoder {
+    fn new(order: ColorOrder) -> Self {
+        Self {
+            order,
+            correction: Correction::NONE,
+        }
+    }
+
+    fn pack(self, led: Rgbw) -> u32 {
+        let led = if self.order.has_white() {
+            self.correction.white.extract(led)
+        } else {
+            led
+        };
+        self.order.pack(self.correction.apply(led))
+    }
+}
+
+/// WS2812 strip driven by one PIO state machine, fed by one DMA channel
+///
+/// `write_frame` packs the pixels into a static frame buffer and starts the
+/// DMA transfer, returning straight away. `is_busy` reports whether the
+/// previous frame is still being clocked out.
+pub struct Ws2812<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const N: usize> {
+    _sm: RunningSm<P, SM>,
+    _pin: DataPin<P>,
+    _backup: Option<DataPin<P>>,
+    reset_cycles: u32,
+    encoder: Encoder,
+    preamble: Option<[u32; 2]>,
+    /// Words the last frame lit, so a shorter one blanks the rest once
+    shown: usize,
+    stream: DmaStream<CH, Tx<(P, SM)>, N>,
+}
+
+impl<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const N: usize> Ws2812<P, SM, CH, N> {
+    /// Whether a frame is still being transferred or shifted out
+    pub fn is_busy(&self) -> bool {
+        self.stream.is_busy()
+    }
+
+    /// Apply the [`GAMMA8`](crate::correction::GAMMA8) curve to every pixel
+    /// as it's packed, so effects can work in linear values
+    pub fn set_gamma(&mut self, enabled: bool) {
+        self.encoder.correction.gamma = enabled;
+    }
+
+    /// Scale every channel by `level` at output time, 255 for full brightness
+    ///
+    /// Effects keep drawing full-range colors; the whole strip dims from here.
+    pub fn set_brightness(&mut self, level: u8) {
+        self.encoder.correction.brightness = level;
+    }
+
+    /// Pick how `set_brightness` levels map to output, `BrightnessCurve::Cie`
+    /// for fades that look even
+    pub fn set_brightness_curve(&mut self, curve: BrightnessCurve) {
+        self.encoder.correction.brightness_curve = curve;
+    }
+
+    /// Per-channel multipliers for this strip's LEDs, e.g.
+    /// [`TYPICAL_LED_STRIP`](crate::correction::TYPICAL_LED_STRIP)
+    pub fn set_calibration(&mut self, multipliers: Rgb) {
+        self.encoder.correction.calibration = multipliers;
+    }
+
+    /// Tint white toward a color temperature, on top of the calibration
+    pub fn set_color_temperature(&mut self, temperature: ColorTemperature) {
+        self.encoder.correction.temperature = temperature;
+    }
+
+    /// On RGBW strips, move the common part of RGB colors onto the white LED
+    pub fn set_white_extraction(&mut self, extraction: WhiteExtraction) {
+        self.encoder.correction.white = extraction;
+    }
+
+    /// Start sending `pixels` to the strip
+    ///
+    /// Waits for the previous frame and the reset latch first, so calling this
+    /// back to back is safe. Pixels beyond the frame buffer length are ignored
+    /// and missing ones are sent as black, only as far as the last frame
+    /// reached, so a short strip isn't clocked for the whole buffer.
+    pub fn write_frame(&mut self, pixels: &[Rgb]) {
+        self.write_pixels(pixels.iter().copied());
+    }
+
+    /// Like `write_frame`, for strips with a white channel
+    pub fn write_frame_rgbw(&mut self, pixels: &[Rgbw]) {
+        let encoder = self.encoder;
+        self.write_words(pixels.iter().map(|&led| encoder.pack(led)));
+    }
+
+    fn write_pixels(&mut self, pixels: impl Iterator<Item = Rgb>) {
+        let encoder = self.encoder;
+        self.write_words(pixels.map(|led| encoder.pack(led.into())));
+    }
+
+    fn write_words(&mut self, words: impl Iterator<Item = u32>) {
+        let words = self.preamble.into_iter().flatten().chain(words);
+        let shown = &mut self.shown;
+        self.stream.send_prefix(self.reset_cycles, |frame| {
+            let mut len = 0;
+            for (slot, word) in frame.iter_mut().zip(words) {
+                *slot = word;
+                len += 1;
+            }
+            let end = len.max(*shown);
+            frame[len..end].fill(0);
+            *shown = len;
+            end
+        });
+    }
+}
+
+/// WS2812 strip fed from the PIO TX FIFO-not-full interrupt instead of DMA
+///
+/// Useful when every DMA channel is spoken for. The driver must live in a
+/// static shared with the `PIOx_IRQ_0` handler, which calls `on_interrupt`.
+/// `write_frame` copies the pixels and enables the interrupt; the handler
+/// tops up the FIFO and switches itself off once the frame is queued.
+pub struct IrqWs2812<P: PIOExt, SM: StateMachineIndex, const N: usize> {
+    _sm: RunningSm<P, SM>,
+    _pin: DataPin<P>,
+    _backup: Option<DataPin<P>>,
+    reset_cycles: u32,
+    encoder: Encoder,
+    preamble: Option<[u32; 2]>,
+    tx: Tx<(P, SM)>,
+    frame: [u32; N],
+    len: usize,
+    /// Words the last frame lit, so a shorter one blanks the rest once
+    shown: usize,
+    next: usize,
+}
+
+impl<P: PIOExt, SM: StateMachineIndex, const N: usize> IrqWs2812<P, SM, N> {
+    /// Whether the interrupt still has words to queue or the FIFO is draining
+    pub fn is_busy(&self) -> bool {
+        self.next < self.len || !self.tx.is_empty()
+    }
+
+    /// Apply the [`GAMMA8`](crate::correction::GAMMA8) curve to every pixel
+    /// as it's packed, so effects can work in linear values
+    pub fn set_gamma(&mut self, enabled: bool) {
+        self.encoder.correction.gamma = enabled;
+    }
+
+    /// Scale every channel by `level` at output time, 255 for full brightness
+    ///
+    /// Effects keep drawing full-range colors; the whole strip dims from here.
+    pub fn set_brightness(&mut self, level: u8) {
+        self.encoder.correction.brightness = level;
+    }
+
+    /// Pick how `set_brightness` levels map to output, `BrightnessCurve::Cie`
+    /// for fades that look even
+    pub fn set_brightness_curve(&mut self, curve: BrightnessCurve) {
+        self.encoder.correction.brightness_curve = curve;
+    }
+
+    /// Per-channel multipliers for this strip's LEDs, e.g.
+    /// [`TYPICAL_LED_STRIP`](crate::correction::TYPICAL_LED_STRIP)
+    pub fn set_calibration(&mut self, multipliers: Rgb) {
+        self.encoder.correction.calibration = multipliers;
+    }
+
+    /// Tint white toward a color temperature, on top of the calibration
+    pub fn set_color_temperature(&mut self, temperature: ColorTemperature) {
+        self.encoder.correction.temperature = temperature;
+    }
+
+    /// On RGBW strips, move the common part of RGB colors onto the white LED
+    pub fn set_white_extraction(&mut self, extraction: WhiteExtraction) {
+        self.encoder.correction.white = extraction;
+    }
+
+    /// Queue `pixels` for the interrupt handler to send
+    ///
+    /// Returns `false` without touching the pending frame if the previous one
+    /// is still going out. Pixels beyond `N` are ignored, and missing ones are
+    /// sent as black as far as the last frame reached.
+    pub fn write_frame(&mut self, pixels: &[Rgb]) -> bool {
+        let encoder = self.encoder;
+        self.queue_words(pixels.iter().map(|&led| encoder.pack(led.into())))
+    }
+
+    /// Like `write_frame`, for strips with a white channel
+    pub fn write_frame_rgbw(&mut self, pixels: &[Rgbw]) -> bool {
+        let encoder = self.encoder;
+        self.queue_words(pixels.iter().map(|&led| encoder.pack(led)))
+    }
+
+    fn queue_words(&mut self, words: impl Iterator<Item = u32>) -> bool {
+        if self.is_busy() {
+            return false;
+        }
+        let words = self.preamble.into_iter().flatten().chain(words);
+        let mut len = 0;
+        for (slot, word) in self.frame.iter_mut().zip(words) {
+            *slot = word;
+            len += 1;
+        }
+        self.len = len.max(self.shown);
+        self.frame[len..self.len].fill(0);
+        self.shown = len;
+        self.next = 0;
+
+        // The last pixel may still be in the OSR, give it the reset latch
+        cortex_m::asm::delay(self.reset_cycles);
+        self.tx.enable_tx_not_full_interrupt(PioIRQ::Irq0);
+        true
+    }
+
+    /// Refill the FIFO; call from the `PIOx_IRQ_0` handler
+    pub fn on_interrupt(&mut self) {
+        while self.next < self.len {
+            if !self.tx.write(self.frame[self.next]) {
+                // FIFO full again, the interrupt fires once there's room
+                return;
+            }
+            self.next += 1;
+        }
+        self.tx.disable_tx_not_full_interrupt(PioIRQ::Irq0);
+    }
+}
+
+impl<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const N: usize> LedOutput
+    for Ws2812<P, SM, CH, N>
+{
+    fn write_pixels(&mut self, pixels: impl Iterator<Item = Rgb>) {
+        self.write_pixels(pixels);
+    }
+
+    fn is_busy(&self) -> bool {
+        self.is_busy()
+    }
+}
+
+/// Frames written while the previous one is still queued are dropped
+impl<P: PIOExt, SM: StateMachineIndex, const N: usize> LedOutput for IrqWs2812<P, SM, N> {
+    fn write_pixels(&mut self, pixels: impl Iterator<Item = Rgb>) {
+        let encoder = self.encoder;
+        self.queue_words(pixels.map(|led| encoder.pack(led.into())));
+    }
+
+    fn is_busy(&self) -> bool {
+        self.is_busy()
+    }
+}
+
+/// Lets smart-leds effect iterators (brightness, gamma, ...) drive the strip directly
+impl<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const N: usize> SmartLedsWrite
+    for Ws2812<P, SM, CH, N>
+{
+    type Error = Infallible;
+    type Color = RGB8;
+
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.write_pixels(iterator.into_iter().map(|c| Rgb::from(c.into())));
+        Ok(())
+    }
+}
+
+/// Both PIO blocks set up for WS2812, handing out up to eight strip outputs
+///
+/// Outputs 0-3 run on PIO0's state machines and 4-7 on PIO1's. Each output
+/// can be claimed once; later calls return `None`. A state machine not
+/// needed for a strip can be claimed for another program instead, with
+/// `state_machine0` to `state_machine7`.
+///
+/// ```ignore
+/// let mut bank = StripBank::new(pac.PIO0, pac.PIO1, &mut pac.RESETS, sys_clk_hz);
+/// let a = bank.output0(pins.gpio15).unwrap().build(dma.ch0, frame_a);
+/// let b = bank.output4(pins.gpio16).unwrap().build(dma.ch1, frame_b);
+/// ```
+pub struct StripBank {
+    pio0: Ws2812Pio<PIO0>,
+    pio1: Ws2812Pio<PIO1>,
+    sm0: Option<UninitStateMachine<(PIO0, SM0)>>,
+    sm1: Option<UninitStateMachine<(PIO0, SM1)>>,
+    sm2: Option<UninitStateMachine<(PIO0, SM2)>>,
+    sm3: Option<UninitStateMachine<(PIO0, SM3)>>,
+    sm4: Option<UninitStateMachine<(PIO1, SM0)>>,
+    sm5: Option<UninitStateMachine<(PIO1, SM1)>>,
+    sm6: Option<UninitStateMachine<(PIO1, SM2)>>,
+    sm7: Option<UninitStateMachine<(PIO1, SM3)>>,
+}
+
+macro_rules! outputs {
+    ($($output:ident: $pio:ident, $sm:ident, $P:ty, $SM:ty;)+) => {
+        $(
+            #[doc = concat!("Claim `", stringify!($P), "` `", stringify!($SM), "` for a strip on `pin`")]
+            pub fn $output<I, F, PT>(&mut self, pin: Pin<I, F, PT>) -> Option<Ws2812Builder<'_, $P, $SM>>
+            where
+                I: PinId + ValidFunction<<$P as PIOExt>::PinFunction>,
+                F: Function,
+                PT: PullType,
+            {
+                let sm = self.$sm.take()?;
+                Some(self.$pio.strip(sm, pin))
+            }
+        )+
+    };
+}
+
+macro_rules! state_machines {
+    ($($claim:ident: $pio:ident, $sm:ident, $P:ty, $SM:ty;)+) => {
+        $(
+            #[doc = concat!("Claim `", stringify!($P), "` `", stringify!($SM), "` for some other program, with its PIO block to install it in")]
+            pub fn $claim(&mut self) -> Option<(&mut PIO<$P>, UninitStateMachine<($P, $SM)>)> {
+                let sm = self.$sm.take()?;
+                Some((self.$pio.pio_mut(), sm))
+            }
+        )+
+    };
+}
+
+impl StripBank {
+    pub fn new(pio0: PIO0, pio1: PIO1, resets: &mut RESETS, sys_clk_hz: u32) -> Self {
+        let (pio0, sm0, sm1, sm2, sm3) = pio0.split(resets);
+        let (pio1, sm4, sm5, sm6, sm7) = pio1.split(resets);
+        Self {
+            pio0: Ws2812Pio::new(pio0, sys_clk_hz),
+            pio1: Ws2812Pio::new(pio1, sys_clk_hz),
+            sm0: Some(sm0),
+            sm1: Some(sm1),
+            sm2: Some(sm2),
+            sm3: Some(sm3),
+            sm4: Some(sm4),
+            sm5: Some(sm5),
+            sm6: Some(sm6),
+            sm7: Some(sm7),
+        }
+    }
+
+    outputs! {
+        output0: pio0, sm0, PIO0, SM0;
+        output1: pio0, sm1, PIO0, SM1;
+        output2: pio0, sm2, PIO0, SM2;
+        output3: pio0, sm3, PIO0, SM3;
+        output4: pio1, sm4, PIO1, SM0;
+        output5: pio1, sm5, PIO1, SM1;
+        output6: pio1, sm6, PIO1, SM2;
+        output7: pio1, sm7, PIO1, SM3;
+    }
+
+    state_machines! {
+        state_machine0: pio0, sm0, PIO0, SM0;
+        state_machine1: pio0, sm1, PIO0, SM1;
+        state_machine2: pio0, sm2, PIO0, SM2;
+        state_machine3: pio0, sm3, PIO0, SM3;
+        state_machine4: pio1, sm4, PIO1, SM0;
+        state_machine5: pio1, sm5, PIO1, SM1;
+        state_machine6: pio1, sm6, PIO1, SM2;
+        state_machine7: pio1, sm7, PIO1, SM3;
+    }
+}
@@ -0,0 +1,130 @@
+// DS18B20 temperature probes on a 1-Wire bus
+
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+
+use crate::onewire::{crc8, OneWire, OneWireError, Rom};
+
+/// ROM family code of the DS18B20
+pub const FAMILY_CODE: u8 = 0x28;
+
+/// Probes kept track of on one bus
+pub const MAX_PROBES: usize = 4;
+
+/// Time between readings
+pub const SAMPLE_MS: u32 = 2000;
+
+/// A 12-bit conversion's worst case
+pub const CONVERSION_MS: u32 = 750;
+
+const CONVERT_T: u8 = 0x44;
+const READ_SCRATCHPAD: u8 = 0xBE;
+
+/// Every DS18B20 found on a bus, all converting at once
+///
+/// The probes need their own 3V3 supply; parasite power isn't supported.
+/// [`poll`](Self::poll) starts a conversion every [`SAMPLE_MS`] and reads the
+/// results [`CONVERSION_MS`] later, so it never waits on the sensors.
+///
+/// ```ignore
+/// let bus = OneWire::new(InOutPin::new(pins.gpio3.into_floating_input()));
+/// let mut probes = Ds18b20::new(bus, &mut delay);
+/// if let Some(temperatures) = probes.poll(now_ms, &mut delay) {
+///     info!("{}", temperatures[0]);
+/// }
+/// ```
+pub struct Ds18b20<P> {
+    bus: OneWire<P>,
+    roms: [Rom; MAX_PROBES],
+    count: usize,
+    temperatures: [Option<i32>; MAX_PROBES],
+    /// When the results of the conversion in progress are ready
+    ready_ms: Option<u32>,
+    next_ms: u32,
+}
+
+impl<P: InputPin + OutputPin> Ds18b20<P> {
+    /// Search the bus for probes; other kinds of device are left alone
+    pub fn new<D: DelayUs<u32>>(mut bus: OneWire<P>, delay: &mut D) -> Self {
+        let mut found = [[0; 8]; MAX_PROBES];
+        let found = match bus.search(delay, &mut found) {
+            Ok(count) => &found[..count],
+            Err(_) => &[],
+        };
+        let mut roms = [[0; 8]; MAX_PROBES];
+        let mut count = 0;
+        for rom in found.iter().filter(|rom| rom[0] == FAMILY_CODE) {
+            roms[count] = *rom;
+            count += 1;
+        }
+        Self {
+            bus,
+            roms,
+            count,
+            temperatures: [None; MAX_PROBES],
+            ready_ms: None,
+            next_ms: 0,
+        }
+    }
+
+    /// ROM codes of the probes found, in search order
+    pub fn probes(&self) -> &[Rom] {
+        &self.roms[..self.count]
+    }
+
+    /// Start and collect conversions; returns each probe's temperature in
+    /// millidegrees C once a round is read, `None` for probes that didn't
+    /// answer
+    pub fn poll<D: DelayUs<u32>>(&mut self, now_ms: u32, delay: &mut D) -> Option<&[Option<i32>]> {
+        if self.count == 0 {
+            return None;
+        }
+        match self.ready_ms {
+            Some(ready) if (now_ms.wrapping_sub(ready) as i32) >= 0 => {
+                self.ready_ms = None;
+                for i in 0..self.count {
+                    let rom = self.roms[i];
+                    self.temperatures[i] = self.read(&rom, delay).ok();
+                }
+                Some(&self.temperatures[..self.count])
+            }
+            Some(_) => None,
+            None => {
+                if (now_ms.wrapping_sub(self.next_ms) as i32) >= 0 {
+                    self.next_ms = now_ms.wrapping_add(SAMPLE_MS);
+                    if self.start_conversion(delay).is_ok() {
+                        self.ready_ms = Some(now_ms.wrapping_add(CONVERSION_MS));
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    /// Tell every probe to measure
+    pub fn start_conversion<D: DelayUs<u32>>(&mut self, delay: &mut D) -> Result<(), OneWireError> {
+        self.bus.select(None, delay)?;
+        self.bus.write_byte(CONVERT_T, delay);
+        Ok(())
+    }
+
+    /// One probe's last conversion, in millidegrees C
+    pub fn read<D: DelayUs<u32>>(&mut self, rom: &Rom, delay: &mut D) -> Result<i32, OneWireError> {
+        self.bus.select(Some(rom), delay)?;
+        self.bus.write_byte(READ_SCRATCHPAD, delay);
+        let mut scratchpad = [0; 9];
+        for byte in scratchpad.iter_mut() {
+            *byte = self.bus.read_byte(delay);
+        }
+        if crc8(&scratchpad) != 0 {
+            return Err(OneWireError::Crc);
+        }
+        // Sixteenths of a degree
+        let raw = i16::from_le_bytes([scratchpad[0], scratchpad[1]]) as i32;
+        Ok(raw * 1000 / 16)
+    }
+
+    pub fn free(self) -> OneWire<P> {
+        self.bus
+    }
+}
@@ -0,0 +1,194 @@
+// E1.31 (streaming ACN, sACN) DMX over UDP, as lighting consoles send it
+
+use crate::dmx::{UniverseMap, DMX_CHANNELS, MAX_UNIVERSES};
+use crate::host::HostFrame;
+
+/// UDP port sACN is sent to
+pub const E131_PORT: u16 = 5568;
+
+/// A source not heard from for this long has gone, as the standard's
+/// network data loss timeout
+pub const E131_TIMEOUT_MS: u32 = 2500;
+
+const ACN_PACKET_ID: &[u8; 12] = b"ASC-E1.17\0\0\0";
+const VECTOR_ROOT_E131_DATA: u32 = 0x0000_0004;
+const VECTOR_E131_DATA_PACKET: u32 = 0x0000_0002;
+const VECTOR_DMP_SET_PROPERTY: u8 = 0x02;
+const DMP_ADDRESS_TYPE: u8 = 0xA1;
+
+const OPTION_PREVIEW: u8 = 0x80;
+const OPTION_TERMINATED: u8 = 0x40;
+
+/// Where the DMX start code sits, the channels following it
+const START_CODE_OFFSET: usize = 125;
+
+/// The multicast group a universe is sent to, 239.255.\<universe\>, for the
+/// network stack to join
+pub fn multicast_address(universe: u16) -> [u8; 4] {
+    let [high, low] = universe.to_be_bytes();
+    [239, 255, high, low]
+}
+
+/// What [`E131Receiver::receive`] made of a datagram
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum E131Event {
+    /// Not E1.31 data for the strip, or from a source that's outranked
+    Ignored,
+    /// Pixels were written
+    Data,
+    /// The source on the strip has stopped its stream, so local effects
+    /// can take over straight away
+    Terminated,
+}
+
+/// The source whose data is on the strip
+#[derive(Copy, Clone, Debug)]
+struct Source {
+    cid: [u8; 16],
+    priority: u8,
+    last_ms: u32,
+}
+
+/// Takes sACN data packets and puts the universes in a [`UniverseMap`] on
+/// the strip
+///
+/// One source drives the strip at a time: a higher priority source takes
+/// over from a lower one, and a lower or equal one waits until the current
+/// source terminates its stream or goes quiet for [`E131_TIMEOUT_MS`].
+/// Packets arriving out of order are dropped by their sequence numbers,
+/// and preview data, meant for visualizers, is never shown. Synchronization
+/// is not supported; universes are shown as they arrive.
+///
+/// ```ignore
+/// match e131.receive(datagram, now_ms, &host_frame) {
+///     E131Event::Data => current = host_effect,
+///     E131Event::Terminated => current = local_effect,
+///     E131Event::Ignored => {}
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct E131Receiver {
+    pub map: UniverseMap,
+    source: Option<Source>,
+    /// Last sequence number of each universe from the current source
+    sequences: [Option<u8>; MAX_UNIVERSES],
+}
+
+impl E131Receiver {
+    pub const fn new(map: UniverseMap) -> Self {
+        Self {
+            map,
+            source: None,
+            sequences: [None; MAX_UNIVERSES],
+        }
+    }
+
+    /// Whether a source is streaming to the strip
+    pub fn is_streaming(&self, now_ms: u32) -> bool {
+        self.source
+            .is_some_and(|source| now_ms.wrapping_sub(source.last_ms) <= E131_TIMEOUT_MS)
+    }
+
+    /// Take one UDP payload
+    pub fn receive<const N: usize>(
+        &mut self,
+        datagram: &[u8],
+        now_ms: u32,
+        frame: &HostFrame<N>,
+    ) -> E131Event {
+        let Some(packet) = DataPacket::parse(datagram) else {
+            return E131Event::Ignored;
+        };
+        let Some(index) = self.map.index(packet.universe, frame.len()) else {
+            return E131Event::Ignored;
+        };
+
+        let ours = self.source.is_some_and(|source| source.cid == packet.cid);
+        if packet.options & OPTION_TERMINATED != 0 {
+            // Terminating packets carry no data worth showing
+            if !ours {
+                return E131Event::Ignored;
+            }
+            self.source = None;
+            return E131Event::Terminated;
+        }
+        if packet.options & OPTION_PREVIEW != 0 {
+            return E131Event::Ignored;
+        }
+
+        if !ours {
+            if self.is_streaming(now_ms)
+                && self
+                    .source
+                    .is_some_and(|source| packet.priority <= source.priority)
+            {
+                return E131Event::Ignored;
+            }
+            self.sequences = [None; MAX_UNIVERSES];
+        }
+        self.source = Some(Source {
+            cid: packet.cid,
+            priority: packet.priority,
+            last_ms: now_ms,
+        });
+
+        if let Some(last) = self.sequences.get_mut(index) {
+            // Behind by up to 20 is a late packet, further is a restart
+            let behind = last.map(|last| packet.sequence.wrapping_sub(last) as i8);
+            if behind.is_some_and(|diff| diff <= 0 && diff > -20) {
+                return E131Event::Ignored;
+            }
+            *last = Some(packet.sequence);
+        }
+
+        self.map.write(packet.universe, packet.channels, frame);
+        E131Event::Data
+    }
+}
+
+/// The parts of an E1.31 data packet used here
+struct DataPacket<'a> {
+    cid: [u8; 16],
+    priority: u8,
+    sequence: u8,
+    options: u8,
+    universe: u16,
+    channels: &'a [u8],
+}
+
+impl<'a> DataPacket<'a> {
+    fn parse(datagram: &'a [u8]) -> Option<Self> {
+        let u16_at = |i: usize| u16::from_be_bytes([datagram[i], datagram[i + 1]]);
+        let u32_at = |i: usize| u32::from_be_bytes(datagram[i..i + 4].try_into().unwrap());
+
+        if datagram.len() <= START_CODE_OFFSET
+            || u16_at(0) != 0x0010
+            || u16_at(2) != 0x0000
+            || &datagram[4..16] != ACN_PACKET_ID
+            || u32_at(18) != VECTOR_ROOT_E131_DATA
+            || u32_at(40) != VECTOR_E131_DATA_PACKET
+            || datagram[117] != VECTOR_DMP_SET_PROPERTY
+            || datagram[118] != DMP_ADDRESS_TYPE
+            || u16_at(119) != 0x0000
+            || u16_at(121) != 0x0001
+        {
+            return None;
+        }
+        // The count includes the start code
+        let count = (u16_at(123) as usize).clamp(1, DMX_CHANNELS + 1);
+        let end = (START_CODE_OFFSET + count).min(datagram.len());
+        // Only start code 0 is levels; others are per-channel priorities,
+        // text and the like
+        if datagram[START_CODE_OFFSET] != 0x00 {
+            return None;
+        }
+        Some(Self {
+            cid: datagram[22..38].try_into().unwrap(),
+            priority: datagram[108],
+            sequence: datagram[111],
+            options: datagram[112],
+            universe: u16_at(113),
+            channels: &datagram[START_CODE_OFFSET + 1..end],
+        })
+    }
+}
@@ -0,0 +1,94 @@
+// Easing curves for transitions and movement
+//
+// Each maps progress 0-255 to an eased 0-255, in integer math.
+
+use crate::math::{cos8, sin8};
+
+/// Elastic-out sampled every 8 steps of progress, overshooting 255
+const ELASTIC_OUT: [i16; 33] = [
+    0, 92, 212, 306, 348, 341, 304, 262, 232, 221, 227, 241, 255, 264, 267, 264, 259, 254, 251,
+    251, 252, 254, 256, 256, 256, 256, 255, 255, 254, 255, 255, 255, 255,
+];
+
+/// Easing curves in the usual in / out / in-out flavours
+///
+/// "In" starts slow, "out" ends slow, "in-out" does both.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    /// Springs past the end and settles back
+    ElasticOut,
+    /// Winds up past the start before setting off
+    ElasticIn,
+    ElasticInOut,
+}
+
+fn quad(t: i32) -> i32 {
+    t * t / 255
+}
+
+fn cubic(t: i32) -> i32 {
+    t * t * t / (255 * 255)
+}
+
+/// Quarter sine, rising from 0 to 255
+fn sine_out(t: i32) -> i32 {
+    (sin8((t >> 2) as u8) as i32 - 128) * 255 / 127
+}
+
+fn elastic_out(t: i32) -> i32 {
+    let (i, frac) = ((t >> 3) as usize, t & 7);
+    let (a, b) = (ELASTIC_OUT[i] as i32, ELASTIC_OUT[i + 1] as i32);
+    a + (b - a) * frac / 8
+}
+
+/// Mirror an "in" curve into its "out" form, or the other way round
+fn flip(f: fn(i32) -> i32, t: i32) -> i32 {
+    255 - f(255 - t)
+}
+
+/// "In" for the first half and "out" for the second
+fn in_out(f: fn(i32) -> i32, t: i32) -> i32 {
+    if t < 128 {
+        f(t * 2) / 2
+    } else {
+        255 - f((255 - t) * 2) / 2
+    }
+}
+
+impl Easing {
+    /// Eased `t`, allowed to overshoot 0-255 for the elastic curves
+    pub fn apply_signed(self, t: u8) -> i16 {
+        let t = t as i32;
+        let eased = match self {
+            Easing::Linear => t,
+            Easing::QuadIn => quad(t),
+            Easing::QuadOut => flip(quad, t),
+            Easing::QuadInOut => in_out(quad, t),
+            Easing::CubicIn => cubic(t),
+            Easing::CubicOut => flip(cubic, t),
+            Easing::CubicInOut => in_out(cubic, t),
+            Easing::SineIn => flip(sine_out, t),
+            Easing::SineOut => sine_out(t),
+            Easing::SineInOut => (255 - cos8((t >> 1) as u8) as i32) * 255 / 254,
+            Easing::ElasticOut => elastic_out(t),
+            Easing::ElasticIn => flip(elastic_out, t),
+            Easing::ElasticInOut => in_out(|t| flip(elastic_out, t), t),
+        };
+        eased as i16
+    }
+
+    /// Eased `t`, clamped to 0-255
+    pub fn apply(self, t: u8) -> u8 {
+        self.apply_signed(t).clamp(0, 255) as u8
+    }
+}
@@ -0,0 +1,78 @@
+// Whole-strip breathing, e.g. as a standby indicator
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::math::{scale8, sin8};
+use crate::pio_programs::Rgb;
+
+/// The whole strip fading in and out along a sine envelope
+pub struct Breathe {
+    pub color: Rgb,
+    /// Length of one breath, dim to bright and back
+    pub period_ms: u32,
+    /// Brightness at the bottom of each breath
+    pub min_level: u8,
+}
+
+impl Breathe {
+    pub fn new(color: Rgb, period_ms: u32) -> Self {
+        Self {
+            color,
+            period_ms,
+            min_level: 0,
+        }
+    }
+
+    pub fn min_level(mut self, min_level: u8) -> Self {
+        self.min_level = min_level;
+        self
+    }
+}
+
+impl Default for Breathe {
+    fn default() -> Self {
+        Self::new(Rgb::WHITE, 4000)
+    }
+}
+
+impl Effect for Breathe {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        let period = self.period_ms.max(1) as u64;
+        let phase = ((t as u64 % period) * 256 / period) as u8;
+        // Start each breath at the bottom of the sine
+        let envelope = sin8(phase.wrapping_sub(64));
+        let level = self.min_level + scale8(envelope, 255 - self.min_level);
+        frame.fill(self.color.scale(level));
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Speed, "Speed"),
+            ParamInfo::new(Param::Intensity, "Floor"),
+            ParamInfo::new(Param::Primary, "Color"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Speed => {
+                ParamValue::Level(255 - (self.period_ms.saturating_sub(500) / 40).min(255) as u8)
+            }
+            Param::Intensity => ParamValue::Level(self.min_level),
+            Param::Primary => ParamValue::Color(self.color),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Speed, ParamValue::Level(level)) => {
+                self.period_ms = 500 + (255 - level as u32) * 40
+            }
+            (Param::Intensity, ParamValue::Level(level)) => self.min_level = level,
+            (Param::Primary, ParamValue::Color(color)) => self.color = color,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,96 @@
+// Candle / torch flicker
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::math::{scale8, Rng};
+use crate::pio_programs::Rgb;
+
+/// Warm flames flickering independently on each of `N` pixels
+///
+/// Each pixel chases a randomly moving target brightness through a low-pass
+/// filter, so it wavers rather than blinks. Dimmer flames also turn redder,
+/// the way a real flame does as it gutters.
+pub struct Candle<const N: usize> {
+    /// Flame color at full brightness
+    pub color: Rgb,
+    /// How much the flames are disturbed: 0 is a still room, 255 a gale
+    pub wind: u8,
+    level: [u8; N],
+    target: [u8; N],
+    rng: Rng,
+}
+
+impl<const N: usize> Candle<N> {
+    pub fn new(wind: u8) -> Self {
+        Self {
+            color: Rgb::new(255, 147, 41),
+            wind,
+            level: [255; N],
+            target: [255; N],
+            rng: Rng::seeded(0xF1A4E),
+        }
+    }
+
+    pub fn color(mut self, color: Rgb) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+impl<const N: usize> Default for Candle<N> {
+    fn default() -> Self {
+        Self::new(40)
+    }
+}
+
+impl<const N: usize> Effect for Candle<N> {
+    fn render(&mut self, frame: &mut [Rgb], _t: u32) {
+        // Wind makes new targets come more often and reach deeper
+        let chance = 16 + self.wind / 4;
+        let depth = 40 + scale8(self.wind, 160);
+
+        for ((led, level), target) in frame
+            .iter_mut()
+            .zip(self.level.iter_mut())
+            .zip(self.target.iter_mut())
+        {
+            if self.rng.random8() < chance {
+                *target = 255 - self.rng.range8(0, depth);
+            }
+            let step = (*target as i16 - *level as i16) / 4;
+            *level = (*level as i16 + step) as u8;
+
+            // Green and blue fall off twice as fast, warming the dim moments
+            let warm = |c: u8| scale8(scale8(c, *level), *level);
+            *led = Rgb::new(
+                scale8(self.color.r, *level),
+                warm(self.color.g),
+                warm(self.color.b),
+            );
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Intensity, "Wind"),
+            ParamInfo::new(Param::Primary, "Color"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Intensity => ParamValue::Level(self.wind),
+            Param::Primary => ParamValue::Color(self.color),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Intensity, ParamValue::Level(level)) => self.wind = level,
+            (Param::Primary, ParamValue::Color(color)) => self.color = color,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,66 @@
+// Theater marquee chase
+
+use super::{ColorSource, Effect, Param, ParamInfo, ParamValue};
+use crate::pio_programs::Rgb;
+
+/// Every `spacing`-th pixel lit, the pattern stepping along the strip
+pub struct TheaterChase {
+    pub colors: ColorSource,
+    /// Pixels from one lit pixel to the next
+    pub spacing: u8,
+    /// Steps per second
+    pub speed: u16,
+}
+
+impl TheaterChase {
+    pub fn new(colors: impl Into<ColorSource>, spacing: u8, speed: u16) -> Self {
+        Self {
+            colors: colors.into(),
+            spacing,
+            speed,
+        }
+    }
+}
+
+impl Effect for TheaterChase {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        let spacing = self.spacing.max(1) as usize;
+        let step = (t as u64 * self.speed as u64 / 1000) as usize;
+        for (i, led) in frame.iter_mut().enumerate() {
+            *led = if (i + spacing - step % spacing).is_multiple_of(spacing) {
+                self.colors.color_at(i, step as u8)
+            } else {
+                Rgb::BLACK
+            };
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Speed, "Speed"),
+            ParamInfo::new(Param::Intensity, "Spacing"),
+            ParamInfo::new(Param::Primary, "Color"),
+            ParamInfo::new(Param::Palette, "Palette"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Speed => ParamValue::Level(self.speed.saturating_mul(4).min(255) as u8),
+            Param::Intensity => {
+                ParamValue::Level(self.spacing.saturating_sub(2).saturating_mul(32))
+            }
+            _ => return self.colors.param(param),
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Speed, ParamValue::Level(level)) => self.speed = level as u16 / 4,
+            (Param::Intensity, ParamValue::Level(level)) => self.spacing = 2 + level / 32,
+            _ => return self.colors.set_param(param, value),
+        }
+        true
+    }
+}
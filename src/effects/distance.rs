@@ -0,0 +1,110 @@
+// Distance meter: a hand over the rangefinder sets the bar length or hue
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::color::Hsv;
+use crate::pio_programs::Rgb;
+use crate::sonar::Proximity;
+
+/// What the distance controls
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum DistanceMode {
+    /// Pixels lit, all of them with a hand at `near_mm`
+    Pixels,
+    /// The hue of the whole strip, held when the hand goes away
+    Hue,
+}
+
+/// Follows the [`Proximity`] reading between `near_mm` and `far_mm`
+///
+/// Readings are eased toward, so a wavering hand moves the strip smoothly.
+pub struct DistanceMeter<'a> {
+    proximity: &'a Proximity,
+    pub mode: DistanceMode,
+    pub color: Rgb,
+    pub near_mm: u16,
+    pub far_mm: u16,
+    /// Eased closeness, 0 at `far_mm` to 255 at `near_mm`, 8 fraction bits
+    shown: u16,
+}
+
+impl<'a> DistanceMeter<'a> {
+    pub fn new(proximity: &'a Proximity, mode: DistanceMode) -> Self {
+        Self {
+            proximity,
+            mode,
+            color: Rgb::new(0, 160, 255),
+            near_mm: 50,
+            far_mm: 500,
+            shown: 0,
+        }
+    }
+
+    pub fn color(mut self, color: Rgb) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn range_mm(mut self, near_mm: u16, far_mm: u16) -> Self {
+        self.near_mm = near_mm;
+        self.far_mm = far_mm;
+        self
+    }
+
+    /// How close the hand is, 0-255, or `None` with nothing in range
+    fn closeness(&self) -> Option<u8> {
+        let mm = self.proximity.distance_mm()?;
+        if mm > self.far_mm {
+            return None;
+        }
+        let span = self.far_mm.saturating_sub(self.near_mm).max(1) as u32;
+        let from_far = self.far_mm.saturating_sub(mm.max(self.near_mm)) as u32;
+        Some((from_far * 255 / span) as u8)
+    }
+}
+
+impl Effect for DistanceMeter<'_> {
+    fn render(&mut self, frame: &mut [Rgb], _t: u32) {
+        let target = match (self.closeness(), self.mode) {
+            (Some(closeness), _) => (closeness as u16) << 8,
+            (None, DistanceMode::Pixels) => 0,
+            (None, DistanceMode::Hue) => self.shown,
+        };
+        let step = (target as i32 - self.shown as i32) / 4;
+        self.shown = (self.shown as i32 + step) as u16;
+        let level = (self.shown >> 8) as u8;
+
+        match self.mode {
+            DistanceMode::Pixels => {
+                let lit = level as usize * frame.len() * 256 / 255;
+                for (i, led) in frame.iter_mut().enumerate() {
+                    *led = match lit.saturating_sub(i * 256) {
+                        0 => Rgb::BLACK,
+                        cover if cover >= 256 => self.color,
+                        cover => self.color.scale(cover as u8),
+                    };
+                }
+            }
+            DistanceMode::Hue => frame.fill(Hsv::new(level, 255, 255).to_rgb()),
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[ParamInfo::new(Param::Primary, "Color")];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Primary => ParamValue::Color(self.color),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Primary, ParamValue::Color(color)) => self.color = color,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,102 @@
+// Fire2012: a heat simulation rising up the strip, after Mark Kriegsman's
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::math::{qadd8, qsub8, scale8, Rng};
+use crate::palette::{color_from_palette, HEAT};
+use crate::pio_programs::Rgb;
+
+/// Flame rising from one end of a segment of up to `N` pixels
+///
+/// Each cell cools a little every frame, heat drifts upward, and new sparks
+/// ignite near the base. Only as many cells as the frame has pixels are
+/// simulated, so the flame's height follows the strip, not the buffer. Run
+/// one per segment, with `reversed` set on those mounted upside down.
+pub struct Fire<const N: usize> {
+    /// How fast the flame cools; higher values give shorter flames (20-100)
+    pub cooling: u8,
+    /// Chance out of 255 of a new spark each frame (50-200)
+    pub sparking: u8,
+    /// Put the base of the flame at the far end of the strip
+    pub reversed: bool,
+    heat: [u8; N],
+    rng: Rng,
+}
+
+impl<const N: usize> Fire<N> {
+    pub fn new(cooling: u8, sparking: u8) -> Self {
+        Self {
+            cooling,
+            sparking,
+            reversed: false,
+            heat: [0; N],
+            rng: Rng::seeded(N as u32 + 1),
+        }
+    }
+
+    pub fn reversed(mut self, reversed: bool) -> Self {
+        self.reversed = reversed;
+        self
+    }
+}
+
+impl<const N: usize> Default for Fire<N> {
+    fn default() -> Self {
+        Self::new(55, 120)
+    }
+}
+
+impl<const N: usize> Effect for Fire<N> {
+    fn render(&mut self, frame: &mut [Rgb], _t: u32) {
+        let len = frame.len().min(N);
+        let heat = &mut self.heat[..len];
+        let max_cooling = (self.cooling as usize * 10 / len.max(1) + 2).min(255) as u8;
+
+        // Every cell cools down a little
+        for cell in heat.iter_mut() {
+            *cell = qsub8(*cell, self.rng.range8(0, max_cooling));
+        }
+
+        // Heat drifts up and diffuses
+        for k in (2..len).rev() {
+            heat[k] = ((heat[k - 1] as u16 + 2 * heat[k - 2] as u16) / 3) as u8;
+        }
+
+        // Randomly ignite new sparks near the bottom
+        if len > 0 && self.rng.random8() < self.sparking {
+            let y = self.rng.range8(0, len.min(7) as u8) as usize;
+            heat[y] = qadd8(heat[y], self.rng.range8(160, 255));
+        }
+
+        for (i, &heat) in heat.iter().enumerate() {
+            // Stay below the palette's wrap back to black
+            let color = color_from_palette(&HEAT, scale8(heat, 240), 255);
+            let pos = if self.reversed { len - 1 - i } else { i };
+            frame[pos] = color;
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Speed, "Cooling"),
+            ParamInfo::new(Param::Intensity, "Sparking"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Speed => ParamValue::Level(self.cooling),
+            Param::Intensity => ParamValue::Level(self.sparking),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Speed, ParamValue::Level(level)) => self.cooling = level,
+            (Param::Intensity, ParamValue::Level(level)) => self.sparking = level,
+            _ => return false,
+        }
+        true
+    }
+}
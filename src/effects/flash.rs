@@ -0,0 +1,208 @@
+// Lightning and strobe, both held under a photosensitivity-safe flash rate
+
+use core::ops::Range;
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::math::Rng;
+use crate::pio_programs::Rgb;
+
+/// Most flashes per second either effect will produce, whatever it's set to
+///
+/// Three a second is the limit photosensitivity guidelines (WCAG 2.3.1, the
+/// UK Ofcom rules) put on general flashing content.
+pub const MAX_FLASH_HZ: u32 = 3;
+
+/// Shortest time from one flash starting to the next
+pub const MIN_FLASH_INTERVAL_MS: u32 = 1000 / MAX_FLASH_HZ;
+
+/// Whether the wrapping millisecond clock `t` has reached `at`
+fn reached(t: u32, at: u32) -> bool {
+    (t.wrapping_sub(at) as i32) >= 0
+}
+
+/// A thunderstorm: bursts of bright flashes on random stretches of the strip,
+/// dying away in an afterglow
+pub struct Lightning {
+    pub color: Rgb,
+    /// How often strikes come, from one every ~10 s at 0 to nearly constant
+    pub frequency: u8,
+    /// Flashes left in the current strike
+    flashes: u8,
+    next_ms: u32,
+    flash_end_ms: u32,
+    level: u8,
+    range: Range<usize>,
+    rng: Rng,
+}
+
+impl Lightning {
+    pub fn new(frequency: u8) -> Self {
+        Self {
+            color: Rgb::new(200, 210, 255),
+            frequency,
+            flashes: 0,
+            next_ms: 0,
+            flash_end_ms: 0,
+            level: 0,
+            range: 0..0,
+            rng: Rng::seeded(0xB017),
+        }
+    }
+
+    pub fn color(mut self, color: Rgb) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Random pause before the next strike, around the set frequency
+    fn strike_gap_ms(&mut self) -> u32 {
+        let mean = 500 + (255 - self.frequency as u32) * 40;
+        mean / 2 + (self.rng.next_u32() % mean.max(1))
+    }
+}
+
+impl Default for Lightning {
+    fn default() -> Self {
+        Self::new(128)
+    }
+}
+
+impl Effect for Lightning {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        for led in frame.iter_mut() {
+            *led = led.fade_to_black_by(40);
+        }
+        if frame.is_empty() {
+            return;
+        }
+
+        if reached(t, self.next_ms) {
+            if self.flashes == 0 {
+                // A new strike: a few flashes on one stretch of sky
+                self.flashes = self.rng.range8(1, 4);
+                let len = frame.len();
+                let start = self.rng.next_u32() as usize % len;
+                let size = 1 + self.rng.next_u32() as usize % (len - start);
+                self.range = start..start + size;
+            }
+            self.flashes -= 1;
+            self.level = self.rng.range8(128, 255);
+            self.flash_end_ms = t.wrapping_add(self.rng.range8(20, 70) as u32);
+            // Never sooner than the flash rate cap allows
+            self.next_ms = t.wrapping_add(match self.flashes {
+                0 => self.strike_gap_ms().max(MIN_FLASH_INTERVAL_MS),
+                _ => MIN_FLASH_INTERVAL_MS + self.rng.range8(0, 200) as u32,
+            });
+        }
+
+        if !reached(t, self.flash_end_ms) {
+            let end = self.range.end.min(frame.len());
+            let start = self.range.start.min(end);
+            frame[start..end].fill(self.color.scale(self.level));
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Intensity, "Frequency"),
+            ParamInfo::new(Param::Primary, "Color"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Intensity => ParamValue::Level(self.frequency),
+            Param::Primary => ParamValue::Color(self.color),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Intensity, ParamValue::Level(level)) => self.frequency = level,
+            (Param::Primary, ParamValue::Color(color)) => self.color = color,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Strobe period at a `Speed` of 0; 255 is the rate cap
+const SLOWEST_STROBE_MS: u32 = MIN_FLASH_INTERVAL_MS * 10;
+
+/// Regular flashes of the whole strip
+///
+/// The period is clamped to [`MIN_FLASH_INTERVAL_MS`], so no setting can
+/// strobe faster than [`MAX_FLASH_HZ`].
+pub struct Strobe {
+    pub color: Rgb,
+    /// Time from one flash to the next
+    pub period_ms: u32,
+    /// How long each flash stays on
+    pub on_ms: u32,
+}
+
+impl Strobe {
+    pub fn new(color: Rgb, period_ms: u32) -> Self {
+        Self {
+            color,
+            period_ms,
+            on_ms: 30,
+        }
+    }
+}
+
+impl Default for Strobe {
+    fn default() -> Self {
+        Self::new(Rgb::WHITE, 1000)
+    }
+}
+
+impl Effect for Strobe {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        let period = self.period_ms.max(MIN_FLASH_INTERVAL_MS);
+        // Keep some dark time even with long flashes
+        let on = self.on_ms.min(period / 2);
+        frame.fill(if t % period < on {
+            self.color
+        } else {
+            Rgb::BLACK
+        });
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Speed, "Rate"),
+            ParamInfo::new(Param::Primary, "Color"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        let span = SLOWEST_STROBE_MS - MIN_FLASH_INTERVAL_MS;
+        Some(match param {
+            Param::Speed => {
+                let slower = self
+                    .period_ms
+                    .clamp(MIN_FLASH_INTERVAL_MS, SLOWEST_STROBE_MS)
+                    - MIN_FLASH_INTERVAL_MS;
+                ParamValue::Level(255 - (slower * 255 / span) as u8)
+            }
+            Param::Primary => ParamValue::Color(self.color),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        let span = SLOWEST_STROBE_MS - MIN_FLASH_INTERVAL_MS;
+        match (param, value) {
+            (Param::Speed, ParamValue::Level(level)) => {
+                self.period_ms = SLOWEST_STROBE_MS - span * level as u32 / 255;
+            }
+            (Param::Primary, ParamValue::Color(color)) => self.color = color,
+            _ => return false,
+        }
+        true
+    }
+}
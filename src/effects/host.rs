@@ -0,0 +1,26 @@
+// Host pixels: whatever a connected computer last sent
+
+use super::Effect;
+use crate::host::HostFrame;
+use crate::pio_programs::Rgb;
+
+/// Shows the [`HostFrame`] as it stands, for a host to drive the strip
+///
+/// Pixels the host hasn't set stay black.
+pub struct HostPixels<'a, const N: usize> {
+    frame: &'a HostFrame<N>,
+}
+
+impl<'a, const N: usize> HostPixels<'a, N> {
+    pub fn new(frame: &'a HostFrame<N>) -> Self {
+        Self { frame }
+    }
+}
+
+impl<const N: usize> Effect for HostPixels<'_, N> {
+    fn render(&mut self, frame: &mut [Rgb], _t: u32) {
+        for (i, led) in frame.iter_mut().enumerate() {
+            *led = self.frame.get(i).unwrap_or(Rgb::BLACK);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+// Liquid: a body of water sloshing along the strip as it's tilted
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::imu::{Movement, ONE_G};
+use crate::palette::{Palette16, OCEAN};
+use crate::pio_programs::Rgb;
+
+/// Accelerometer axis that runs along the strip, from the first pixel
+/// toward the last
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+/// Liquid in a tube: tilt the strip and it runs to the low end, sloshing
+/// and settling
+///
+/// The liquid fills `volume`/255 of the strip and slides under `gravity`
+/// pixels/s² per g of tilt, losing speed to `damping`. Each shake moves
+/// its color along `palette`.
+pub struct Liquid<'a> {
+    movement: &'a Movement,
+    pub axis: Axis,
+    pub palette: &'static Palette16,
+    pub volume: u8,
+    pub gravity: u16,
+    /// Fraction of the speed lost per second, out of 256
+    pub damping: u16,
+    /// Low edge of the liquid and its speed, in 1/256 pixels
+    pos: i32,
+    vel: i32,
+    color: u8,
+    shakes: u32,
+    last_t: Option<u32>,
+}
+
+impl<'a> Liquid<'a> {
+    pub fn new(movement: &'a Movement) -> Self {
+        Self {
+            movement,
+            axis: Axis::X,
+            palette: &OCEAN,
+            volume: 128,
+            gravity: 60,
+            damping: 384,
+            pos: 0,
+            vel: 0,
+            color: 160,
+            shakes: movement.shakes(),
+            last_t: None,
+        }
+    }
+
+    pub fn axis(mut self, axis: Axis) -> Self {
+        self.axis = axis;
+        self
+    }
+
+    pub fn volume(mut self, volume: u8) -> Self {
+        self.volume = volume;
+        self
+    }
+}
+
+impl Effect for Liquid<'_> {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        let n = frame.len() as i32;
+        if n == 0 {
+            return;
+        }
+        // Long gaps would fling the liquid through the walls
+        let dt = self.last_t.map_or(0, |last| t.wrapping_sub(last).min(50)) as i32;
+        self.last_t = Some(t);
+
+        let shakes = self.movement.shakes();
+        if shakes != self.shakes {
+            self.shakes = shakes;
+            self.color = self.color.wrapping_add(16);
+        }
+
+        let accel = self.movement.accel();
+        let along = match self.axis {
+            Axis::X => accel.x,
+            Axis::Y => accel.y,
+            Axis::Z => accel.z,
+        } as i32;
+        // The axis reads +1 g pointing up, so the liquid runs the other way
+        let a = -along * self.gravity as i32 * 256 / ONE_G;
+        self.vel += a * dt / 1000;
+        self.vel -= self.vel * self.damping as i32 / 256 * dt / 1000;
+        self.pos += self.vel * dt / 1000;
+
+        let len = self.volume as i32 * n * 256 / 255;
+        let max_pos = n * 256 - len;
+        if self.pos < 0 || self.pos > max_pos {
+            self.pos = self.pos.clamp(0, max_pos.max(0));
+            self.vel = -self.vel / 4;
+        }
+
+        let color = self.palette.lookup(self.color);
+        let (start, end) = (self.pos, self.pos + len);
+        for (i, led) in frame.iter_mut().enumerate() {
+            let (lo, hi) = (i as i32 * 256, i as i32 * 256 + 256);
+            let cover = (end.min(hi) - start.max(lo)).clamp(0, 256);
+            *led = match cover {
+                0 => Rgb::BLACK,
+                256 => color,
+                cover => color.scale(cover as u8),
+            };
+        }
+        // A lighter surface on the high side
+        let surface = if a < 0 { (end - 1) / 256 } else { start / 256 };
+        if len > 0 && (0..n).contains(&surface) {
+            let led = &mut frame[surface as usize];
+            *led = led.saturating_add(Rgb::new(48, 48, 48));
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Intensity, "Volume"),
+            ParamInfo::new(Param::Palette, "Palette"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Intensity => ParamValue::Level(self.volume),
+            Param::Palette => ParamValue::Palette(self.palette),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Intensity, ParamValue::Level(level)) => self.volume = level,
+            (Param::Palette, ParamValue::Palette(palette)) => self.palette = palette,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,90 @@
+// Meteor rain: a bright head dragging a ragged, decaying tail
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::math::Rng;
+use crate::pio_programs::Rgb;
+
+/// A meteor falling down the strip, over and over
+///
+/// The tail is what the head leaves behind, faded at random so it breaks up
+/// as it decays.
+pub struct Meteor {
+    pub color: Rgb,
+    /// Pixels in the head
+    pub size: u8,
+    /// How much of the tail fades when it does; higher values give shorter tails
+    pub trail_decay: u8,
+    /// Pixels per second
+    pub speed: u16,
+    rng: Rng,
+}
+
+impl Meteor {
+    pub fn new(color: Rgb, size: u8, trail_decay: u8) -> Self {
+        Self {
+            color,
+            size,
+            trail_decay,
+            speed: 30,
+            rng: Rng::seeded(0xC0FFEE),
+        }
+    }
+
+    pub fn speed(mut self, speed: u16) -> Self {
+        self.speed = speed;
+        self
+    }
+}
+
+impl Default for Meteor {
+    fn default() -> Self {
+        Self::new(Rgb::WHITE, 3, 64)
+    }
+}
+
+impl Effect for Meteor {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        for led in frame.iter_mut() {
+            if self.rng.random8() < 128 {
+                *led = led.fade_to_black_by(self.trail_decay);
+            }
+        }
+
+        // Carry on past the end so the tail drains off before the next one
+        let len = frame.len();
+        let step = (t as u64 * self.speed as u64 / 1000) as usize;
+        let head = step % (2 * len).max(1);
+        let start = head.saturating_sub(self.size as usize);
+        if start < len {
+            frame[start..head.min(len)].fill(self.color);
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Speed, "Speed"),
+            ParamInfo::new(Param::Intensity, "Trail"),
+            ParamInfo::new(Param::Primary, "Color"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Speed => ParamValue::Level(self.speed.saturating_mul(2).min(255) as u8),
+            Param::Intensity => ParamValue::Level(255 - self.trail_decay),
+            Param::Primary => ParamValue::Color(self.color),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Speed, ParamValue::Level(level)) => self.speed = level as u16 / 2,
+            (Param::Intensity, ParamValue::Level(level)) => self.trail_decay = 255 - level,
+            (Param::Primary, ParamValue::Color(color)) => self.color = color,
+            _ => return false,
+        }
+        true
+    }
+}
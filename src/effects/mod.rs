@@ -0,0 +1,164 @@
+// Animations rendered frame by frame by the engine
+
+mod breathe;
+mod candle;
+mod chase;
+mod distance;
+mod fire;
+mod flash;
+mod host;
+mod liquid;
+mod meteor;
+mod notes;
+mod pacifica;
+mod params;
+mod plasma;
+mod pov;
+mod pride;
+mod rainbow;
+mod scanner;
+mod sparkle;
+mod spectrum;
+mod thermometer;
+mod vu;
+mod weather;
+mod wipe;
+
+pub use breathe::Breathe;
+pub use candle::Candle;
+pub use chase::TheaterChase;
+pub use distance::{DistanceMeter, DistanceMode};
+pub use fire::Fire;
+pub use flash::{Lightning, Strobe, MAX_FLASH_HZ, MIN_FLASH_INTERVAL_MS};
+pub use host::HostPixels;
+pub use liquid::{Axis, Liquid};
+pub use meteor::Meteor;
+pub use notes::{NoteFlash, MAX_NOTE_SEGMENTS};
+pub use pacifica::Pacifica;
+pub use params::{Param, ParamInfo, ParamValue};
+pub use plasma::Plasma;
+pub use pov::PovWheel;
+pub use pride::Pride;
+pub use rainbow::RainbowCycle;
+pub use scanner::Scanner;
+pub use sparkle::Sparkle;
+pub use spectrum::Spectrum;
+pub use thermometer::{Thermometer, ThermometerMode};
+pub use vu::VuMeter;
+pub use weather::Weather;
+pub use wipe::ColorWipe;
+
+use crate::palette::Palette16;
+use crate::pio_programs::Rgb;
+
+/// An animation that draws one frame at a time
+///
+/// `frame` still holds the previous frame, so effects can fade or shift it
+/// rather than redraw from scratch. `t` is milliseconds since the engine
+/// started.
+///
+/// Effects also list the common [`Param`]s they respond to, so buttons, knobs
+/// and serial commands can adjust whichever effect is running.
+pub trait Effect {
+    fn render(&mut self, frame: &mut [Rgb], t: u32);
+
+    /// The parameters this effect responds to
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[];
+        PARAMS
+    }
+
+    /// Current setting of `param`, if the effect has it
+    fn param(&self, _param: Param) -> Option<ParamValue> {
+        None
+    }
+
+    /// Change `param`; false if the effect doesn't have it or `value` is the
+    /// wrong kind
+    fn set_param(&mut self, _param: Param, _value: ParamValue) -> bool {
+        false
+    }
+}
+
+/// Every pixel one color
+pub struct Solid {
+    pub color: Rgb,
+}
+
+impl Solid {
+    pub fn new(color: Rgb) -> Self {
+        Self { color }
+    }
+}
+
+impl Effect for Solid {
+    fn render(&mut self, frame: &mut [Rgb], _t: u32) {
+        frame.fill(self.color);
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[ParamInfo::new(Param::Primary, "Color")];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        match param {
+            Param::Primary => Some(ParamValue::Color(self.color)),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Primary, ParamValue::Color(color)) => self.color = color,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Where an effect takes its colors from
+#[derive(Copy, Clone, Debug)]
+pub enum ColorSource {
+    /// One fixed color
+    Color(Rgb),
+    /// Spread a palette along the strip
+    Palette(&'static Palette16),
+}
+
+impl ColorSource {
+    /// As the `Primary` or `Palette` parameter, whichever this is
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        match (param, self) {
+            (Param::Primary, ColorSource::Color(color)) => Some(ParamValue::Color(*color)),
+            (Param::Palette, ColorSource::Palette(palette)) => Some(ParamValue::Palette(palette)),
+            _ => None,
+        }
+    }
+
+    /// Set from a `Primary` color or a `Palette`
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        *self = match (param, value) {
+            (Param::Primary, ParamValue::Color(color)) => ColorSource::Color(color),
+            (Param::Palette, ParamValue::Palette(palette)) => ColorSource::Palette(palette),
+            _ => return false,
+        };
+        true
+    }
+
+    /// The color for pixel `i`, with palettes advanced by `offset`
+    pub fn color_at(&self, i: usize, offset: u8) -> Rgb {
+        match self {
+            ColorSource::Color(color) => *color,
+            ColorSource::Palette(palette) => {
+                palette.lookup(offset.wrapping_add((i as u8).wrapping_mul(8)))
+            }
+        }
+    }
+}
+
+impl From<Rgb> for ColorSource {
+    fn from(color: Rgb) -> Self {
+        ColorSource::Color(color)
+    }
+}
@@ -0,0 +1,90 @@
+// Note flashes: MIDI notes lighting up zones of the strip
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::color::Hsv;
+use crate::midi::Midi;
+use crate::pio_programs::Rgb;
+
+/// Most zones the strip can be split into
+pub const MAX_NOTE_SEGMENTS: usize = 16;
+
+/// Each note struck flashes one zone of the strip, which then fades
+///
+/// Notes go round the `segments` zones in order, so a scale climbs along
+/// the strip; how hard the note was struck sets how bright, and its pitch
+/// class (C, C#, D ...) its color, twelve steps round the color wheel from
+/// the controller's hue.
+pub struct NoteFlash<'a> {
+    midi: &'a Midi,
+    pub segments: u8,
+    /// How much a flash fades each frame
+    pub decay: u8,
+    levels: [u8; MAX_NOTE_SEGMENTS],
+    hues: [u8; MAX_NOTE_SEGMENTS],
+}
+
+impl<'a> NoteFlash<'a> {
+    pub fn new(midi: &'a Midi) -> Self {
+        Self {
+            midi,
+            segments: 8,
+            decay: 8,
+            levels: [0; MAX_NOTE_SEGMENTS],
+            hues: [0; MAX_NOTE_SEGMENTS],
+        }
+    }
+
+    pub fn segments(mut self, segments: u8) -> Self {
+        self.segments = segments;
+        self
+    }
+
+    pub fn decay(mut self, decay: u8) -> Self {
+        self.decay = decay;
+        self
+    }
+}
+
+impl Effect for NoteFlash<'_> {
+    fn render(&mut self, frame: &mut [Rgb], _t: u32) {
+        let segments = (self.segments as usize).clamp(1, MAX_NOTE_SEGMENTS);
+        for note in 0..128 {
+            let velocity = self.midi.take_strike(note);
+            if velocity == 0 {
+                continue;
+            }
+            let segment = note as usize % segments;
+            self.levels[segment] = self.levels[segment].max(velocity << 1 | 1);
+            self.hues[segment] = self.midi.hue().wrapping_add(note % 12 * 21);
+        }
+
+        let n = frame.len().max(1);
+        for (i, led) in frame.iter_mut().enumerate() {
+            let segment = i * segments / n;
+            *led = Hsv::new(self.hues[segment], 255, self.levels[segment]).to_rgb();
+        }
+        for level in &mut self.levels {
+            *level = level.saturating_sub(self.decay);
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[ParamInfo::new(Param::Speed, "Decay")];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Speed => ParamValue::Level(self.decay),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Speed, ParamValue::Level(decay)) => self.decay = decay,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,94 @@
+// Pacifica: gentle ocean waves, after Mark Kriegsman's FastLED sketch
+
+use super::Effect;
+use crate::math::{beat16, beat8, beatsin16, beatsin8, qadd8, scale8, sin8};
+use crate::palette::{color_from_palette, Palette16, PACIFICA_1, PACIFICA_2, PACIFICA_3};
+use crate::pio_programs::Rgb;
+
+/// Four layers of blue-green waves drifting past each other, with whitecaps
+/// where they pile up
+#[derive(Default)]
+pub struct Pacifica;
+
+impl Pacifica {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Add one layer of waves scrolled to `start`, `scale` setting how tightly
+/// they're packed
+fn layer(frame: &mut [Rgb], pal: &Palette16, start: u16, scale: u16, bri: u8, offset: u16) {
+    let half = scale / 2 + 20;
+    let mut ci = start;
+    let mut angle = offset;
+    for led in frame.iter_mut() {
+        angle = angle.wrapping_add(250);
+        let s = sin8((angle >> 8) as u8) as u16;
+        ci = ci.wrapping_add(((s * half) >> 8) + half);
+        let index = scale8(sin8((ci >> 8) as u8), 240);
+        *led = led.saturating_add(color_from_palette(pal, index, bri));
+    }
+}
+
+impl Effect for Pacifica {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        frame.fill(Rgb::new(2, 6, 10));
+
+        // Each layer scrolls at its own pace
+        let scroll = |rate: u32| t.wrapping_mul(rate) as u16;
+        layer(
+            frame,
+            &PACIFICA_1,
+            scroll(11).wrapping_neg(),
+            beatsin16(3, 11 * 256, 14 * 256, t),
+            beatsin8(10, 70, 130, t),
+            beat16(301, t).wrapping_neg(),
+        );
+        layer(
+            frame,
+            &PACIFICA_2,
+            scroll(8).wrapping_neg(),
+            beatsin16(4, 6 * 256, 9 * 256, t),
+            beatsin8(17, 40, 80, t),
+            beat16(401, t),
+        );
+        layer(
+            frame,
+            &PACIFICA_3,
+            scroll(5),
+            6 * 256,
+            beatsin8(9, 10, 38, t),
+            beat16(503, t).wrapping_neg(),
+        );
+        layer(
+            frame,
+            &PACIFICA_3,
+            scroll(4),
+            5 * 256,
+            beatsin8(8, 10, 28, t),
+            beat16(601, t),
+        );
+
+        // Whitecaps where the layers add up brightest
+        let base = beatsin8(9, 55, 65, t);
+        let mut wave = beat8(7, t);
+        for led in frame.iter_mut() {
+            let threshold = scale8(sin8(wave), 20) + base;
+            wave = wave.wrapping_add(7);
+            let light = ((led.r as u16 + led.g as u16 + led.b as u16) / 3) as u8;
+            if light > threshold {
+                let over = light - threshold;
+                let over2 = qadd8(over, over);
+                *led = led.saturating_add(Rgb::new(over, over2, qadd8(over2, over2)));
+            }
+        }
+
+        // Deepen the blues and greens
+        for led in frame.iter_mut() {
+            led.r = scale8(led.r, 145) | 2;
+            led.g = scale8(led.g, 200) | 5;
+            led.b |= 7;
+        }
+    }
+}
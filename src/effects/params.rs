@@ -0,0 +1,65 @@
+// Effect-independent parameters, so controls can drive any effect
+
+use crate::palette::Palette16;
+use crate::pio_programs::Rgb;
+
+/// The knobs common to effects; each effect picks the ones it responds to
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Param {
+    /// How fast the effect moves, as a 0-255 level
+    Speed,
+    /// The effect's main tuning knob (tail length, density, ...), 0-255
+    Intensity,
+    Primary,
+    Secondary,
+    Palette,
+}
+
+impl Param {
+    /// The name serial commands and settings documents use for it
+    pub fn key(self) -> &'static str {
+        match self {
+            Param::Speed => "speed",
+            Param::Intensity => "intensity",
+            Param::Primary => "primary",
+            Param::Secondary => "secondary",
+            Param::Palette => "palette",
+        }
+    }
+
+    pub fn from_key(key: &[u8]) -> Option<Self> {
+        [
+            Param::Speed,
+            Param::Intensity,
+            Param::Primary,
+            Param::Secondary,
+            Param::Palette,
+        ]
+        .into_iter()
+        .find(|param| key.eq_ignore_ascii_case(param.key().as_bytes()))
+    }
+}
+
+/// A parameter's setting
+#[derive(Copy, Clone, Debug, PartialEq, defmt::Format)]
+pub enum ParamValue {
+    /// For `Speed` and `Intensity`
+    Level(u8),
+    /// For `Primary` and `Secondary`
+    Color(Rgb),
+    Palette(&'static Palette16),
+}
+
+/// Describes one parameter an effect exposes, for building UIs
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct ParamInfo {
+    pub param: Param,
+    /// What the parameter does in this effect, e.g. "Cooling"
+    pub name: &'static str,
+}
+
+impl ParamInfo {
+    pub const fn new(param: Param, name: &'static str) -> Self {
+        Self { param, name }
+    }
+}
@@ -0,0 +1,90 @@
+// Plasma / lava lamp: a drifting noise field mapped through a palette
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::noise::inoise8;
+use crate::palette::{Palette16, PARTY};
+use crate::pio_programs::Rgb;
+
+/// Slowly morphing blobs of color from a 2D noise field
+///
+/// The field scrolls through time, so on a strip the blobs swell, drift and
+/// merge. Set `width` on a row-major matrix to sample the field in 2D.
+pub struct Plasma {
+    pub palette: &'static Palette16,
+    /// Noise units between neighbouring pixels; 256 is one blob per pixel
+    pub scale: u16,
+    /// Noise units the field moves per second
+    pub speed: u16,
+    /// Pixels per row on a matrix, 0 for a plain strip
+    pub width: u16,
+}
+
+impl Plasma {
+    pub fn new(palette: &'static Palette16, scale: u16, speed: u16) -> Self {
+        Self {
+            palette,
+            scale,
+            speed,
+            width: 0,
+        }
+    }
+
+    pub fn width(mut self, width: u16) -> Self {
+        self.width = width;
+        self
+    }
+}
+
+impl Default for Plasma {
+    fn default() -> Self {
+        Self::new(&PARTY, 40, 100)
+    }
+}
+
+impl Effect for Plasma {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        let z = (t as u64 * self.speed as u64 / 1000) as u16;
+        let width = match self.width {
+            0 => frame.len().max(1),
+            w => w as usize,
+        };
+
+        for (i, led) in frame.iter_mut().enumerate() {
+            let (col, row) = ((i % width) as u16, (i / width) as u16);
+            let x = col.wrapping_mul(self.scale).wrapping_add(z / 2);
+            let y = row.wrapping_mul(self.scale).wrapping_add(z);
+            // Stretch the noise's mid-heavy output over the whole palette
+            let n = inoise8(x, y) as i16;
+            let index = ((n - 128) * 2 + 128).clamp(0, 255) as u8;
+            *led = self.palette.lookup(index);
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Speed, "Speed"),
+            ParamInfo::new(Param::Intensity, "Scale"),
+            ParamInfo::new(Param::Palette, "Palette"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Speed => ParamValue::Level((self.speed / 4).min(255) as u8),
+            Param::Intensity => ParamValue::Level(self.scale.min(255) as u8),
+            Param::Palette => ParamValue::Palette(self.palette),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Speed, ParamValue::Level(level)) => self.speed = level as u16 * 4,
+            (Param::Intensity, ParamValue::Level(level)) => self.scale = level as u16,
+            (Param::Palette, ParamValue::Palette(palette)) => self.palette = palette,
+            _ => return false,
+        }
+        true
+    }
+}
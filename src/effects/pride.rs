@@ -0,0 +1,53 @@
+// Pride2015: an ever-changing rainbow, after Mark Kriegsman's FastLED sketch
+
+use super::Effect;
+use crate::color::Hsv;
+use crate::math::{beatsin88, sin8};
+use crate::pio_programs::Rgb;
+
+/// Saturated rainbow whose hue spread, saturation and brightness waves all
+/// drift at unrelated rates, so it never quite repeats
+#[derive(Default)]
+pub struct Pride {
+    pseudotime: u16,
+    hue16: u16,
+    last_t: u32,
+}
+
+impl Pride {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Effect for Pride {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        let sat = beatsin88(87, 220, 250, t) as u8;
+        let depth = beatsin88(341, 96, 224, t) as u8;
+        let theta_step = beatsin88(203, 25 * 256, 40 * 256, t);
+        let ms_multiplier = beatsin88(147, 23, 60, t);
+        let hue_step = beatsin88(113, 1, 3000, t);
+
+        let dt = t.wrapping_sub(self.last_t) as u16;
+        self.last_t = t;
+        self.pseudotime = self.pseudotime.wrapping_add(dt.wrapping_mul(ms_multiplier));
+        self.hue16 = self
+            .hue16
+            .wrapping_add(dt.wrapping_mul(beatsin88(400, 5, 9, t)));
+
+        let mut hue16 = self.hue16;
+        let mut theta = self.pseudotime;
+        // Drawn from the far end, as in the original
+        for led in frame.iter_mut().rev() {
+            hue16 = hue16.wrapping_add(hue_step);
+            theta = theta.wrapping_add(theta_step);
+
+            // Squared sine, so pixels spend longer dim than bright
+            let b = sin8((theta >> 8) as u8) as u32;
+            let bri = (b * b * depth as u32 / 65_025) as u8 + (255 - depth);
+
+            let color = Hsv::new((hue16 >> 8) as u8, sat, bri).to_rgb();
+            *led = led.lerp(color, 64);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+// Rainbow scrolling along the strip
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::pio_programs::{rainbow, Rgb};
+
+/// The color wheel laid along the strip and scrolled over time
+pub struct RainbowCycle {
+    /// Wheel steps (of 256) the pattern moves per second
+    pub speed: u16,
+    /// Wheel steps between neighbouring LEDs
+    pub spread: u8,
+}
+
+impl RainbowCycle {
+    pub fn new(speed: u16, spread: u8) -> Self {
+        Self { speed, spread }
+    }
+}
+
+impl Default for RainbowCycle {
+    /// A turn of the wheel every ~2.5 seconds, spread over eight LEDs
+    fn default() -> Self {
+        Self::new(100, 32)
+    }
+}
+
+impl Effect for RainbowCycle {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        let offset = (t as u64 * self.speed as u64 / 1000) as u8;
+        for (i, led) in frame.iter_mut().enumerate() {
+            *led = rainbow(offset.wrapping_add((i as u8).wrapping_mul(self.spread)));
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Speed, "Speed"),
+            ParamInfo::new(Param::Intensity, "Spread"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Speed => ParamValue::Level((self.speed / 2).min(255) as u8),
+            Param::Intensity => ParamValue::Level(self.spread),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Speed, ParamValue::Level(level)) => self.speed = level as u16 * 2,
+            (Param::Intensity, ParamValue::Level(level)) => self.spread = level,
+            _ => return false,
+        }
+        true
+    }
+}
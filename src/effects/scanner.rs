@@ -0,0 +1,100 @@
+// Larson scanner, the KITT / Cylon eye
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::easing::Easing;
+use crate::pio_programs::Rgb;
+
+/// An eye bouncing end to end, leaving a fading tail
+pub struct Scanner {
+    pub color: Rgb,
+    /// Pixels lit at full brightness
+    pub width: u8,
+    /// Average pixels per second
+    pub speed: u16,
+    /// How much of the tail fades each frame; lower values leave longer tails
+    pub fade: u8,
+    /// How the eye moves along each sweep; `SineInOut` slows it at the ends
+    pub easing: Easing,
+}
+
+impl Scanner {
+    pub fn new(color: Rgb, width: u8, speed: u16) -> Self {
+        Self {
+            color,
+            width,
+            speed,
+            fade: 64,
+            easing: Easing::Linear,
+        }
+    }
+
+    pub fn fade(mut self, fade: u8) -> Self {
+        self.fade = fade;
+        self
+    }
+
+    pub fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self::new(Rgb::RED, 2, 20)
+    }
+}
+
+impl Effect for Scanner {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        for led in frame.iter_mut() {
+            *led = led.fade_to_black_by(self.fade);
+        }
+
+        let width = (self.width.max(1) as usize).min(frame.len());
+        let travel = frame.len() - width;
+        let pos = if travel == 0 || self.speed == 0 {
+            0
+        } else {
+            // Out and back again, each sweep eased
+            let sweep_ms = (travel as u64 * 1000 / self.speed as u64).max(1);
+            let phase = t as u64 % (2 * sweep_ms);
+            let progress = (phase % sweep_ms * 255 / sweep_ms) as u8;
+            let pos = (self.easing.apply(progress) as usize * travel + 127) / 255;
+            if phase < sweep_ms {
+                pos
+            } else {
+                travel - pos
+            }
+        };
+        frame[pos..pos + width].fill(self.color);
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Speed, "Speed"),
+            ParamInfo::new(Param::Intensity, "Tail"),
+            ParamInfo::new(Param::Primary, "Color"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Speed => ParamValue::Level(self.speed.saturating_mul(2).min(255) as u8),
+            Param::Intensity => ParamValue::Level(255 - self.fade),
+            Param::Primary => ParamValue::Color(self.color),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Speed, ParamValue::Level(level)) => self.speed = level as u16 / 2,
+            (Param::Intensity, ParamValue::Level(level)) => self.fade = 255 - level,
+            (Param::Primary, ParamValue::Color(color)) => self.color = color,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,82 @@
+// Random twinkling pixels
+
+use super::{ColorSource, Effect, Param, ParamInfo, ParamValue};
+use crate::math::Rng;
+use crate::pio_programs::Rgb;
+
+/// Pixels lighting up at random and fading back into a background
+pub struct Sparkle {
+    pub colors: ColorSource,
+    pub background: Rgb,
+    /// Chance out of 255 of a new sparkle each frame
+    pub density: u8,
+    /// How far each pixel fades toward the background per frame
+    pub fade: u8,
+    rng: Rng,
+}
+
+impl Sparkle {
+    pub fn new(colors: impl Into<ColorSource>, density: u8, fade: u8) -> Self {
+        Self {
+            colors: colors.into(),
+            background: Rgb::BLACK,
+            density,
+            fade,
+            rng: Rng::seeded(0x5EED),
+        }
+    }
+
+    pub fn background(mut self, background: Rgb) -> Self {
+        self.background = background;
+        self
+    }
+}
+
+impl Default for Sparkle {
+    fn default() -> Self {
+        Self::new(Rgb::WHITE, 80, 24)
+    }
+}
+
+impl Effect for Sparkle {
+    fn render(&mut self, frame: &mut [Rgb], _t: u32) {
+        for led in frame.iter_mut() {
+            *led = led.lerp(self.background, self.fade);
+        }
+
+        if !frame.is_empty() && self.rng.random8() < self.density {
+            let i = self.rng.next_u32() as usize % frame.len();
+            frame[i] = self.colors.color_at(i, self.rng.random8());
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Speed, "Fade"),
+            ParamInfo::new(Param::Intensity, "Density"),
+            ParamInfo::new(Param::Primary, "Color"),
+            ParamInfo::new(Param::Secondary, "Background"),
+            ParamInfo::new(Param::Palette, "Palette"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Speed => ParamValue::Level(self.fade),
+            Param::Intensity => ParamValue::Level(self.density),
+            Param::Secondary => ParamValue::Color(self.background),
+            _ => return self.colors.param(param),
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Speed, ParamValue::Level(level)) => self.fade = level,
+            (Param::Intensity, ParamValue::Level(level)) => self.density = level,
+            (Param::Secondary, ParamValue::Color(color)) => self.background = color,
+            _ => return self.colors.set_param(param, value),
+        }
+        true
+    }
+}
@@ -0,0 +1,115 @@
+// Spectrum analyzer: frequency bands as strip segments or matrix columns
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::audio::{Audio, BANDS};
+use crate::palette::{Palette16, PARTY};
+use crate::pio_programs::Rgb;
+
+/// Shows the [`Audio`] spectrum, bass first
+///
+/// On a strip each band gets an equal segment whose brightness follows the
+/// band. With `width` set, on a row-major matrix, each column is a bar
+/// rising from the bottom row. Bands jump up with the music and fall back
+/// at `fall` levels a second, so bars don't flicker.
+pub struct Spectrum<'a> {
+    audio: &'a Audio,
+    /// Colors across the bands
+    pub palette: &'static Palette16,
+    pub fall: u16,
+    /// Pixels per row on a matrix, 0 for a plain strip
+    pub width: u16,
+    shown: [u8; BANDS],
+    last_t: u32,
+}
+
+impl<'a> Spectrum<'a> {
+    pub fn new(audio: &'a Audio) -> Self {
+        Self {
+            audio,
+            palette: &PARTY,
+            fall: 400,
+            width: 0,
+            shown: [0; BANDS],
+            last_t: 0,
+        }
+    }
+
+    pub fn palette(mut self, palette: &'static Palette16) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    pub fn width(mut self, width: u16) -> Self {
+        self.width = width;
+        self
+    }
+
+    fn band_color(&self, band: usize) -> Rgb {
+        self.palette.lookup((band * 240 / (BANDS - 1)) as u8)
+    }
+}
+
+impl Effect for Spectrum<'_> {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        let n = frame.len();
+        if n == 0 {
+            return;
+        }
+        let dt = t.wrapping_sub(self.last_t);
+        self.last_t = t;
+
+        let fall = (self.fall as u32 * dt / 1000).min(255) as u8;
+        for (shown, level) in self.shown.iter_mut().zip(self.audio.bands()) {
+            *shown = shown.saturating_sub(fall).max(level);
+        }
+
+        match self.width as usize {
+            0 => {
+                for (i, led) in frame.iter_mut().enumerate() {
+                    let band = i * BANDS / n;
+                    *led = self.band_color(band).scale(self.shown[band]);
+                }
+            }
+            width => {
+                let height = (n / width).max(1);
+                for (i, led) in frame.iter_mut().enumerate() {
+                    let (col, row) = (i % width, i / width);
+                    let band = (col * BANDS / width).min(BANDS - 1);
+                    // Rows lit from the bottom, the top one partly
+                    let lit = self.shown[band] as usize * height * 256 / 255;
+                    let from_bottom = (height - 1).saturating_sub(row) * 256;
+                    *led = match lit.saturating_sub(from_bottom) {
+                        0 => Rgb::BLACK,
+                        cover if cover >= 256 => self.band_color(band),
+                        cover => self.band_color(band).scale(cover as u8),
+                    };
+                }
+            }
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Speed, "Fall"),
+            ParamInfo::new(Param::Palette, "Palette"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Speed => ParamValue::Level((self.fall / 4).min(255) as u8),
+            Param::Palette => ParamValue::Palette(self.palette),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Speed, ParamValue::Level(level)) => self.fall = level as u16 * 4,
+            (Param::Palette, ParamValue::Palette(palette)) => self.palette = palette,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,116 @@
+// Thermometer: the temperature as the strip's color or as a rising bar
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::climate::Climate;
+use crate::palette::{Palette16, TEMPERATURE};
+use crate::pio_programs::Rgb;
+
+/// How the temperature is shown
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum ThermometerMode {
+    /// The whole strip in the temperature's color
+    Fill,
+    /// A bar from the start of the strip, the palette running along it like
+    /// a thermometer's scale; the whole strip lit at `hot_mc`
+    Bar,
+}
+
+/// Shows the [`Climate`] temperature
+///
+/// `cold_mc` and colder shows as the start of the palette, `hot_mc` and
+/// hotter as the end. The display eases to each new reading rather than
+/// jumping. The strip stays dark until the first reading.
+pub struct Thermometer<'a> {
+    climate: &'a Climate,
+    pub mode: ThermometerMode,
+    pub palette: &'static Palette16,
+    pub cold_mc: i32,
+    pub hot_mc: i32,
+    /// Palette index shown, with 8 fraction bits
+    shown: Option<u16>,
+}
+
+impl<'a> Thermometer<'a> {
+    pub fn new(climate: &'a Climate, mode: ThermometerMode) -> Self {
+        Self {
+            climate,
+            mode,
+            palette: &TEMPERATURE,
+            cold_mc: 15_000,
+            hot_mc: 30_000,
+            shown: None,
+        }
+    }
+
+    pub fn palette(mut self, palette: &'static Palette16) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    pub fn range_mc(mut self, cold_mc: i32, hot_mc: i32) -> Self {
+        self.cold_mc = cold_mc;
+        self.hot_mc = hot_mc;
+        self
+    }
+
+    /// Where `mc` falls in the palette, stopping short of the wrap back to
+    /// the first entry
+    fn index(&self, mc: i32) -> u16 {
+        let span = (self.hot_mc - self.cold_mc).max(1);
+        let above = (mc.clamp(self.cold_mc, self.hot_mc) - self.cold_mc) as i64;
+        (above * (240 << 8) / span as i64) as u16
+    }
+}
+
+impl Effect for Thermometer<'_> {
+    fn render(&mut self, frame: &mut [Rgb], _t: u32) {
+        let Some(mc) = self.climate.temperature_mc() else {
+            frame.fill(Rgb::BLACK);
+            return;
+        };
+        let target = self.index(mc);
+        let shown = match self.shown {
+            Some(shown) => (shown as i32 + (target as i32 - shown as i32) / 16) as u16,
+            None => target,
+        };
+        self.shown = Some(shown);
+
+        match self.mode {
+            ThermometerMode::Fill => frame.fill(self.palette.lookup((shown >> 8) as u8)),
+            ThermometerMode::Bar => {
+                let n = frame.len();
+                // Lit length in 1/256 pixels
+                let lit = shown as usize * n / 240;
+                let last = n.saturating_sub(1).max(1);
+                for (i, led) in frame.iter_mut().enumerate() {
+                    let color = self.palette.lookup((i * 240 / last) as u8);
+                    *led = match lit.saturating_sub(i * 256) {
+                        0 => Rgb::BLACK,
+                        cover if cover >= 256 => color,
+                        cover => color.scale(cover as u8),
+                    };
+                }
+            }
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[ParamInfo::new(Param::Palette, "Palette")];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Palette => ParamValue::Palette(self.palette),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Palette, ParamValue::Palette(palette)) => self.palette = palette,
+            _ => return false,
+        }
+        true
+    }
+}
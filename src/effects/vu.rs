@@ -0,0 +1,118 @@
+// VU meter: the strip fills with the sound level, with a falling peak dot
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::audio::Audio;
+use crate::palette::{Palette16, VU};
+use crate::pio_programs::Rgb;
+
+/// Fills the strip from the start in proportion to the audio level
+///
+/// The top lit pixel is blended by how far the level reaches into it, so
+/// the bar moves smoothly. The highest recent level stays marked in
+/// `peak_color` for `peak_hold_ms`, then drops at `peak_fall` pixels a
+/// second.
+pub struct VuMeter<'a> {
+    audio: &'a Audio,
+    /// Colors up the bar; lookups stop at index 240, short of the wrap
+    pub palette: &'static Palette16,
+    pub peak_color: Rgb,
+    pub peak_hold_ms: u32,
+    pub peak_fall: u8,
+    /// Peak position in 1/256 pixels, and when it was last pushed up
+    peak: u32,
+    peak_t: u32,
+    last_t: u32,
+}
+
+impl<'a> VuMeter<'a> {
+    pub fn new(audio: &'a Audio) -> Self {
+        Self {
+            audio,
+            palette: &VU,
+            peak_color: Rgb::WHITE,
+            peak_hold_ms: 500,
+            peak_fall: 20,
+            peak: 0,
+            peak_t: 0,
+            last_t: 0,
+        }
+    }
+
+    pub fn palette(mut self, palette: &'static Palette16) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    pub fn peak_color(mut self, color: Rgb) -> Self {
+        self.peak_color = color;
+        self
+    }
+
+    pub fn peak_hold_ms(mut self, ms: u32) -> Self {
+        self.peak_hold_ms = ms;
+        self
+    }
+}
+
+impl Effect for VuMeter<'_> {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        let n = frame.len();
+        if n == 0 {
+            return;
+        }
+        let dt = t.wrapping_sub(self.last_t);
+        self.last_t = t;
+
+        let lit = self.audio.level() as u32 * n as u32 * 256 / 255;
+        if lit >= self.peak {
+            self.peak = lit;
+            self.peak_t = t;
+        } else if t.wrapping_sub(self.peak_t) >= self.peak_hold_ms {
+            let fall = self.peak_fall as u32 * 256 * dt / 1000;
+            self.peak = self.peak.saturating_sub(fall).max(lit);
+        }
+
+        let last = (n - 1).max(1) as u32;
+        for (i, led) in frame.iter_mut().enumerate() {
+            let start = i as u32 * 256;
+            let color = self.palette.lookup((i as u32 * 240 / last) as u8);
+            *led = match lit.saturating_sub(start) {
+                0 => Rgb::BLACK,
+                cover if cover >= 256 => color,
+                cover => color.scale(cover as u8),
+            };
+        }
+        if self.peak > 0 {
+            let top = self.peak.div_ceil(256) as usize;
+            frame[top.min(n) - 1] = self.peak_color;
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Speed, "Peak fall"),
+            ParamInfo::new(Param::Secondary, "Peak color"),
+            ParamInfo::new(Param::Palette, "Palette"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Speed => ParamValue::Level(self.peak_fall),
+            Param::Secondary => ParamValue::Color(self.peak_color),
+            Param::Palette => ParamValue::Palette(self.palette),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Speed, ParamValue::Level(level)) => self.peak_fall = level,
+            (Param::Secondary, ParamValue::Color(color)) => self.peak_color = color,
+            (Param::Palette, ParamValue::Palette(palette)) => self.palette = palette,
+            _ => return false,
+        }
+        true
+    }
+}
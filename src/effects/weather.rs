@@ -0,0 +1,107 @@
+// Weather: drifting clouds of color that follow the barometer and humidity
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::climate::Climate;
+use crate::noise::inoise8;
+use crate::palette::{Palette16, OCEAN, STORM, SUNNY};
+use crate::pio_programs::Rgb;
+
+/// Color the humid air mists everything toward
+const MIST: Rgb = Rgb {
+    r: 140,
+    g: 140,
+    b: 150,
+};
+
+/// Slow noise clouds in colors from the [`Climate`] pressure trend
+///
+/// Falling pressure, weather on the way, turns the clouds to the `falling`
+/// palette; rising pressure, clearing up, to `rising`; steady shows
+/// `steady`. The blend is complete at a change of `swing_pa` over three
+/// hours (the 3 hPa a forecast would call falling or rising quickly).
+/// Above 60 %RH the colors wash out toward a grey mist, fully at 100 %.
+pub struct Weather<'a> {
+    climate: &'a Climate,
+    pub falling: &'static Palette16,
+    pub steady: &'static Palette16,
+    pub rising: &'static Palette16,
+    pub swing_pa: i32,
+    /// Noise units the clouds move per second
+    pub speed: u16,
+    /// Eased trend blend, -256 falling to 256 rising
+    shown: i32,
+}
+
+impl<'a> Weather<'a> {
+    pub fn new(climate: &'a Climate) -> Self {
+        Self {
+            climate,
+            falling: &STORM,
+            steady: &OCEAN,
+            rising: &SUNNY,
+            swing_pa: 300,
+            speed: 40,
+            shown: 0,
+        }
+    }
+
+    pub fn palettes(
+        mut self,
+        falling: &'static Palette16,
+        steady: &'static Palette16,
+        rising: &'static Palette16,
+    ) -> Self {
+        self.falling = falling;
+        self.steady = steady;
+        self.rising = rising;
+        self
+    }
+}
+
+impl Effect for Weather<'_> {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        let swing = self.swing_pa.max(1);
+        let target = self
+            .climate
+            .pressure_trend()
+            .map_or(0, |trend| trend.clamp(-swing, swing) * 256 / swing);
+        self.shown += (target - self.shown) / 32;
+
+        let (other, blend) = if self.shown < 0 {
+            (self.falling, (-self.shown).min(255) as u8)
+        } else {
+            (self.rising, self.shown.min(255) as u8)
+        };
+        let mist = match self.climate.humidity_permille() {
+            Some(rh) if rh > 600 => ((rh.min(1000) - 600) * 255 / 400) as u8,
+            _ => 0,
+        };
+
+        let z = (t as u64 * self.speed as u64 / 1000) as u16;
+        for (i, led) in frame.iter_mut().enumerate() {
+            let index = inoise8((i as u16).wrapping_mul(30).wrapping_add(z), z / 3);
+            let color = self.steady.lookup(index).lerp(other.lookup(index), blend);
+            *led = color.lerp(MIST, mist);
+        }
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[ParamInfo::new(Param::Speed, "Speed")];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Speed => ParamValue::Level((self.speed / 2).min(255) as u8),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Speed, ParamValue::Level(level)) => self.speed = level as u16 * 2,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,97 @@
+// Color wipe: the strip filling up one pixel at a time
+
+use super::{Effect, Param, ParamInfo, ParamValue};
+use crate::pio_programs::Rgb;
+
+/// Fills the strip pixel by pixel, then starts over
+///
+/// With an `alternate` color, successive passes wipe each color over the
+/// other; otherwise every pass starts again from black.
+pub struct ColorWipe {
+    pub color: Rgb,
+    pub alternate: Option<Rgb>,
+    /// Fill from the far end of the strip
+    pub reversed: bool,
+    /// Pixels per second
+    pub speed: u16,
+}
+
+impl ColorWipe {
+    pub fn new(color: Rgb, speed: u16) -> Self {
+        Self {
+            color,
+            alternate: None,
+            reversed: false,
+            speed,
+        }
+    }
+
+    pub fn alternate(mut self, color: Rgb) -> Self {
+        self.alternate = Some(color);
+        self
+    }
+
+    pub fn reversed(mut self, reversed: bool) -> Self {
+        self.reversed = reversed;
+        self
+    }
+}
+
+impl Default for ColorWipe {
+    fn default() -> Self {
+        Self::new(Rgb::GREEN, 20)
+    }
+}
+
+impl Effect for ColorWipe {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        let len = frame.len();
+        if len == 0 {
+            return;
+        }
+        let step = (t as u64 * self.speed as u64 / 1000) as usize;
+        let filled = step % len + 1;
+        let (fill, under) = match self.alternate {
+            Some(other) if (step / len) % 2 == 1 => (other, self.color),
+            Some(other) => (self.color, other),
+            None => (self.color, Rgb::BLACK),
+        };
+
+        let (head, tail) = frame.split_at_mut(if self.reversed { len - filled } else { filled });
+        let (done, rest) = if self.reversed {
+            (tail, head)
+        } else {
+            (head, tail)
+        };
+        done.fill(fill);
+        rest.fill(under);
+    }
+
+    fn params(&self) -> &'static [ParamInfo] {
+        const PARAMS: &[ParamInfo] = &[
+            ParamInfo::new(Param::Speed, "Speed"),
+            ParamInfo::new(Param::Primary, "Color"),
+            ParamInfo::new(Param::Secondary, "Alternate"),
+        ];
+        PARAMS
+    }
+
+    fn param(&self, param: Param) -> Option<ParamValue> {
+        Some(match param {
+            Param::Speed => ParamValue::Level(self.speed.saturating_mul(2).min(255) as u8),
+            Param::Primary => ParamValue::Color(self.color),
+            Param::Secondary => ParamValue::Color(self.alternate?),
+            _ => return None,
+        })
+    }
+
+    fn set_param(&mut self, param: Param, value: ParamValue) -> bool {
+        match (param, value) {
+            (Param::Speed, ParamValue::Level(level)) => self.speed = level as u16 / 2,
+            (Param::Primary, ParamValue::Color(color)) => self.color = color,
+            (Param::Secondary, ParamValue::Color(color)) => self.alternate = Some(color),
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,101 @@
+// Quadrature rotary encoder decoding, with acceleration for fast turns
+
+use embedded_hal::digital::v2::InputPin;
+
+/// Steps per detent for the common EC11-style encoders
+pub const DEFAULT_STEPS_PER_DETENT: u8 = 4;
+
+/// Direction of each (previous, current) AB state pair, indexed by
+/// `previous << 2 | current`; invalid jumps count as no movement
+const TRANSITIONS: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
+
+/// A two-channel rotary encoder on a pair of GPIOs
+///
+/// Both channels are read as active low, wired to ground with pull-ups. Poll
+/// it every millisecond or so, since a quick turn moves through the states
+/// fast. Swap the pins if it counts the wrong way. The push switch, if any,
+/// is just a [`Button`](crate::button::Button).
+///
+/// ```ignore
+/// let mut knob = RotaryEncoder::new(
+///     pins.gpio17.into_pull_up_input(),
+///     pins.gpio18.into_pull_up_input(),
+/// );
+/// let level = (brightness as i16 + knob.poll(now_ms)).clamp(0, 255) as u8;
+/// ```
+pub struct RotaryEncoder<A, B> {
+    a: A,
+    b: B,
+    state: u8,
+    /// Quadrature steps since the last whole detent
+    steps: i8,
+    steps_per_detent: u8,
+    accelerate: bool,
+    last_detent_ms: u32,
+}
+
+impl<A: InputPin, B: InputPin> RotaryEncoder<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        let mut encoder = Self {
+            a,
+            b,
+            state: 0,
+            steps: 0,
+            steps_per_detent: DEFAULT_STEPS_PER_DETENT,
+            accelerate: true,
+            last_detent_ms: 0,
+        };
+        encoder.state = encoder.read();
+        encoder
+    }
+
+    /// For encoders that click every one or two steps instead of four
+    pub fn steps_per_detent(mut self, steps: u8) -> Self {
+        self.steps_per_detent = steps.clamp(1, 4);
+        self
+    }
+
+    /// Whether fast turns count extra, on by default
+    pub fn acceleration(mut self, accelerate: bool) -> Self {
+        self.accelerate = accelerate;
+        self
+    }
+
+    fn read(&self) -> u8 {
+        let a = matches!(self.a.is_low(), Ok(true)) as u8;
+        let b = matches!(self.b.is_low(), Ok(true)) as u8;
+        a << 1 | b
+    }
+
+    /// Detents turned since the last poll, clockwise positive
+    ///
+    /// With acceleration, detents come less than 100 ms apart count double,
+    /// and more for quicker spins, so a flick sweeps the whole range.
+    pub fn poll(&mut self, now_ms: u32) -> i16 {
+        let state = self.read();
+        self.steps += TRANSITIONS[(self.state << 2 | state) as usize];
+        self.state = state;
+
+        let detent = self.steps_per_detent as i8;
+        if self.steps.abs() < detent {
+            return 0;
+        }
+        let direction = self.steps.signum() as i16;
+        self.steps = 0;
+
+        let gap = now_ms.wrapping_sub(self.last_detent_ms);
+        self.last_detent_ms = now_ms;
+        let multiplier = match gap {
+            _ if !self.accelerate => 1,
+            0..=19 => 8,
+            20..=49 => 4,
+            50..=99 => 2,
+            _ => 1,
+        };
+        direction * multiplier
+    }
+
+    pub fn free(self) -> (A, B) {
+        (self.a, self.b)
+    }
+}
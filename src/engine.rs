@@ -0,0 +1,200 @@
+// Runs the active effect at a fixed frame rate and pushes frames to an output
+
+use crate::effects::Effect;
+use crate::framebuffer::FrameBuffer;
+use crate::math::scale8;
+use crate::output::LedOutput;
+use crate::pio_programs::Rgb;
+
+/// Default frame rate; a 30-pixel WS2812 strip can go well past 500
+pub const DEFAULT_FPS: u32 = 60;
+
+/// Something other than the caller that renders frames, such as the
+/// RP2040's second core, for [`Engine::tick_on`]
+pub trait RenderWorker {
+    /// Start rendering `effect` into `frame`, `time_ms` into the effect,
+    /// and return without waiting for it
+    ///
+    /// # Safety
+    ///
+    /// Until [`wait`](Self::wait) returns, nothing else may touch `effect`,
+    /// `frame` or anything the effect reads, and none of them may move.
+    unsafe fn start(&mut self, effect: &mut dyn Effect, frame: &mut [Rgb], time_ms: u32);
+
+    /// Block until the frame last started is rendered; at once if it is
+    fn wait(&mut self);
+}
+
+/// Frame scheduler for one output
+///
+/// Call `tick` from the main loop with the current time. When a frame is
+/// due the effect renders into the back buffer, which is swapped and sent.
+/// If the loop falls behind, missed frames are dropped rather than rendered
+/// back to back. The strip can be shorter than the `N` pixels the buffers
+/// hold, set with `set_leds`.
+///
+/// ```ignore
+/// let mut engine = Engine::<_, NUM_LEDS>::new(ws, DEFAULT_FPS);
+/// loop {
+///     let now_ms = (timer.get_counter().ticks() / 1000) as u32;
+///     engine.tick(&mut effect, now_ms);
+///     delay.delay_ms(engine.ms_until_next_frame(now_ms));
+/// }
+/// ```
+pub struct Engine<O: LedOutput, const N: usize> {
+    output: O,
+    frames: FrameBuffer<N>,
+    frame_ms: u32,
+    leds: usize,
+    start_ms: Option<u32>,
+    next_ms: u32,
+    brightness: u8,
+    dimmer: u8,
+    /// A frame's been started on a worker, to send at the next
+    started: bool,
+}
+
+impl<O: LedOutput, const N: usize> Engine<O, N> {
+    pub fn new(output: O, fps: u32) -> Self {
+        Self {
+            output,
+            frames: FrameBuffer::new(),
+            frame_ms: 1000 / fps.max(1),
+            leds: N,
+            start_ms: None,
+            next_ms: 0,
+            brightness: 255,
+            dimmer: 255,
+            started: false,
+        }
+    }
+
+    /// Master brightness, applied to frames on their way to the output
+    ///
+    /// Works the same on every output, on top of any brightness the driver
+    /// itself applies.
+    pub fn set_brightness(&mut self, level: u8) {
+        self.brightness = level;
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// Automatic dimming on top of the brightness, for sensors and timers
+    /// to turn the strip down without touching the user's setting
+    pub fn set_dimmer(&mut self, level: u8) {
+        self.dimmer = level;
+    }
+
+    pub fn dimmer(&self) -> u8 {
+        self.dimmer
+    }
+
+    /// How many pixels effects render and the output is sent, up to `N`;
+    /// the output shows black past them
+    pub fn set_leds(&mut self, leds: usize) {
+        self.leds = leds.min(N);
+    }
+
+    pub fn leds(&self) -> usize {
+        self.leds
+    }
+
+    /// Render and send a frame if one is due; returns whether it did
+    pub fn tick(&mut self, effect: &mut dyn Effect, now_ms: u32) -> bool {
+        let Some(start_ms) = self.due(now_ms) else {
+            return false;
+        };
+        let leds = self.leds;
+        effect.render(&mut self.frames.back_mut()[..leds], now_ms.wrapping_sub(start_ms));
+        self.frames.swap();
+        self.send();
+        self.advance(now_ms);
+        true
+    }
+
+    /// As [`tick`](Self::tick), with `worker` rendering: the frame sent is
+    /// the one it rendered over the last frame period, so it goes out on
+    /// time however long the effect took, and the next is started before
+    /// this returns
+    ///
+    /// Each frame is rendered for the time it's sent, a period on; the
+    /// first call only starts one.
+    ///
+    /// # Safety
+    ///
+    /// Until `worker.wait()` returns, nothing may touch `effect` or what it
+    /// reads, and neither it nor the engine may move, as for
+    /// [`RenderWorker::start`].
+    pub unsafe fn tick_on<W: RenderWorker>(
+        &mut self,
+        worker: &mut W,
+        effect: &mut dyn Effect,
+        now_ms: u32,
+    ) -> bool {
+        let Some(start_ms) = self.due(now_ms) else {
+            return false;
+        };
+        worker.wait();
+        if self.started {
+            self.frames.swap();
+            self.send();
+        }
+        self.advance(now_ms);
+        let leds = self.leds;
+        let time_ms = self.next_ms.wrapping_sub(start_ms);
+        worker.start(effect, &mut self.frames.back_mut()[..leds], time_ms);
+        self.started = true;
+        true
+    }
+
+    /// The time since the first frame if one is due now
+    fn due(&mut self, now_ms: u32) -> Option<u32> {
+        let start_ms = match self.start_ms {
+            Some(start_ms) => start_ms,
+            None => {
+                // First call, render straight away
+                self.start_ms = Some(now_ms);
+                self.next_ms = now_ms;
+                now_ms
+            }
+        };
+        // Wrapping compare, the millisecond clock rolls over after 49 days
+        (now_ms.wrapping_sub(self.next_ms) as i32 >= 0).then_some(start_ms)
+    }
+
+    /// The front buffer out to the strip, at the brightness
+    fn send(&mut self) {
+        let frame = &self.frames.front()[..self.leds];
+        match scale8(self.brightness, self.dimmer) {
+            255 => self.output.write_frame(frame),
+            level => self
+                .output
+                .write_pixels(frame.iter().map(|led| led.scale(level))),
+        }
+    }
+
+    /// On to the next frame's time
+    fn advance(&mut self, now_ms: u32) {
+        self.next_ms = self.next_ms.wrapping_add(self.frame_ms);
+        if (now_ms.wrapping_sub(self.next_ms) as i32) >= 0 {
+            // Fell more than a frame behind, skip ahead
+            self.next_ms = now_ms.wrapping_add(self.frame_ms);
+        }
+    }
+
+    /// Milliseconds until `tick` will next render, 0 if a frame is due
+    pub fn ms_until_next_frame(&self, now_ms: u32) -> u32 {
+        (self.next_ms.wrapping_sub(now_ms) as i32).max(0) as u32
+    }
+
+    /// The last frame sent
+    pub fn frame(&self) -> &[Rgb] {
+        &self.frames.front()[..self.leds]
+    }
+
+    pub fn output_mut(&mut self) -> &mut O {
+        &mut self.output
+    }
+}
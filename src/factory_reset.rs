@@ -0,0 +1,91 @@
+// Factory reset: the mode button held through power-up wipes everything
+// saved, for a strip whose settings have got it into a state
+
+use embedded_hal::digital::v2::InputPin;
+
+use crate::button::{Button, DEBOUNCE_MS};
+use crate::effects::Effect;
+use crate::engine::Engine;
+use crate::output::LedOutput;
+use crate::pio_programs::Rgb;
+use crate::store;
+
+/// How long the button must be held from power-up
+pub const HOLD_MS: u32 = 10_000;
+
+/// Each flash of the confirmation, and each gap
+const FLASH_MS: u32 = 150;
+
+/// The confirmation shows at least this long: three flashes, then three
+/// more with the colors swapped
+const CONFIRM_MS: u32 = 12 * FLASH_MS;
+
+/// Watch the mode button from power-up for the reset, showing it on the
+/// strip; true once the store's wiped and the button's let go
+///
+/// Held, the strip fills red over [`HOLD_MS`]; then the store is wiped
+/// and the strip flashes alternate LEDs red and white, swapping each time,
+/// until the button's up. Let go sooner, or not pressed at all, it returns
+/// false with nothing changed, taking only the debounce time when there's
+/// no press. The caller restarts after a reset, so everything comes up
+/// from the defaults.
+pub fn run<P: InputPin, O: LedOutput, const N: usize>(
+    button: &mut Button<P>,
+    engine: &mut Engine<O, N>,
+    mut now_ms: impl FnMut() -> u32,
+) -> bool {
+    let start_ms = now_ms();
+    let mut show = ResetShow {
+        held_ms: 0,
+        wiped_ms: None,
+    };
+    loop {
+        let now = now_ms();
+        button.poll(now);
+        let held_ms = now.wrapping_sub(start_ms);
+        // It takes the debounce time before a press shows
+        let down = button.is_pressed() || held_ms <= 2 * DEBOUNCE_MS;
+        match show.wiped_ms {
+            None if !down => return false,
+            None if held_ms >= HOLD_MS => {
+                store::clear();
+                show.wiped_ms = Some(held_ms);
+            }
+            Some(wiped_ms) if !down && held_ms - wiped_ms >= CONFIRM_MS => return true,
+            _ => {}
+        }
+        show.held_ms = held_ms;
+        engine.tick(&mut show, now);
+    }
+}
+
+/// The countdown, then the confirmation
+struct ResetShow {
+    held_ms: u32,
+    /// When the store was wiped, in the hold
+    wiped_ms: Option<u32>,
+}
+
+impl Effect for ResetShow {
+    fn render(&mut self, frame: &mut [Rgb], _t: u32) {
+        match self.wiped_ms {
+            None => {
+                let lit = frame.len() * self.held_ms as usize / HOLD_MS as usize;
+                for (i, led) in frame.iter_mut().enumerate() {
+                    *led = if i < lit { Rgb::RED } else { Rgb::BLACK };
+                }
+            }
+            Some(wiped_ms) => {
+                let phase = (self.held_ms - wiped_ms) / FLASH_MS;
+                let swapped = !(phase / 6).is_multiple_of(2);
+                for (i, led) in frame.iter_mut().enumerate() {
+                    *led = match (phase.is_multiple_of(2), i.is_multiple_of(2) != swapped) {
+                        (false, _) => Rgb::BLACK,
+                        (true, true) => Rgb::RED,
+                        (true, false) => Rgb::WHITE,
+                    };
+                }
+            }
+        }
+    }
+}
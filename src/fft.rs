@@ -0,0 +1,133 @@
+// Fixed-point radix-2 FFT for audio spectra
+//
+// Samples are Q15; each butterfly stage halves its output so nothing can
+// overflow, which leaves the result scaled by 1/N.
+
+/// Largest transform the twiddle table covers
+pub const MAX_FFT_LEN: usize = 256;
+
+/// First quarter of a sine period over 256 steps, Q15, end point included
+const QUARTER_SIN: [i16; 65] = [
+    0, 804, 1608, 2410, 3212, 4011, 4808, 5602, 6393, 7179, 7962, 8739, 9512, 10278, 11039, 11793,
+    12539, 13279, 14010, 14732, 15446, 16151, 16846, 17530, 18204, 18868, 19519, 20159, 20787,
+    21403, 22005, 22594, 23170, 23731, 24279, 24811, 25329, 25832, 26319, 26790, 27245, 27683,
+    28105, 28510, 28898, 29268, 29621, 29956, 30273, 30571, 30852, 31113, 31356, 31580, 31785,
+    31971, 32137, 32285, 32412, 32521, 32609, 32678, 32728, 32757, 32767,
+];
+
+/// Sine of `theta` (256 steps per turn) in Q15
+fn sin_q15(theta: u8) -> i32 {
+    let quarter = theta as usize & 63;
+    let value = match theta >> 6 {
+        0 => QUARTER_SIN[quarter],
+        1 => QUARTER_SIN[64 - quarter],
+        2 => -QUARTER_SIN[quarter],
+        _ => -QUARTER_SIN[64 - quarter],
+    };
+    value as i32
+}
+
+fn cos_q15(theta: u8) -> i32 {
+    sin_q15(theta.wrapping_add(64))
+}
+
+/// Taper `samples` with a Hann window, so a tone that doesn't fit the
+/// window a whole number of times stays in its own bins instead of
+/// smearing across the spectrum
+pub fn hann_window(samples: &mut [i16]) {
+    let n = samples.len();
+    if !(2..=MAX_FFT_LEN).contains(&n) {
+        return;
+    }
+    for (i, s) in samples.iter_mut().enumerate() {
+        let theta = (i * 256 / n) as u8;
+        let weight = (32768 - cos_q15(theta)) / 2;
+        *s = ((*s as i32 * weight) >> 15) as i16;
+    }
+}
+
+/// In-place FFT of `re` + j`im`, scaled by 1/N
+///
+/// The length must be a power of two up to [`MAX_FFT_LEN`], and the same
+/// for both; anything else is left untouched. For real input, zero `im`
+/// and read the first half of the bins.
+pub fn fft(re: &mut [i16], im: &mut [i16]) {
+    let n = re.len();
+    if n != im.len() || !n.is_power_of_two() || !(2..=MAX_FFT_LEN).contains(&n) {
+        return;
+    }
+
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let step = MAX_FFT_LEN / len;
+        let half = len / 2;
+        for start in (0..n).step_by(len) {
+            for k in 0..half {
+                let theta = (k * step) as u8;
+                let (wr, wi) = (cos_q15(theta), -sin_q15(theta));
+                let (a, b) = (start + k, start + k + half);
+                let (br, bi) = (re[b] as i32, im[b] as i32);
+                let tr = (br * wr - bi * wi) >> 15;
+                let ti = (br * wi + bi * wr) >> 15;
+                let (ar, ai) = (re[a] as i32, im[a] as i32);
+                re[a] = ((ar + tr) >> 1) as i16;
+                im[a] = ((ai + ti) >> 1) as i16;
+                re[b] = ((ar - tr) >> 1) as i16;
+                im[b] = ((ai - ti) >> 1) as i16;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Length of the complex value `re` + j`im`
+pub fn magnitude(re: i16, im: i16) -> u16 {
+    let squared = (re as i32 * re as i32) as u32 + (im as i32 * im as i32) as u32;
+    isqrt(squared) as u16
+}
+
+/// Integer square root, rounded down
+pub fn isqrt(x: u32) -> u32 {
+    let mut root = 0u32;
+    let mut bit = 1u32 << 30;
+    let mut x = x;
+    while bit > x {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if x >= root + bit {
+            x -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+/// Base-2 logarithm in 8.8 fixed point, 0 for 0
+///
+/// The fraction is a straight line between powers of two, within 0.09 of
+/// the real thing, plenty for mapping loudness to brightness.
+pub fn log2_8_8(x: u32) -> u16 {
+    if x == 0 {
+        return 0;
+    }
+    let whole = 31 - x.leading_zeros();
+    let fraction = if whole >= 8 {
+        (x >> (whole - 8)) & 0xFF
+    } else {
+        (x << (8 - whole)) & 0xFF
+    };
+    (whole << 8 | fraction) as u16
+}
@@ -0,0 +1,254 @@
+// Erasing and programming the Pico's own flash, for what's kept across
+// power cycles
+
+use core::ptr::addr_of_mut;
+
+/// The Pico's 2 MiB W25Q16 flash
+pub const FLASH_SIZE: u32 = 2 * 1024 * 1024;
+
+/// The smallest erase
+pub const SECTOR_SIZE: u32 = 4096;
+
+/// The smallest program; a page's bytes can go from 1 to 0 but only an
+/// erase takes them back to 1
+pub const PAGE_SIZE: usize = 256;
+
+/// Sectors given over to the store, at the end of flash where `memory.x`
+/// keeps the program out
+pub const STORE_SECTORS: u32 = 4;
+pub const STORE_OFFSET: u32 = FLASH_SIZE - STORE_SECTORS * SECTOR_SIZE;
+
+/// Flash is mapped for reading from here
+const XIP_BASE: u32 = 0x1000_0000;
+
+/// 64 KiB block erase, used by the ROM for any whole blocks in a range
+const BLOCK_SIZE: u32 = 1 << 16;
+const BLOCK_ERASE: u8 = 0xD8;
+
+/// W25Q "read unique ID"
+const READ_UNIQUE_ID: u8 = 0x4B;
+
+/// A copy of the second stage bootloader in RAM, to bring fast XIP back
+/// after a write as boot did; the ROM's own fallback is the slowest read
+/// mode there is, which would starve rendering of instruction fetches
+static mut BOOT2: [u32; 64] = [0; 64];
+
+/// What's at `offset` in flash
+pub fn read(offset: u32, len: usize) -> &'static [u8] {
+    assert!(offset as usize + len <= FLASH_SIZE as usize);
+    // Safety: flash is mapped in full, and only changed by `erase` and
+    // `program` below, which don't return until it's readable again
+    unsafe { core::slice::from_raw_parts((XIP_BASE + offset) as *const u8, len) }
+}
+
+/// Erase the sectors from `offset`, leaving them all 0xFF
+///
+/// `offset` and `len` must be whole sectors. Interrupts are off
+/// throughout, some tens of milliseconds a sector; DMA carries on, so a
+/// frame already going out to the strip finishes. Nothing may run from
+/// flash meanwhile, so core 1 must be idle or running from RAM.
+pub fn erase(offset: u32, len: u32) {
+    assert!(offset.is_multiple_of(SECTOR_SIZE) && len.is_multiple_of(SECTOR_SIZE));
+    assert!(offset + len <= FLASH_SIZE);
+    run(offset, len, &[]);
+}
+
+/// Program whole pages from `offset`, a page boundary
+///
+/// Programming only clears bits, so 0xFF bytes leave what's there alone,
+/// and a page can be filled a piece at a time. A page takes under a
+/// millisecond, with interrupts off as for [`erase`].
+pub fn program(offset: u32, data: &[u8]) {
+    assert!((offset as usize).is_multiple_of(PAGE_SIZE) && data.len().is_multiple_of(PAGE_SIZE));
+    assert!(offset as usize + data.len() <= FLASH_SIZE as usize);
+    run(offset, 0, data);
+}
+
+/// The flash chip's 64-bit unique ID, set at the factory and different on
+/// every part, so on every board
+///
+/// Interrupts are off for the few microseconds it takes, and core 1 must
+/// be idle or running from RAM, as for [`erase`].
+pub fn unique_id() -> [u8; 8] {
+    // The command, four dummy bytes, then the ID clocked out
+    let mut tx = [0; 13];
+    tx[0] = READ_UNIQUE_ID;
+    let mut rx = [0; 13];
+    let rom = Rom::lookup();
+    cortex_m::interrupt::free(|_| {
+        // Safety: as for `run`; the read runs from RAM with XIP down
+        unsafe {
+            let boot2 = copy_boot2();
+            id_from_ram(&rom, boot2, tx.as_ptr(), rx.as_mut_ptr(), tx.len());
+        }
+    });
+    rx[5..].try_into().unwrap()
+}
+
+fn run(offset: u32, erase_len: u32, data: &[u8]) {
+    let rom = Rom::lookup();
+    cortex_m::interrupt::free(|_| {
+        // Safety: interrupts are off and the write runs from RAM, calling
+        // only the ROM, so nothing fetches from flash while XIP is down
+        unsafe {
+            let boot2 = copy_boot2();
+            write_from_ram(&rom, boot2, offset, erase_len, data.as_ptr(), data.len());
+        }
+    });
+}
+
+/// Copy boot2 out of flash into [`BOOT2`], while flash can still be read
+///
+/// # Safety
+///
+/// Interrupts must be off, nothing else touching [`BOOT2`].
+unsafe fn copy_boot2() -> *const u32 {
+    let boot2 = &mut *addr_of_mut!(BOOT2);
+    let image = read(0, 256);
+    for (word, bytes) in boot2.iter_mut().zip(image.chunks_exact(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().unwrap());
+    }
+    boot2.as_ptr()
+}
+
+/// The ROM's flash functions, looked up while flash can still be read
+struct Rom {
+    connect_internal_flash: unsafe extern "C" fn(),
+    flash_exit_xip: unsafe extern "C" fn(),
+    flash_range_erase: unsafe extern "C" fn(u32, usize, u32, u8),
+    flash_range_program: unsafe extern "C" fn(u32, *const u8, usize),
+    flash_flush_cache: unsafe extern "C" fn(),
+}
+
+impl Rom {
+    fn lookup() -> Self {
+        type LookupFn = unsafe extern "C" fn(*const u16, u32) -> usize;
+        // Safety: these are the ROM's documented function table and lookup
+        // function, and each looked-up address is a function of the given
+        // signature (RP2040 datasheet 2.8.3.1)
+        unsafe {
+            let lookup = core::mem::transmute::<usize, LookupFn>(*(0x18 as *const u16) as usize);
+            let table = *(0x14 as *const u16) as *const u16;
+            let find = |tag: &[u8; 2]| lookup(table, u16::from_le_bytes(*tag) as u32);
+            Self {
+                connect_internal_flash: core::mem::transmute::<usize, unsafe extern "C" fn()>(
+                    find(b"IF"),
+                ),
+                flash_exit_xip: core::mem::transmute::<usize, unsafe extern "C" fn()>(find(b"EX")),
+                flash_range_erase: core::mem::transmute::<
+                    usize,
+                    unsafe extern "C" fn(u32, usize, u32, u8),
+                >(find(b"RE")),
+                flash_range_program: core::mem::transmute::<
+                    usize,
+                    unsafe extern "C" fn(u32, *const u8, usize),
+                >(find(b"RP")),
+                flash_flush_cache: core::mem::transmute::<usize, unsafe extern "C" fn()>(find(
+                    b"FC",
+                )),
+            }
+        }
+    }
+}
+
+/// The write itself, in RAM so it can run with XIP off
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn write_from_ram(
+    rom: &Rom,
+    boot2: *const u32,
+    offset: u32,
+    erase_len: u32,
+    data: *const u8,
+    len: usize,
+) {
+    (rom.connect_internal_flash)();
+    (rom.flash_exit_xip)();
+    if erase_len > 0 {
+        (rom.flash_range_erase)(offset, erase_len as usize, BLOCK_SIZE, BLOCK_ERASE);
+    }
+    if len > 0 {
+        (rom.flash_range_program)(offset, data, len);
+    }
+    (rom.flash_flush_cache)();
+    // Into boot2's entry, Thumb bit set, for fast XIP again
+    let enter_xip = core::mem::transmute::<usize, extern "C" fn()>(boot2 as usize | 1);
+    enter_xip();
+}
+
+/// The unique ID read, in RAM like [`write_from_ram`]; the ROM has no
+/// function for it, so this drives the SSI itself with CS held low
+///
+/// The registers are reached in assembly, as `read_volatile` and friends
+/// may be calls into flash in an unoptimised build. All of `tx` goes into
+/// the 16-deep FIFO before anything's read back, so it must be shorter.
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn id_from_ram(rom: &Rom, boot2: *const u32, tx: *const u8, rx: *mut u8, len: usize) {
+    (rom.connect_internal_flash)();
+    (rom.flash_exit_xip)();
+    core::arch::asm!(
+        // CS low: IO_QSPI's SS_CTRL at 0x4001_800C, its output override
+        // bits cleared, then set to drive low
+        "movs r4, #0x40",
+        "lsls r4, r4, #8",
+        "adds r4, #0x01",
+        "lsls r4, r4, #8",
+        "adds r4, #0x80",
+        "lsls r4, r4, #8",
+        "adds r4, #0x0C",
+        "ldr r3, [r4]",
+        "movs r5, #3",
+        "lsls r5, r5, #8",
+        "bics r3, r5",
+        "movs r5, #2",
+        "lsls r5, r5, #8",
+        "orrs r3, r5",
+        "str r3, [r4]",
+        // The SSI at 0x1800_0000: every byte out to DR0
+        "movs r3, #0x18",
+        "lsls r3, r3, #24",
+        "adds r5, r0, r2",
+        "2:",
+        "ldrb r4, [r0]",
+        "str r4, [r3, #0x60]",
+        "adds r0, #1",
+        "cmp r0, r5",
+        "bne 2b",
+        // Then as many back, each once SR's RFNE, bit 3, says it's there
+        "adds r5, r1, r2",
+        "3:",
+        "ldr r4, [r3, #0x28]",
+        "lsrs r4, r4, #4",
+        "bcc 3b",
+        "ldr r4, [r3, #0x60]",
+        "strb r4, [r1]",
+        "adds r1, #1",
+        "cmp r1, r5",
+        "bne 3b",
+        // CS high
+        "movs r4, #0x40",
+        "lsls r4, r4, #8",
+        "adds r4, #0x01",
+        "lsls r4, r4, #8",
+        "adds r4, #0x80",
+        "lsls r4, r4, #8",
+        "adds r4, #0x0C",
+        "ldr r3, [r4]",
+        "movs r5, #3",
+        "lsls r5, r5, #8",
+        "orrs r3, r5",
+        "str r3, [r4]",
+        inout("r0") tx => _,
+        inout("r1") rx => _,
+        in("r2") len,
+        out("r3") _,
+        out("r4") _,
+        out("r5") _,
+        options(nostack),
+    );
+    (rom.flash_flush_cache)();
+    // Fast XIP again, as after a write
+    let enter_xip = core::mem::transmute::<usize, extern "C" fn()>(boot2 as usize | 1);
+    enter_xip();
+}
@@ -0,0 +1,46 @@
+// Double-buffered pixel storage for tear-free rendering
+
+use crate::strip::LedStrip;
+
+/// Two pixel buffers: the front one is being shown, the back one is being drawn
+///
+/// Render the next frame into `back_mut()`, then `swap()` and send `front()`
+/// to the driver. A half-drawn frame is never what the strip receives.
+pub struct FrameBuffer<const N: usize> {
+    buffers: [LedStrip<N>; 2],
+    front: usize,
+}
+
+impl<const N: usize> FrameBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            buffers: [LedStrip::new(); 2],
+            front: 0,
+        }
+    }
+
+    /// The last completed frame
+    pub fn front(&self) -> &LedStrip<N> {
+        &self.buffers[self.front]
+    }
+
+    /// The frame currently being rendered
+    pub fn back_mut(&mut self) -> &mut LedStrip<N> {
+        &mut self.buffers[self.front ^ 1]
+    }
+
+    /// Present the back buffer
+    ///
+    /// The new back buffer starts as a copy of the frame just presented, so
+    /// effects that fade or shift the previous frame keep working.
+    pub fn swap(&mut self) {
+        self.front ^= 1;
+        self.buffers[self.front ^ 1] = self.buffers[self.front];
+    }
+}
+
+impl<const N: usize> Default for FrameBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
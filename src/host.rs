@@ -0,0 +1,86 @@
+// Pixels set from a host computer, shared with the effect that shows them
+
+use core::cell::Cell;
+
+use crate::pio_programs::Rgb;
+
+/// A frame of pixels a host writes, over serial commands or a streaming
+/// protocol, for [`HostPixels`](crate::effects::HostPixels) to put on the
+/// strip
+///
+/// Pixels past the end are ignored, so hosts can send whatever length they
+/// think the strip is.
+#[derive(Debug)]
+pub struct HostFrame<const N: usize> {
+    pixels: [Cell<Rgb>; N],
+}
+
+impl<const N: usize> HostFrame<N> {
+    pub const fn new() -> Self {
+        Self {
+            pixels: [const { Cell::new(Rgb::BLACK) }; N],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        N
+    }
+
+    pub fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<Rgb> {
+        self.pixels.get(index).map(Cell::get)
+    }
+
+    /// Set one pixel; false if it's past the end of the strip
+    pub fn set(&self, index: usize, color: Rgb) -> bool {
+        match self.pixels.get(index) {
+            Some(pixel) => {
+                pixel.set(color);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set the pixels from the start of the strip
+    pub fn copy_from(&self, pixels: &[Rgb]) {
+        for (pixel, &color) in self.pixels.iter().zip(pixels) {
+            pixel.set(color);
+        }
+    }
+
+    /// Set pixels from `start` on from RGB byte triples, as network
+    /// protocols carry them; a partial triple at the end is ignored
+    pub fn write_rgb(&self, start: usize, bytes: &[u8]) {
+        let pixels = self.pixels.iter().skip(start);
+        for (pixel, rgb) in pixels.zip(bytes.chunks_exact(3)) {
+            pixel.set(Rgb::new(rgb[0], rgb[1], rgb[2]));
+        }
+    }
+
+    pub fn fill(&self, color: Rgb) {
+        for pixel in &self.pixels {
+            pixel.set(color);
+        }
+    }
+}
+
+/// What a streaming protocol's reader made of a byte
+#[derive(Debug, PartialEq, Eq)]
+pub enum StreamEvent<'a> {
+    /// Not part of a frame, as far as can be told yet
+    Pass,
+    /// Part of a frame's header or pixels
+    Taken,
+    /// The last byte of a frame: the pixels that fit on the strip
+    Frame(&'a [Rgb]),
+}
+
+impl<const N: usize> Default for HostFrame<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
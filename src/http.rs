@@ -0,0 +1,133 @@
+// Just enough HTTP/1.1 for the device's own API and pages
+
+use core::fmt::{self, Write};
+
+/// Longest request head and body taken
+pub const MAX_REQUEST: usize = 2048;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Method {
+    Get,
+    Post,
+    Other,
+}
+
+/// Why a request couldn't be read
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum HttpError {
+    /// Not an HTTP request line or headers
+    BadRequest,
+    /// Bigger than [`MAX_REQUEST`]
+    TooLarge,
+}
+
+/// A request, borrowed from the bytes it was read from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Request<'a> {
+    pub method: Method,
+    /// Without the query string
+    pub path: &'a [u8],
+    pub query: &'a [u8],
+    pub body: &'a [u8],
+}
+
+impl<'a> Request<'a> {
+    /// Read a request from what's arrived on a connection so far; `Ok(None)`
+    /// until the head and as much body as `Content-Length` says are in
+    pub fn parse(received: &'a [u8]) -> Result<Option<Self>, HttpError> {
+        let Some(head_len) = received.windows(4).position(|w| w == b"\r\n\r\n") else {
+            return if received.len() >= MAX_REQUEST {
+                Err(HttpError::TooLarge)
+            } else {
+                Ok(None)
+            };
+        };
+        let mut lines = received[..head_len].split(|&b| b == b'\n');
+        let request_line = lines.next().ok_or(HttpError::BadRequest)?;
+        let mut words = request_line.trim_ascii().split(|&b| b == b' ');
+        let method = match words.next() {
+            Some(b"GET") => Method::Get,
+            Some(b"POST") => Method::Post,
+            Some(_) => Method::Other,
+            None => return Err(HttpError::BadRequest),
+        };
+        let target = words.next().ok_or(HttpError::BadRequest)?;
+        if !words
+            .next()
+            .is_some_and(|version| version.starts_with(b"HTTP/1."))
+        {
+            return Err(HttpError::BadRequest);
+        }
+        let (path, query) = match target.iter().position(|&b| b == b'?') {
+            Some(i) => (&target[..i], &target[i + 1..]),
+            None => (target, &target[target.len()..]),
+        };
+
+        let mut content_length = 0;
+        for line in lines {
+            let Some(colon) = line.iter().position(|&b| b == b':') else {
+                return Err(HttpError::BadRequest);
+            };
+            if line[..colon].eq_ignore_ascii_case(b"content-length") {
+                content_length = core::str::from_utf8(line[colon + 1..].trim_ascii())
+                    .ok()
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .ok_or(HttpError::BadRequest)?;
+            }
+        }
+
+        let body_start = head_len + 4;
+        if body_start + content_length > MAX_REQUEST {
+            return Err(HttpError::TooLarge);
+        }
+        Ok(received
+            .get(body_start..body_start + content_length)
+            .map(|body| Request {
+                method,
+                path,
+                query,
+                body,
+            }))
+    }
+}
+
+/// Counts what's written, to give a body's length before sending it
+struct Counter(usize);
+
+impl Write for Counter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+/// Write a complete response, `body` formatted twice: once to measure it
+/// for `Content-Length` and once to send it
+///
+/// ```ignore
+/// http::respond(&mut socket, 200, "application/json", &state)?;
+/// ```
+pub fn respond(
+    out: &mut impl Write,
+    status: u16,
+    content_type: &str,
+    body: &dyn fmt::Display,
+) -> fmt::Result {
+    let mut counter = Counter(0);
+    write!(counter, "{body}")?;
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Content Too Large",
+        _ => "",
+    };
+    write!(
+        out,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: {content_type}\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\
+         Access-Control-Allow-Origin: *\r\n\r\n{body}",
+        counter.0
+    )
+}
@@ -0,0 +1,160 @@
+// I2S MEMS microphone input from a PIO state machine
+
+use crate::platform::hal::{
+    gpio::{Function, Pin, PinId, PullType, ValidFunction},
+    pio::{
+        Buffers, PIOBuilder, PIOExt, PinDir, Running, Rx, ShiftDirection, StateMachine,
+        StateMachineIndex, UninitStateMachine, PIO,
+    },
+};
+
+use crate::audio::Envelope;
+use crate::driver::{fixed_point_divisor, into_data_pin, DataPin};
+use crate::pio_programs;
+
+/// Default sample rate; the INMP441 runs from about 8 to 50 kHz
+pub const I2S_SAMPLE_RATE_HZ: u32 = 16_000;
+
+/// PIO cycles per sample: two per bit, 64 bits per stereo frame
+const CYCLES_PER_SAMPLE: u32 = 128;
+
+/// Configures a state machine to clock an I2S microphone and read it
+///
+/// The PIO drives BCLK (SCK) and WS, which must be the next pin up; the
+/// microphone's L/R pin goes to ground so it sends on the left channel.
+///
+/// ```ignore
+/// let mic = I2sMicBuilder::new(&mut pio, sm3, pins.gpio10, pins.gpio11, pins.gpio12, sys_clk_hz)
+///     .build();
+/// ```
+pub struct I2sMicBuilder<'a, P: PIOExt, SM: StateMachineIndex> {
+    pio: &'a mut PIO<P>,
+    sm: UninitStateMachine<(P, SM)>,
+    bclk: DataPin<P>,
+    ws: DataPin<P>,
+    data: DataPin<P>,
+    sys_clk_hz: u32,
+    sample_rate_hz: u32,
+}
+
+impl<'a, P: PIOExt, SM: StateMachineIndex> I2sMicBuilder<'a, P, SM> {
+    pub fn new<BI, BF, BP, WI, WF, WP, DI, DF, DP>(
+        pio: &'a mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        bclk: Pin<BI, BF, BP>,
+        ws: Pin<WI, WF, WP>,
+        data: Pin<DI, DF, DP>,
+        sys_clk_hz: u32,
+    ) -> Self
+    where
+        BI: PinId + ValidFunction<P::PinFunction>,
+        BF: Function,
+        BP: PullType,
+        WI: PinId + ValidFunction<P::PinFunction>,
+        WF: Function,
+        WP: PullType,
+        DI: PinId + ValidFunction<P::PinFunction>,
+        DF: Function,
+        DP: PullType,
+    {
+        Self {
+            pio,
+            sm,
+            bclk: into_data_pin::<P, _, _, _>(bclk),
+            ws: into_data_pin::<P, _, _, _>(ws),
+            data: into_data_pin::<P, _, _, _>(data),
+            sys_clk_hz,
+            sample_rate_hz: I2S_SAMPLE_RATE_HZ,
+        }
+    }
+
+    /// Change the sample rate from the 16 kHz default
+    pub fn sample_rate_hz(mut self, hz: u32) -> Self {
+        self.sample_rate_hz = hz;
+        self
+    }
+
+    /// Start clocking the microphone
+    ///
+    /// # Panics
+    ///
+    /// If WS isn't the pin after BCLK, or the program doesn't fit in the
+    /// PIO block's instruction memory.
+    pub fn build(self) -> I2sMic<P, SM> {
+        let bclk_id = self.bclk.id().num;
+        let ws_id = self.ws.id().num;
+        let data_id = self.data.id().num;
+        assert_eq!(ws_id, bclk_id + 1, "I2S WS must be the pin after BCLK");
+
+        let installed = self.pio.install(&pio_programs::i2s_mic()).unwrap();
+        let pio_clk_hz = self.sample_rate_hz * CYCLES_PER_SAMPLE;
+        let (div_int, div_frac) = fixed_point_divisor(self.sys_clk_hz, pio_clk_hz);
+
+        let (mut sm, rx, _) = PIOBuilder::from_program(installed)
+            .side_set_pin_base(bclk_id)
+            .in_pin_base(data_id)
+            .in_shift_direction(ShiftDirection::Left)
+            .autopush(true)
+            .push_threshold(32)
+            .buffers(Buffers::OnlyRx)
+            .clock_divisor_fixed_point(div_int, div_frac)
+            .build(self.sm);
+        sm.set_pindirs([
+            (bclk_id, PinDir::Output),
+            (ws_id, PinDir::Output),
+            (data_id, PinDir::Input),
+        ]);
+
+        I2sMic {
+            _sm: sm.start(),
+            _pins: [self.bclk, self.ws, self.data],
+            rx,
+            sample_rate_hz: self.sample_rate_hz,
+        }
+    }
+}
+
+/// An I2S microphone clocked by one PIO state machine
+///
+/// Samples collect in the (joined, eight deep) RX FIFO; read them at least
+/// every half millisecond at 16 kHz, or the state machine stalls and the
+/// microphone loses a few.
+pub struct I2sMic<P: PIOExt, SM: StateMachineIndex> {
+    _sm: StateMachine<(P, SM), Running>,
+    _pins: [DataPin<P>; 3],
+    rx: Rx<(P, SM)>,
+    sample_rate_hz: u32,
+}
+
+impl<P: PIOExt, SM: StateMachineIndex> I2sMic<P, SM> {
+    pub fn sample_rate_hz(&self) -> u32 {
+        self.sample_rate_hz
+    }
+
+    /// The next sample, if one has arrived
+    pub fn read(&mut self) -> Option<i16> {
+        // 24 bits left-justified in the word; the top 16 are plenty
+        self.rx.read().map(|word| (word >> 16) as i16)
+    }
+
+    /// Feed every sample waiting in the FIFO to `envelope`
+    pub fn sample(&mut self, envelope: &mut Envelope) {
+        while let Some(sample) = self.read() {
+            envelope.push(sample);
+        }
+    }
+
+    /// Fill `samples` with fresh, consecutive samples, blocking until they
+    /// arrive
+    pub fn capture(&mut self, samples: &mut [i16]) {
+        // Whatever's queued may be from before a stall
+        while self.rx.read().is_some() {}
+        for s in samples.iter_mut() {
+            *s = loop {
+                if let Some(sample) = self.read() {
+                    break sample;
+                }
+            };
+        }
+    }
+}
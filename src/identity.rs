@@ -0,0 +1,50 @@
+// Telling boards apart by their flash chip's unique ID
+
+use crate::flash;
+
+/// Hex digits as `picotool info` shows the ID
+const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+/// What's unique to this board: the ID programmed into its flash chip at
+/// the factory
+///
+/// It gives the USB serial number, so the host names each board's ports
+/// apart; the mDNS name's suffix; and the effects' random seed, so boards
+/// on one desk don't sparkle in step.
+///
+/// ```ignore
+/// let identity = Identity::read();
+/// Rng::set_board_seed(identity.seed());
+/// let mut mdns = MdnsResponder::new(Hostname::new(identity.short_id()), &SERVICES);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Identity(pub [u8; 8]);
+
+impl Identity {
+    /// Read the ID, before core 1 is started
+    pub fn read() -> Self {
+        Self(flash::unique_id())
+    }
+
+    /// The ID as 16 hex digits, for the USB serial number
+    pub fn serial(&self) -> [u8; 16] {
+        let mut digits = [0; 16];
+        for (pair, byte) in digits.chunks_exact_mut(2).zip(self.0) {
+            pair[0] = HEX[(byte >> 4) as usize];
+            pair[1] = HEX[(byte & 0x0F) as usize];
+        }
+        digits
+    }
+
+    /// The ID's last two bytes, for the host name; the same as the serial
+    /// number's last four digits, so one can be told from the other
+    pub fn short_id(&self) -> u16 {
+        u16::from_be_bytes([self.0[6], self.0[7]])
+    }
+
+    /// The ID folded to 32 bits, for [`Rng::set_board_seed`](crate::math::Rng::set_board_seed)
+    pub fn seed(&self) -> u32 {
+        let [a, b, c, d, e, f, g, h] = self.0;
+        u32::from_le_bytes([a, b, c, d]) ^ u32::from_le_bytes([e, f, g, h])
+    }
+}
@@ -0,0 +1,180 @@
+// MPU6050 accelerometer over I2C, and tilt and shake tracking for effects
+
+use core::cell::Cell;
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+use crate::fft::isqrt;
+
+/// MPU6050 address with its AD0 pin low
+pub const MPU6050_ADDR_LOW: u8 = 0x68;
+/// MPU6050 address with its AD0 pin high
+pub const MPU6050_ADDR_HIGH: u8 = 0x69;
+
+/// Readings per g in the ±2 g range
+pub const ONE_G: i32 = 16_384;
+
+const CONFIG: u8 = 0x1A;
+const ACCEL_CONFIG: u8 = 0x1C;
+const ACCEL_XOUT_H: u8 = 0x3B;
+const PWR_MGMT_1: u8 = 0x6B;
+const WHO_AM_I: u8 = 0x75;
+
+/// Acceleration in 1/[`ONE_G`] g, gravity included
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, defmt::Format)]
+pub struct Accel {
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+}
+
+impl Accel {
+    /// Total acceleration, [`ONE_G`] at rest
+    pub fn magnitude(&self) -> u32 {
+        let (x, y, z) = (self.x as i32, self.y as i32, self.z as i32);
+        isqrt((x * x) as u32 + (y * y) as u32 + (z * z) as u32)
+    }
+}
+
+/// MPU6050 6-axis IMU; only the accelerometer is used
+///
+/// ```ignore
+/// let mut imu = Mpu6050::new(i2c, MPU6050_ADDR_LOW)?;
+/// let accel = imu.read_accel()?;
+/// ```
+pub struct Mpu6050<I2C> {
+    i2c: I2C,
+    addr: u8,
+}
+
+impl<I2C, E> Mpu6050<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Wake the sensor, in the ±2 g range with the 44 Hz low-pass filter
+    pub fn new(mut i2c: I2C, addr: u8) -> Result<Self, E> {
+        // Out of sleep, clocked from the X gyro's PLL
+        i2c.write(addr, &[PWR_MGMT_1, 0x01])?;
+        i2c.write(addr, &[CONFIG, 0x03])?;
+        i2c.write(addr, &[ACCEL_CONFIG, 0x00])?;
+        Ok(Self { i2c, addr })
+    }
+
+    /// Contents of WHO_AM_I, 0x68 on a genuine part
+    pub fn who_am_i(&mut self) -> Result<u8, E> {
+        let mut id = [0];
+        self.i2c.write_read(self.addr, &[WHO_AM_I], &mut id)?;
+        Ok(id[0])
+    }
+
+    pub fn read_accel(&mut self) -> Result<Accel, E> {
+        let mut raw = [0; 6];
+        self.i2c.write_read(self.addr, &[ACCEL_XOUT_H], &mut raw)?;
+        Ok(Accel {
+            x: i16::from_be_bytes([raw[0], raw[1]]),
+            y: i16::from_be_bytes([raw[2], raw[3]]),
+            z: i16::from_be_bytes([raw[4], raw[5]]),
+        })
+    }
+
+    pub fn free(self) -> I2C {
+        self.i2c
+    }
+}
+
+/// Jolts needed within [`SHAKE_WINDOW_MS`] to count as a shake
+const SHAKE_JOLTS: u8 = 3;
+const SHAKE_WINDOW_MS: u32 = 800;
+/// Quiet time after a shake before the next can start
+const SHAKE_HOLDOFF_MS: u32 = 1000;
+
+/// Spots deliberate shakes: a few sharp jolts in quick succession
+///
+/// A jolt is the total acceleration straying more than `threshold` (in
+/// 1/[`ONE_G`] g) from 1 g; single bumps and steps don't add up to a shake.
+#[derive(Copy, Clone, Debug)]
+pub struct ShakeDetector {
+    pub threshold: u32,
+    jolts: u8,
+    first_jolt_ms: u32,
+    jolting: bool,
+    holdoff_until: u32,
+}
+
+impl ShakeDetector {
+    pub const fn new() -> Self {
+        Self {
+            threshold: ONE_G as u32 * 3 / 4,
+            jolts: 0,
+            first_jolt_ms: 0,
+            jolting: false,
+            holdoff_until: 0,
+        }
+    }
+
+    /// Take in a reading; true on a shake
+    pub fn update(&mut self, accel: Accel, now_ms: u32) -> bool {
+        let jolt = accel.magnitude().abs_diff(ONE_G as u32) > self.threshold;
+        let started = jolt && !self.jolting;
+        self.jolting = jolt;
+        if !started || (now_ms.wrapping_sub(self.holdoff_until) as i32) < 0 {
+            return false;
+        }
+
+        if self.jolts == 0 || now_ms.wrapping_sub(self.first_jolt_ms) > SHAKE_WINDOW_MS {
+            self.jolts = 0;
+            self.first_jolt_ms = now_ms;
+        }
+        self.jolts += 1;
+        if self.jolts < SHAKE_JOLTS {
+            return false;
+        }
+        self.jolts = 0;
+        self.holdoff_until = now_ms.wrapping_add(SHAKE_HOLDOFF_MS);
+        true
+    }
+}
+
+impl Default for ShakeDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the accelerometer senses, shared with the effects that react to it
+///
+/// Like [`Audio`](crate::audio::Audio): the main loop updates it, effects
+/// hold a reference and read it while rendering.
+#[derive(Debug, Default)]
+pub struct Movement {
+    accel: Cell<Accel>,
+    shakes: Cell<u32>,
+}
+
+impl Movement {
+    pub const fn new() -> Self {
+        Self {
+            accel: Cell::new(Accel { x: 0, y: 0, z: 0 }),
+            shakes: Cell::new(0),
+        }
+    }
+
+    /// Latest acceleration, gravity included
+    pub fn accel(&self) -> Accel {
+        self.accel.get()
+    }
+
+    pub fn set_accel(&self, accel: Accel) {
+        self.accel.set(accel);
+    }
+
+    /// Shakes so far; effects keep the count they last saw
+    pub fn shakes(&self) -> u32 {
+        self.shakes.get()
+    }
+
+    /// Count a shake
+    pub fn shake(&self) {
+        self.shakes.set(self.shakes.get().wrapping_add(1));
+    }
+}
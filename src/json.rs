@@ -0,0 +1,393 @@
+// JSON settings documents: the running state read and written as one object
+
+use core::fmt;
+
+use crate::command::parse_color;
+use crate::effects::{Effect, Param, ParamValue};
+use crate::pio_programs::{ColorOrder, Rgb};
+
+/// Why a document wasn't taken
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum JsonError {
+    /// Not JSON, or JSON this reader doesn't handle (string escapes)
+    Syntax,
+    UnknownKey,
+    /// A number where a color should be, or the like
+    WrongType,
+    /// A level past 255, an effect number past the end, or a channel order
+    /// that isn't one
+    OutOfRange,
+    /// A parameter the effect that would be running doesn't have
+    NoSuchParam,
+}
+
+impl JsonError {
+    /// Short description for the reply to the host
+    pub fn message(&self) -> &'static str {
+        match self {
+            JsonError::Syntax => "syntax",
+            JsonError::UnknownKey => "unknown key",
+            JsonError::WrongType => "wrong type",
+            JsonError::OutOfRange => "out of range",
+            JsonError::NoSuchParam => "no such param",
+        }
+    }
+}
+
+/// A JSON value; objects and arrays are kept as their text, to be read with
+/// [`for_each_member`] and [`for_each_element`] when wanted
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum JsonValue<'a> {
+    Null,
+    Bool(bool),
+    /// Integers only; anything with a fraction or exponent is
+    /// [`JsonError::WrongType`] wherever a number is read
+    Number(Option<i32>),
+    /// The raw text between the quotes
+    String(&'a [u8]),
+    Object(&'a [u8]),
+    Array(&'a [u8]),
+}
+
+impl JsonValue<'_> {
+    pub(crate) fn level(self) -> Result<u8, JsonError> {
+        match self {
+            JsonValue::Number(Some(n)) => u8::try_from(n).map_err(|_| JsonError::OutOfRange),
+            _ => Err(JsonError::WrongType),
+        }
+    }
+
+    pub(crate) fn index(self) -> Result<usize, JsonError> {
+        match self {
+            JsonValue::Number(Some(n)) => usize::try_from(n).map_err(|_| JsonError::OutOfRange),
+            _ => Err(JsonError::WrongType),
+        }
+    }
+
+    pub(crate) fn color(self) -> Result<Rgb, JsonError> {
+        match self {
+            JsonValue::String(hex) => parse_color(hex).map_err(|_| JsonError::WrongType),
+            _ => Err(JsonError::WrongType),
+        }
+    }
+
+    /// A channel order by name, or `null` for the chipset's
+    pub(crate) fn color_order(self) -> Result<Option<ColorOrder>, JsonError> {
+        match self {
+            JsonValue::String(name) => ColorOrder::from_name(name)
+                .map(Some)
+                .ok_or(JsonError::OutOfRange),
+            JsonValue::Null => Ok(None),
+            _ => Err(JsonError::WrongType),
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&mut self) -> Option<u8> {
+        while let Some(b' ' | b'\t' | b'\r' | b'\n') = self.text.get(self.pos) {
+            self.pos += 1;
+        }
+        self.text.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), JsonError> {
+        if self.peek() != Some(byte) {
+            return Err(JsonError::Syntax);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<&'a [u8], JsonError> {
+        self.expect(b'"')?;
+        let start = self.pos;
+        loop {
+            match self.text.get(self.pos) {
+                Some(b'"') => break,
+                Some(b'\\') | None => return Err(JsonError::Syntax),
+                Some(_) => self.pos += 1,
+            }
+        }
+        self.pos += 1;
+        Ok(&self.text[start..self.pos - 1])
+    }
+
+    /// Step over an object or array, returning its text with the brackets
+    fn nested(&mut self) -> Result<&'a [u8], JsonError> {
+        let start = self.pos;
+        let mut depth = 0usize;
+        loop {
+            match self.peek().ok_or(JsonError::Syntax)? {
+                b'"' => {
+                    self.string()?;
+                    continue;
+                }
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' => depth -= 1,
+                _ => {}
+            }
+            self.pos += 1;
+            if depth == 0 {
+                return Ok(&self.text[start..self.pos]);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonValue<'a>, JsonError> {
+        Ok(match self.peek().ok_or(JsonError::Syntax)? {
+            b'"' => JsonValue::String(self.string()?),
+            b'{' => JsonValue::Object(self.nested()?),
+            b'[' => JsonValue::Array(self.nested()?),
+            b'-' | b'0'..=b'9' => {
+                let start = self.pos;
+                while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') =
+                    self.text.get(self.pos)
+                {
+                    self.pos += 1;
+                }
+                let number = core::str::from_utf8(&self.text[start..self.pos])
+                    .map_err(|_| JsonError::Syntax)?;
+                JsonValue::Number(number.parse().ok())
+            }
+            _ => {
+                let rest = &self.text[self.pos..];
+                let (value, len) = if rest.starts_with(b"true") {
+                    (JsonValue::Bool(true), 4)
+                } else if rest.starts_with(b"false") {
+                    (JsonValue::Bool(false), 5)
+                } else if rest.starts_with(b"null") {
+                    (JsonValue::Null, 4)
+                } else {
+                    return Err(JsonError::Syntax);
+                };
+                self.pos += len;
+                value
+            }
+        })
+    }
+}
+
+/// Call `f` with each key and value of the object `text`, stopping at the
+/// first error
+pub fn for_each_member<'a>(
+    text: &'a [u8],
+    mut f: impl FnMut(&'a [u8], JsonValue<'a>) -> Result<(), JsonError>,
+) -> Result<(), JsonError> {
+    let mut parser = Parser { text, pos: 0 };
+    parser.expect(b'{')?;
+    if parser.peek() == Some(b'}') {
+        parser.pos += 1;
+    } else {
+        loop {
+            let key = parser.string()?;
+            parser.expect(b':')?;
+            f(key, parser.value()?)?;
+            match parser.peek() {
+                Some(b',') => parser.pos += 1,
+                Some(b'}') => {
+                    parser.pos += 1;
+                    break;
+                }
+                _ => return Err(JsonError::Syntax),
+            }
+        }
+    }
+    match parser.peek() {
+        None => Ok(()),
+        Some(_) => Err(JsonError::Syntax),
+    }
+}
+
+/// Call `f` with each element of the array `text`, stopping at the first
+/// error
+pub fn for_each_element<'a>(
+    text: &'a [u8],
+    mut f: impl FnMut(JsonValue<'a>) -> Result<(), JsonError>,
+) -> Result<(), JsonError> {
+    let mut parser = Parser { text, pos: 0 };
+    parser.expect(b'[')?;
+    if parser.peek() == Some(b']') {
+        parser.pos += 1;
+    } else {
+        loop {
+            f(parser.value()?)?;
+            match parser.peek() {
+                Some(b',') => parser.pos += 1,
+                Some(b']') => {
+                    parser.pos += 1;
+                    break;
+                }
+                _ => return Err(JsonError::Syntax),
+            }
+        }
+    }
+    match parser.peek() {
+        None => Ok(()),
+        Some(_) => Err(JsonError::Syntax),
+    }
+}
+
+/// The effect parameters a document can carry, under their
+/// [`key`](Param::key)s; palettes are picked with the controls, not by name
+pub(crate) const PARAMS: [Param; 4] = [
+    Param::Speed,
+    Param::Intensity,
+    Param::Primary,
+    Param::Secondary,
+];
+
+/// The changes a document asks for, every key optional
+///
+/// ```text
+/// {"brightness": 128, "effect": 3, "color_order": "rgb", "params": {"speed": 200}}
+/// ```
+///
+/// The keys [`ConfigDoc`] writes that can't be changed at run time (`fps`,
+/// `effects`) are ignored, so a document read back can be edited and
+/// written as is.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, defmt::Format)]
+pub struct ConfigUpdate {
+    pub brightness: Option<u8>,
+    pub effect: Option<usize>,
+    /// Strip length
+    pub leds: Option<usize>,
+    /// Channel order, None for the chipset's, for the next power-up
+    pub color_order: Option<Option<ColorOrder>>,
+    pub speed: Option<u8>,
+    pub intensity: Option<u8>,
+    pub primary: Option<Rgb>,
+    pub secondary: Option<Rgb>,
+}
+
+impl ConfigUpdate {
+    pub fn parse(doc: &[u8]) -> Result<Self, JsonError> {
+        let mut update = Self::default();
+        for_each_member(doc, |key, value| {
+            match key {
+                b"brightness" => update.brightness = Some(value.level()?),
+                b"effect" => update.effect = Some(value.index()?),
+                b"leds" => update.leds = Some(value.index()?),
+                b"color_order" => update.color_order = Some(value.color_order()?),
+                b"params" => {
+                    let JsonValue::Object(params) = value else {
+                        return Err(JsonError::WrongType);
+                    };
+                    for_each_member(params, |key, value| {
+                        match key {
+                            b"speed" => update.speed = Some(value.level()?),
+                            b"intensity" => update.intensity = Some(value.level()?),
+                            b"primary" => update.primary = Some(value.color()?),
+                            b"secondary" => update.secondary = Some(value.color()?),
+                            _ => return Err(JsonError::UnknownKey),
+                        }
+                        Ok(())
+                    })?;
+                }
+                b"fps" | b"effects" => {}
+                _ => return Err(JsonError::UnknownKey),
+            }
+            Ok(())
+        })?;
+        Ok(update)
+    }
+
+    /// The parameter changes, for [`Effect::set_param`]
+    pub fn params(&self) -> impl Iterator<Item = (Param, ParamValue)> {
+        [
+            self.speed.map(|s| (Param::Speed, ParamValue::Level(s))),
+            self.intensity
+                .map(|i| (Param::Intensity, ParamValue::Level(i))),
+            self.primary.map(|c| (Param::Primary, ParamValue::Color(c))),
+            self.secondary
+                .map(|c| (Param::Secondary, ParamValue::Color(c))),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Check the update against the effects before anything's changed,
+    /// returning the effect it leaves running
+    ///
+    /// Parameters are looked up in that effect's [`Effect::params`], so one
+    /// it takes but hasn't a value for yet, a wipe's unset second color say,
+    /// is still settable.
+    pub fn check(
+        &self,
+        effects: &[&mut dyn Effect],
+        current: usize,
+        max_leds: usize,
+    ) -> Result<usize, JsonError> {
+        let effect = self.effect.unwrap_or(current);
+        let running = effects.get(effect).ok_or(JsonError::OutOfRange)?;
+        let takes = |param| running.params().iter().any(|info| info.param == param);
+        if self.params().any(|(param, _)| !takes(param)) {
+            return Err(JsonError::NoSuchParam);
+        }
+        if self
+            .leds
+            .is_some_and(|leds| !(1..=max_leds).contains(&leds))
+        {
+            return Err(JsonError::OutOfRange);
+        }
+        Ok(effect)
+    }
+}
+
+/// The running state as a document, written with `{}`
+///
+/// ```text
+/// {"brightness":128,"effect":3,"effects":20,"leds":60,"color_order":"grb","fps":60,"params":{"speed":200,"primary":"ff0000"}}
+/// ```
+///
+/// `params` holds whichever of speed, intensity, primary and secondary the
+/// running effect has.
+pub struct ConfigDoc<'a> {
+    pub brightness: u8,
+    pub effect: usize,
+    pub effects: usize,
+    pub leds: usize,
+    /// As saved, `null` for the chipset's; it's used from the next power-up
+    pub color_order: Option<ColorOrder>,
+    pub fps: u32,
+    pub running: &'a dyn Effect,
+}
+
+impl fmt::Display for ConfigDoc<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{{\"brightness\":{},\"effect\":{},\"effects\":{},\"leds\":{},",
+            self.brightness, self.effect, self.effects, self.leds
+        )?;
+        match self.color_order {
+            Some(order) => write!(f, "\"color_order\":\"{}\",", order.name())?,
+            None => write!(f, "\"color_order\":null,")?,
+        }
+        write!(f, "\"fps\":{},\"params\":{{", self.fps)?;
+        let mut first = true;
+        for param in PARAMS {
+            let Some(value) = self.running.param(param) else {
+                continue;
+            };
+            let name = param.key();
+            let separator = if first { "" } else { "," };
+            first = false;
+            match value {
+                ParamValue::Level(level) => write!(f, "{separator}\"{name}\":{level}")?,
+                ParamValue::Color(c) => write!(
+                    f,
+                    "{separator}\"{name}\":\"{:02x}{:02x}{:02x}\"",
+                    c.r, c.g, c.b
+                )?,
+                ParamValue::Palette(_) => {}
+            }
+        }
+        f.write_str("}}")
+    }
+}
@@ -0,0 +1,77 @@
+#![no_std]
+
+pub mod adalight;
+pub mod ambient;
+pub mod apa102;
+pub mod artnet;
+pub mod audio;
+pub mod beat;
+pub mod bme280;
+pub mod button;
+pub mod chipset;
+pub mod climate;
+pub mod clock;
+pub mod color;
+pub mod command;
+pub mod config_drive;
+pub mod core1;
+pub mod correction;
+pub mod ddp;
+pub mod dmx;
+pub mod driver;
+pub mod ds18b20;
+pub mod e131;
+pub mod easing;
+pub mod effects;
+pub mod encoder;
+pub mod engine;
+pub mod factory_reset;
+pub mod fft;
+pub mod flash;
+pub mod framebuffer;
+pub mod host;
+pub mod http;
+pub mod i2s_mic;
+pub mod identity;
+pub mod imu;
+pub mod json;
+pub mod lpd8806;
+pub mod math;
+pub mod mdns;
+pub mod midi;
+pub mod motion;
+pub mod mqtt;
+pub mod nmea;
+pub mod noise;
+pub mod onewire;
+pub mod output;
+pub mod packet;
+pub mod palette;
+pub mod pio_programs;
+pub mod platform;
+pub mod playlist;
+pub mod pot;
+pub mod preset;
+pub mod pwm_rgb;
+pub mod realtime;
+pub mod rotation;
+pub mod segment;
+pub mod settings;
+pub mod sntp;
+pub mod sonar;
+#[cfg(feature = "spi-ws2812")]
+pub mod spi_ws2812;
+pub mod store;
+pub mod straps;
+pub mod strip;
+pub mod thermal;
+pub mod touch;
+pub mod tpm2;
+pub mod usb_hid;
+#[cfg(feature = "usb-log")]
+pub mod usb_log;
+pub mod usb_midi;
+pub mod usb_msc;
+pub mod usb_serial;
+pub mod web;
+pub mod wled;
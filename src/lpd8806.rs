@@ -0,0 +1,174 @@
+// LPD8806 driver: 7-bit clocked output from a PIO state machine over DMA
+
+use crate::platform::hal::{
+    dma::SingleChannel,
+    gpio::{Function, Pin, PinId, PullType, ValidFunction},
+    pio::{
+        PIOBuilder, PIOExt, PinDir, Running, ShiftDirection, StateMachine, StateMachineIndex, Tx,
+        UninitStateMachine, PIO,
+    },
+};
+
+use crate::driver::{fixed_point_divisor, into_data_pin, DataPin, DmaStream, Frame};
+use crate::output::LedOutput;
+use crate::pio_programs::{self, Rgb};
+
+/// Default clock rate; the LPD8806 is rated for 2 MHz, salvaged strips with
+/// long runs may need less
+pub const LPD8806_CLOCK_HZ: u32 = 2_000_000;
+
+/// Zero bytes that latch `leds` pixels, one per 32 LEDs
+const fn latch_bytes(leds: usize) -> usize {
+    leds.div_ceil(32)
+}
+
+/// Frame buffer words needed for `leds` pixels
+///
+/// Three bytes per LED then the zero latch bytes, rounded up to whole words.
+/// The padding is zero too, so it's just a longer latch.
+pub const fn frame_words(leds: usize) -> usize {
+    (3 * leds + latch_bytes(leds)).div_ceil(4)
+}
+
+/// An LPD8806 color byte: high bit set, 7 bits of color
+fn color_byte(c: u8) -> u8 {
+    0x80 | c >> 1
+}
+
+/// Configures a state machine for LPD8806 output on a clock and data pin
+///
+/// ```ignore
+/// let frame = cortex_m::singleton!(: [u32; frame_words(32)] = [0; frame_words(32)]).unwrap();
+/// let strip = Lpd8806Builder::new(&mut pio, sm0, pins.gpio14, pins.gpio15, sys_clk_hz)
+///     .build(dma.ch0, frame);
+/// ```
+pub struct Lpd8806Builder<'a, P: PIOExt, SM: StateMachineIndex> {
+    pio: &'a mut PIO<P>,
+    sm: UninitStateMachine<(P, SM)>,
+    clock: DataPin<P>,
+    data: DataPin<P>,
+    sys_clk_hz: u32,
+    clock_hz: u32,
+}
+
+impl<'a, P: PIOExt, SM: StateMachineIndex> Lpd8806Builder<'a, P, SM> {
+    pub fn new<CI, CF, CP, DI, DF, DP>(
+        pio: &'a mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock: Pin<CI, CF, CP>,
+        data: Pin<DI, DF, DP>,
+        sys_clk_hz: u32,
+    ) -> Self
+    where
+        CI: PinId + ValidFunction<P::PinFunction>,
+        CF: Function,
+        CP: PullType,
+        DI: PinId + ValidFunction<P::PinFunction>,
+        DF: Function,
+        DP: PullType,
+    {
+        Self {
+            pio,
+            sm,
+            clock: into_data_pin::<P, _, _, _>(clock),
+            data: into_data_pin::<P, _, _, _>(data),
+            sys_clk_hz,
+            clock_hz: LPD8806_CLOCK_HZ,
+        }
+    }
+
+    /// Change the clock rate from the 2 MHz default
+    pub fn clock_hz(mut self, clock_hz: u32) -> Self {
+        self.clock_hz = clock_hz;
+        self
+    }
+
+    /// Finish with DMA output from the static `frame` buffer on channel `ch`
+    ///
+    /// Size the buffer with [`frame_words`].
+    pub fn build<CH: SingleChannel, const W: usize>(
+        self,
+        ch: CH,
+        frame: Frame<W>,
+    ) -> Lpd8806<P, SM, CH, W> {
+        let clock_id = self.clock.id().num;
+        let data_id = self.data.id().num;
+        // Same clock-and-data shift-out as APA102, only the framing differs
+        let installed = self.pio.install(&pio_programs::apa102()).unwrap();
+        let (div_int, div_frac) = fixed_point_divisor(self.sys_clk_hz, self.clock_hz * 2);
+
+        let (mut sm, _, tx) = PIOBuilder::from_program(installed)
+            .side_set_pin_base(clock_id)
+            .out_pins(data_id, 1)
+            .out_shift_direction(ShiftDirection::Left)
+            .autopull(true)
+            .pull_threshold(32)
+            .clock_divisor_fixed_point(div_int, div_frac)
+            .build(self.sm);
+        sm.set_pindirs([(clock_id, PinDir::Output), (data_id, PinDir::Output)]);
+
+        let max_leds = (0..W).rev().find(|&n| frame_words(n) <= W).unwrap_or(0);
+        Lpd8806 {
+            _sm: sm.start(),
+            _clock: self.clock,
+            _data: self.data,
+            max_leds,
+            stream: DmaStream::new(ch, frame, tx),
+        }
+    }
+}
+
+/// LPD8806 strip driven by one PIO state machine, fed by one DMA channel
+pub struct Lpd8806<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const W: usize> {
+    _sm: StateMachine<(P, SM), Running>,
+    _clock: DataPin<P>,
+    _data: DataPin<P>,
+    max_leds: usize,
+    stream: DmaStream<CH, Tx<(P, SM)>, W>,
+}
+
+impl<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const W: usize> Lpd8806<P, SM, CH, W> {
+    /// Whether a frame is still being clocked out
+    pub fn is_busy(&self) -> bool {
+        self.stream.is_busy()
+    }
+
+    /// Start sending `pixels` to the strip
+    ///
+    /// Waits for the previous frame first. Colors lose their lowest bit to
+    /// the 7-bit format, and pixels that don't fit the frame buffer are ignored.
+    pub fn write_frame(&mut self, pixels: &[Rgb]) {
+        self.write_pixels(pixels.iter().copied());
+    }
+
+    fn write_pixels(&mut self, pixels: impl Iterator<Item = Rgb>) {
+        let leds = self.max_leds;
+        self.stream.send(0, |frame| {
+            // LPD8806 strips are wired GRB; black past the pixels given, so
+            // the rest don't keep their colors, then everything's latch
+            let mut bytes = pixels
+                .chain(core::iter::repeat(Rgb::BLACK))
+                .take(leds)
+                .flat_map(|led| [led.g, led.r, led.b].map(color_byte));
+            for word in frame.iter_mut() {
+                let mut be = [0; 4];
+                for byte in &mut be {
+                    *byte = bytes.next().unwrap_or(0);
+                }
+                *word = u32::from_be_bytes(be);
+            }
+        });
+    }
+}
+
+impl<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const W: usize> LedOutput
+    for Lpd8806<P, SM, CH, W>
+{
+    fn write_pixels(&mut self, pixels: impl Iterator<Item = Rgb>) {
+        self.write_pixels(pixels);
+    }
+
+    fn is_busy(&self) -> bool {
+        self.is_busy()
+    }
+}
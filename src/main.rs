@@ -1,34 +1,450 @@
 #![no_std]
 #![no_main]
 
-mod pio_programs;
-
 use bsp::entry;
 use defmt::*;
+#[cfg(not(feature = "usb-log"))]
 use defmt_rtt as _;
+#[cfg(not(any(feature = "pico-w", feature = "rp2040-zero", feature = "plasma-2040")))]
 use embedded_hal::digital::v2::OutputPin;
-use embedded_time::fixed_point::FixedPoint;
 use panic_halt as _;
 
-// Board Support Package for Raspberry Pi Pico
+// Board Support Package for Raspberry Pi Pico; the other RP2040 boards
+// share its 12 MHz crystal and boot2 for W25Q080-style flash, and differ
+// only in pins
 use rp_pico as bsp;
+#[cfg(not(feature = "irq-fifo"))]
+use bsp::hal::dma::DMAExt;
 use bsp::hal::{
+    adc::Adc,
     clocks::{init_clocks_and_plls, Clock},
     pac,
-    pio::PIOExt,
     sio::Sio,
+    timer::Timer,
     watchdog::Watchdog,
 };
 
-use pio_programs::{Rgb, rainbow};
+use pico_display::button::{Button, ButtonEvent};
+#[cfg(not(any(feature = "apa102", feature = "lpd8806")))]
+use pico_display::driver::StripBank;
+use pico_display::effects::{Effect, Fire, Pacifica, Plasma, Pride, RainbowCycle, Solid};
+#[cfg(not(any(
+    feature = "feather-scorpio",
+    feature = "plasma-2040",
+    feature = "rp2040-zero"
+)))]
+use pico_display::encoder::RotaryEncoder;
+use pico_display::engine::{Engine, DEFAULT_FPS};
+use pico_display::factory_reset;
+use pico_display::identity::Identity;
+#[cfg(any(feature = "pir", feature = "ambient"))]
+use pico_display::math::scale8;
+use pico_display::math::Rng;
+use pico_display::pio_programs::Rgb;
+use pico_display::settings::Settings;
+use pico_display::thermal::{DieTemperature, ThermalLimiter};
+// The knob's, and those of features a board without one may not have
+#[cfg_attr(
+    any(
+        feature = "feather-scorpio",
+        feature = "plasma-2040",
+        feature = "rp2040-zero"
+    ),
+    allow(unused_imports)
+)]
+use pico_display::{
+    effects::{Param, ParamValue},
+    preset::Preset,
+};
+
+#[cfg(feature = "apa102")]
+use {
+    bsp::hal::pio::PIOExt,
+    pico_display::apa102::{frame_words, Apa102Builder},
+};
+
+#[cfg(feature = "lpd8806")]
+use {
+    bsp::hal::pio::PIOExt,
+    pico_display::lpd8806::{frame_words, Lpd8806Builder},
+};
+
+#[cfg(all(feature = "apa102", feature = "irq-fifo"))]
+compile_error!("the APA102 output is DMA driven, `irq-fifo` only applies to WS2812");
+
+#[cfg(all(feature = "lpd8806", any(feature = "apa102", feature = "irq-fifo")))]
+compile_error!("`lpd8806` selects the LPD8806 output on its own, drop `apa102` and `irq-fifo`");
+
+#[cfg(any(feature = "irq-fifo", feature = "hall"))]
+use {bsp::hal::pac::interrupt, core::cell::RefCell, critical_section::Mutex};
+#[cfg(feature = "irq-fifo")]
+use {bsp::hal::pio::SM0, pico_display::driver::IrqWs2812, pico_display::output::LedOutput};
+
+#[cfg(any(
+    feature = "pot",
+    feature = "ambient",
+    all(feature = "mic", not(feature = "i2s-mic"))
+))]
+use bsp::hal::adc::AdcPin;
+#[cfg(feature = "pot")]
+use pico_display::pot::{PotTarget, Potentiometer};
+
+#[cfg(any(feature = "touch", feature = "dht22", feature = "ds18b20"))]
+use bsp::hal::gpio::InOutPin;
+#[cfg(feature = "touch")]
+use pico_display::touch::TouchPad;
+
+#[cfg(feature = "pir")]
+use pico_display::motion::MotionLight;
+
+#[cfg(feature = "straps")]
+use pico_display::{
+    playlist::{Playlist, PlaylistEntry},
+    straps::Straps,
+};
+
+#[cfg(feature = "dual-core")]
+use pico_display::{core1::Core1, engine::RenderWorker};
+
+#[cfg(feature = "ambient")]
+use pico_display::ambient::{AutoBrightness, Photoresistor};
+
+#[cfg(all(feature = "mic", not(feature = "i2s-mic")))]
+use pico_display::audio::AdcMic;
+#[cfg(feature = "tap-tempo")]
+use pico_display::{beat::TapTempo, button::DEBOUNCE_MS};
+#[cfg(any(feature = "mic", feature = "tap-tempo"))]
+use {
+    pico_display::audio::Audio,
+    pico_display::beat::BeatSync,
+    pico_display::effects::{Breathe, TheaterChase},
+    pico_display::palette::PARTY,
+};
+#[cfg(feature = "mic")]
+use {
+    pico_display::audio::{Envelope, SpectrumAnalyzer},
+    pico_display::beat::BeatDetector,
+    pico_display::effects::{Spectrum, VuMeter},
+};
+
+#[cfg(feature = "sonar")]
+use {
+    pico_display::effects::{DistanceMeter, DistanceMode},
+    pico_display::sonar::{Proximity, SonarBuilder},
+};
+
+#[cfg(feature = "dht22")]
+use pico_display::climate::Dht22;
+#[cfg(feature = "ds18b20")]
+use {
+    pico_display::climate::Celsius, pico_display::ds18b20::Ds18b20, pico_display::onewire::OneWire,
+};
+#[cfg(any(feature = "dht22", feature = "ds18b20", feature = "bme280"))]
+use {
+    pico_display::climate::Climate,
+    pico_display::effects::{Thermometer, ThermometerMode},
+};
+
+#[cfg(feature = "bme280")]
+use {
+    pico_display::bme280::{Bme280, BME280_ADDR_LOW, CHIP_ID},
+    pico_display::climate::PressureTrend,
+    pico_display::effects::Weather,
+};
+
+#[cfg(any(
+    all(
+        feature = "pico-w",
+        any(
+            feature = "rp2040-zero",
+            feature = "feather-scorpio",
+            feature = "plasma-2040"
+        )
+    ),
+    all(
+        feature = "rp2040-zero",
+        any(feature = "feather-scorpio", feature = "plasma-2040")
+    ),
+    all(feature = "feather-scorpio", feature = "plasma-2040"),
+))]
+compile_error!("pick one board feature at most");
+
+#[cfg(all(feature = "feather-scorpio", feature = "tap-tempo"))]
+compile_error!("the SCORPIO's red LED is on GPIO13, where the tap tempo button goes");
+
+#[cfg(all(feature = "feather-scorpio", any(feature = "touch", feature = "pir")))]
+compile_error!("the SCORPIO's outputs 5 and 6 are GPIO21/22, where the touch pad and PIR go");
+
+#[cfg(all(feature = "plasma-2040", feature = "i2s-mic"))]
+compile_error!("the Plasma 2040's button A is GPIO12, where the I2S microphone's data goes");
+
+#[cfg(all(feature = "bme280", any(feature = "dht22", feature = "ds18b20")))]
+compile_error!("the BME280's I2C1 pins, GPIO2/3, are where the DHT22 and DS18B20 go; pick one");
+
+#[cfg(all(feature = "straps", any(feature = "sonar", feature = "hall")))]
+compile_error!("the strap pins, GPIO6-9, are where the sonar and hall sensor go; pick one");
+
+#[cfg(all(feature = "dual-core", feature = "hall"))]
+compile_error!(
+    "POV effects draw where the strip is in its turn now, not a frame on; drop `dual-core`"
+);
+
+#[cfg(any(feature = "imu", feature = "bme280", feature = "gps"))]
+use bsp::hal::fugit::RateExtU32;
+#[cfg(any(feature = "imu", feature = "bme280"))]
+use bsp::hal::{
+    gpio::{FunctionI2C, Pin, PullUp},
+    I2C,
+};
+#[cfg(feature = "i2s-mic")]
+use pico_display::i2s_mic::I2sMicBuilder;
+#[cfg(feature = "imu")]
+use {
+    pico_display::effects::Liquid,
+    pico_display::imu::{Movement, Mpu6050, ShakeDetector, MPU6050_ADDR_LOW},
+};
+
+#[cfg(feature = "gps")]
+use {
+    bsp::hal::gpio::FunctionUart,
+    bsp::hal::uart::{DataBits, StopBits, UartConfig, UartPeripheral},
+    embedded_hal::serial::Read,
+    pico_display::clock::{Dst, WallClock},
+    pico_display::nmea::NmeaParser,
+};
+
+#[cfg(feature = "usb-hid")]
+use pico_display::usb_hid::{HidControl, HidRequest, HidStatus};
+#[cfg(feature = "usb-log")]
+use pico_display::usb_log;
+#[cfg(any(feature = "usb-serial", feature = "usb-log"))]
+use pico_display::usb_serial::{UsbSerial, MAX_PACKET};
+#[cfg(feature = "usb-msc")]
+use pico_display::{
+    config_drive::{self, ConfigDrive, ConfigText, Sectors, SECTORS},
+    usb_msc::{MassStorage, BLOCK_SIZE},
+};
+#[cfg(any(
+    feature = "usb-serial",
+    feature = "usb-hid",
+    feature = "usb-midi",
+    feature = "usb-log",
+    feature = "usb-msc"
+))]
+use {
+    bsp::hal::usb::UsbBus,
+    usb_device::{class_prelude::UsbBusAllocator, prelude::*},
+};
+#[cfg(feature = "usb-serial")]
+use {
+    core::fmt::Write as _,
+    pico_display::adalight::AdalightReader,
+    pico_display::command::{Command, CommandError, LineReader, HELP},
+    pico_display::effects::HostPixels,
+    pico_display::host::{HostFrame, StreamEvent},
+    pico_display::json::{ConfigDoc, ConfigUpdate},
+    pico_display::packet::{
+        encode_reply, Nack, PacketEvent, PacketReader, Reply, Request, Status, MAX_REPLY,
+    },
+    pico_display::preset::PRESETS,
+    pico_display::tpm2::Tpm2Reader,
+};
+#[cfg(feature = "usb-midi")]
+use {
+    pico_display::effects::NoteFlash,
+    pico_display::midi::{Midi, MidiMessage, CC_BRIGHTNESS, CC_HUE, CC_SPEED},
+    pico_display::usb_midi::UsbMidi,
+};
+
+#[cfg(feature = "hall")]
+use {
+    bsp::hal::gpio::{self, Interrupt::EdgeLow},
+    core::cell::Cell,
+    pico_display::effects::PovWheel,
+    pico_display::rotation::{Rotation, RotationTimer},
+};
+
+/// The board built for
+#[cfg(not(any(
+    feature = "pico-w",
+    feature = "rp2040-zero",
+    feature = "feather-scorpio",
+    feature = "plasma-2040"
+)))]
+const BOARD: &str = "Pico";
+#[cfg(feature = "pico-w")]
+const BOARD: &str = "Pico W";
+#[cfg(feature = "rp2040-zero")]
+const BOARD: &str = "RP2040-Zero";
+#[cfg(feature = "feather-scorpio")]
+const BOARD: &str = "Feather RP2040 SCORPIO";
+#[cfg(feature = "plasma-2040")]
+const BOARD: &str = "Plasma 2040";
 
 // Configuration for the LED strip
-const NUM_LEDS: usize = 8; // Change this to match your LED strip length
-const LED_PIN: u8 = 15;    // GPIO pin connected to the LED strip data line (GPIO15 = physical pin 20)
+const NUM_LEDS: usize = 300; // The longest strip the buffers hold; `leds` sets a shorter one
+const DEFAULT_LEDS: u16 = 8; // The length before one's set, the 8-pixel test strip
+
+/// Frame rate; a spinning strip needs the frames close together for a
+/// sharp picture
+#[cfg(feature = "hall")]
+const FPS: u32 = DEFAULT_FPS * 8;
+#[cfg(not(feature = "hall"))]
+const FPS: u32 = DEFAULT_FPS;
+
+/// What the potentiometer controls
+#[cfg(feature = "pot")]
+const POT_TARGET: PotTarget = PotTarget::Brightness;
+
+/// Die temperatures, in millidegrees, where brightness starts coming down
+/// and where it bottoms out
+const THERMAL_START_MC: i32 = 55_000;
+const THERMAL_MAX_MC: i32 = 75_000;
+
+/// How long the strip stays on after the last motion, and how long it then
+/// takes to fade out
+#[cfg(feature = "pir")]
+const PIR_TIMEOUT_MS: u32 = 60_000;
+#[cfg(feature = "pir")]
+const PIR_FADE_MS: u32 = 3_000;
+
+/// Photoresistor readings for a dark and a bright room; tune these to the
+/// LDR and where the strip lives
+#[cfg(feature = "ambient")]
+const AMBIENT_DARK: u16 = 300;
+#[cfg(feature = "ambient")]
+const AMBIENT_BRIGHT: u16 = 3000;
+
+/// Analog microphone sample period, for about 10 kHz; the spectrum reaches
+/// half that. The I2S microphone runs at 16 kHz.
+#[cfg(all(feature = "mic", not(feature = "i2s-mic")))]
+const MIC_SAMPLE_US: u32 = 100;
+/// FFT size; each frame captures half of it
+#[cfg(feature = "mic")]
+const FFT_LEN: usize = 128;
+
+/// Local time against UTC, in minutes; GPS time is always UTC
+#[cfg(feature = "gps")]
+const UTC_OFFSET_MIN: i16 = 0;
+
+/// The local summer time rule, if any: `Some(Dst::eu(UTC_OFFSET_MIN))` in
+/// Europe, `Some(Dst::US)` in North America
+#[cfg(feature = "gps")]
+const DST: Option<Dst> = None;
+
+/// A gap in streamed frames after which the next one takes over the strip
+/// again
+#[cfg(feature = "usb-serial")]
+const STREAM_IDLE_MS: u32 = 2000;
+
+/// How long `reboot` and `bootsel` wait, for their "ok" to reach the host
+#[cfg(feature = "usb-serial")]
+const REBOOT_DELAY_MS: u32 = 100;
+
+/// What `bootsel` must be given before it drops into the bootloader, so a
+/// stray command can't take an installed strip offline; set per
+/// installation with `BOOTSEL_PIN` at build time, and without it `bootsel`
+/// is refused
+#[cfg(feature = "usb-serial")]
+const BOOTSEL_PIN: Option<usize> = match option_env!("BOOTSEL_PIN") {
+    Some(pin) => match usize::from_str_radix(pin, 10) {
+        Ok(pin) => Some(pin),
+        Err(_) => core::panic!("BOOTSEL_PIN isn't a number"),
+    },
+    None => None,
+};
+
+/// The status LED as a pin mask, for the bootloader to flash with drive
+/// activity; none where the LED isn't a plain pin (the Pico W's GPIO25 is
+/// the radio's chip select)
+#[cfg(feature = "usb-serial")]
+const STATUS_LED_MASK: u32 = if cfg!(feature = "feather-scorpio") {
+    1 << 13
+} else if cfg!(any(
+    feature = "pico-w",
+    feature = "rp2040-zero",
+    feature = "plasma-2040"
+)) {
+    0
+} else {
+    1 << 25
+};
+
+/// Strip lengths the bank straps pick: GPIO6 jumpered, GPIO7, then both
+#[cfg(feature = "straps")]
+const STRAP_LEDS: [u16; 3] = [30, 60, 144];
+#[cfg(feature = "straps")]
+const _: () = {
+    let mut i = 0;
+    while i < STRAP_LEDS.len() {
+        core::assert!(
+            STRAP_LEDS[i] as usize <= NUM_LEDS,
+            "a strap bank is longer than NUM_LEDS"
+        );
+        i += 1;
+    }
+};
+
+/// Colors a long touch on the pad steps through
+#[cfg(feature = "touch")]
+const TOUCH_COLORS: [Rgb; 6] = [
+    Rgb {
+        r: 255,
+        g: 147,
+        b: 41,
+    }, // Candle-warm white
+    Rgb::RED,
+    Rgb::GREEN,
+    Rgb::BLUE,
+    Rgb::MAGENTA,
+    Rgb::WHITE,
+];
+
+/// Interrupt-fed strip, shared between the main loop and `PIO0_IRQ_0`
+#[cfg(feature = "irq-fifo")]
+static STRIP: Mutex<RefCell<Option<IrqWs2812<pac::PIO0, SM0, NUM_LEDS>>>> =
+    Mutex::new(RefCell::new(None));
+
+/// Hall sensor pin and a timer to timestamp its marks, for `IO_IRQ_BANK0`
+#[cfg(feature = "hall")]
+static HALL: Mutex<RefCell<Option<(HallPin, Timer)>>> = Mutex::new(RefCell::new(None));
+
+#[cfg(feature = "hall")]
+type HallPin = gpio::Pin<gpio::bank0::Gpio8, gpio::FunctionSioInput, gpio::PullUp>;
+
+/// Turn timing, marked by the hall sensor interrupt
+#[cfg(feature = "hall")]
+static ROTATION: Mutex<Cell<RotationTimer>> = Mutex::new(Cell::new(RotationTimer::new()));
+
+/// `LedOutput` handle on the shared strip, for the engine
+#[cfg(feature = "irq-fifo")]
+struct SharedStrip;
+
+#[cfg(feature = "irq-fifo")]
+impl LedOutput for SharedStrip {
+    fn write_pixels(&mut self, pixels: impl Iterator<Item = Rgb>) {
+        critical_section::with(|cs| {
+            if let Some(ws) = STRIP.borrow_ref_mut(cs).as_mut() {
+                ws.write_pixels(pixels);
+            }
+        });
+    }
+
+    fn is_busy(&self) -> bool {
+        critical_section::with(|cs| STRIP.borrow_ref(cs).as_ref().is_some_and(|ws| ws.is_busy()))
+    }
+}
 
 #[entry]
 fn main() -> ! {
-    info!("🌈 WS2812 NeoPixel Controller Starting!");
+    info!("🌈 WS2812 NeoPixel Controller Starting on a {}!", BOARD);
+    // The flash chip's unique ID tells boards apart: it's the USB serial
+    // number, and seeds the effects' randomness, so they're made after
+    let identity = Identity::read();
+    let serial_number = cortex_m::singleton!(: [u8; 16] = identity.serial()).unwrap();
+    let serial_number = core::str::from_utf8(serial_number).unwrap();
+    info!("🪪 Board ID {}", serial_number);
+    Rng::set_board_seed(identity.seed());
     let mut pac = pac::Peripherals::take().unwrap();
     let core = pac::CorePeripherals::take().unwrap();
     let mut watchdog = Watchdog::new(pac.WATCHDOG);
@@ -57,67 +473,1323 @@ fn main() -> ! {
         &mut pac.RESETS,
     );
 
-    // Setup onboard LED for status indication
+    // Setup onboard LED for status indication; the Pico W's is on the
+    // radio, and the RP2040-Zero and Plasma 2040 have RGB LEDs instead
+    #[cfg(not(any(
+        feature = "pico-w",
+        feature = "rp2040-zero",
+        feature = "plasma-2040",
+        feature = "feather-scorpio"
+    )))]
     let mut led_pin = pins.led.into_push_pull_output();
+    #[cfg(feature = "feather-scorpio")]
+    let mut led_pin = pins.gpio13.into_push_pull_output();
+
+    // Mode button between GPIO20 (physical pin 26) and ground; on the
+    // SCORPIO GPIO17-23 are strip outputs, so it's on D24, and the Plasma
+    // 2040's button A is it
+    #[cfg(not(any(feature = "feather-scorpio", feature = "plasma-2040")))]
+    let mut button = Button::new(pins.gpio20.into_pull_up_input());
+    #[cfg(feature = "feather-scorpio")]
+    // The Pico's BSP names GPIO24 for its VBUS sense
+    let mut button = Button::new(pins.vbus_detect.into_pull_up_input());
+    #[cfg(feature = "plasma-2040")]
+    let mut button = Button::new(pins.gpio12.into_pull_up_input());
+
+    // Rotary encoder on GPIO17/GPIO18 (physical pins 22 and 24), its push
+    // switch on GPIO19 (physical pin 25), all switching to ground; the
+    // SCORPIO's outputs, the Plasma 2040's RGB LED and the RP2040-Zero's
+    // missing pins leave them no knob
+    #[cfg(not(any(
+        feature = "feather-scorpio",
+        feature = "plasma-2040",
+        feature = "rp2040-zero"
+    )))]
+    let mut knob = RotaryEncoder::new(
+        pins.gpio17.into_pull_up_input(),
+        pins.gpio18.into_pull_up_input(),
+    );
+    #[cfg(not(any(
+        feature = "feather-scorpio",
+        feature = "plasma-2040",
+        feature = "rp2040-zero"
+    )))]
+    let mut knob_switch = Button::new(pins.gpio19.into_pull_up_input());
+
+    // Potentiometer wiper on GPIO26 (physical pin 31), ends to 3V3 and AGND;
+    // GPIO27 and GPIO28 work the same
+    // The ADC also reads the die temperature, brightness backs off if the
+    // board gets too hot
+    let mut adc = Adc::new(pac.ADC, &mut pac.RESETS);
+    let mut die = DieTemperature::new(adc.take_temp_sensor().unwrap());
+    let mut thermal = ThermalLimiter::new(THERMAL_START_MC, THERMAL_MAX_MC);
+    #[cfg(feature = "pot")]
+    let mut pot = Potentiometer::new(AdcPin::new(pins.gpio26.into_floating_input()));
+
+    // Photoresistor from 3V3 to GPIO27 (physical pin 32), 10k from there to AGND
+    #[cfg(feature = "ambient")]
+    let (mut ldr, mut auto_brightness) = (
+        Photoresistor::new(AdcPin::new(pins.gpio27.into_floating_input())),
+        AutoBrightness::new(AMBIENT_DARK, AMBIENT_BRIGHT),
+    );
+
+    // Analog microphone module output on GPIO28 (physical pin 34), powered
+    // from 3V3
+    #[cfg(all(feature = "mic", not(feature = "i2s-mic")))]
+    let mut mic = AdcMic::new(AdcPin::new(pins.gpio28.into_floating_input()));
+    #[cfg(feature = "mic")]
+    let (mut envelope, mut analyzer, mut beats) = (
+        Envelope::new(),
+        SpectrumAnalyzer::<FFT_LEN>::new(),
+        BeatDetector::new(),
+    );
+    #[cfg(any(feature = "mic", feature = "tap-tempo"))]
+    let audio = Audio::new();
+
+    // Tap tempo button between GPIO13 (physical pin 17) and ground; a long
+    // press clears the tapped tempo
+    #[cfg(feature = "tap-tempo")]
+    let (mut tap_button, mut tap_tempo, mut tap_down) = (
+        Button::new(pins.gpio13.into_pull_up_input()),
+        TapTempo::new(),
+        false,
+    );
+
+    // MPU6050 on I2C0, SDA on GPIO4 (physical pin 6) and SCL on GPIO5 (7);
+    // runs without it if it doesn't answer
+    #[cfg(feature = "imu")]
+    let (mut imu, mut shake, movement) = {
+        let sda: Pin<_, FunctionI2C, PullUp> = pins.gpio4.reconfigure();
+        let scl: Pin<_, FunctionI2C, PullUp> = pins.gpio5.reconfigure();
+        let i2c = I2C::i2c0(
+            pac.I2C0,
+            sda,
+            scl,
+            400.kHz(),
+            &mut pac.RESETS,
+            &clocks.system_clock,
+        );
+        let imu = Mpu6050::new(i2c, MPU6050_ADDR_LOW).ok();
+        if imu.is_none() {
+            warn!("⚠️ No MPU6050 on I2C0");
+        }
+        (imu, ShakeDetector::new(), Movement::new())
+    };
+
+    // Touch pad (bare wire or foil) on GPIO21 (physical pin 27)
+    #[cfg(feature = "touch")]
+    let mut pad = TouchPad::new(InOutPin::new(pins.gpio21.into_pull_up_input()));
+
+    // BME280 on I2C1, SDA on GPIO2 (physical pin 4) and SCL on GPIO3 (5)
+    #[cfg(feature = "bme280")]
+    let (mut bme, mut pressure_trend) = {
+        let sda: Pin<_, FunctionI2C, PullUp> = pins.gpio2.reconfigure();
+        let scl: Pin<_, FunctionI2C, PullUp> = pins.gpio3.reconfigure();
+        let i2c = I2C::i2c1(
+            pac.I2C1,
+            sda,
+            scl,
+            400.kHz(),
+            &mut pac.RESETS,
+            &clocks.system_clock,
+        );
+        let mut bme = Bme280::new(i2c, BME280_ADDR_LOW).ok();
+        match bme.as_mut().map(|bme| bme.chip_id()) {
+            Some(Ok(CHIP_ID)) => {}
+            Some(_) => warn!("⚠️ Not a BME280 on I2C1, readings may be off"),
+            None => warn!("⚠️ No BME280 on I2C1"),
+        }
+        (bme, PressureTrend::new())
+    };
+
+    // DHT22 data on GPIO2 (physical pin 4)
+    #[cfg(feature = "dht22")]
+    let mut dht = Dht22::new(InOutPin::new(pins.gpio2.into_pull_up_input()));
+
+    // DS18B20 probes on GPIO3 (physical pin 5), with a 4.7k pull-up to 3V3
+    #[cfg(feature = "ds18b20")]
+    let mut probes = {
+        let bus = OneWire::new(InOutPin::new(pins.gpio3.into_floating_input()));
+        let probes = Ds18b20::new(bus, &mut delay);
+        match probes.probes().len() {
+            0 => warn!("⚠️ No DS18B20 on GPIO3"),
+            count => info!("🌡️ {} DS18B20 probe(s) on GPIO3", count),
+        }
+        probes
+    };
+
+    // GPS module (NEO-6M or similar) at its default 9600 baud, its RX on
+    // GPIO0 (physical pin 1) and its TX on GPIO1 (2)
+    #[cfg(feature = "gps")]
+    let (mut gps, mut nmea, mut clock) = {
+        let pins = (
+            pins.gpio0.into_function::<FunctionUart>(),
+            pins.gpio1.into_function::<FunctionUart>(),
+        );
+        let gps = UartPeripheral::new(pac.UART0, pins, &mut pac.RESETS)
+            .enable(
+                UartConfig::new(9600.Hz(), DataBits::Eight, None, StopBits::One),
+                clocks.peripheral_clock.freq(),
+            )
+            .unwrap();
+        let mut clock = WallClock::new().utc_offset_min(UTC_OFFSET_MIN);
+        clock.dst = DST;
+        (gps, NmeaParser::new(), clock)
+    };
+
+    // PIR sensor output on GPIO22 (physical pin 29); HC-SR501 boards run
+    // from 5V (VBUS) but their output is 3.3V
+    #[cfg(feature = "pir")]
+    let mut pir = MotionLight::new(
+        pins.gpio22.into_pull_down_input(),
+        PIR_TIMEOUT_MS,
+        PIR_FADE_MS,
+    );
+
+    // Brightness, effect, strip length, channel order and calibration as
+    // last saved; only the serial commands and the USB drive change them
+    #[cfg_attr(
+        not(any(feature = "usb-serial", feature = "usb-msc")),
+        allow(unused_mut)
+    )]
+    let mut settings = Settings::load().unwrap_or(Settings::new(DEFAULT_LEDS));
+    info!("⚙️ Settings: {}", settings);
+
+    // Jumpers from GPIO6-9 (physical pins 9-12) to ground, read once: a
+    // strip length from the bank, RGB channel order, and demo mode. They
+    // win over the saved settings for this power-up without changing them.
+    #[cfg(feature = "straps")]
+    let straps = {
+        let bank = (
+            pins.gpio6.into_pull_up_input(),
+            pins.gpio7.into_pull_up_input(),
+        );
+        let (rgb, demo) = (
+            pins.gpio8.into_pull_up_input(),
+            pins.gpio9.into_pull_up_input(),
+        );
+        delay.delay_us(100);
+        Straps::read([&bank.0, &bank.1], &rgb, &demo)
+    };
+    #[cfg(feature = "straps")]
+    info!("🔌 Straps: {}", straps);
+    #[cfg(feature = "straps")]
+    let start = Settings {
+        leds: straps.leds(&STRAP_LEDS).unwrap_or(settings.leds),
+        color_order: straps.color_order().or(settings.color_order),
+        ..settings
+    };
+    #[cfg(not(feature = "straps"))]
+    let start = settings;
 
     // === WS2812 PIO Setup ===
-    let (mut pio, sm0, _, _, _) = pac.PIO0.split(&mut pac.RESETS);
-    
-    // Install WS2812 program
-    let ws2812_program = pio_programs::ws2812();
-    let installed = pio.install(&ws2812_program).unwrap();
-    
-    // Configure state machine for WS2812 timing
-    // WS2812 requires specific timing: The clock should be set for proper bit timing
-    // With side-set and delays in the PIO program, we need ~800kHz effective rate
-    let (mut sm, _, mut tx) = bsp::hal::pio::PIOBuilder::from_program(installed)
-        .side_set_pin_base(LED_PIN)
-        .out_shift_direction(bsp::hal::pio::ShiftDirection::Left)
-        .autopull(true)
-        .pull_threshold(24) // Pull every 24 bits (one RGB pixel)
-        .clock_divisor_fixed_point(6, 25) // Slower clock for proper WS2812 timing
-        .build(sm0);
-
-    // Configure the LED data pin for PIO output
-    let _led_data_pin = pins.gpio15.into_mode::<bsp::hal::gpio::FunctionPio0>();
-    
-    // Start the PIO state machine
-    sm.set_pindirs([(LED_PIN, bsp::hal::pio::PinDir::Output)]);
-    let sm = sm.start();
-
-    info!("✅ WS2812 PIO program running on GPIO{}", LED_PIN);
-    info!("🎨 Controlling {} NeoPixel LEDs", NUM_LEDS);
+    // Outputs 0-3 are PIO0's state machines, 4-7 PIO1's
+    // Bit timing is derived from the actual system clock, so overclocking is safe
+    let sys_clk_hz = clocks.system_clock.freq().to_Hz();
+    #[cfg(not(any(feature = "apa102", feature = "lpd8806")))]
+    let mut bank = StripBank::new(pac.PIO0, pac.PIO1, &mut pac.RESETS, sys_clk_hz);
+
+    // GPIO pin connected to the LED strip data line (GPIO15 = physical pin 20)
+    // The driver switches it to the PIO function and uses it as the side-set base
+    #[cfg(not(feature = "feather-scorpio"))]
+    let led_data_pin = pins.gpio15;
+    // The SCORPIO's eight level-shifted outputs are GPIO16-23
+    #[cfg(feature = "feather-scorpio")]
+    let led_data_pin = pins.gpio16;
+    let led_data_gpio = led_data_pin.id().num;
+
+    // One DMA channel streams the whole frame buffer into the PIO TX FIFO,
+    // paced by the FIFO's DREQ, so a strip update doesn't occupy the CPU.
+    #[cfg(not(any(feature = "irq-fifo", feature = "apa102", feature = "lpd8806")))]
+    let ws = {
+        let dma = pac.DMA.split(&mut pac.RESETS);
+        let frame = cortex_m::singleton!(: [u32; NUM_LEDS] = [0; NUM_LEDS]).unwrap();
+        let strip = bank.output0(led_data_pin).unwrap();
+        let strip = match start.color_order {
+            Some(order) => strip.color_order(order),
+            None => strip,
+        };
+        let mut ws = strip.build(dma.ch0, frame);
+        ws.set_calibration(settings.calibration);
+        ws
+    };
+
+    // Or an APA102 strip, with its clock line on GPIO14 (physical pin 19)
+    #[cfg(feature = "apa102")]
+    let ws = {
+        const WORDS: usize = frame_words(NUM_LEDS);
+        let (mut pio, sm0, _, _, _) = pac.PIO0.split(&mut pac.RESETS);
+        let dma = pac.DMA.split(&mut pac.RESETS);
+        let frame = cortex_m::singleton!(: [u32; WORDS] = [0; WORDS]).unwrap();
+        Apa102Builder::new(&mut pio, sm0, pins.gpio14, led_data_pin, sys_clk_hz)
+            .build(dma.ch0, frame)
+    };
+
+    // Or an LPD8806 strip, clock on GPIO14 as for APA102
+    #[cfg(feature = "lpd8806")]
+    let ws = {
+        const WORDS: usize = frame_words(NUM_LEDS);
+        let (mut pio, sm0, _, _, _) = pac.PIO0.split(&mut pac.RESETS);
+        let dma = pac.DMA.split(&mut pac.RESETS);
+        let frame = cortex_m::singleton!(: [u32; WORDS] = [0; WORDS]).unwrap();
+        Lpd8806Builder::new(&mut pio, sm0, pins.gpio14, led_data_pin, sys_clk_hz)
+            .build(dma.ch0, frame)
+    };
+
+    // Or the PIO0 interrupt tops up the FIFO whenever it has room
+    #[cfg(feature = "irq-fifo")]
+    let ws = {
+        let strip = bank.output0(led_data_pin).unwrap();
+        let strip = match start.color_order {
+            Some(order) => strip.color_order(order),
+            None => strip,
+        };
+        let mut ws = strip.build_irq();
+        ws.set_calibration(settings.calibration);
+        critical_section::with(|cs| STRIP.borrow_ref_mut(cs).replace(ws));
+        // Safety: STRIP is populated, so the handler has something to feed
+        unsafe { pac::NVIC::unmask(pac::Interrupt::PIO0_IRQ_0) };
+        SharedStrip
+    };
+
+    // The clocked strips only take PIO0, leaving PIO1 to the sensors below
+    #[cfg(all(
+        any(feature = "apa102", feature = "lpd8806"),
+        any(feature = "i2s-mic", feature = "sonar")
+    ))]
+    #[allow(unused_variables)]
+    let (mut pio1, _, _, pio1_sm2, pio1_sm3) = pac.PIO1.split(&mut pac.RESETS);
+
+    // I2S microphone on the last PIO1 state machine: SCK on GPIO10
+    // (physical pin 14), WS on GPIO11 (15), SD on GPIO12 (16), L/R to ground
+    #[cfg(all(feature = "i2s-mic", not(any(feature = "apa102", feature = "lpd8806"))))]
+    let mut mic = {
+        let (pio, sm) = bank.state_machine7().unwrap();
+        I2sMicBuilder::new(pio, sm, pins.gpio10, pins.gpio11, pins.gpio12, sys_clk_hz).build()
+    };
+    #[cfg(all(feature = "i2s-mic", any(feature = "apa102", feature = "lpd8806")))]
+    let mut mic = I2sMicBuilder::new(
+        &mut pio1,
+        pio1_sm3,
+        pins.gpio10,
+        pins.gpio11,
+        pins.gpio12,
+        sys_clk_hz,
+    )
+    .build();
+
+    // HC-SR04 on the PIO1 state machine before that: trigger on GPIO6
+    // (physical pin 9), echo on GPIO7 (10) through a 5 V to 3.3 V divider
+    #[cfg(all(feature = "sonar", not(any(feature = "apa102", feature = "lpd8806"))))]
+    let mut sonar = {
+        let (pio, sm) = bank.state_machine6().unwrap();
+        SonarBuilder::new(pio, sm, pins.gpio6, pins.gpio7, sys_clk_hz).build()
+    };
+    #[cfg(all(feature = "sonar", any(feature = "apa102", feature = "lpd8806")))]
+    let mut sonar =
+        SonarBuilder::new(&mut pio1, pio1_sm2, pins.gpio6, pins.gpio7, sys_clk_hz).build();
+
+    info!("✅ WS2812 PIO program running on GPIO{}", led_data_gpio);
+    info!(
+        "🎨 Controlling {} of up to {} NeoPixel LEDs",
+        start.leds.min(NUM_LEDS as u16),
+        NUM_LEDS
+    );
+    #[cfg(not(any(
+        feature = "pico-w",
+        feature = "rp2040-zero",
+        feature = "plasma-2040",
+        feature = "feather-scorpio"
+    )))]
     info!("📍 Status LED on GPIO25 (onboard)");
+    #[cfg(feature = "feather-scorpio")]
+    info!("📍 Status LED on GPIO13 (onboard)");
+    #[cfg(not(any(feature = "feather-scorpio", feature = "plasma-2040")))]
+    info!("🔘 Mode button on GPIO20");
+    #[cfg(feature = "feather-scorpio")]
+    info!("🔘 Mode button on GPIO24");
+    #[cfg(feature = "plasma-2040")]
+    info!("🔘 Mode button on GPIO12 (button A)");
+    #[cfg(not(any(
+        feature = "feather-scorpio",
+        feature = "plasma-2040",
+        feature = "rp2040-zero"
+    )))]
+    info!("🎛️ Rotary encoder on GPIO17/18, switch on GPIO19");
+
+    info!("🚀 Starting effect engine at {} fps", FPS);
+
+    let timer = Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+    let mut engine = Engine::<_, NUM_LEDS>::new(ws, FPS);
+    // Over the strip fitted, so the reset below counts along its length
+    engine.set_brightness(settings.brightness);
+    engine.set_leds(start.leds as usize);
+
+    // Holding the mode button through power-up for ten seconds wipes the
+    // saved settings and presets: the strip fills red as it counts, flashes
+    // red and white once they're gone, and letting go restarts with the
+    // defaults. Letting go sooner carries on as normal.
+    if factory_reset::run(&mut button, &mut engine, || {
+        (timer.get_counter().ticks() / 1000) as u32
+    }) {
+        info!("🧹 Factory reset, restarting");
+        cortex_m::peripheral::SCB::sys_reset();
+    }
+
+    // Hall sensor (A3144 or similar, open collector) on GPIO8 (physical
+    // pin 11), pulling low as the magnet passes once a turn
+    #[cfg(feature = "hall")]
+    {
+        let hall: HallPin = pins.gpio8.reconfigure();
+        hall.set_interrupt_enabled(EdgeLow, true);
+        critical_section::with(|cs| HALL.borrow_ref_mut(cs).replace((hall, timer)));
+        // Safety: HALL is populated, so the handler has a pin to clear
+        unsafe { pac::NVIC::unmask(pac::Interrupt::IO_IRQ_BANK0) };
+    }
+    #[cfg(feature = "hall")]
+    let (rotation, mut next_rpm_log_ms) = (Rotation::new(), 0u32);
 
-    info!("🚀 Starting LED test...");
+    // USB serial port, HID interface and MIDI port for a host to send
+    // commands to, and a port for the logs, on the Pico's own USB socket
+    #[cfg(any(
+        feature = "usb-serial",
+        feature = "usb-hid",
+        feature = "usb-midi",
+        feature = "usb-log",
+        feature = "usb-msc"
+    ))]
+    let usb_bus = UsbBusAllocator::new(UsbBus::new(
+        pac.USBCTRL_REGS,
+        pac.USBCTRL_DPRAM,
+        clocks.usb_clock,
+        true,
+        &mut pac.RESETS,
+    ));
+    #[cfg(feature = "usb-serial")]
+    let (mut serial, mut lines) = (UsbSerial::new(&usb_bus), LineReader::new());
+    #[cfg(feature = "usb-serial")]
+    let (mut adalight, mut tpm2, mut last_stream_ms) = (
+        AdalightReader::<NUM_LEDS>::new(),
+        Tpm2Reader::<NUM_LEDS>::new(),
+        None,
+    );
+    #[cfg(feature = "usb-serial")]
+    let mut packets = PacketReader::new();
+    // Whether a terminal has opened the shell, which echoes and prompts
+    #[cfg(feature = "usb-serial")]
+    let (mut shell, mut reboot_at) = (false, None);
+    // Reboot into the USB bootloader rather than the firmware
+    #[cfg(feature = "usb-serial")]
+    let mut to_bootsel = false;
+    #[cfg(feature = "usb-hid")]
+    let mut hid = HidControl::new(&usb_bus);
+    #[cfg(feature = "usb-midi")]
+    let (mut midi_port, midi) = (UsbMidi::new(&usb_bus), Midi::new());
+    // After the command port, so that stays ttyACM0
+    #[cfg(feature = "usb-log")]
+    let mut log_port = UsbSerial::new(&usb_bus);
+    #[cfg(feature = "usb-msc")]
+    let mut msc = MassStorage::new(
+        &usb_bus,
+        ConfigDrive::new(cortex_m::singleton!(: Sectors = [[0; BLOCK_SIZE]; SECTORS]).unwrap()),
+    );
+    #[cfg(any(
+        feature = "usb-serial",
+        feature = "usb-hid",
+        feature = "usb-midi",
+        feature = "usb-log",
+        feature = "usb-msc"
+    ))]
+    let mut usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x16c0, 0x27dd))
+        .manufacturer("segfault88")
+        .product("pico-display")
+        .serial_number(serial_number)
+        .composite_with_iads()
+        .build();
 
-    // Simple test: all LEDs red
-    let test_color = Rgb::new(255, 0, 0); // Bright red
-    let mut strip = [test_color; NUM_LEDS];
+    // A short press moves on to the next effect and a double press goes back
+    // one; holding the button dims, the next hold brightens again
+    let mut rainbow = RainbowCycle::default();
+    let mut pride = Pride::new();
+    let mut fire = Fire::<NUM_LEDS>::default();
+    let mut pacifica = Pacifica::new();
+    let mut plasma = Plasma::default();
+    let mut solid = Solid::new(Rgb::WHITE);
+    // Demo mode plays its own copies of the animations, each for a while
+    // before crossfading to the next, so the effects chosen otherwise keep
+    // their settings
+    #[cfg(feature = "straps")]
+    let (mut demo_rainbow, mut demo_pride, mut demo_fire, mut demo_pacifica, mut demo_plasma) = (
+        RainbowCycle::default(),
+        Pride::new(),
+        Fire::<NUM_LEDS>::default(),
+        Pacifica::new(),
+        Plasma::default(),
+    );
+    #[cfg(feature = "straps")]
+    let mut demo = straps.demo.then(|| {
+        Playlist::<NUM_LEDS, 5>::new([
+            PlaylistEntry::new(&mut demo_rainbow, 10_000),
+            PlaylistEntry::new(&mut demo_pride, 15_000),
+            PlaylistEntry::new(&mut demo_fire, 20_000),
+            PlaylistEntry::new(&mut demo_pacifica, 20_000),
+            PlaylistEntry::new(&mut demo_plasma, 15_000),
+        ])
+    });
+    #[cfg(feature = "mic")]
+    let (mut vu, mut spectrum) = (VuMeter::new(&audio), Spectrum::new(&audio));
+    // The chase steps, and the pulse breathes once, and both change color,
+    // on the beat
+    #[cfg(any(feature = "mic", feature = "tap-tempo"))]
+    let (mut chase, mut pulse) = (
+        TheaterChase::new(Rgb::RED, 3, 8),
+        Breathe::new(Rgb::RED, 1000).min_level(16),
+    );
+    #[cfg(any(feature = "mic", feature = "tap-tempo"))]
+    let (mut beat_chase, mut beat_pulse) = (
+        BeatSync::new(&audio, &mut chase)
+            .step_ms(125)
+            .colors(&PARTY),
+        BeatSync::new(&audio, &mut pulse)
+            .step_ms(1000)
+            .colors(&PARTY),
+    );
+    #[cfg(feature = "imu")]
+    let mut liquid = Liquid::new(&movement);
+    #[cfg(feature = "sonar")]
+    let proximity = Proximity::new();
+    #[cfg(feature = "sonar")]
+    let (mut reach, mut reach_hue) = (
+        DistanceMeter::new(&proximity, DistanceMode::Pixels),
+        DistanceMeter::new(&proximity, DistanceMode::Hue),
+    );
+    #[cfg(any(feature = "dht22", feature = "ds18b20", feature = "bme280"))]
+    let climate = Climate::new();
+    #[cfg(any(feature = "dht22", feature = "ds18b20", feature = "bme280"))]
+    let (mut thermometer, mut thermometer_bar) = (
+        Thermometer::new(&climate, ThermometerMode::Fill),
+        Thermometer::new(&climate, ThermometerMode::Bar),
+    );
+    #[cfg(feature = "bme280")]
+    let mut weather = Weather::new(&climate);
+    #[cfg(feature = "hall")]
+    let mut pov = PovWheel::new(&rotation);
+    #[cfg(feature = "usb-midi")]
+    let mut note_flash = NoteFlash::new(&midi);
+    #[cfg(feature = "usb-serial")]
+    let host_frame = HostFrame::<NUM_LEDS>::new();
+    #[cfg(feature = "usb-serial")]
+    let mut host = HostPixels::new(&host_frame);
+    // Solid stays last, the touch pad picks it
+    #[cfg_attr(
+        any(
+            feature = "feather-scorpio",
+            feature = "plasma-2040",
+            feature = "rp2040-zero"
+        ),
+        allow(unused_mut)
+    )]
+    let mut effects: [&mut dyn Effect; _] = [
+        &mut rainbow,
+        &mut pride,
+        &mut fire,
+        &mut pacifica,
+        &mut plasma,
+        #[cfg(feature = "mic")]
+        &mut vu,
+        #[cfg(feature = "mic")]
+        &mut spectrum,
+        #[cfg(any(feature = "mic", feature = "tap-tempo"))]
+        &mut beat_chase,
+        #[cfg(any(feature = "mic", feature = "tap-tempo"))]
+        &mut beat_pulse,
+        #[cfg(feature = "imu")]
+        &mut liquid,
+        #[cfg(feature = "sonar")]
+        &mut reach,
+        #[cfg(feature = "sonar")]
+        &mut reach_hue,
+        #[cfg(any(feature = "dht22", feature = "ds18b20", feature = "bme280"))]
+        &mut thermometer,
+        #[cfg(any(feature = "dht22", feature = "ds18b20", feature = "bme280"))]
+        &mut thermometer_bar,
+        #[cfg(feature = "bme280")]
+        &mut weather,
+        #[cfg(feature = "hall")]
+        &mut pov,
+        #[cfg(feature = "usb-midi")]
+        &mut note_flash,
+        #[cfg(feature = "usb-serial")]
+        &mut host,
+        &mut solid,
+    ];
+    // Pixel commands switch to the host's pixels, just before solid
+    #[cfg(feature = "usb-serial")]
+    let host_effect = effects.len() - 2;
+    let mut current = (settings.effect as usize).min(effects.len() - 1);
+    // The drive starts out with the settings the strip came up with, the
+    // saved length rather than a strap's
+    #[cfg(feature = "usb-msc")]
+    msc.disk_mut().format(&ConfigText {
+        brightness: engine.brightness(),
+        effect: current,
+        effects: effects.len(),
+        leds: settings.leds as usize,
+        max_leds: NUM_LEDS,
+        running: &*effects[current],
+    });
+    let mut dimming = false;
+    // The preset last recalled or saved, and whether the knob was turned
+    // during this push of its switch
+    #[cfg(any(
+        feature = "usb-serial",
+        not(any(
+            feature = "feather-scorpio",
+            feature = "plasma-2040",
+            feature = "rp2040-zero"
+        ))
+    ))]
+    let mut preset = None;
+    #[cfg(not(any(
+        feature = "feather-scorpio",
+        feature = "plasma-2040",
+        feature = "rp2040-zero"
+    )))]
+    let (mut knob_down, mut knob_turned) = (false, false);
+    // The effect demo mode took over from; choosing another ends the demo
+    #[cfg(feature = "straps")]
+    let demo_over = current;
+
+    // A touch switches the strip off and on again; a long touch steps the
+    // solid color along TOUCH_COLORS
+    #[cfg(feature = "touch")]
+    let (mut lit_brightness, mut touch_color) = (None, 0);
+
+    // Core 1 starts once nothing's left that writes flash with it running
+    #[cfg(feature = "dual-core")]
+    let mut core1 = Core1::spawn(&mut pac.PSM, &mut pac.PPB, sio.fifo);
 
     loop {
-        // Status LED heartbeat - slower for easier observation
-        led_pin.set_high().unwrap();
-        delay.delay_ms(100);
-        led_pin.set_low().unwrap();
-        delay.delay_ms(100);
+        let now_ms = (timer.get_counter().ticks() / 1000) as u32;
+
+        // Listen just before each frame; this blocks for half an FFT window,
+        // which core 1 can spend rendering. Levels and beats are kept until
+        // the effects can be given them.
+        #[cfg(feature = "mic")]
+        let heard = (engine.ms_until_next_frame(now_ms) == 0).then(|| {
+            let mut samples = [0; FFT_LEN / 2];
+            #[cfg(not(feature = "i2s-mic"))]
+            mic.capture(&mut adc, &mut delay, MIC_SAMPLE_US, &mut samples);
+            #[cfg(feature = "i2s-mic")]
+            mic.capture(&mut samples);
+            samples.iter().for_each(|&s| envelope.push(s));
+            analyzer.push(&samples);
+            let bands = *analyzer.analyze();
+            // Beats from the kick drum, in the lowest bands
+            let bass = bands[..3].iter().copied().max().unwrap_or(0);
+            // A tapped tempo takes over from the music's
+            #[cfg(feature = "tap-tempo")]
+            let beat = beats.update(bass, now_ms) && !tap_tempo.is_set();
+            #[cfg(not(feature = "tap-tempo"))]
+            let beat = beats.update(bass, now_ms);
+            (envelope.update(now_ms), bands, beat)
+        });
+
+        // The effects, and the audio, sensor and host state they read, are
+        // core 1's while it renders; everything from here on touches them
+        #[cfg(feature = "dual-core")]
+        core1.wait();
+
+        #[cfg(feature = "mic")]
+        if let Some((level, bands, beat)) = heard {
+            audio.set_level(level);
+            audio.set_bands(&bands);
+            if beat {
+                audio.beat();
+                audio.set_bpm(beats.bpm());
+            }
+        }
+
+        // In demo mode a short press skips ahead instead
+        #[cfg(feature = "straps")]
+        let pressed = match (button.poll(now_ms), &mut demo) {
+            (Some(ButtonEvent::Short), Some(demo)) => {
+                demo.next();
+                None
+            }
+            (event, _) => event,
+        };
+        #[cfg(not(feature = "straps"))]
+        let pressed = button.poll(now_ms);
+        match pressed {
+            Some(ButtonEvent::Short) => current = (current + 1) % effects.len(),
+            Some(ButtonEvent::Double) => current = (current + effects.len() - 1) % effects.len(),
+            Some(ButtonEvent::Long) => dimming = !dimming,
+            None => {}
+        }
+
+        // Turning the knob sets brightness, or the effect's speed while pushed in
+        #[cfg(not(any(
+            feature = "feather-scorpio",
+            feature = "plasma-2040",
+            feature = "rp2040-zero"
+        )))]
+        {
+            let turned = knob.poll(now_ms);
+            let pushed = knob_switch.poll(now_ms);
+            if knob_switch.is_pressed() && !knob_down {
+                knob_turned = false;
+            }
+            knob_down = knob_switch.is_pressed();
+            let adjust = |level: u8| (level as i16 + turned).clamp(0, 255) as u8;
+            if turned != 0 && knob_down {
+                knob_turned = true;
+                let effect = &mut *effects[current];
+                if let Some(ParamValue::Level(speed)) = effect.param(Param::Speed) {
+                    effect.set_param(Param::Speed, ParamValue::Level(adjust(speed)));
+                }
+            } else if turned != 0 {
+                engine.set_brightness(adjust(engine.brightness()));
+            }
+
+            // Pushing the knob without turning it steps through the saved
+            // presets, a double push going back; holding it saves the look into
+            // the preset showing, or the first
+            match pushed {
+                _ if knob_turned => {}
+                Some(event @ (ButtonEvent::Short | ButtonEvent::Double)) => {
+                    if let Some((slot, saved)) = Preset::step(preset, event == ButtonEvent::Short) {
+                        current = saved.apply(&mut effects);
+                        engine.set_brightness(saved.brightness);
+                        preset = Some(slot);
+                        info!("🎛️ Preset {}", slot);
+                    }
+                }
+                Some(ButtonEvent::Long) => {
+                    let slot = preset.unwrap_or(0);
+                    Preset::capture(engine.brightness(), current, &*effects[current]).save(slot);
+                    preset = Some(slot);
+                    info!("🎛️ Saved preset {}", slot);
+                }
+                None => {}
+            }
+        }
+
+        #[cfg(feature = "touch")]
+        match pad.poll(now_ms) {
+            Some(ButtonEvent::Short | ButtonEvent::Double) => match lit_brightness.take() {
+                Some(level) => engine.set_brightness(level),
+                None => {
+                    lit_brightness = Some(engine.brightness());
+                    engine.set_brightness(0);
+                }
+            },
+            Some(ButtonEvent::Long) => {
+                current = effects.len() - 1;
+                touch_color = (touch_color + 1) % TOUCH_COLORS.len();
+                effects[current]
+                    .set_param(Param::Primary, ParamValue::Color(TOUCH_COLORS[touch_color]));
+            }
+            None => {}
+        }
+
+        #[cfg(feature = "tap-tempo")]
+        {
+            if tap_button.poll(now_ms) == Some(ButtonEvent::Long) {
+                tap_tempo.clear();
+                info!("🥁 Tap tempo cleared");
+            }
+            let down = tap_button.is_pressed();
+            if down && !tap_down {
+                // The press is seen once it's been steady for the debounce time
+                let bpm = tap_tempo.bpm();
+                tap_tempo.tap(now_ms.wrapping_sub(DEBOUNCE_MS));
+                if tap_tempo.bpm() != bpm {
+                    info!("🥁 Tapped {} BPM", tap_tempo.bpm());
+                }
+            }
+            tap_down = down;
+            if tap_tempo.poll(now_ms) {
+                audio.beat();
+                audio.set_bpm(tap_tempo.bpm());
+            }
+        }
+
+        // Tilt and shakes, read once a frame
+        #[cfg(feature = "imu")]
+        if engine.ms_until_next_frame(now_ms) == 0 {
+            if let Some(accel) = imu.as_mut().and_then(|imu| imu.read_accel().ok()) {
+                movement.set_accel(accel);
+                if shake.update(accel, now_ms) {
+                    movement.shake();
+                }
+            }
+        }
+
+        #[cfg(feature = "sonar")]
+        if let Some(distance) = sonar.poll(now_ms) {
+            proximity.set_distance_mm(distance);
+        }
 
-        info!("Sending red to {} LEDs", NUM_LEDS);
+        #[cfg(feature = "dht22")]
+        if dht.due(now_ms) {
+            match dht.read(&mut delay) {
+                Ok(reading) => {
+                    info!("🌡️ Room {}", reading);
+                    // A probe takes over the temperature when there is one
+                    #[cfg(not(feature = "ds18b20"))]
+                    climate.update(reading);
+                    #[cfg(feature = "ds18b20")]
+                    climate.set_humidity_permille(reading.humidity_permille);
+                }
+                Err(e) => warn!("⚠️ DHT22 read failed: {}", e),
+            }
+        }
+
+        #[cfg(feature = "bme280")]
+        if let Some(bme) = bme.as_mut() {
+            if let Some(reading) = bme.due(now_ms).then(|| bme.read()).and_then(Result::ok) {
+                pressure_trend.push(now_ms, reading.pressure_pa);
+                let trend = pressure_trend.pa_per_3h();
+                info!("🌦️ {}, {} Pa/3h", reading, trend);
+                climate.set_temperature_mc(reading.temperature_mc);
+                climate.set_humidity_permille(reading.humidity_permille);
+                climate.set_pressure(reading.pressure_pa, trend);
+            }
+        }
+
+        // The first probe drives the thermometer, the rest are only logged
+        #[cfg(feature = "ds18b20")]
+        if let Some(temperatures) = probes.poll(now_ms, &mut delay) {
+            for (i, mc) in temperatures.iter().enumerate() {
+                match mc {
+                    Some(mc) => info!("🌡️ Probe {}: {}", i, Celsius(*mc)),
+                    None => warn!("⚠️ Probe {} didn't answer", i),
+                }
+            }
+            if let Some(mc) = temperatures[0] {
+                climate.set_temperature_mc(mc);
+            }
+        }
+
+        // The receiver sends a fix once a second; the clock runs on the
+        // timer in between, and without a fix
+        #[cfg(feature = "gps")]
+        while let Ok(byte) = gps.read() {
+            if let Some(fix) = nmea.push(byte) {
+                if !clock.is_set() {
+                    info!("🛰️ GPS time {} {}", fix.date, fix.time);
+                }
+                clock.set(now_ms, fix.date, fix.time);
+            }
+        }
+
+        #[cfg(any(
+            feature = "usb-serial",
+            feature = "usb-hid",
+            feature = "usb-midi",
+            feature = "usb-log",
+            feature = "usb-msc"
+        ))]
+        #[cfg_attr(
+            not(any(
+                feature = "usb-serial",
+                feature = "usb-hid",
+                feature = "usb-midi",
+                feature = "usb-log"
+            )),
+            allow(unused_variables)
+        )]
+        let usb_event = usb_dev.poll(&mut [
+            #[cfg(feature = "usb-serial")]
+            &mut serial,
+            #[cfg(feature = "usb-hid")]
+            &mut hid,
+            #[cfg(feature = "usb-midi")]
+            &mut midi_port,
+            #[cfg(feature = "usb-log")]
+            &mut log_port,
+            #[cfg(feature = "usb-msc")]
+            &mut msc,
+        ]);
 
-        // Send colors to LED strip
-        for led in &strip {
-            let grb_data = led.to_grb24();
-            info!("Sending GRB data: 0x{:06X}", grb_data);
-            
-            // Send 24-bit color data to PIO (will block if FIFO is full)
-            while !tx.write(grb_data) {
-                cortex_m::asm::nop();
+        // Logs wait in their buffer until a terminal opens the port
+        #[cfg(feature = "usb-log")]
+        {
+            // The host has nothing to say on it; keep the endpoint clear
+            if usb_event {
+                log_port.read(&mut [0; MAX_PACKET]);
+            }
+            if log_port.dtr() {
+                usb_log::drain(|bytes| log_port.write(bytes));
             }
         }
 
-        // Important: Add reset delay for WS2812 (>50μs)
-        delay.delay_ms(1);
+        // Adalight and TPM2 frames go straight on the strip, binary packets
+        // are each answered with an ACK or NACK, and anything else is command
+        // lines, each answered with "ok" or "err" and why
+        #[cfg(feature = "usb-serial")]
+        if usb_event {
+            // The terminal went away
+            if !serial.dtr() {
+                shell = false;
+            }
+            let mut packet = [0; MAX_PACKET];
+            let count = serial.read(&mut packet);
+            for &byte in &packet[..count] {
+                if !tpm2.in_frame() && !adalight.in_frame() {
+                    let (seq, reply) = match packets.push(byte, now_ms) {
+                        PacketEvent::Pass => (0, None),
+                        PacketEvent::Taken => {
+                            lines.clear();
+                            continue;
+                        }
+                        PacketEvent::Packet(Err((seq, nack))) => (seq, Some(Reply::Nack(nack))),
+                        PacketEvent::Packet(Ok(packet)) => {
+                            let reply = match packet.request {
+                                Request::Ping => Reply::Ack,
+                                Request::Frame { start, pixels } => {
+                                    let start = start as usize;
+                                    if start + pixels.len() / 3 > engine.leds() {
+                                        Reply::Nack(Nack::OutOfRange)
+                                    } else {
+                                        for (i, rgb) in pixels.chunks_exact(3).enumerate() {
+                                            host_frame
+                                                .set(start + i, Rgb::new(rgb[0], rgb[1], rgb[2]));
+                                        }
+                                        if last_stream_ms.is_none_or(|ms: u32| {
+                                            now_ms.wrapping_sub(ms) > STREAM_IDLE_MS
+                                        }) {
+                                            current = host_effect;
+                                        }
+                                        last_stream_ms = Some(now_ms);
+                                        Reply::Ack
+                                    }
+                                }
+                                Request::SetBrightness(level) => {
+                                    engine.set_brightness(level);
+                                    Reply::Ack
+                                }
+                                Request::SetEffect(index) if (index as usize) < effects.len() => {
+                                    current = index as usize;
+                                    Reply::Ack
+                                }
+                                Request::SetEffect(_) => Reply::Nack(Nack::OutOfRange),
+                                Request::GetStatus => Reply::Status(Status {
+                                    effect: current as u8,
+                                    effects: effects.len() as u8,
+                                    brightness: engine.brightness(),
+                                    leds: engine.leds() as u16,
+                                }),
+                            };
+                            (packet.seq, Some(reply))
+                        }
+                    };
+                    if let Some(reply) = reply {
+                        let mut frame = [0; MAX_REPLY];
+                        let len = encode_reply(seq, &reply, &mut frame);
+                        serial.write(&frame[..len]);
+                        lines.clear();
+                        continue;
+                    }
+                }
+
+                // Whichever protocol is mid-frame gets the byte to itself
+                let event = if tpm2.in_frame() {
+                    tpm2.push(byte)
+                } else {
+                    match adalight.push(byte) {
+                        StreamEvent::Pass => tpm2.push(byte),
+                        event => event,
+                    }
+                };
+                match event {
+                    StreamEvent::Pass => {}
+                    StreamEvent::Taken => {
+                        lines.clear();
+                        continue;
+                    }
+                    StreamEvent::Frame(pixels) => {
+                        host_frame.copy_from(pixels);
+                        // Switch to the stream when it starts, but leave the
+                        // button free to move off it
+                        if last_stream_ms
+                            .is_none_or(|ms: u32| now_ms.wrapping_sub(ms) > STREAM_IDLE_MS)
+                        {
+                            current = host_effect;
+                        }
+                        last_stream_ms = Some(now_ms);
+                        continue;
+                    }
+                }
+                // A terminal shows only what comes back
+                if shell {
+                    match byte {
+                        0x08 | 0x7F if !lines.is_empty() => {
+                            serial.write(b"\x08 \x08");
+                        }
+                        0x20..=0x7E => {
+                            serial.write(&[byte]);
+                        }
+                        _ => {}
+                    }
+                }
+                let Some(line) = lines.push(byte) else {
+                    continue;
+                };
+                if shell {
+                    serial.write(b"\r\n");
+                }
+                // A blank line, Enter in a terminal, opens the shell
+                if let Ok([]) = line {
+                    if !shell {
+                        shell = true;
+                        serial.write(b"pico-display shell, help for commands\r\n");
+                    }
+                    serial.write(b"> ");
+                    continue;
+                }
+                // A line that starts with a brace is a settings document,
+                // answered with the settings after it's applied
+                if let Ok(doc @ [b'{', ..]) = line {
+                    let result = ConfigUpdate::parse(doc).and_then(|update| {
+                        // Check everything before changing anything
+                        let effect = update.check(&effects, current, NUM_LEDS)?;
+                        if let Some(level) = update.brightness {
+                            engine.set_brightness(level);
+                        }
+                        if let Some(leds) = update.leds {
+                            engine.set_leds(leds);
+                            settings.leds = leds as u16;
+                        }
+                        if let Some(order) = update.color_order {
+                            settings.color_order = order;
+                        }
+                        settings.save();
+                        current = effect;
+                        for (param, value) in update.params() {
+                            effects[current].set_param(param, value);
+                        }
+                        Ok(())
+                    });
+                    let _ = match result {
+                        Ok(()) => core::write!(
+                            serial,
+                            "{}\r\n",
+                            ConfigDoc {
+                                brightness: engine.brightness(),
+                                effect: current,
+                                effects: effects.len(),
+                                leds: engine.leds(),
+                                color_order: settings.color_order,
+                                fps: FPS,
+                                running: &*effects[current],
+                            }
+                        ),
+                        Err(e) => core::write!(serial, "{{\"error\":\"{}\"}}\r\n", e.message()),
+                    };
+                    if shell {
+                        serial.write(b"> ");
+                    }
+                    continue;
+                }
+                let result = line.and_then(Command::parse).and_then(|command| {
+                    match command {
+                        Command::Pixel { index, color } => {
+                            if !host_frame.set(index, color) {
+                                return Err(CommandError::OutOfRange);
+                            }
+                            current = host_effect;
+                        }
+                        Command::Fill(color) => {
+                            host_frame.fill(color);
+                            current = host_effect;
+                        }
+                        Command::Brightness(level) => engine.set_brightness(level),
+                        Command::Effect(index) if index < effects.len() => current = index,
+                        Command::Effect(_) => return Err(CommandError::OutOfRange),
+                        Command::Set { param, value } => {
+                            if !effects[current].set_param(param, value) {
+                                return Err(CommandError::NoSuchParam);
+                            }
+                        }
+                        // The strip's length is kept straight away, it
+                        // goes with the wiring rather than the look
+                        Command::Leds(leds) if (1..=NUM_LEDS).contains(&leds) => {
+                            engine.set_leds(leds);
+                            settings.leds = leds as u16;
+                            settings.save();
+                        }
+                        Command::Leds(_) => return Err(CommandError::OutOfRange),
+                        Command::Order(order) => {
+                            settings.color_order = order;
+                            settings.save();
+                        }
+                        Command::Calibrate(multipliers) => {
+                            settings.calibration = multipliers;
+                            settings.save();
+                        }
+                        Command::Save { preset: None } => {
+                            settings.brightness = engine.brightness();
+                            settings.effect = current as u8;
+                            settings.save();
+                        }
+                        Command::Save { preset: Some(slot) } if slot < PRESETS => {
+                            Preset::capture(engine.brightness(), current, &*effects[current])
+                                .save(slot);
+                            preset = Some(slot);
+                        }
+                        Command::Preset(slot) if slot < PRESETS => {
+                            let saved = Preset::load(slot).ok_or(CommandError::EmptyPreset)?;
+                            current = saved.apply(&mut effects);
+                            engine.set_brightness(saved.brightness);
+                            preset = Some(slot);
+                        }
+                        Command::Save { .. } | Command::Preset(_) => {
+                            return Err(CommandError::OutOfRange)
+                        }
+                        Command::Reboot => reboot_at = Some(now_ms),
+                        Command::Bootsel { pin } if Some(pin) == BOOTSEL_PIN => {
+                            reboot_at = Some(now_ms);
+                            to_bootsel = true;
+                        }
+                        Command::Bootsel { .. } => return Err(CommandError::Denied),
+                        Command::Quit => shell = false,
+                        Command::Help => {
+                            serial.write(HELP.as_bytes());
+                        }
+                        Command::Status => {
+                            let _ = core::write!(
+                                serial,
+                                "effect {} of {}, brightness {}, dimmer {}, {} leds at {} fps\r\n",
+                                current,
+                                effects.len(),
+                                engine.brightness(),
+                                engine.dimmer(),
+                                engine.leds(),
+                                FPS
+                            );
+                        }
+                    }
+                    Ok(())
+                });
+                let _ = match result {
+                    Ok(()) => core::write!(serial, "ok\r\n"),
+                    Err(e) => core::write!(serial, "err {}\r\n", e.message()),
+                };
+                if shell {
+                    serial.write(b"> ");
+                }
+            }
+        }
+        #[cfg(feature = "usb-serial")]
+        if reboot_at.is_some_and(|at: u32| now_ms.wrapping_sub(at) > REBOOT_DELAY_MS) {
+            if to_bootsel {
+                info!("Rebooting to BOOTSEL");
+                // The ROM doesn't return; the status LED shows mass-storage
+                // activity, and both the drive and PICOBOOT are offered, as
+                // after holding the button
+                bsp::hal::rom_data::reset_to_usb_boot(STATUS_LED_MASK, 0);
+            }
+            cortex_m::peripheral::SCB::sys_reset();
+        }
+
+        // Notes flash zones of the strip; the volume fader, mod wheel and a
+        // knob set brightness, hue and the running effect's speed
+        #[cfg(feature = "usb-midi")]
+        if usb_event {
+            while let Some(message) = midi_port.read() {
+                match message {
+                    MidiMessage::NoteOn { note, velocity, .. } => midi.strike(note, velocity),
+                    MidiMessage::ControlChange { control, value, .. } => {
+                        // 0-127 stretched to 0-255
+                        let level = value << 1 | value >> 6;
+                        match control {
+                            CC_BRIGHTNESS => engine.set_brightness(level),
+                            CC_HUE => midi.set_hue(value),
+                            CC_SPEED => {
+                                effects[current].set_param(Param::Speed, ParamValue::Level(level));
+                            }
+                            _ => {}
+                        }
+                    }
+                    MidiMessage::NoteOff { .. } => {}
+                }
+            }
+        }
+
+        // Feature reports the host wrote, then what it reads back next
+        #[cfg(feature = "usb-hid")]
+        {
+            if usb_event {
+                while let Some(request) = hid.take_request() {
+                    match request {
+                        HidRequest::Brightness(level) => engine.set_brightness(level),
+                        HidRequest::Effect(index) if (index as usize) < effects.len() => {
+                            current = index as usize
+                        }
+                        HidRequest::Effect(_) => {}
+                        HidRequest::Color(color) => {
+                            effects[current].set_param(Param::Primary, ParamValue::Color(color));
+                        }
+                    }
+                }
+            }
+            let color = match effects[current].param(Param::Primary) {
+                Some(ParamValue::Color(color)) => color,
+                _ => Rgb::BLACK,
+            };
+            hid.set_status(HidStatus {
+                brightness: engine.brightness(),
+                effect: current as u8,
+                effects: effects.len() as u8,
+                color,
+            });
+        }
 
-        delay.delay_ms(1000); // Wait 1 second between updates
+        // config.txt as the host saved it, applied all or nothing and kept
+        // for the next power-up; after an eject the drive's written afresh,
+        // for the next time it's mounted
+        #[cfg(feature = "usb-msc")]
+        if let Some(text) = msc.disk_mut().poll(now_ms) {
+            match config_drive::parse(text) {
+                Ok(update) => {
+                    // Check everything before changing anything, as for a
+                    // JSON document
+                    match update.check(&effects, current, NUM_LEDS) {
+                        Ok(effect) => {
+                            if let Some(level) = update.brightness {
+                                engine.set_brightness(level);
+                            }
+                            if let Some(leds) = update.leds {
+                                engine.set_leds(leds);
+                                settings.leds = leds as u16;
+                            }
+                            current = effect;
+                            for (param, value) in update.params() {
+                                effects[current].set_param(param, value);
+                            }
+                            settings.brightness = engine.brightness();
+                            settings.effect = current as u8;
+                            settings.save();
+                            info!("💾 Applied config.txt");
+                        }
+                        Err(e) => warn!("⚠️ config.txt not applied: {}", e.message()),
+                    }
+                }
+                Err(e) => warn!("⚠️ config.txt line {}: {}", e.line, e.error.message()),
+            }
+            if !msc.disk().is_mounted() {
+                msc.disk_mut().format(&ConfigText {
+                    brightness: engine.brightness(),
+                    effect: current,
+                    effects: effects.len(),
+                    leds: settings.leds as usize,
+                    max_leds: NUM_LEDS,
+                    running: &*effects[current],
+                });
+            }
+        }
+
+        #[cfg(feature = "pot")]
+        if let Some(level) = pot.read(&mut adc) {
+            POT_TARGET.apply(level, &mut engine, &mut *effects[current]);
+        }
+
+        // Sensors dim the strip between them, underneath the brightness setting
+        if thermal.due(now_ms) {
+            if let Some(state) = die.read(&mut adc).and_then(|mc| thermal.update(mc)) {
+                let mc = thermal.temperature().unwrap_or(0);
+                info!("🌡️ {} at {}.{} °C", state, mc / 1000, mc % 1000 / 100);
+            }
+        }
+        let dimmer = thermal.level();
+        #[cfg(feature = "pir")]
+        let dimmer = scale8(dimmer, pir.poll(now_ms));
+        #[cfg(feature = "ambient")]
+        let dimmer = {
+            if auto_brightness.due(now_ms) {
+                if let Some(light) = ldr.read(&mut adc) {
+                    auto_brightness.update(light);
+                }
+            }
+            scale8(dimmer, auto_brightness.level())
+        };
+        engine.set_dimmer(dimmer);
+
+        // Where the strip is in its turn, as late as possible before drawing
+        #[cfg(feature = "hall")]
+        {
+            let timing = critical_section::with(|cs| ROTATION.borrow(cs).get());
+            rotation.update(&timing, timer.get_counter_low());
+            if (now_ms.wrapping_sub(next_rpm_log_ms) as i32) >= 0 && rotation.rpm() != 0 {
+                next_rpm_log_ms = now_ms.wrapping_add(1000);
+                info!("🌀 {} RPM, {} turns", rotation.rpm(), timing.revolutions());
+            }
+        }
+
+        #[cfg(feature = "straps")]
+        if current != demo_over {
+            demo = None;
+        }
+        #[cfg(feature = "straps")]
+        let effect: &mut dyn Effect = match &mut demo {
+            Some(demo) => demo,
+            None => &mut *effects[current],
+        };
+        #[cfg(not(feature = "straps"))]
+        let effect = &mut *effects[current];
+
+        #[cfg(not(feature = "dual-core"))]
+        let ticked = engine.tick(effect, now_ms);
+        // Safety: the loop waits on core 1 before touching the effect or what
+        // it reads again, and neither the effects nor the engine move
+        #[cfg(feature = "dual-core")]
+        let ticked = unsafe { engine.tick_on(&mut core1, effect, now_ms) };
+        if ticked && button.is_held() {
+            let level = engine.brightness();
+            engine.set_brightness(if dimming {
+                level.saturating_sub(3).max(8)
+            } else {
+                level.saturating_add(3)
+            });
+        }
+
+        // Status LED heartbeat, 1 Hz
+        #[cfg(not(any(feature = "pico-w", feature = "rp2040-zero", feature = "plasma-2040")))]
+        if now_ms % 1000 < 100 {
+            led_pin.set_high().unwrap();
+        } else {
+            led_pin.set_low().unwrap();
+        }
+
+        // Come round at least every millisecond to keep up with the encoder
+        delay.delay_ms(engine.ms_until_next_frame(now_ms).min(1));
     }
 }
+
+/// Time a pass of the magnet
+#[cfg(feature = "hall")]
+#[interrupt]
+fn IO_IRQ_BANK0() {
+    critical_section::with(|cs| {
+        if let Some((hall, timer)) = HALL.borrow_ref_mut(cs).as_mut() {
+            hall.clear_interrupt(EdgeLow);
+            let timing = ROTATION.borrow(cs);
+            let mut marked = timing.get();
+            marked.mark(timer.get_counter_low());
+            timing.set(marked);
+        }
+    });
+}
+
+/// Refill the WS2812 FIFO from the pending frame
+#[cfg(feature = "irq-fifo")]
+#[interrupt]
+fn PIO0_IRQ_0() {
+    critical_section::with(|cs| {
+        if let Some(ws) = STRIP.borrow_ref_mut(cs).as_mut() {
+            ws.on_interrupt();
+        }
+    });
+}
@@ -0,0 +1,138 @@
+// Integer math for effects: saturating ops, scaling and table-based trig
+//
+// Everything here is integer-only, the Cortex-M0+ has no FPU.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// One full sine period over 256 steps, centered on 128
+const SIN8: [u8; 256] = [
+    128, 131, 134, 137, 140, 144, 147, 150, 153, 156, 159, 162, 165, 168, 171, 174, 177, 179, 182,
+    185, 188, 191, 193, 196, 199, 201, 204, 206, 209, 211, 213, 216, 218, 220, 222, 224, 226, 228,
+    230, 232, 234, 235, 237, 239, 240, 241, 243, 244, 245, 246, 248, 249, 250, 250, 251, 252, 253,
+    253, 254, 254, 254, 255, 255, 255, 255, 255, 255, 255, 254, 254, 254, 253, 253, 252, 251, 250,
+    250, 249, 248, 246, 245, 244, 243, 241, 240, 239, 237, 235, 234, 232, 230, 228, 226, 224, 222,
+    220, 218, 216, 213, 211, 209, 206, 204, 201, 199, 196, 193, 191, 188, 185, 182, 179, 177, 174,
+    171, 168, 165, 162, 159, 156, 153, 150, 147, 144, 140, 137, 134, 131, 128, 125, 122, 119, 116,
+    112, 109, 106, 103, 100, 97, 94, 91, 88, 85, 82, 79, 77, 74, 71, 68, 65, 63, 60, 57, 55, 52,
+    50, 47, 45, 43, 40, 38, 36, 34, 32, 30, 28, 26, 24, 22, 21, 19, 17, 16, 15, 13, 12, 11, 10, 8,
+    7, 6, 6, 5, 4, 3, 3, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 3, 3, 4, 5, 6, 6, 7, 8, 10, 11, 12,
+    13, 15, 16, 17, 19, 21, 22, 24, 26, 28, 30, 32, 34, 36, 38, 40, 43, 45, 47, 50, 52, 55, 57, 60,
+    63, 65, 68, 71, 74, 77, 79, 82, 85, 88, 91, 94, 97, 100, 103, 106, 109, 112, 116, 119, 122,
+    125,
+];
+
+/// Scale `c` by `level`/256, 255 leaving it unchanged
+pub fn scale8(c: u8, level: u8) -> u8 {
+    ((c as u16 * (level as u16 + 1)) >> 8) as u8
+}
+
+/// Add, saturating at 255
+pub fn qadd8(a: u8, b: u8) -> u8 {
+    a.saturating_add(b)
+}
+
+/// Subtract, saturating at 0
+pub fn qsub8(a: u8, b: u8) -> u8 {
+    a.saturating_sub(b)
+}
+
+/// Sine of `theta` (256 steps per turn), 0-255 with 128 at zero crossings
+pub fn sin8(theta: u8) -> u8 {
+    SIN8[theta as usize]
+}
+
+/// Cosine to match [`sin8`]
+pub fn cos8(theta: u8) -> u8 {
+    sin8(theta.wrapping_add(64))
+}
+
+/// Sawtooth rising 0-255 `bpm` times a minute at time `t_ms`
+pub fn beat8(bpm: u8, t_ms: u32) -> u8 {
+    (t_ms as u64 * bpm as u64 * 256 / 60_000) as u8
+}
+
+/// Sine wave between `low` and `high`, `bpm` times a minute at time `t_ms`
+pub fn beatsin8(bpm: u8, low: u8, high: u8, t_ms: u32) -> u8 {
+    let range = high.saturating_sub(low);
+    low + scale8(sin8(beat8(bpm, t_ms)), range)
+}
+
+/// Sawtooth rising 0-65535 `bpm` times a minute at time `t_ms`
+pub fn beat16(bpm: u16, t_ms: u32) -> u16 {
+    (t_ms as u64 * bpm as u64 * 65_536 / 60_000) as u16
+}
+
+/// Sine wave between `low` and `high` like [`beatsin8`], for wider ranges
+pub fn beatsin16(bpm: u16, low: u16, high: u16, t_ms: u32) -> u16 {
+    let range = high.saturating_sub(low) as u32;
+    let s = sin8((beat16(bpm, t_ms) >> 8) as u8) as u32;
+    low + ((s * (range + 1)) >> 8) as u16
+}
+
+/// [`beat16`] with `bpm88` in 8.8 fixed point, for beats slower than one a
+/// minute
+pub fn beat88(bpm88: u16, t_ms: u32) -> u16 {
+    (t_ms as u64 * bpm88 as u64 * 256 / 60_000) as u16
+}
+
+/// [`beatsin16`] with an 8.8 fixed-point `bpm88`
+pub fn beatsin88(bpm88: u16, low: u16, high: u16, t_ms: u32) -> u16 {
+    let range = high.saturating_sub(low) as u32;
+    let s = sin8((beat88(bpm88, t_ms) >> 8) as u8) as u32;
+    low + ((s * (range + 1)) >> 8) as u16
+}
+
+/// Triangle wave over 256 steps, 0 at the ends and 254 in the middle
+pub fn triwave8(theta: u8) -> u8 {
+    let t = if theta & 0x80 != 0 {
+        255 - theta
+    } else {
+        theta
+    };
+    t << 1
+}
+
+/// Mixed into every [`Rng::seeded`]; 0 until it's set at boot
+static BOARD_SEED: AtomicU32 = AtomicU32::new(0);
+
+/// Small xorshift PRNG for effects; not for anything that needs real randomness
+#[derive(Copy, Clone, Debug)]
+pub struct Rng(u32);
+
+impl Rng {
+    /// `seed` of 0 is replaced, xorshift would get stuck there
+    pub const fn new(seed: u32) -> Self {
+        Self(if seed == 0 { 0x2545_F491 } else { seed })
+    }
+
+    /// Seeded from `salt`, different for each effect, and the board's seed,
+    /// so two boards side by side don't flicker in step
+    pub fn seeded(salt: u32) -> Self {
+        Self::new(salt ^ BOARD_SEED.load(Ordering::Relaxed))
+    }
+
+    /// Set the seed that's mixed in, before the effects are made; one made
+    /// earlier keeps its sequence
+    pub fn set_board_seed(seed: u32) {
+        BOARD_SEED.store(seed, Ordering::Relaxed);
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    pub fn random8(&mut self) -> u8 {
+        (self.next_u32() >> 24) as u8
+    }
+
+    /// Random value in `low..high`, or `low` if the range is empty
+    pub fn range8(&mut self, low: u8, high: u8) -> u8 {
+        let span = high.saturating_sub(low) as u16;
+        low + ((self.random8() as u16 * span) >> 8) as u8
+    }
+}
@@ -0,0 +1,469 @@
+// mDNS and DNS-SD responder, so apps find the strip as
+// pico-display-XXXX.local without knowing its address
+
+use crate::ddp::DDP_PORT;
+
+/// UDP port and multicast group mDNS runs on; replies go to the group
+pub const MDNS_PORT: u16 = 5353;
+pub const MDNS_ADDRESS: [u8; 4] = [224, 0, 0, 251];
+
+/// Big enough for an announcement of every service
+pub const MAX_RESPONSE: usize = 512;
+
+/// Services this many or fewer can be advertised
+pub const MAX_SERVICES: usize = 7;
+
+/// "pico-display-" and four hex digits
+pub const HOSTNAME_LEN: usize = 17;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+/// Records only this device has, which caches replace rather than add to
+const CACHE_FLUSH: u16 = 0x8000;
+
+/// Times to live, as RFC 6762 recommends for host and other records
+const HOST_TTL: u32 = 120;
+const OTHER_TTL: u32 = 4500;
+
+const ENUMERATION: &str = "_services._dns-sd._udp";
+
+/// A DNS-SD service offered on the device
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Service {
+    /// Service and protocol, e.g. `_http._tcp`
+    pub kind: &'static str,
+    pub port: u16,
+    /// TXT record strings, `key=value`
+    pub txt: &'static [&'static str],
+}
+
+/// The web UI and WLED API, and the DDP receiver
+pub const SERVICES: [Service; 3] = [
+    Service {
+        kind: "_http._tcp",
+        port: 80,
+        txt: &["path=/"],
+    },
+    // WLED apps look for this one
+    Service {
+        kind: "_wled._tcp",
+        port: 80,
+        txt: &[],
+    },
+    Service {
+        kind: "_ddp._udp",
+        port: DDP_PORT,
+        txt: &[],
+    },
+];
+
+/// The device's host name, `pico-display-` and four hex digits of
+/// something unique to the board, so several on one network don't clash
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Hostname([u8; HOSTNAME_LEN]);
+
+impl Hostname {
+    pub fn new(id: u16) -> Self {
+        let mut name = *b"pico-display-0000";
+        for (i, digit) in name[13..].iter_mut().enumerate() {
+            let nibble = (id >> (12 - 4 * i)) as u8 & 0x0F;
+            *digit = b"0123456789abcdef"[nibble as usize];
+        }
+        Self(name)
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.0).unwrap_or("pico-display")
+    }
+}
+
+/// One of the records the responder has, by number: the host's address
+/// first, then four for each service
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Record {
+    Address,
+    /// The service's type pointing at this device's instance of it
+    Pointer(usize),
+    Server(usize),
+    Text(usize),
+    /// `_services._dns-sd._udp` pointing at the service type
+    Enumeration(usize),
+}
+
+impl Record {
+    fn bit(self) -> u32 {
+        match self {
+            Record::Address => 1,
+            Record::Pointer(i) => 1 << (1 + 4 * i),
+            Record::Server(i) => 1 << (2 + 4 * i),
+            Record::Text(i) => 1 << (3 + 4 * i),
+            Record::Enumeration(i) => 1 << (4 + 4 * i),
+        }
+    }
+}
+
+/// Answers mDNS queries for the host name and DNS-SD queries for the
+/// services
+///
+/// Replies are multicast to [`MDNS_ADDRESS`]; queries from ports other
+/// than 5353 (one-shot "legacy" resolvers wanting a unicast reply) are
+/// answered the same way, which most of them miss. On joining the network,
+/// send [`announce`](Self::announce) a couple of times, a second apart.
+/// Known-answer suppression and probing for name conflicts are left out;
+/// with the board's ID in the name, two devices clashing is unlikely.
+///
+/// ```ignore
+/// let mut mdns = MdnsResponder::new(Hostname::new(identity.short_id()), &SERVICES);
+/// mdns.ip = ip;
+/// if let Some(len) = mdns.respond(datagram, &mut reply) {
+///     send_to(MDNS_ADDRESS, MDNS_PORT, &reply[..len]);
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct MdnsResponder {
+    pub hostname: Hostname,
+    /// The device's address, to be filled in once the network is up
+    pub ip: [u8; 4],
+    pub services: &'static [Service],
+}
+
+impl MdnsResponder {
+    pub fn new(hostname: Hostname, services: &'static [Service]) -> Self {
+        Self {
+            hostname,
+            ip: [0; 4],
+            services: &services[..services.len().min(MAX_SERVICES)],
+        }
+    }
+
+    /// The reply to a query, if it asks about anything here
+    pub fn respond(&self, query: &[u8], out: &mut [u8; MAX_RESPONSE]) -> Option<usize> {
+        let header = query.get(..12)?;
+        // Responses, and queries with an opcode, aren't for answering
+        if header[2] & 0xF8 != 0 {
+            return None;
+        }
+        let questions = u16::from_be_bytes([header[4], header[5]]);
+
+        let mut answers = 0;
+        let mut pos = 12;
+        for _ in 0..questions {
+            let name = pos;
+            pos = skip_name(query, pos)?;
+            let fields = query.get(pos..pos + 4)?;
+            pos += 4;
+            let kind = u16::from_be_bytes([fields[0], fields[1]]);
+            // The top bit asks for a unicast reply, which isn't given
+            if u16::from_be_bytes([fields[2], fields[3]]) & !CACHE_FLUSH != CLASS_IN {
+                continue;
+            }
+            answers |= self.answers(query, name, kind);
+        }
+        if answers == 0 {
+            return None;
+        }
+
+        // What a resolver would ask for next goes along as additional records
+        let mut additional = 0;
+        for i in 0..self.services.len() {
+            if answers & Record::Pointer(i).bit() != 0 {
+                additional |= Record::Server(i).bit() | Record::Text(i).bit();
+            }
+        }
+        if (answers | additional) & !(Record::Address.bit()) != 0 {
+            additional |= Record::Address.bit();
+        }
+        self.write(answers, additional & !answers, out)
+    }
+
+    /// An unsolicited reply with every record, for telling the network the
+    /// device is here
+    pub fn announce(&self, out: &mut [u8; MAX_RESPONSE]) -> Option<usize> {
+        self.write(u32::MAX >> (31 - 4 * self.services.len()), 0, out)
+    }
+
+    /// The records a question about the name at `name` wants
+    fn answers(&self, query: &[u8], name: usize, kind: u16) -> u32 {
+        let wants = |record_type| kind == record_type || kind == TYPE_ANY;
+        let host = self.hostname.as_str();
+        let mut answers = 0;
+        if wants(TYPE_A) && name_is(query, name, &[host, "local"]) {
+            answers |= Record::Address.bit();
+        }
+        for (i, service) in self.services.iter().enumerate() {
+            if wants(TYPE_PTR) && name_is(query, name, &[service.kind, "local"]) {
+                answers |= Record::Pointer(i).bit();
+            }
+            if wants(TYPE_PTR) && name_is(query, name, &[ENUMERATION, "local"]) {
+                answers |= Record::Enumeration(i).bit();
+            }
+            if name_is(query, name, &[host, service.kind, "local"]) {
+                if wants(TYPE_SRV) {
+                    answers |= Record::Server(i).bit();
+                }
+                if wants(TYPE_TXT) {
+                    answers |= Record::Text(i).bit();
+                }
+            }
+        }
+        answers
+    }
+
+    fn write(&self, answers: u32, additional: u32, out: &mut [u8; MAX_RESPONSE]) -> Option<usize> {
+        let mut w = Writer {
+            out,
+            len: 12,
+            host: self.hostname.as_str(),
+            services: self.services,
+            names: Names::default(),
+        };
+        w.out[..12].fill(0);
+        // A response, authoritative
+        w.out[2] = 0x84;
+        w.out[6..8].copy_from_slice(&(answers.count_ones() as u16).to_be_bytes());
+        w.out[10..12].copy_from_slice(&(additional.count_ones() as u16).to_be_bytes());
+        for set in [answers, additional] {
+            if set & Record::Address.bit() != 0 {
+                self.write_record(&mut w, Record::Address)?;
+            }
+            for i in 0..self.services.len() {
+                for record in [
+                    Record::Pointer(i),
+                    Record::Server(i),
+                    Record::Text(i),
+                    Record::Enumeration(i),
+                ] {
+                    if set & record.bit() != 0 {
+                        self.write_record(&mut w, record)?;
+                    }
+                }
+            }
+        }
+        Some(w.len)
+    }
+
+    fn write_record(&self, w: &mut Writer, record: Record) -> Option<()> {
+        let (kind, ttl) = match record {
+            Record::Address => {
+                w.host()?;
+                (TYPE_A, HOST_TTL)
+            }
+            Record::Pointer(i) => {
+                w.kind(i)?;
+                (TYPE_PTR, OTHER_TTL)
+            }
+            Record::Enumeration(_) => {
+                w.enumeration()?;
+                (TYPE_PTR, OTHER_TTL)
+            }
+            Record::Server(i) => {
+                w.instance(i)?;
+                (TYPE_SRV, HOST_TTL)
+            }
+            Record::Text(i) => {
+                w.instance(i)?;
+                (TYPE_TXT, OTHER_TTL)
+            }
+        };
+        // Pointers are shared between devices offering the service, so
+        // they don't flush caches
+        let class = match record {
+            Record::Pointer(_) | Record::Enumeration(_) => CLASS_IN,
+            _ => CLASS_IN | CACHE_FLUSH,
+        };
+        w.bytes(&kind.to_be_bytes())?;
+        w.bytes(&class.to_be_bytes())?;
+        w.bytes(&ttl.to_be_bytes())?;
+
+        let length_at = w.len;
+        w.bytes(&[0, 0])?;
+        match record {
+            Record::Address => w.bytes(&self.ip)?,
+            Record::Pointer(i) => w.instance(i)?,
+            Record::Enumeration(i) => w.kind(i)?,
+            Record::Server(i) => {
+                // Priority and weight 0, then the port and the host
+                w.bytes(&[0, 0, 0, 0])?;
+                w.bytes(&self.services[i].port.to_be_bytes())?;
+                w.host()?;
+            }
+            Record::Text(i) => {
+                // An empty TXT record is one empty string
+                if self.services[i].txt.is_empty() {
+                    w.bytes(&[0])?;
+                }
+                for entry in self.services[i].txt {
+                    w.bytes(&[entry.len().min(255) as u8])?;
+                    w.bytes(&entry.as_bytes()[..entry.len().min(255)])?;
+                }
+            }
+        }
+        let length = (w.len - length_at - 2) as u16;
+        w.out[length_at..length_at + 2].copy_from_slice(&length.to_be_bytes());
+        Some(())
+    }
+}
+
+/// Where names were first written, to point back to
+#[derive(Default)]
+struct Names {
+    local: Option<usize>,
+    host: Option<usize>,
+    enumeration: Option<usize>,
+    kinds: [Option<usize>; MAX_SERVICES],
+    instances: [Option<usize>; MAX_SERVICES],
+}
+
+/// Writes records, compressing names: each is written once and pointed
+/// back to after, which keeps an announcement well inside one packet
+struct Writer<'a> {
+    out: &'a mut [u8; MAX_RESPONSE],
+    len: usize,
+    host: &'a str,
+    services: &'a [Service],
+    names: Names,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, bytes: &[u8]) -> Option<()> {
+        let end = self.len + bytes.len();
+        self.out.get_mut(self.len..end)?.copy_from_slice(bytes);
+        self.len = end;
+        Some(())
+    }
+
+    fn label(&mut self, label: &str) -> Option<()> {
+        let len = label.len().min(63);
+        self.bytes(&[len as u8])?;
+        self.bytes(&label.as_bytes()[..len])
+    }
+
+    /// A pointer to the name at `at` if there's one, else note that the
+    /// name starts here; true if the pointer ended the name
+    fn point(&mut self, at: fn(&mut Names) -> &mut Option<usize>) -> Option<bool> {
+        let here = self.len;
+        match *at(&mut self.names) {
+            Some(offset) => {
+                self.bytes(&(0xC000 | offset as u16).to_be_bytes())?;
+                Some(true)
+            }
+            None => {
+                *at(&mut self.names) = Some(here);
+                Some(false)
+            }
+        }
+    }
+
+    fn local(&mut self) -> Option<()> {
+        if !self.point(|names| &mut names.local)? {
+            self.label("local")?;
+            self.bytes(&[0])?;
+        }
+        Some(())
+    }
+
+    /// `<host>.local`
+    fn host(&mut self) -> Option<()> {
+        if !self.point(|names| &mut names.host)? {
+            self.label(self.host)?;
+            self.local()?;
+        }
+        Some(())
+    }
+
+    /// `_services._dns-sd._udp.local`
+    fn enumeration(&mut self) -> Option<()> {
+        if !self.point(|names| &mut names.enumeration)? {
+            for label in ENUMERATION.split('.') {
+                self.label(label)?;
+            }
+            self.local()?;
+        }
+        Some(())
+    }
+
+    /// The service type, `<kind>.local`
+    fn kind(&mut self, i: usize) -> Option<()> {
+        let here = self.len;
+        match self.names.kinds[i] {
+            Some(offset) => self.bytes(&(0xC000 | offset as u16).to_be_bytes()),
+            None => {
+                self.names.kinds[i] = Some(here);
+                for label in self.services[i].kind.split('.') {
+                    self.label(label)?;
+                }
+                self.local()
+            }
+        }
+    }
+
+    /// This device's instance of the service, `<host>.<kind>.local`
+    fn instance(&mut self, i: usize) -> Option<()> {
+        let here = self.len;
+        match self.names.instances[i] {
+            Some(offset) => self.bytes(&(0xC000 | offset as u16).to_be_bytes()),
+            None => {
+                self.names.instances[i] = Some(here);
+                self.label(self.host)?;
+                self.kind(i)
+            }
+        }
+    }
+}
+
+fn labels<'a>(parts: &'a [&'a str]) -> impl Iterator<Item = &'a str> {
+    parts.iter().flat_map(|part| part.split('.'))
+}
+
+/// Where the name at `pos` ends
+fn skip_name(packet: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        match *packet.get(pos)? {
+            0 => return Some(pos + 1),
+            // A pointer ends the name
+            len if len & 0xC0 == 0xC0 => return Some(pos + 2),
+            len => pos += 1 + len as usize,
+        }
+    }
+}
+
+/// Whether the name at `pos`, following compression pointers, is the
+/// dotted parts, ignoring case as DNS does
+fn name_is(packet: &[u8], mut pos: usize, parts: &[&str]) -> bool {
+    let mut expected = labels(parts);
+    // Enough jumps for any honest name; more is a pointer loop
+    let mut jumps = 0;
+    loop {
+        let Some(&len) = packet.get(pos) else {
+            return false;
+        };
+        if len & 0xC0 == 0xC0 {
+            let Some(&low) = packet.get(pos + 1) else {
+                return false;
+            };
+            jumps += 1;
+            if jumps > 16 {
+                return false;
+            }
+            pos = ((len as usize & 0x3F) << 8) | low as usize;
+            continue;
+        }
+        if len == 0 {
+            return expected.next().is_none();
+        }
+        let Some(label) = packet.get(pos + 1..pos + 1 + len as usize) else {
+            return false;
+        };
+        if !expected
+            .next()
+            .is_some_and(|want| want.as_bytes().eq_ignore_ascii_case(label))
+        {
+            return false;
+        }
+        pos += 1 + len as usize;
+    }
+}
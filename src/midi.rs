@@ -0,0 +1,100 @@
+// MIDI messages, and the note and controller state effects play along to
+
+use core::cell::Cell;
+
+/// Controller numbers `main.rs` maps; CC 7 is channel volume, CC 1 the
+/// mod wheel and CC 76 vibrato rate, which most controllers send from a
+/// fader, the wheel and a knob
+pub const CC_BRIGHTNESS: u8 = 7;
+pub const CC_HUE: u8 = 1;
+pub const CC_SPEED: u8 = 76;
+
+/// A MIDI channel message, channels counted from 0
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum MidiMessage {
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    NoteOff { channel: u8, note: u8 },
+    ControlChange { channel: u8, control: u8, value: u8 },
+}
+
+impl MidiMessage {
+    /// Decode a 4-byte USB-MIDI event packet; `None` for the messages not
+    /// used here (aftertouch, pitch bend, SysEx, clock ...)
+    pub fn from_usb_packet(packet: [u8; 4]) -> Option<Self> {
+        // The code index number repeats the message's status nibble
+        let [header, status, data1, data2] = packet;
+        let channel = status & 0x0F;
+        let (data1, data2) = (data1 & 0x7F, data2 & 0x7F);
+        Some(match (header & 0x0F, status >> 4) {
+            // Note on at velocity 0 is how running status sends note off
+            (0x9, 0x9) if data2 == 0 => MidiMessage::NoteOff {
+                channel,
+                note: data1,
+            },
+            (0x9, 0x9) => MidiMessage::NoteOn {
+                channel,
+                note: data1,
+                velocity: data2,
+            },
+            (0x8, 0x8) => MidiMessage::NoteOff {
+                channel,
+                note: data1,
+            },
+            (0xB, 0xB) => MidiMessage::ControlChange {
+                channel,
+                control: data1,
+                value: data2,
+            },
+            _ => return None,
+        })
+    }
+}
+
+/// Notes struck and controller settings from the MIDI input, shared with
+/// the effects that follow them
+///
+/// Each note keeps the velocity it was last struck at until an effect
+/// takes it, so notes shorter than a frame still show.
+#[derive(Debug)]
+pub struct Midi {
+    strikes: [Cell<u8>; 128],
+    hue: Cell<u8>,
+}
+
+impl Midi {
+    pub const fn new() -> Self {
+        Self {
+            strikes: [const { Cell::new(0) }; 128],
+            hue: Cell::new(0),
+        }
+    }
+
+    pub fn strike(&self, note: u8, velocity: u8) {
+        if let Some(strike) = self.strikes.get(note as usize) {
+            strike.set(velocity);
+        }
+    }
+
+    /// Velocity `note` was struck at since the last call, 0 for not struck
+    pub fn take_strike(&self, note: u8) -> u8 {
+        self.strikes
+            .get(note as usize)
+            .map_or(0, |strike| strike.replace(0))
+    }
+
+    /// Hue the controller has set, 0-255
+    pub fn hue(&self) -> u8 {
+        self.hue.get()
+    }
+
+    /// Take a hue from a 0-127 controller value
+    pub fn set_hue(&self, value: u8) {
+        self.hue.set(value.min(127) << 1);
+    }
+}
+
+impl Default for Midi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
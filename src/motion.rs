@@ -0,0 +1,73 @@
+// PIR motion sensor: light up on motion, fade out once the room is empty
+
+use embedded_hal::digital::v2::InputPin;
+
+/// Fade-in time when motion turns the strip on
+pub const FADE_IN_MS: u32 = 500;
+
+/// A PIR sensor (HC-SR501 and similar) whose output is high while it sees
+/// motion
+///
+/// `poll` gives a level to dim the strip by: full while there's been motion
+/// in the last `timeout_ms`, then fading to off over `fade_ms`. Feed it to
+/// [`Engine::set_dimmer`](crate::engine::Engine::set_dimmer).
+///
+/// ```ignore
+/// let mut pir = MotionLight::new(pins.gpio22.into_pull_down_input(), 60_000, 3_000);
+/// engine.set_dimmer(pir.poll(now_ms));
+/// ```
+pub struct MotionLight<P> {
+    pin: P,
+    pub timeout_ms: u32,
+    pub fade_ms: u32,
+    last_motion_ms: Option<u32>,
+    /// When the strip last came on from dark
+    lit_since_ms: u32,
+}
+
+impl<P: InputPin> MotionLight<P> {
+    pub fn new(pin: P, timeout_ms: u32, fade_ms: u32) -> Self {
+        Self {
+            pin,
+            timeout_ms,
+            fade_ms,
+            last_motion_ms: None,
+            lit_since_ms: 0,
+        }
+    }
+
+    /// Whether the sensor sees motion right now
+    pub fn motion(&self) -> bool {
+        matches!(self.pin.is_high(), Ok(true))
+    }
+
+    /// Level the strip should be at, 0-255
+    pub fn poll(&mut self, now_ms: u32) -> u8 {
+        if self.motion() {
+            if self.level_at(now_ms) == 0 {
+                self.lit_since_ms = now_ms;
+            }
+            self.last_motion_ms = Some(now_ms);
+        }
+        self.level_at(now_ms)
+    }
+
+    fn level_at(&self, now_ms: u32) -> u8 {
+        let Some(last) = self.last_motion_ms else {
+            return 0;
+        };
+        let idle = now_ms.wrapping_sub(last);
+        let fading = idle.saturating_sub(self.timeout_ms);
+        if fading >= self.fade_ms {
+            return 0;
+        }
+        let out = 255 - fading * 255 / self.fade_ms.max(1);
+        let lit = now_ms.wrapping_sub(self.lit_since_ms);
+        let fade_in = lit.min(FADE_IN_MS) * 255 / FADE_IN_MS;
+        out.min(fade_in) as u8
+    }
+
+    pub fn free(self) -> P {
+        self.pin
+    }
+}
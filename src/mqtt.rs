@@ -0,0 +1,617 @@
+// MQTT 3.1.1 client over whatever TCP connection the network stack gives
+// it, with Home Assistant's MQTT light discovery
+
+use core::fmt::{self, Write};
+
+use crate::effects::{Effect, Param, ParamValue};
+use crate::json::{for_each_member, JsonError, JsonValue};
+use crate::pio_programs::Rgb;
+
+/// The broker port for plain TCP
+pub const MQTT_PORT: u16 = 1883;
+
+/// Longest packet taken from the broker; commands are far shorter, and a
+/// longer one is skipped
+pub const MAX_INCOMING: usize = 512;
+
+/// Room for the discovery message with a few dozen effects listed
+pub const MAX_OUTGOING: usize = 1024;
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const SUBSCRIBE: u8 = 0x82;
+const SUBACK: u8 = 0x90;
+const PINGREQ: u8 = 0xC0;
+const PINGRESP: u8 = 0xD0;
+const DISCONNECT: u8 = 0xE0;
+
+const RETAIN: u8 = 0x01;
+
+/// CONNECT flags
+const CLEAN_SESSION: u8 = 0x02;
+const WILL: u8 = 0x04;
+const WILL_RETAIN: u8 = 0x20;
+const PASSWORD: u8 = 0x40;
+const USERNAME: u8 = 0x80;
+
+/// Fixed header room: the type byte and up to four of remaining length
+const HEADER_ROOM: usize = 5;
+
+/// Writes formatted text into a byte slice, failing once it's full
+struct SliceWriter<'a> {
+    out: &'a mut [u8],
+    len: usize,
+}
+
+impl Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        self.out
+            .get_mut(self.len..end)
+            .ok_or(fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Builds one packet's variable header and payload after room for its
+/// fixed header, which `finish` fills in
+struct PacketWriter<'a> {
+    out: SliceWriter<'a>,
+}
+
+impl<'a> PacketWriter<'a> {
+    fn new(out: &'a mut [u8]) -> Self {
+        Self {
+            out: SliceWriter {
+                out,
+                len: HEADER_ROOM,
+            },
+        }
+    }
+
+    fn byte(&mut self, byte: u8) -> fmt::Result {
+        let slot = self.out.out.get_mut(self.out.len).ok_or(fmt::Error)?;
+        *slot = byte;
+        self.out.len += 1;
+        Ok(())
+    }
+
+    fn u16(&mut self, n: u16) -> fmt::Result {
+        let [high, low] = n.to_be_bytes();
+        self.byte(high)?;
+        self.byte(low)
+    }
+
+    /// A length-prefixed string, formatted in place
+    fn string(&mut self, s: &dyn fmt::Display) -> fmt::Result {
+        let at = self.out.len;
+        self.u16(0)?;
+        write!(self.out, "{s}")?;
+        let len = u16::try_from(self.out.len - at - 2).map_err(|_| fmt::Error)?;
+        self.out.out[at..at + 2].copy_from_slice(&len.to_be_bytes());
+        Ok(())
+    }
+
+    /// Fill in the fixed header and move the packet to the start of the
+    /// buffer, returning its length
+    fn finish(self, kind: u8) -> Option<usize> {
+        let SliceWriter { out, len } = self.out;
+        let mut remaining = len - HEADER_ROOM;
+        if remaining >= 1 << 28 {
+            return None;
+        }
+        let mut header = [kind, 0, 0, 0, 0];
+        let mut header_len = 1;
+        loop {
+            let mut digit = (remaining % 128) as u8;
+            remaining /= 128;
+            if remaining > 0 {
+                digit |= 0x80;
+            }
+            header[header_len] = digit;
+            header_len += 1;
+            if remaining == 0 {
+                break;
+            }
+        }
+        let start = HEADER_ROOM - header_len;
+        out[start..HEADER_ROOM].copy_from_slice(&header[..header_len]);
+        out.copy_within(start..len, 0);
+        Some(len - start)
+    }
+}
+
+/// A packet from the broker
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Incoming<'a> {
+    /// The answer to CONNECT, 0 for accepted
+    ConnAck(u8),
+    Publish {
+        topic: &'a [u8],
+        payload: &'a [u8],
+    },
+    SubAck,
+    PingResp,
+    /// Anything else, ignored
+    Other,
+}
+
+/// Splits the bytes from the broker into packets
+#[derive(Clone, Debug)]
+pub struct MqttReader {
+    buf: [u8; MAX_INCOMING],
+    len: usize,
+    kind: Option<u8>,
+    /// Remaining length as read so far, and the bytes of it read
+    remaining: usize,
+    length_bytes: u8,
+    length_done: bool,
+}
+
+impl MqttReader {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MAX_INCOMING],
+            len: 0,
+            kind: None,
+            remaining: 0,
+            length_bytes: 0,
+            length_done: false,
+        }
+    }
+
+    /// Take one byte; a packet once its last byte is in
+    pub fn push(&mut self, byte: u8) -> Option<Incoming<'_>> {
+        let Some(kind) = self.kind else {
+            self.kind = Some(byte);
+            self.remaining = 0;
+            self.length_bytes = 0;
+            self.length_done = false;
+            self.len = 0;
+            return None;
+        };
+        if !self.length_done {
+            self.remaining |= ((byte & 0x7F) as usize) << (7 * self.length_bytes);
+            self.length_bytes += 1;
+            if byte & 0x80 != 0 && self.length_bytes < 4 {
+                return None;
+            }
+            self.length_done = true;
+            if self.remaining > 0 {
+                return None;
+            }
+        } else {
+            if let Some(slot) = self.buf.get_mut(self.len) {
+                *slot = byte;
+            }
+            self.len += 1;
+            if self.len < self.remaining {
+                return None;
+            }
+        }
+
+        self.kind = None;
+        if self.remaining > MAX_INCOMING {
+            return None;
+        }
+        let body = &self.buf[..self.remaining];
+        Some(match kind & 0xF0 {
+            CONNACK if body.len() >= 2 => Incoming::ConnAck(body[1]),
+            PUBLISH if body.len() >= 2 => {
+                let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+                // QoS 1 and 2 carry a packet identifier after the topic
+                let id_len = if kind & 0x06 != 0 { 2 } else { 0 };
+                match body.get(2..2 + topic_len) {
+                    Some(topic) if body.len() >= 2 + topic_len + id_len => Incoming::Publish {
+                        topic,
+                        payload: &body[2 + topic_len + id_len..],
+                    },
+                    _ => Incoming::Other,
+                }
+            }
+            SUBACK => Incoming::SubAck,
+            PINGRESP => Incoming::PingResp,
+            _ => Incoming::Other,
+        })
+    }
+}
+
+impl Default for MqttReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What [`MqttClient::receive`] made of a byte
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum MqttEvent {
+    /// The broker took the connection: time to
+    /// [`announce`](MqttClient::announce)
+    Connected,
+    /// The broker turned the connection down, with its reason code
+    Refused(u8),
+    Command(LightCommand),
+    /// A message on the command topic that isn't a command
+    BadCommand(JsonError),
+}
+
+/// One strip as a Home Assistant light, its topics under `base`
+///
+/// `base/status` is the availability, `online` once connected and
+/// `offline` as the broker's last will; `base/state` is the state, retained,
+/// in Home Assistant's JSON schema; commands come in on `base/set`. The
+/// discovery message goes to `homeassistant/light/<client_id>/config`,
+/// retained, so Home Assistant picks the light up whenever it starts.
+///
+/// Only QoS 0 is used, both ways; a missed state is put right by the next.
+///
+/// ```ignore
+/// let mut mqtt = MqttClient::new("pico-display-1a2b", "pico-display/1a2b");
+/// socket.write(&out[..mqtt.connect(now_ms, &mut out)]);
+/// for byte in received {
+///     match mqtt.receive(byte) {
+///         Some(MqttEvent::Connected) => {
+///             let discovery = Discovery { name: "Strip", effects: effects.len() };
+///             mqtt.announce(&discovery, &state, now_ms, &mut out, |packet| socket.write(packet));
+///         }
+///         Some(MqttEvent::Command(command)) => { /* apply, then publish the state */ }
+///         _ => {}
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct MqttClient {
+    pub client_id: &'static str,
+    pub base: &'static str,
+    pub username: Option<&'static str>,
+    pub password: Option<&'static str>,
+    pub keep_alive_s: u16,
+    reader: MqttReader,
+    connected: bool,
+    last_sent_ms: u32,
+}
+
+impl MqttClient {
+    pub const fn new(client_id: &'static str, base: &'static str) -> Self {
+        Self {
+            client_id,
+            base,
+            username: None,
+            password: None,
+            keep_alive_s: 60,
+            reader: MqttReader::new(),
+            connected: false,
+            last_sent_ms: 0,
+        }
+    }
+
+    pub fn credentials(mut self, username: &'static str, password: &'static str) -> Self {
+        self.username = Some(username);
+        self.password = Some(password);
+        self
+    }
+
+    pub fn keep_alive_s(mut self, seconds: u16) -> Self {
+        self.keep_alive_s = seconds;
+        self
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// The CONNECT packet starting a session on a new TCP connection, with
+    /// `offline` as the last will
+    pub fn connect(&mut self, now_ms: u32, out: &mut [u8]) -> Option<usize> {
+        self.connected = false;
+        self.reader = MqttReader::new();
+        self.last_sent_ms = now_ms;
+        let mut flags = CLEAN_SESSION | WILL | WILL_RETAIN;
+        if self.username.is_some() {
+            flags |= USERNAME;
+        }
+        if self.password.is_some() {
+            flags |= PASSWORD;
+        }
+        let mut packet = PacketWriter::new(out);
+        packet.string(&"MQTT").ok()?;
+        packet.byte(4).ok()?;
+        packet.byte(flags).ok()?;
+        packet.u16(self.keep_alive_s).ok()?;
+        packet.string(&self.client_id).ok()?;
+        packet.string(&Topic(self.base, "status")).ok()?;
+        packet.string(&"offline").ok()?;
+        if let Some(username) = self.username {
+            packet.string(&username).ok()?;
+        }
+        if let Some(password) = self.password {
+            packet.string(&password).ok()?;
+        }
+        packet.finish(CONNECT)
+    }
+
+    /// Take one byte from the broker
+    pub fn receive(&mut self, byte: u8) -> Option<MqttEvent> {
+        match self.reader.push(byte)? {
+            Incoming::ConnAck(0) => {
+                self.connected = true;
+                Some(MqttEvent::Connected)
+            }
+            Incoming::ConnAck(code) => Some(MqttEvent::Refused(code)),
+            Incoming::Publish { topic, payload } => {
+                let command = Topic(self.base, "set");
+                if !command.matches(topic) {
+                    return None;
+                }
+                Some(match LightCommand::parse(payload) {
+                    Ok(command) => MqttEvent::Command(command),
+                    Err(e) => MqttEvent::BadCommand(e),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Once connected: subscribe to commands, then publish discovery,
+    /// availability and the state, handing each packet to `send`
+    pub fn announce(
+        &mut self,
+        discovery: &Discovery,
+        state: &LightState,
+        now_ms: u32,
+        out: &mut [u8],
+        mut send: impl FnMut(&[u8]),
+    ) {
+        if let Some(len) = self.subscribe(out) {
+            send(&out[..len]);
+        }
+        let topic = DiscoveryTopic(self.client_id);
+        let doc = DiscoveryDoc {
+            discovery,
+            client_id: self.client_id,
+            base: self.base,
+        };
+        if let Some(len) = self.publish(&topic, &doc, true, now_ms, out) {
+            send(&out[..len]);
+        }
+        let status = Topic(self.base, "status");
+        if let Some(len) = self.publish(&status, &"online", true, now_ms, out) {
+            send(&out[..len]);
+        }
+        if let Some(len) = self.publish_state(state, now_ms, out) {
+            send(&out[..len]);
+        }
+    }
+
+    /// SUBSCRIBE to the command topic, QoS 0
+    fn subscribe(&self, out: &mut [u8]) -> Option<usize> {
+        let mut packet = PacketWriter::new(out);
+        packet.u16(1).ok()?;
+        packet.string(&Topic(self.base, "set")).ok()?;
+        packet.byte(0).ok()?;
+        packet.finish(SUBSCRIBE)
+    }
+
+    /// The state, for after any change
+    pub fn publish_state(
+        &mut self,
+        state: &LightState,
+        now_ms: u32,
+        out: &mut [u8],
+    ) -> Option<usize> {
+        self.publish(&Topic(self.base, "state"), state, true, now_ms, out)
+    }
+
+    /// A QoS 0 PUBLISH; None if it doesn't fit in `out`
+    pub fn publish(
+        &mut self,
+        topic: &dyn fmt::Display,
+        payload: &dyn fmt::Display,
+        retain: bool,
+        now_ms: u32,
+        out: &mut [u8],
+    ) -> Option<usize> {
+        let mut packet = PacketWriter::new(out);
+        packet.string(topic).ok()?;
+        write!(packet.out, "{payload}").ok()?;
+        self.last_sent_ms = now_ms;
+        packet.finish(if retain { PUBLISH | RETAIN } else { PUBLISH })
+    }
+
+    /// A PINGREQ when nothing's been sent for half the keep-alive, so the
+    /// broker doesn't give up on the connection
+    pub fn poll(&mut self, now_ms: u32, out: &mut [u8]) -> Option<usize> {
+        let due = self.keep_alive_s as u32 * 500;
+        if !self.connected || now_ms.wrapping_sub(self.last_sent_ms) < due {
+            return None;
+        }
+        self.last_sent_ms = now_ms;
+        PacketWriter::new(out).finish(PINGREQ)
+    }
+
+    /// A DISCONNECT, closing the session without the last will being sent
+    /// (so publish `offline` first)
+    pub fn disconnect(&mut self, out: &mut [u8]) -> Option<usize> {
+        self.connected = false;
+        PacketWriter::new(out).finish(DISCONNECT)
+    }
+}
+
+/// `base/suffix`
+struct Topic(&'static str, &'static str);
+
+impl Topic {
+    fn matches(&self, topic: &[u8]) -> bool {
+        let (base, suffix) = (self.0.as_bytes(), self.1.as_bytes());
+        topic.len() == base.len() + 1 + suffix.len()
+            && topic.starts_with(base)
+            && topic[base.len()] == b'/'
+            && topic.ends_with(suffix)
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.0, self.1)
+    }
+}
+
+struct DiscoveryTopic(&'static str);
+
+impl fmt::Display for DiscoveryTopic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "homeassistant/light/{}/config", self.0)
+    }
+}
+
+/// What Home Assistant is told about the light
+#[derive(Copy, Clone, Debug)]
+pub struct Discovery<'a> {
+    /// The device's name in Home Assistant
+    pub name: &'a str,
+    pub effects: usize,
+}
+
+struct DiscoveryDoc<'a> {
+    discovery: &'a Discovery<'a>,
+    client_id: &'a str,
+    base: &'a str,
+}
+
+impl fmt::Display for DiscoveryDoc<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{{\"~\":\"{base}\",\"name\":null,\"uniq_id\":\"{id}\",\"schema\":\"json\",\
+             \"cmd_t\":\"~/set\",\"stat_t\":\"~/state\",\"avty_t\":\"~/status\",\
+             \"brightness\":true,\"supported_color_modes\":[\"rgb\"],\"effect\":true,\
+             \"effect_list\":[",
+            base = self.base,
+            id = self.client_id,
+        )?;
+        for i in 0..self.discovery.effects {
+            let separator = if i == 0 { "" } else { "," };
+            write!(f, "{separator}\"{}\"", EffectName(i))?;
+        }
+        write!(
+            f,
+            "],\"dev\":{{\"ids\":[\"{}\"],\"name\":\"{}\",\"mdl\":\"pico-display\",\
+             \"sw\":\"{}\"}}}}",
+            self.client_id,
+            self.discovery.name,
+            env!("CARGO_PKG_VERSION"),
+        )
+    }
+}
+
+/// Effects have no names of their own, so they go by number
+struct EffectName(usize);
+
+impl fmt::Display for EffectName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Effect {}", self.0)
+    }
+}
+
+/// A command from Home Assistant, in its JSON schema
+///
+/// ```text
+/// {"state":"ON","brightness":128,"color":{"r":255,"g":0,"b":0},"effect":"Effect 3"}
+/// ```
+///
+/// Transitions and flashes aren't supported and are ignored.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, defmt::Format)]
+pub struct LightCommand {
+    pub on: Option<bool>,
+    pub brightness: Option<u8>,
+    /// For the running effect's primary color
+    pub color: Option<Rgb>,
+    pub effect: Option<usize>,
+}
+
+impl LightCommand {
+    pub fn parse(payload: &[u8]) -> Result<Self, JsonError> {
+        let mut command = Self::default();
+        for_each_member(payload, |key, value| {
+            match key {
+                b"state" => match value {
+                    JsonValue::String(b"ON") => command.on = Some(true),
+                    JsonValue::String(b"OFF") => command.on = Some(false),
+                    _ => return Err(JsonError::WrongType),
+                },
+                b"brightness" => command.brightness = Some(value.level()?),
+                b"color" => {
+                    let JsonValue::Object(color) = value else {
+                        return Err(JsonError::WrongType);
+                    };
+                    let mut rgb = Rgb::BLACK;
+                    for_each_member(color, |key, value| {
+                        match key {
+                            b"r" => rgb.r = value.level()?,
+                            b"g" => rgb.g = value.level()?,
+                            b"b" => rgb.b = value.level()?,
+                            _ => {}
+                        }
+                        Ok(())
+                    })?;
+                    command.color = Some(rgb);
+                }
+                b"effect" => {
+                    let number = match value {
+                        JsonValue::String(name) => name.strip_prefix(b"Effect "),
+                        _ => return Err(JsonError::WrongType),
+                    };
+                    let effect = number
+                        .and_then(|n| core::str::from_utf8(n).ok())
+                        .and_then(|n| n.parse().ok())
+                        .ok_or(JsonError::OutOfRange)?;
+                    command.effect = Some(effect);
+                }
+                _ => {}
+            }
+            Ok(())
+        })?;
+        Ok(command)
+    }
+
+    /// The color change, for [`Effect::set_param`]
+    pub fn params(&self) -> impl Iterator<Item = (Param, ParamValue)> {
+        self.color
+            .map(|c| (Param::Primary, ParamValue::Color(c)))
+            .into_iter()
+    }
+}
+
+/// The state as Home Assistant reads it, written with `{}`
+///
+/// Off is brightness 0 here, so `on` and the brightness to come back to
+/// are kept by whoever switches the strip off. The color is the running
+/// effect's primary, left out for effects without one.
+pub struct LightState<'a> {
+    pub on: bool,
+    pub brightness: u8,
+    pub effect: usize,
+    pub running: &'a dyn Effect,
+}
+
+impl fmt::Display for LightState<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{{\"state\":\"{}\",\"brightness\":{},\"effect\":\"{}\"",
+            if self.on { "ON" } else { "OFF" },
+            self.brightness,
+            EffectName(self.effect)
+        )?;
+        if let Some(ParamValue::Color(Rgb { r, g, b })) = self.running.param(Param::Primary) {
+            write!(
+                f,
+                ",\"color_mode\":\"rgb\",\"color\":{{\"r\":{r},\"g\":{g},\"b\":{b}}}"
+            )?;
+        }
+        f.write_str("}")
+    }
+}
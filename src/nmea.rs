@@ -0,0 +1,143 @@
+// NMEA 0183 sentence parsing, for UTC time and date from a GPS module
+
+use crate::clock::{Date, TimeOfDay};
+
+/// Longest sentence the standard allows, `$` to checksum, without CR LF
+pub const MAX_SENTENCE: usize = 82;
+
+/// UTC time and date from a GPS receiver's RMC sentence
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct GpsTime {
+    pub date: Date,
+    pub time: TimeOfDay,
+}
+
+/// Assembles NMEA sentences from the serial bytes and picks out the time
+///
+/// Only RMC (recommended minimum) sentences from any talker (`$GPRMC`,
+/// `$GNRMC`, ...) are read, and only once the receiver reports a fix, when
+/// its clock is set from the satellites. Sentences with a bad checksum are
+/// dropped. The time is that of the second the sentence is about, which
+/// receivers send a few hundred milliseconds after it starts.
+///
+/// ```ignore
+/// while let Ok(byte) = uart.read() {
+///     if let Some(gps) = nmea.push(byte) {
+///         clock.set(now_ms, gps.date, gps.time);
+///     }
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct NmeaParser {
+    buf: [u8; MAX_SENTENCE],
+    len: usize,
+    /// Inside a sentence that still fits
+    active: bool,
+}
+
+impl NmeaParser {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MAX_SENTENCE],
+            len: 0,
+            active: false,
+        }
+    }
+
+    /// Take in one byte; returns the time when it completes an RMC sentence
+    /// with a fix
+    pub fn push(&mut self, byte: u8) -> Option<GpsTime> {
+        match byte {
+            b'$' => {
+                self.len = 0;
+                self.active = true;
+                None
+            }
+            b'\r' | b'\n' => {
+                let done = core::mem::take(&mut self.active);
+                if done {
+                    parse_sentence(&self.buf[..self.len])
+                } else {
+                    None
+                }
+            }
+            _ if self.active => {
+                if self.len < self.buf.len() {
+                    self.buf[self.len] = byte;
+                    self.len += 1;
+                } else {
+                    self.active = false;
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Default for NmeaParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Check the checksum and read the time out of an RMC sentence, `$`
+/// already stripped
+fn parse_sentence(sentence: &[u8]) -> Option<GpsTime> {
+    let star = sentence.iter().position(|&b| b == b'*')?;
+    let (body, checksum) = (&sentence[..star], &sentence[star + 1..]);
+    let expected = u8::from_str_radix(core::str::from_utf8(checksum).ok()?, 16).ok()?;
+    if body.iter().fold(0, |sum, b| sum ^ b) != expected {
+        return None;
+    }
+
+    // $--RMC,hhmmss.ss,A,llll.ll,a,yyyyy.yy,a,x.x,x.x,ddmmyy,...
+    let mut fields = body.split(|&b| b == b',');
+    let kind = fields.next()?;
+    if kind.len() != 5 || &kind[2..] != b"RMC" {
+        return None;
+    }
+    let time = fields.next()?;
+    if fields.next()? != b"A" {
+        return None;
+    }
+    let date = fields.nth(6)?;
+
+    let (hour, minute, second) = (digits(time, 0)?, digits(time, 2)?, digits(time, 4)?);
+    let (day, month, year) = (digits(date, 0)?, digits(date, 2)?, digits(date, 4)?);
+    if hour > 23
+        || minute > 59
+        || second > 60
+        || !(1..=31).contains(&day)
+        || !(1..=12).contains(&month)
+    {
+        return None;
+    }
+    // Receivers that send fractions of a second mostly send .00, but not all
+    let mut millis = 0;
+    if let Some(fraction) = time.get(7..).filter(|_| time[6] == b'.') {
+        for (&d, scale) in fraction.iter().zip([100, 10, 1]) {
+            if !d.is_ascii_digit() {
+                return None;
+            }
+            millis += (d - b'0') as u32 * scale;
+        }
+    }
+    // A leap second reads as 60; hold it at the end of the minute
+    let time = TimeOfDay(TimeOfDay::from_hms(hour, minute, second.min(59)).0 + millis);
+    Some(GpsTime {
+        date: Date {
+            year: 2000 + year as u16,
+            month,
+            day,
+        },
+        time,
+    })
+}
+
+/// Two decimal digits at `at`
+fn digits(field: &[u8], at: usize) -> Option<u8> {
+    let pair = field.get(at..at + 2)?;
+    pair.iter()
+        .try_fold(0, |n, &d| d.is_ascii_digit().then(|| n * 10 + (d - b'0')))
+}
@@ -0,0 +1,166 @@
+// Fixed-point gradient noise, after FastLED's inoise8/inoise16
+//
+// `inoise8` takes 8.8 fixed-point coordinates and `inoise16` 16.16: the
+// integer part picks the lattice cell and the fraction the position inside
+// it, so stepping a coordinate by one whole unit moves one noise feature.
+// Both come in 1D, 2D and 3D; time makes a good extra dimension.
+
+/// Ken Perlin's reference permutation
+const PERM: [u8; 256] = [
+    151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194, 233, 7, 225, 140, 36, 103, 30, 69,
+    142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148, 247, 120, 234, 75, 0, 26, 197, 62, 94, 252, 219,
+    203, 117, 35, 11, 32, 57, 177, 33, 88, 237, 149, 56, 87, 174, 20, 125, 136, 171, 168, 68, 175,
+    74, 165, 71, 134, 139, 48, 27, 166, 77, 146, 158, 231, 83, 111, 229, 122, 60, 211, 133, 230,
+    220, 105, 92, 41, 55, 46, 245, 40, 244, 102, 143, 54, 65, 25, 63, 161, 1, 216, 80, 73, 209, 76,
+    132, 187, 208, 89, 18, 169, 200, 196, 135, 130, 116, 188, 159, 86, 164, 100, 109, 198, 173,
+    186, 3, 64, 52, 217, 226, 250, 124, 123, 5, 202, 38, 147, 118, 126, 255, 82, 85, 212, 207, 206,
+    59, 227, 47, 16, 58, 17, 182, 189, 28, 42, 223, 183, 170, 213, 119, 248, 152, 2, 44, 154, 163,
+    70, 221, 153, 101, 155, 167, 43, 172, 9, 129, 22, 39, 253, 19, 98, 108, 110, 79, 113, 224, 232,
+    178, 185, 112, 104, 218, 246, 97, 228, 251, 34, 242, 193, 238, 210, 144, 12, 191, 179, 162,
+    241, 81, 51, 145, 235, 249, 14, 239, 107, 49, 192, 214, 31, 181, 199, 106, 157, 184, 84, 204,
+    176, 115, 121, 50, 45, 127, 4, 150, 254, 138, 236, 205, 93, 222, 114, 67, 29, 24, 72, 243, 141,
+    128, 195, 78, 66, 215, 61, 156, 180,
+];
+
+fn hash(i: u8) -> u8 {
+    PERM[i as usize]
+}
+
+/// One lattice cell, in the 16.16 fractions the core works in
+const CELL: i64 = 1 << 16;
+
+/// Split a 16.16 coordinate into its cell and the offset inside it
+fn split(c: u32) -> (u8, i64) {
+    ((c >> 16) as u8, (c & 0xFFFF) as i64)
+}
+
+/// Smoothstep over a cell
+fn fade(t: i64) -> i64 {
+    (t * t * (3 * CELL - 2 * t)) >> 32
+}
+
+fn lerp(a: i64, b: i64, t: i64) -> i64 {
+    a + (((b - a) * t) >> 16)
+}
+
+fn grad1(hash: u8, x: i64) -> i64 {
+    x * ((hash & 15) as i64 - 8) / 4
+}
+
+fn grad2(hash: u8, x: i64, y: i64) -> i64 {
+    match hash & 7 {
+        0 => x + y,
+        1 => y - x,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+/// Perlin's twelve cube edges, padded to sixteen
+fn grad3(hash: u8, x: i64, y: i64, z: i64) -> i64 {
+    match hash & 15 {
+        0 | 12 => x + y,
+        1 | 13 => y - x,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => z - x,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 | 14 => z - y,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+fn noise1(x: u32) -> i64 {
+    let (xi, xf) = split(x);
+    let u = fade(xf);
+    lerp(
+        grad1(hash(xi), xf),
+        grad1(hash(xi.wrapping_add(1)), xf - CELL),
+        u,
+    )
+}
+
+fn noise2(x: u32, y: u32) -> i64 {
+    let ((xi, xf), (yi, yf)) = (split(x), split(y));
+    let (u, v) = (fade(xf), fade(yf));
+
+    let a = hash(xi).wrapping_add(yi);
+    let b = hash(xi.wrapping_add(1)).wrapping_add(yi);
+    let (aa, ab) = (hash(a), hash(a.wrapping_add(1)));
+    let (ba, bb) = (hash(b), hash(b.wrapping_add(1)));
+
+    let x1 = lerp(grad2(aa, xf, yf), grad2(ba, xf - CELL, yf), u);
+    let x2 = lerp(grad2(ab, xf, yf - CELL), grad2(bb, xf - CELL, yf - CELL), u);
+    lerp(x1, x2, v)
+}
+
+fn noise3(x: u32, y: u32, z: u32) -> i64 {
+    let ((xi, xf), (yi, yf), (zi, zf)) = (split(x), split(y), split(z));
+    let (u, v, w) = (fade(xf), fade(yf), fade(zf));
+
+    let a = hash(xi).wrapping_add(yi);
+    let b = hash(xi.wrapping_add(1)).wrapping_add(yi);
+    let aa = hash(a).wrapping_add(zi);
+    let ab = hash(a.wrapping_add(1)).wrapping_add(zi);
+    let ba = hash(b).wrapping_add(zi);
+    let bb = hash(b.wrapping_add(1)).wrapping_add(zi);
+
+    // The near and far faces along z, each blended over x and y
+    let face = |dz: u8, zf: i64| {
+        let h = |c: u8| hash(c.wrapping_add(dz));
+        let x1 = lerp(grad3(h(aa), xf, yf, zf), grad3(h(ba), xf - CELL, yf, zf), u);
+        let x2 = lerp(
+            grad3(h(ab), xf, yf - CELL, zf),
+            grad3(h(bb), xf - CELL, yf - CELL, zf),
+            u,
+        );
+        lerp(x1, x2, v)
+    };
+    lerp(face(0, zf), face(1, zf - CELL), w)
+}
+
+/// Map the core's output, around -1..1 cell, onto the full u16 range
+fn to16(n: i64) -> u16 {
+    ((n + CELL) >> 1).clamp(0, u16::MAX as i64) as u16
+}
+
+/// Smooth 2D noise, 0-65535 over 16.16 coordinates
+///
+/// Like all Perlin noise it clusters around the middle; most of the output
+/// falls within about a fifth of the range either side of it.
+pub fn inoise16(x: u32, y: u32) -> u16 {
+    to16(noise2(x, y))
+}
+
+/// Smooth 1D noise, 0-65535 over a 16.16 coordinate
+pub fn inoise16_1d(x: u32) -> u16 {
+    to16(noise1(x))
+}
+
+/// Smooth 3D noise, 0-65535 over 16.16 coordinates
+pub fn inoise16_3d(x: u32, y: u32, z: u32) -> u16 {
+    to16(noise3(x, y, z))
+}
+
+/// Smooth 2D noise, 0-255 over 8.8 coordinates
+pub fn inoise8(x: u16, y: u16) -> u8 {
+    (inoise16((x as u32) << 8, (y as u32) << 8) >> 8) as u8
+}
+
+/// Smooth 1D noise, 0-255 over an 8.8 coordinate
+pub fn inoise8_1d(x: u16) -> u8 {
+    (inoise16_1d((x as u32) << 8) >> 8) as u8
+}
+
+/// Smooth 3D noise, 0-255 over 8.8 coordinates
+pub fn inoise8_3d(x: u16, y: u16, z: u16) -> u8 {
+    (inoise16_3d((x as u32) << 8, (y as u32) << 8, (z as u32) << 8) >> 8) as u8
+}
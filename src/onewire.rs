@@ -0,0 +1,198 @@
+// Bit-banged 1-Wire bus master, at standard speed
+
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+
+/// A device's 64-bit ROM code: family code, 48-bit serial, CRC
+pub type Rom = [u8; 8];
+
+const SEARCH_ROM: u8 = 0xF0;
+const MATCH_ROM: u8 = 0x55;
+const SKIP_ROM: u8 = 0xCC;
+
+/// What went wrong on the bus
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum OneWireError {
+    /// No device answered the reset pulse
+    NoPresence,
+    /// Data failed its CRC
+    Crc,
+}
+
+/// Dallas/Maxim CRC-8, as 1-Wire devices append to ROM codes and data;
+/// running it over data and its CRC gives 0
+pub fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |mut crc, &byte| {
+        let mut byte = byte;
+        for _ in 0..8 {
+            let mix = (crc ^ byte) & 1;
+            crc >>= 1;
+            if mix != 0 {
+                crc ^= 0x8C;
+            }
+            byte >>= 1;
+        }
+        crc
+    })
+}
+
+/// A 1-Wire bus on one GPIO
+///
+/// The pin must both drive low and read back, e.g. the HAL's open-drain
+/// `InOutPin`, with a 4.7k pull-up from the line to 3V3. Each time slot runs
+/// with interrupts off, at most half a millisecond for a reset.
+///
+/// ```ignore
+/// let mut bus = OneWire::new(InOutPin::new(pins.gpio3.into_floating_input()));
+/// let mut roms = [[0; 8]; 4];
+/// let found = bus.search(&mut delay, &mut roms)?;
+/// ```
+pub struct OneWire<P> {
+    pin: P,
+}
+
+impl<P: InputPin + OutputPin> OneWire<P> {
+    pub fn new(mut pin: P) -> Self {
+        let _ = pin.set_high();
+        Self { pin }
+    }
+
+    /// Reset pulse; `Ok` if any device is there to answer it
+    pub fn reset<D: DelayUs<u32>>(&mut self, delay: &mut D) -> Result<(), OneWireError> {
+        let present = critical_section::with(|_| {
+            let _ = self.pin.set_low();
+            delay.delay_us(480);
+            let _ = self.pin.set_high();
+            delay.delay_us(70);
+            matches!(self.pin.is_low(), Ok(true))
+        });
+        delay.delay_us(410);
+        if present {
+            Ok(())
+        } else {
+            Err(OneWireError::NoPresence)
+        }
+    }
+
+    pub fn write_bit<D: DelayUs<u32>>(&mut self, bit: bool, delay: &mut D) {
+        critical_section::with(|_| {
+            let _ = self.pin.set_low();
+            // A short pulse for a 1, held through the slot for a 0
+            delay.delay_us(if bit { 6 } else { 60 });
+            let _ = self.pin.set_high();
+        });
+        delay.delay_us(if bit { 64 } else { 10 });
+    }
+
+    pub fn read_bit<D: DelayUs<u32>>(&mut self, delay: &mut D) -> bool {
+        let bit = critical_section::with(|_| {
+            let _ = self.pin.set_low();
+            delay.delay_us(6);
+            let _ = self.pin.set_high();
+            delay.delay_us(9);
+            matches!(self.pin.is_high(), Ok(true))
+        });
+        delay.delay_us(55);
+        bit
+    }
+
+    /// Least significant bit first, as on the wire
+    pub fn write_byte<D: DelayUs<u32>>(&mut self, byte: u8, delay: &mut D) {
+        for i in 0..8 {
+            self.write_bit(byte & (1 << i) != 0, delay);
+        }
+    }
+
+    pub fn read_byte<D: DelayUs<u32>>(&mut self, delay: &mut D) -> u8 {
+        (0..8).fold(0, |byte, i| byte | (self.read_bit(delay) as u8) << i)
+    }
+
+    /// Reset and address one device, or with `None` every device at once
+    pub fn select<D: DelayUs<u32>>(
+        &mut self,
+        rom: Option<&Rom>,
+        delay: &mut D,
+    ) -> Result<(), OneWireError> {
+        self.reset(delay)?;
+        match rom {
+            Some(rom) => {
+                self.write_byte(MATCH_ROM, delay);
+                for &byte in rom {
+                    self.write_byte(byte, delay);
+                }
+            }
+            None => self.write_byte(SKIP_ROM, delay),
+        }
+        Ok(())
+    }
+
+    /// Find the devices on the bus, filling `roms` with their codes;
+    /// returns how many were found, up to the length of `roms`
+    ///
+    /// The ROM search from Maxim's application note 187: with every device
+    /// answering each bit at once, the bits where they disagree are walked
+    /// one way and then, on later passes, the other.
+    pub fn search<D: DelayUs<u32>>(
+        &mut self,
+        delay: &mut D,
+        roms: &mut [Rom],
+    ) -> Result<usize, OneWireError> {
+        let mut found = 0;
+        let mut rom: Rom = [0; 8];
+        // 1-based bit where the last pass took the 0 branch, 0 for none left
+        let mut last_discrepancy = 0;
+
+        while found < roms.len() {
+            self.reset(delay)?;
+            self.write_byte(SEARCH_ROM, delay);
+
+            let mut discrepancy = 0;
+            for bit in 1..=64 {
+                let id = self.read_bit(delay);
+                let complement = self.read_bit(delay);
+                let branch = match (id, complement) {
+                    (true, true) => return Err(OneWireError::NoPresence),
+                    (false, false) => {
+                        // Devices differ here: retrace the last pass until
+                        // its last fork, take the 1 branch there, and the 0
+                        // branch at any new fork past it
+                        let branch = match bit.cmp(&last_discrepancy) {
+                            core::cmp::Ordering::Less => {
+                                rom[(bit - 1) / 8] & (1 << ((bit - 1) % 8)) != 0
+                            }
+                            core::cmp::Ordering::Equal => true,
+                            core::cmp::Ordering::Greater => false,
+                        };
+                        if !branch {
+                            discrepancy = bit;
+                        }
+                        branch
+                    }
+                    (id, _) => id,
+                };
+                if branch {
+                    rom[(bit - 1) / 8] |= 1 << ((bit - 1) % 8);
+                } else {
+                    rom[(bit - 1) / 8] &= !(1 << ((bit - 1) % 8));
+                }
+                self.write_bit(branch, delay);
+            }
+
+            if crc8(&rom) != 0 {
+                return Err(OneWireError::Crc);
+            }
+            roms[found] = rom;
+            found += 1;
+
+            last_discrepancy = discrepancy;
+            if last_discrepancy == 0 {
+                break;
+            }
+        }
+        Ok(found)
+    }
+
+    pub fn free(self) -> P {
+        self.pin
+    }
+}
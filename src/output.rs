@@ -0,0 +1,28 @@
+// Common interface over every kind of LED output
+
+use crate::pio_programs::Rgb;
+
+/// Anything that shows a frame of colors: an addressable strip, or a single
+/// analog RGB channel set
+///
+/// Effects written against this trait run unchanged on any output, so
+/// addressable and analog strips can be mixed in one build.
+pub trait LedOutput {
+    /// Send the colors from `pixels`, as many as the output has room for
+    ///
+    /// Outputs with fewer pixels than the iterator yields stop early; longer
+    /// ones show black past its end.
+    fn write_pixels(&mut self, pixels: impl Iterator<Item = Rgb>);
+
+    /// Whether the previous frame is still going out
+    fn is_busy(&self) -> bool;
+
+    fn write_frame(&mut self, pixels: &[Rgb]) {
+        self.write_pixels(pixels.iter().copied());
+    }
+
+    /// Show one color across the whole output
+    fn set_color(&mut self, color: Rgb) {
+        self.write_pixels(core::iter::repeat(color));
+    }
+}
@@ -0,0 +1,331 @@
+// Binary packets: postcard-style messages, COBS framed and CRC checked
+
+/// Largest packet taken in, still COBS encoded; a frame of 330 LEDs fits
+pub const MAX_PACKET_LEN: usize = 1024;
+
+/// Largest reply, framed, that [`encode_reply`] writes
+pub const MAX_REPLY: usize = 16;
+
+/// A packet that stops arriving for this long is dropped, and the stream
+/// goes back to text and the other protocols
+pub const PACKET_TIMEOUT_MS: u32 = 100;
+
+/// CRC-16/CCITT-FALSE (poly 0x1021, init 0xFFFF), what `crc`'s
+/// `CRC_16_IBM_3740` computes
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// COBS-encode `data` into `out`, without the 0x00 delimiter; `None` if
+/// `out` is too small
+pub fn cobs_encode(data: &[u8], out: &mut [u8]) -> Option<usize> {
+    let (mut code_at, mut write, mut code) = (0, 1, 1u8);
+    for &byte in data {
+        if byte != 0 {
+            *out.get_mut(write)? = byte;
+            write += 1;
+            code += 1;
+        }
+        if byte == 0 || code == 0xFF {
+            *out.get_mut(code_at)? = code;
+            code_at = write;
+            write += 1;
+            code = 1;
+        }
+    }
+    *out.get_mut(code_at)? = code;
+    Some(write)
+}
+
+/// Undo COBS in place; `None` if the encoding is broken
+fn cobs_decode(buf: &mut [u8]) -> Option<usize> {
+    let (mut read, mut write) = (0, 0);
+    while read < buf.len() {
+        let code = buf[read] as usize;
+        if code == 0 || read + code > buf.len() {
+            return None;
+        }
+        buf.copy_within(read + 1..read + code, write);
+        write += code - 1;
+        read += code;
+        // Every block but a full one, or the last, stood in for a zero
+        if code < 0xFF && read < buf.len() {
+            buf[write] = 0;
+            write += 1;
+        }
+    }
+    Some(write)
+}
+
+/// Why a packet was refused
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Nack {
+    /// Damaged on the way: broken COBS or a CRC mismatch
+    Crc,
+    /// Intact, but not a request this end knows how to read
+    Malformed,
+    /// A pixel or effect number past the end
+    OutOfRange,
+    /// Longer than [`MAX_PACKET_LEN`]
+    TooLong,
+}
+
+/// What a host can ask for
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Request<'a> {
+    Ping,
+    /// RGB bytes for the LEDs from `start` on, so long strips can be sent
+    /// in pieces
+    Frame {
+        start: u16,
+        pixels: &'a [u8],
+    },
+    SetBrightness(u8),
+    SetEffect(u8),
+    GetStatus,
+}
+
+/// A request and the sequence number its reply echoes
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Packet<'a> {
+    pub seq: u8,
+    pub request: Request<'a>,
+}
+
+/// The state a [`Reply::Status`] reports
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Status {
+    pub effect: u8,
+    pub effects: u8,
+    pub brightness: u8,
+    pub leds: u16,
+}
+
+/// The answer to every packet
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Reply {
+    Ack,
+    Nack(Nack),
+    Status(Status),
+}
+
+/// Reads a packet body the way postcard lays it out: enums as a varint
+/// variant index, integers wider than a byte as LEB128 varints, and byte
+/// slices as a varint length and the bytes
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        let (&first, rest) = self.buf.split_first()?;
+        self.buf = rest;
+        Some(first)
+    }
+
+    fn varint(&mut self) -> Option<u32> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7F) as u32).checked_shl(shift)?;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.varint()? as usize;
+        if len > self.buf.len() {
+            return None;
+        }
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Some(bytes)
+    }
+}
+
+impl<'a> Packet<'a> {
+    /// Read a packet body, CRC already checked and stripped
+    pub fn decode(body: &'a [u8]) -> Result<Self, Nack> {
+        let mut reader = Reader { buf: body };
+        let malformed = Nack::Malformed;
+        let seq = reader.byte().ok_or(malformed)?;
+        let request = match reader.varint().ok_or(malformed)? {
+            0 => Request::Ping,
+            1 => {
+                let start = reader.varint().ok_or(malformed)?;
+                let start = u16::try_from(start).map_err(|_| Nack::OutOfRange)?;
+                let pixels = reader.bytes().ok_or(malformed)?;
+                if !pixels.len().is_multiple_of(3) {
+                    return Err(malformed);
+                }
+                Request::Frame { start, pixels }
+            }
+            2 => Request::SetBrightness(reader.byte().ok_or(malformed)?),
+            3 => Request::SetEffect(reader.byte().ok_or(malformed)?),
+            4 => Request::GetStatus,
+            _ => return Err(malformed),
+        };
+        if !reader.buf.is_empty() {
+            return Err(malformed);
+        }
+        Ok(Packet { seq, request })
+    }
+}
+
+/// Encode a reply as a complete frame, a leading and trailing 0x00 and the
+/// COBS-encoded body and CRC between them; returns its length
+pub fn encode_reply(seq: u8, reply: &Reply, out: &mut [u8; MAX_REPLY]) -> usize {
+    let mut body = [0; 10];
+    body[0] = seq;
+    let len = match *reply {
+        Reply::Ack => {
+            body[1] = 0;
+            2
+        }
+        Reply::Nack(nack) => {
+            body[1..3].copy_from_slice(&[1, nack as u8]);
+            3
+        }
+        Reply::Status(status) => {
+            body[1..5].copy_from_slice(&[2, status.effect, status.effects, status.brightness]);
+            // The LED count as a varint, at most three bytes for a u16
+            let mut len = 5;
+            let mut leds = status.leds;
+            while leds >= 0x80 {
+                body[len] = leds as u8 | 0x80;
+                leds >>= 7;
+                len += 1;
+            }
+            body[len] = leds as u8;
+            len + 1
+        }
+    };
+    let crc = crc16(&body[..len]);
+    body[len..len + 2].copy_from_slice(&crc.to_le_bytes());
+
+    out[0] = 0;
+    // Ten bytes encode to at most eleven, so this always fits
+    let encoded = cobs_encode(&body[..len + 2], &mut out[1..]).unwrap_or(0);
+    out[1 + encoded] = 0;
+    encoded + 2
+}
+
+/// What [`PacketReader::push`] made of a byte
+#[derive(Debug, PartialEq, Eq)]
+pub enum PacketEvent<'a> {
+    /// Not part of a packet
+    Pass,
+    /// Part of a packet still coming in
+    Taken,
+    /// The delimiter after a packet: the packet, or the sequence number to
+    /// refuse and why
+    Packet(Result<Packet<'a>, (u8, Nack)>),
+}
+
+/// Picks binary packets out of a serial stream
+///
+/// A packet is its body and a little-endian [`crc16`] of the body, COBS
+/// encoded, with a 0x00 on each side; back-to-back packets can share
+/// one. The body is a postcard-encoded `Packet`, so a host written in Rust
+/// can serialize the same types with postcard and frame them with
+/// `cobs` and `crc`. Text never contains 0x00, so nothing is taken until
+/// the first delimiter.
+///
+/// ```ignore
+/// if let PacketEvent::Packet(packet) = packets.push(byte, now_ms) {
+///     let reply = match packet { /* act on it */ };
+///     serial.write(&reply);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PacketReader {
+    buf: [u8; MAX_PACKET_LEN],
+    len: usize,
+    overflow: bool,
+    /// Time of the last byte, while between delimiters
+    active_ms: Option<u32>,
+}
+
+impl PacketReader {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MAX_PACKET_LEN],
+            len: 0,
+            overflow: false,
+            active_ms: None,
+        }
+    }
+
+    /// Whether a delimiter has come and the bytes since are being kept,
+    /// until the next or a [`PACKET_TIMEOUT_MS`] pause
+    pub fn in_frame(&self, now_ms: u32) -> bool {
+        self.active_ms
+            .is_some_and(|ms| now_ms.wrapping_sub(ms) <= PACKET_TIMEOUT_MS)
+    }
+
+    pub fn push(&mut self, byte: u8, now_ms: u32) -> PacketEvent<'_> {
+        if !self.in_frame(now_ms) {
+            self.active_ms = None;
+            self.len = 0;
+            self.overflow = false;
+            if byte != 0 {
+                return PacketEvent::Pass;
+            }
+        }
+        self.active_ms = Some(now_ms);
+
+        if byte != 0 {
+            match self.buf.get_mut(self.len) {
+                Some(slot) => {
+                    *slot = byte;
+                    self.len += 1;
+                }
+                None => self.overflow = true,
+            }
+            return PacketEvent::Taken;
+        }
+
+        let len = core::mem::take(&mut self.len);
+        if core::mem::take(&mut self.overflow) {
+            // Still encoded, but a first block longer than one byte starts
+            // with the sequence number
+            let seq = if self.buf[0] > 1 { self.buf[1] } else { 0 };
+            return PacketEvent::Packet(Err((seq, Nack::TooLong)));
+        }
+        // A leading delimiter, or padding
+        if len == 0 {
+            return PacketEvent::Taken;
+        }
+
+        let frame = &mut self.buf[..len];
+        let Some(len) = cobs_decode(frame).filter(|&len| len >= 3) else {
+            return PacketEvent::Packet(Err((0, Nack::Crc)));
+        };
+        let (body, crc) = frame[..len].split_at(len - 2);
+        // The first byte is the sequence number if anything is
+        let seq = body[0];
+        if crc16(body).to_le_bytes() != crc {
+            return PacketEvent::Packet(Err((seq, Nack::Crc)));
+        }
+        PacketEvent::Packet(Packet::decode(body).map_err(|nack| (seq, nack)))
+    }
+}
+
+impl Default for PacketReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
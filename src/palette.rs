@@ -0,0 +1,106 @@
+// 16-entry color palettes with interpolated lookup, after FastLED's
+
+use crate::pio_programs::Rgb;
+
+/// Sixteen colors spread evenly over a 0-255 index, wrapping back to the
+/// first after the last
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Palette16(pub [Rgb; 16]);
+
+const fn hex(c: u32) -> Rgb {
+    Rgb {
+        r: (c >> 16) as u8,
+        g: (c >> 8) as u8,
+        b: c as u8,
+    }
+}
+
+const fn palette(colors: [u32; 16]) -> Palette16 {
+    let mut entries = [Rgb { r: 0, g: 0, b: 0 }; 16];
+    let mut i = 0;
+    while i < 16 {
+        entries[i] = hex(colors[i]);
+        i += 1;
+    }
+    Palette16(entries)
+}
+
+/// Black through red and yellow to white, for fire
+pub const HEAT: Palette16 = palette([
+    0x000000, 0x330000, 0x660000, 0x990000, 0xCC0000, 0xFF0000, 0xFF3300, 0xFF6600, 0xFF9900,
+    0xFFCC00, 0xFFFF00, 0xFFFF33, 0xFFFF66, 0xFFFF99, 0xFFFFCC, 0xFFFFFF,
+]);
+
+/// Deep blues, teals and aqua
+pub const OCEAN: Palette16 = palette([
+    0x191970, 0x00008B, 0x191970, 0x000080, 0x00008B, 0x0000CD, 0x2E8B57, 0x008080, 0x5F9EA0,
+    0x0000FF, 0x008B8B, 0x6495ED, 0x7FFFD4, 0x2E8B57, 0x00FFFF, 0x87CEFA,
+]);
+
+/// Greens from dark forest to lawn
+pub const FOREST: Palette16 = palette([
+    0x006400, 0x006400, 0x556B2F, 0x006400, 0x008000, 0x228B22, 0x6B8E23, 0x008000, 0x2E8B57,
+    0x66CDAA, 0x32CD32, 0x9ACD32, 0x90EE90, 0x7CFC00, 0x66CDAA, 0x228B22,
+]);
+
+/// Saturated purples, reds and oranges, no greens
+pub const PARTY: Palette16 = palette([
+    0x5500AB, 0x84007C, 0xB5004B, 0xE5001B, 0xE81700, 0xB84700, 0xAB7700, 0xABAB00, 0xAB5500,
+    0xDD2200, 0xF2000E, 0xC2003E, 0x8F0071, 0x5F00A1, 0x2F00D0, 0x0007F9,
+]);
+
+/// Pacifica's two open-water layers, near black with blue-green crests
+pub const PACIFICA_1: Palette16 = palette([
+    0x000507, 0x000409, 0x00030B, 0x00030D, 0x000210, 0x000212, 0x000114, 0x000117, 0x000019,
+    0x00001C, 0x000026, 0x000031, 0x00003B, 0x000046, 0x14554B, 0x28AA50,
+]);
+pub const PACIFICA_2: Palette16 = palette([
+    0x000507, 0x000409, 0x00030B, 0x00030D, 0x000210, 0x000212, 0x000114, 0x000117, 0x000019,
+    0x00001C, 0x000026, 0x000031, 0x00003B, 0x000046, 0x0C5F52, 0x19BE5F,
+]);
+
+/// Pacifica's deeper, bluer layer
+pub const PACIFICA_3: Palette16 = palette([
+    0x000208, 0x00030E, 0x000514, 0x00061A, 0x000820, 0x000927, 0x000B2D, 0x000C33, 0x000E39,
+    0x001040, 0x001450, 0x001860, 0x001C70, 0x002080, 0x1040BF, 0x2060FF,
+]);
+
+/// Meter colors: green for most of the way, through yellow to red at the top
+pub const VU: Palette16 = palette([
+    0x00FF00, 0x00FF00, 0x00FF00, 0x00FF00, 0x00FF00, 0x00FF00, 0x00FF00, 0x7FFF00, 0xFFFF00,
+    0xFFFF00, 0xFFBF00, 0xFF8000, 0xFF4000, 0xFF0000, 0xFF0000, 0xFF0000,
+]);
+
+/// Thermometer colors: blue for cold through cyan, green and yellow to red
+pub const TEMPERATURE: Palette16 = palette([
+    0x0000FF, 0x0040FF, 0x0080FF, 0x00C0FF, 0x00FFFF, 0x00FFA0, 0x00FF40, 0x40FF00, 0xA0FF00,
+    0xFFFF00, 0xFFC000, 0xFF8000, 0xFF4000, 0xFF1000, 0xFF0000, 0xFF0000,
+]);
+
+/// Slate, indigo and bruised purple, for falling pressure
+pub const STORM: Palette16 = palette([
+    0x101020, 0x1A1A3A, 0x2A2050, 0x3A2A66, 0x2F3F5F, 0x1F2A44, 0x40406A, 0x50307A, 0x2A2A40,
+    0x1A2238, 0x303858, 0x4A4A70, 0x281E48, 0x1A1A30, 0x3A3A5A, 0x141428,
+]);
+
+/// Warm golds, ambers and sky blue, for rising pressure
+pub const SUNNY: Palette16 = palette([
+    0xFFB000, 0xFFC830, 0xFFD860, 0xFFA020, 0xFF8800, 0xFFC000, 0x87CEEB, 0x60B0FF, 0xFFE080,
+    0xFFB840, 0xFF9A10, 0xFFD000, 0xFFE8A0, 0x70C0F0, 0xFFC850, 0xFFA800,
+]);
+
+impl Palette16 {
+    /// The color at `index`, blended between the two nearest entries
+    pub fn lookup(&self, index: u8) -> Rgb {
+        let entry = (index >> 4) as usize;
+        let frac = (index & 0x0F) << 4;
+        let a = self.0[entry];
+        let b = self.0[(entry + 1) % 16];
+        a.lerp(b, frac)
+    }
+}
+
+/// Pick a color from `pal` at `index` and scale it by `brightness`
+pub fn color_from_palette(pal: &Palette16, index: u8, brightness: u8) -> Rgb {
+    pal.lookup(index).scale(brightness)
+}
@@ -1,7 +1,64 @@
 // PIO Programs for WS2812 NeoPixel control
 
 use pio_proc::pio_asm;
-use pio::Program;
+use pio::{Assembler, JmpCondition, OutDestination, Program, SideSet};
+use smart_leds_trait::RGB8;
+
+/// PIO cycles per bit of the `ws2812` program: 4 low, 2 high, then 5 more
+/// high for a one or low for a zero
+pub const WS2812_CYCLES_PER_BIT: u32 = 11;
+
+/// Phase lengths of a WS2812-style bit, in PIO cycles (1-8 each)
+///
+/// Every bit starts high for `t1`, stays high (one) or goes low (zero) for
+/// `t2`, then is low for `t3`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct BitCycles {
+    pub t1: u8,
+    pub t2: u8,
+    pub t3: u8,
+}
+
+impl BitCycles {
+    /// The phases of the stock `ws2812` program
+    pub const WS2812: BitCycles = BitCycles { t1: 2, t2: 5, t3: 4 };
+
+    pub const fn total(self) -> u32 {
+        self.t1 as u32 + self.t2 as u32 + self.t3 as u32
+    }
+}
+
+/// The `ws2812` program with custom phase lengths
+///
+/// Same instructions as `ws2812()`, with the delays taken from `cycles` so
+/// each chipset's high times can be matched. `inverted` swaps the side-set
+/// levels for chips like the TM1814 that idle high and pulse low.
+///
+/// With `mirrored` the signal goes out on two consecutive side-set pins, for
+/// the WS2813/WS2815 backup data input. Side-set is then mandatory rather
+/// than optional, which keeps the full 3-bit delay field.
+pub fn ws2812_timed(cycles: BitCycles, inverted: bool, mirrored: bool) -> Program<32> {
+    let (side_set, all_high) = if mirrored {
+        (SideSet::new(false, 2, false), 0b11)
+    } else {
+        (SideSet::new(true, 1, false), 0b1)
+    };
+    let (low, high) = if inverted { (all_high, 0) } else { (0, all_high) };
+    let mut a = Assembler::<32>::new_with_side_set(side_set);
+    let mut bitloop = a.label();
+    let mut do_zero = a.label();
+    let mut wrap_source = a.label();
+
+    a.bind(&mut bitloop);
+    a.out_with_delay_and_side_set(OutDestination::X, 1, cycles.t3 - 1, low);
+    a.jmp_with_delay_and_side_set(JmpCondition::XIsZero, &mut do_zero, cycles.t1 - 1, high);
+    a.jmp_with_delay_and_side_set(JmpCondition::Always, &mut bitloop, cycles.t2 - 1, high);
+    a.bind(&mut do_zero);
+    a.nop_with_delay_and_side_set(cycles.t2 - 1, low);
+    a.bind(&mut wrap_source);
+
+    a.assemble_with_wrap(wrap_source, bitloop)
+}
 
 /// WS2812 (NeoPixel) driver program
 /// Reads 24-bit RGB data from FIFO and outputs WS2812 protocol
@@ -25,8 +82,79 @@ pub fn ws2812() -> Program<32> {
     ).program
 }
 
+/// APA102 / SK9822 (DotStar) driver program
+/// A transmit-only SPI: clock on the side-set pin, data on the OUT pin
+///
+/// Each bit takes two PIO cycles, so the clock runs at half the PIO clock.
+/// With autopull at 32 bits the clock idles low between frames.
+pub fn apa102() -> Program<32> {
+    pio_asm!(
+        ".side_set 1",
+        ".wrap_target",
+        "    out pins, 1    side 0", // Stall here when no data (still asserts clock low)
+        "    nop            side 1", // Data is sampled on the rising edge
+        ".wrap",
+    ).program
+}
+
+/// I2S microphone receiver program (INMP441, SPH0645 and the like)
+/// Generates BCLK and WS on two side-set pins (BCLK, then WS) and samples
+/// the data pin on each rising edge of BCLK
+///
+/// Two PIO cycles per bit, 32 bits per channel. WS changes with the falling
+/// edge before each channel's last bit, as I2S expects. Only the left
+/// channel (L/R pin low) is read; with autopush at 32 bits, every word is
+/// one left sample, MSB first.
+pub fn i2s_mic() -> Program<32> {
+    pio_asm!(
+        ".side_set 2",                   // Bit 0 BCLK, bit 1 WS
+        ".wrap_target",
+        "    set x, 29      side 0b00",  // Left bit 0, falling edge
+        "left:",
+        "    in pins, 1     side 0b01",  // Sample on the rising edge
+        "    jmp x-- left   side 0b00",  // Falling edge of the next bit
+        "    in pins, 1     side 0b01",  // Bit 30
+        "    nop            side 0b10",  // Bit 31: WS high for the right channel
+        "    in pins, 1     side 0b11",
+        "    set x, 29      side 0b10",  // Right channel, clocked but not read
+        "right:",
+        "    nop            side 0b11",
+        "    jmp x-- right  side 0b10",
+        "    nop            side 0b11",
+        "    nop            side 0b00",  // WS low again for the next left channel
+        "    nop            side 0b01",
+        ".wrap",
+    ).program
+}
+
+/// HC-SR04 ultrasonic rangefinder program
+/// Each word written to the TX FIFO fires the trigger pin (SET) for 10 µs,
+/// then the echo pulse (IN base and JMP pin) is timed and its length pushed
+///
+/// Run at 2 MHz: the counting loop takes two cycles, so the result is in
+/// microseconds.
+pub fn hc_sr04() -> Program<32> {
+    pio_asm!(
+        ".wrap_target",
+        "    pull block",                // Wait for the CPU to ask
+        "    set pins, 1 [19]",          // 10 µs trigger pulse
+        "    set pins, 0",
+        "    mov x, ~null",              // Count down from 0xFFFFFFFF
+        "    wait 1 pin 0",              // Echo starts
+        "count:",
+        "    jmp pin high",
+        "    jmp done",                  // Echo over
+        "high:",
+        "    jmp x-- count",
+        "done:",
+        "    mov isr, ~x",               // Microseconds the echo was high
+        "    push noblock",
+        ".wrap",
+    ).program
+}
+
 /// RGB color structure for easy color handling
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
 pub struct Rgb {
     pub r: u8,
     pub g: u8,
@@ -55,6 +183,122 @@ impl Rgb {
     pub const MAGENTA: Rgb = Rgb { r: 255, g: 0, b: 255 };
 }
 
+/// RGBW color for strips with a dedicated white LED (SK6812 RGBW)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Rgbw {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub w: u8,
+}
+
+impl Rgbw {
+    pub fn new(r: u8, g: u8, b: u8, w: u8) -> Self {
+        Self { r, g, b, w }
+    }
+
+    /// Convert RGBW to the 32-bit format expected by SK6812 RGBW
+    /// SK6812 expects GRBW format (Green-Red-Blue-White)
+    pub fn to_grbw32(&self) -> u32 {
+        ((self.g as u32) << 24) | ((self.r as u32) << 16) | ((self.b as u32) << 8) | (self.w as u32)
+    }
+}
+
+/// An RGB color with the white LED off
+impl From<Rgb> for Rgbw {
+    fn from(c: Rgb) -> Self {
+        Self::new(c.r, c.g, c.b, 0)
+    }
+}
+
+impl From<RGB8> for Rgb {
+    fn from(c: RGB8) -> Self {
+        Self::new(c.r, c.g, c.b)
+    }
+}
+
+impl From<Rgb> for RGB8 {
+    fn from(c: Rgb) -> Self {
+        RGB8::new(c.r, c.g, c.b)
+    }
+}
+
+/// Order the color channels are sent in, most significant byte first
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum ColorOrder {
+    Rgb,
+    Rbg,
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+    Rgbw,
+    Grbw,
+    Wrgb,
+}
+
+impl ColorOrder {
+    /// Every order, under the name commands and documents give it
+    const NAMED: [(&'static str, ColorOrder); 9] = [
+        ("rgb", ColorOrder::Rgb),
+        ("rbg", ColorOrder::Rbg),
+        ("grb", ColorOrder::Grb),
+        ("gbr", ColorOrder::Gbr),
+        ("brg", ColorOrder::Brg),
+        ("bgr", ColorOrder::Bgr),
+        ("rgbw", ColorOrder::Rgbw),
+        ("grbw", ColorOrder::Grbw),
+        ("wrgb", ColorOrder::Wrgb),
+    ];
+
+    /// The order's name, lowercase: `grb`, `rgbw`, ...
+    pub fn name(self) -> &'static str {
+        Self::NAMED
+            .iter()
+            .find(|&&(_, o)| o == self)
+            .map_or("", |&(name, _)| name)
+    }
+
+    /// The order with this name, regardless of case
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        Self::NAMED
+            .iter()
+            .find(|(n, _)| name.eq_ignore_ascii_case(n.as_bytes()))
+            .map(|&(_, order)| order)
+    }
+
+    /// Whether pixels carry a fourth, white channel
+    pub fn has_white(self) -> bool {
+        matches!(self, ColorOrder::Rgbw | ColorOrder::Grbw | ColorOrder::Wrgb)
+    }
+
+    pub fn bits_per_pixel(self) -> u8 {
+        if self.has_white() {
+            32
+        } else {
+            24
+        }
+    }
+
+    /// Pack a pixel into the top bits of a word, for MSB-first shifting
+    ///
+    /// The white channel is dropped for three-channel orders.
+    pub fn pack(self, led: Rgbw) -> u32 {
+        let bytes = match self {
+            ColorOrder::Rgb => [led.r, led.g, led.b, 0],
+            ColorOrder::Rbg => [led.r, led.b, led.g, 0],
+            ColorOrder::Grb => [led.g, led.r, led.b, 0],
+            ColorOrder::Gbr => [led.g, led.b, led.r, 0],
+            ColorOrder::Brg => [led.b, led.r, led.g, 0],
+            ColorOrder::Bgr => [led.b, led.g, led.r, 0],
+            ColorOrder::Rgbw => [led.r, led.g, led.b, led.w],
+            ColorOrder::Grbw => [led.g, led.r, led.b, led.w],
+            ColorOrder::Wrgb => [led.w, led.r, led.g, led.b],
+        };
+        u32::from_be_bytes(bytes)
+    }
+}
+
 /// Generate a rainbow color based on position (0-255)
 pub fn rainbow(pos: u8) -> Rgb {
     match pos {
@@ -0,0 +1,10 @@
+// The chip support the drivers are built on, named in this one place
+
+/// The RP2040's HAL, by way of the Pico's BSP
+///
+/// Drivers take their PIO, DMA, GPIO and SPI types from here rather than
+/// naming the crate, and use only the parts rp2040-hal and rp235x-hal
+/// have in common, so the RP2350 is a matter of another HAL here rather
+/// than changes through the drivers. `flash` is the exception: it calls the
+/// RP2040's boot ROM, which the RP2350's replaces.
+pub use rp_pico::hal;
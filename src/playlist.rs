@@ -0,0 +1,151 @@
+// Cycles through a list of effects, for unattended demos
+
+use crate::easing::Easing;
+use crate::effects::Effect;
+use crate::pio_programs::Rgb;
+use crate::strip::LedStrip;
+
+/// One effect in a [`Playlist`] and how long it runs
+pub struct PlaylistEntry<'a> {
+    pub effect: &'a mut dyn Effect,
+    pub duration_ms: u32,
+}
+
+impl<'a> PlaylistEntry<'a> {
+    pub fn new(effect: &'a mut dyn Effect, duration_ms: u32) -> Self {
+        Self {
+            effect,
+            duration_ms,
+        }
+    }
+}
+
+/// How one entry hands over to the next
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Transition {
+    /// Switch straight over
+    Cut,
+    /// Crossfade over this many milliseconds
+    Fade(u32),
+}
+
+/// `E` effects over an `N`-pixel strip, each run for its duration in turn
+///
+/// It is itself an effect, so hand it to the engine. Each entry's effect sees
+/// time counted from when it came on and draws on its own canvas, so during
+/// a crossfade both keep animating.
+///
+/// ```ignore
+/// let (mut rainbow, mut fire) = (RainbowCycle::default(), Fire::<NUM_LEDS>::default());
+/// let mut demo = Playlist::<NUM_LEDS, 2>::new([
+///     PlaylistEntry::new(&mut rainbow, 10_000),
+///     PlaylistEntry::new(&mut fire, 20_000),
+/// ]);
+/// engine.tick(&mut demo, now_ms);
+/// ```
+pub struct Playlist<'a, const N: usize, const E: usize> {
+    entries: [PlaylistEntry<'a>; E],
+    pub transition: Transition,
+    /// Curve the crossfade follows
+    pub easing: Easing,
+    current: usize,
+    /// When the current entry came on, set by the first render after a switch
+    started_ms: Option<u32>,
+    /// The outgoing entry while a crossfade runs, and when it came on
+    previous: Option<(usize, u32)>,
+    skip: bool,
+    /// Current entry's canvas, then the outgoing one's
+    canvases: [LedStrip<N>; 2],
+}
+
+impl<'a, const N: usize, const E: usize> Playlist<'a, N, E> {
+    pub fn new(entries: [PlaylistEntry<'a>; E]) -> Self {
+        Self {
+            entries,
+            transition: Transition::Fade(1000),
+            easing: Easing::SineInOut,
+            current: 0,
+            started_ms: None,
+            previous: None,
+            skip: false,
+            canvases: [LedStrip::new(); 2],
+        }
+    }
+
+    pub fn transition(mut self, transition: Transition) -> Self {
+        self.transition = transition;
+        self
+    }
+
+    pub fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Index of the entry now playing
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Move on to the next entry at the next frame, without waiting out
+    /// this one's duration
+    pub fn next(&mut self) {
+        self.skip = true;
+    }
+
+    fn fade_ms(&self) -> u32 {
+        match self.transition {
+            Transition::Cut => 0,
+            Transition::Fade(ms) => ms,
+        }
+    }
+}
+
+impl<const N: usize, const E: usize> Effect for Playlist<'_, N, E> {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        if E == 0 {
+            frame.fill(Rgb::BLACK);
+            return;
+        }
+
+        let started = *self.started_ms.get_or_insert(t);
+        if self.skip || t.wrapping_sub(started) >= self.entries[self.current].duration_ms {
+            self.skip = false;
+            self.previous = Some((self.current, started));
+            self.current = (self.current + 1) % E;
+            self.started_ms = Some(t);
+            // The outgoing canvas carries on, the incoming starts dark
+            self.canvases.swap(0, 1);
+            self.canvases[0].clear();
+        }
+        let started = self.started_ms.unwrap_or(t);
+        let elapsed = t.wrapping_sub(started);
+
+        let fade_ms = self.fade_ms();
+        let len = frame.len().min(N);
+        let [incoming, outgoing] = &mut self.canvases;
+        self.entries[self.current]
+            .effect
+            .render(&mut incoming[..len], elapsed);
+
+        match self.previous {
+            Some((prev, prev_started)) if elapsed < fade_ms && prev != self.current => {
+                self.entries[prev]
+                    .effect
+                    .render(&mut outgoing[..len], t.wrapping_sub(prev_started));
+                let mix = self
+                    .easing
+                    .apply((elapsed as u64 * 255 / fade_ms as u64) as u8);
+                for ((out, &old), &new) in
+                    frame.iter_mut().zip(outgoing.iter()).zip(incoming.iter())
+                {
+                    *out = old.lerp(new, mix);
+                }
+            }
+            _ => {
+                self.previous = None;
+                frame[..len].copy_from_slice(&incoming[..len]);
+            }
+        }
+    }
+}
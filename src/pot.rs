@@ -0,0 +1,111 @@
+// Potentiometer input on an ADC channel, filtered down to a steady 0-255
+
+use embedded_hal::adc::{Channel, OneShot};
+
+use crate::color::Hsv;
+use crate::effects::{Effect, Param, ParamValue};
+use crate::engine::Engine;
+use crate::output::LedOutput;
+
+/// Dead band either side of the current level, in 12-bit ADC counts
+///
+/// One level is 16 counts; a reading must get this far past the middle of
+/// the current level before the level changes, so a knob resting on a
+/// boundary doesn't flicker between two.
+const HYSTERESIS: i32 = 12;
+
+/// A potentiometer wiper on an ADC pin (GPIO26-28 on the Pico)
+///
+/// Readings are smoothed with a running average and hysteresis, so only
+/// real movement of the knob changes the level.
+///
+/// ```ignore
+/// let mut adc = Adc::new(pac.ADC, &mut pac.RESETS);
+/// let mut pot = Potentiometer::new(AdcPin::new(pins.gpio26.into_floating_input()));
+/// if let Some(level) = pot.read(&mut adc) {
+///     engine.set_brightness(level);
+/// }
+/// ```
+pub struct Potentiometer<PIN> {
+    pin: PIN,
+    /// Running average of the 12-bit readings, with 4 fraction bits
+    filtered: Option<u32>,
+    level: u8,
+}
+
+impl<PIN> Potentiometer<PIN> {
+    pub fn new(pin: PIN) -> Self {
+        Self {
+            pin,
+            filtered: None,
+            level: 0,
+        }
+    }
+
+    /// Last reported level
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// Take a reading; returns the new level if the knob has moved
+    pub fn read<ADC, A>(&mut self, adc: &mut A) -> Option<u8>
+    where
+        PIN: Channel<ADC>,
+        A: OneShot<ADC, u16, PIN>,
+    {
+        let sample = (nb::block!(adc.read(&mut self.pin)).ok()? as u32).min(4095) << 4;
+        let filtered = match self.filtered {
+            Some(f) => f - f / 8 + sample / 8,
+            // First reading goes straight through
+            None => {
+                self.filtered = Some(sample);
+                self.level = (sample >> 8) as u8;
+                return Some(self.level);
+            }
+        };
+        self.filtered = Some(filtered);
+
+        let counts = (filtered >> 4) as i32;
+        if (counts - (self.level as i32 * 16 + 8)).abs() <= HYSTERESIS {
+            return None;
+        }
+        self.level = (counts >> 4) as u8;
+        Some(self.level)
+    }
+
+    pub fn free(self) -> PIN {
+        self.pin
+    }
+}
+
+/// What a potentiometer controls
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum PotTarget {
+    /// The engine's master brightness
+    Brightness,
+    /// The running effect's `Speed`
+    Speed,
+    /// The hue of the running effect's `Primary` color, fully saturated
+    Hue,
+}
+
+impl PotTarget {
+    /// Set the target to `level`
+    pub fn apply<O: LedOutput, const N: usize>(
+        self,
+        level: u8,
+        engine: &mut Engine<O, N>,
+        effect: &mut dyn Effect,
+    ) {
+        match self {
+            PotTarget::Brightness => engine.set_brightness(level),
+            PotTarget::Speed => {
+                effect.set_param(Param::Speed, ParamValue::Level(level));
+            }
+            PotTarget::Hue => {
+                let color = Hsv::new(level, 255, 255).to_rgb();
+                effect.set_param(Param::Primary, ParamValue::Color(color));
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+// Preset slots: whole looks, saved and recalled in one go
+
+use crate::effects::{Effect, Param, ParamValue};
+use crate::pio_programs::Rgb;
+use crate::store::{self, PRESET_KEYS};
+use crate::wled::PALETTES;
+
+/// How many presets there are
+pub const PRESETS: usize = 8;
+
+/// Bytes a preset takes in the store
+const LEN: usize = 12;
+
+/// Which of the optional fields a slot holds
+const SPEED: u8 = 1 << 0;
+const INTENSITY: u8 = 1 << 1;
+const PRIMARY: u8 = 1 << 2;
+const SECONDARY: u8 = 1 << 3;
+const PALETTE: u8 = 1 << 4;
+
+/// A snapshot of the brightness, the running effect and its parameters
+///
+/// Each slot is a record in the [`store`], so a slot never saved or torn
+/// mid-write reads as empty and leaves the others be. Parameters the effect
+/// doesn't have are left out, and a palette is kept only if it's one of
+/// [`PALETTES`].
+///
+/// ```ignore
+/// Preset::capture(engine.brightness(), current, &*effects[current]).save(slot);
+/// if let Some(preset) = Preset::load(slot) {
+///     current = preset.apply(&mut effects);
+///     engine.set_brightness(preset.brightness);
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Preset {
+    pub brightness: u8,
+    /// Effect number, counting from 0
+    pub effect: u8,
+    pub speed: Option<u8>,
+    pub intensity: Option<u8>,
+    pub primary: Option<Rgb>,
+    pub secondary: Option<Rgb>,
+    /// Index into [`PALETTES`]
+    pub palette: Option<u8>,
+}
+
+impl Preset {
+    /// What's showing now
+    pub fn capture(brightness: u8, effect: usize, running: &dyn Effect) -> Self {
+        let level = |param| match running.param(param) {
+            Some(ParamValue::Level(level)) => Some(level),
+            _ => None,
+        };
+        let color = |param| match running.param(param) {
+            Some(ParamValue::Color(color)) => Some(color),
+            _ => None,
+        };
+        let palette = match running.param(Param::Palette) {
+            Some(ParamValue::Palette(palette)) => PALETTES
+                .iter()
+                .position(|(_, known)| **known == *palette)
+                .map(|i| i as u8),
+            _ => None,
+        };
+        Self {
+            brightness,
+            effect: effect as u8,
+            speed: level(Param::Speed),
+            intensity: level(Param::Intensity),
+            primary: color(Param::Primary),
+            secondary: color(Param::Secondary),
+            palette,
+        }
+    }
+
+    /// The parameters to set on the effect, each once
+    pub fn params(&self) -> impl Iterator<Item = (Param, ParamValue)> {
+        let level = |param, level: Option<u8>| level.map(|l| (param, ParamValue::Level(l)));
+        let color = |param, color: Option<Rgb>| color.map(|c| (param, ParamValue::Color(c)));
+        let palette = self
+            .palette
+            .and_then(|i| PALETTES.get(i as usize))
+            .map(|(_, palette)| (Param::Palette, ParamValue::Palette(palette)));
+        [
+            level(Param::Speed, self.speed),
+            level(Param::Intensity, self.intensity),
+            color(Param::Primary, self.primary),
+            color(Param::Secondary, self.secondary),
+            palette,
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Set the preset's parameters on its effect, returning the effect's
+    /// number to run; the last effect for one that's not in this build
+    pub fn apply(&self, effects: &mut [&mut dyn Effect]) -> usize {
+        let effect = (self.effect as usize).min(effects.len() - 1);
+        for (param, value) in self.params() {
+            effects[effect].set_param(param, value);
+        }
+        effect
+    }
+
+    /// The stored bytes: effect, brightness, a bit for each field present,
+    /// then the fields
+    fn encode(&self) -> [u8; LEN] {
+        let mut slot = [0xFF; LEN];
+        let mut present = 0;
+        let mut set = |bit: u8, at: usize, bytes: &[u8]| {
+            present |= bit;
+            slot[at..at + bytes.len()].copy_from_slice(bytes);
+        };
+        if let Some(speed) = self.speed {
+            set(SPEED, 3, &[speed]);
+        }
+        if let Some(intensity) = self.intensity {
+            set(INTENSITY, 4, &[intensity]);
+        }
+        if let Some(Rgb { r, g, b }) = self.primary {
+            set(PRIMARY, 5, &[r, g, b]);
+        }
+        if let Some(Rgb { r, g, b }) = self.secondary {
+            set(SECONDARY, 8, &[r, g, b]);
+        }
+        if let Some(palette) = self.palette {
+            set(PALETTE, 11, &[palette]);
+        }
+        slot[..3].copy_from_slice(&[self.effect, self.brightness, present]);
+        slot
+    }
+
+    fn decode(slot: &[u8]) -> Option<Self> {
+        if slot.len() < LEN {
+            return None;
+        }
+        let present = slot[2];
+        let field = |bit: u8, at: usize| (present & bit != 0).then_some(slot[at]);
+        let color = |bit: u8, at: usize| {
+            (present & bit != 0).then(|| Rgb::new(slot[at], slot[at + 1], slot[at + 2]))
+        };
+        Some(Self {
+            effect: slot[0],
+            brightness: slot[1],
+            speed: field(SPEED, 3),
+            intensity: field(INTENSITY, 4),
+            primary: color(PRIMARY, 5),
+            secondary: color(SECONDARY, 8),
+            palette: field(PALETTE, 11),
+        })
+    }
+
+    /// What's saved in `slot`, None if it's empty or past the last
+    pub fn load(slot: usize) -> Option<Self> {
+        if slot >= PRESETS {
+            return None;
+        }
+        store::read(PRESET_KEYS + slot as u8).and_then(Self::decode)
+    }
+
+    /// The next saved preset after `from`, going round, or before it going
+    /// back; None if nothing's saved
+    pub fn step(from: Option<usize>, forward: bool) -> Option<(usize, Self)> {
+        let start = from.unwrap_or(if forward { PRESETS - 1 } else { 0 });
+        (1..=PRESETS)
+            .map(|i| {
+                if forward {
+                    (start + i) % PRESETS
+                } else {
+                    (start + PRESETS - i) % PRESETS
+                }
+            })
+            .find_map(|slot| Some((slot, Self::load(slot)?)))
+    }
+
+    /// Keep the preset in `slot`; nothing's written when it's already there
+    ///
+    /// # Panics
+    ///
+    /// If `slot` is past the last.
+    pub fn save(&self, slot: usize) {
+        assert!(slot < PRESETS);
+        store::write(PRESET_KEYS + slot as u8, &self.encode());
+    }
+}
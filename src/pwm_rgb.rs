@@ -0,0 +1,80 @@
+// Non-addressable 12V analog RGB strips on three PWM channels
+
+use embedded_hal::PwmPin;
+
+use crate::output::LedOutput;
+use crate::pio_programs::Rgb;
+
+/// Analog RGB strip switched by three PWM channels, through MOSFETs or a
+/// driver board
+///
+/// The whole strip is one pixel, so it shows the first color of a frame.
+/// Any channels implementing `PwmPin` work; with the RP2040 HAL that's a
+/// channel from each of three slices, or two channels sharing one.
+///
+/// ```ignore
+/// let mut pwm = Slices::new(pac.PWM, &mut pac.RESETS);
+/// pwm.pwm1.enable();
+/// pwm.pwm2.enable();
+/// pwm.pwm1.channel_a.output_to(pins.gpio2);
+/// pwm.pwm1.channel_b.output_to(pins.gpio3);
+/// pwm.pwm2.channel_a.output_to(pins.gpio4);
+/// let analog = PwmRgb::new(pwm.pwm1.channel_a, pwm.pwm1.channel_b, pwm.pwm2.channel_a);
+/// ```
+pub struct PwmRgb<R, G, B> {
+    r: R,
+    g: G,
+    b: B,
+}
+
+impl<R, G, B> PwmRgb<R, G, B>
+where
+    R: PwmPin<Duty = u16>,
+    G: PwmPin<Duty = u16>,
+    B: PwmPin<Duty = u16>,
+{
+    /// Take the three channels and enable them, starting dark
+    pub fn new(mut r: R, mut g: G, mut b: B) -> Self {
+        r.set_duty(0);
+        g.set_duty(0);
+        b.set_duty(0);
+        r.enable();
+        g.enable();
+        b.enable();
+        Self { r, g, b }
+    }
+
+    /// Set the strip's color
+    pub fn set_color(&mut self, color: Rgb) {
+        set_level(&mut self.r, color.r);
+        set_level(&mut self.g, color.g);
+        set_level(&mut self.b, color.b);
+    }
+
+    /// Hand the channels back
+    pub fn free(self) -> (R, G, B) {
+        (self.r, self.g, self.b)
+    }
+}
+
+/// Scale an 8-bit level to the channel's duty range
+fn set_level(pin: &mut impl PwmPin<Duty = u16>, level: u8) {
+    let duty = level as u32 * pin.get_max_duty() as u32 / 255;
+    pin.set_duty(duty as u16);
+}
+
+impl<R, G, B> LedOutput for PwmRgb<R, G, B>
+where
+    R: PwmPin<Duty = u16>,
+    G: PwmPin<Duty = u16>,
+    B: PwmPin<Duty = u16>,
+{
+    fn write_pixels(&mut self, mut pixels: impl Iterator<Item = Rgb>) {
+        self.set_color(pixels.next().unwrap_or(Rgb::BLACK));
+    }
+
+    /// PWM takes the new duty at the end of the current period, nothing queues
+    fn is_busy(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,124 @@
+// WLED's UDP realtime protocols: raw pixels in a datagram, as LedFx,
+// Hyperion and plenty of scripts send them
+
+use crate::host::HostFrame;
+use crate::pio_programs::Rgb;
+
+/// UDP port WLED takes realtime data on, shared with its sync packets
+pub const REALTIME_PORT: u16 = 21324;
+
+/// Timeout for packets that give 0, as WLED uses for DDP and E1.31
+pub const DEFAULT_TIMEOUT_MS: u32 = 2500;
+
+/// The timeout byte that holds the stream until something else takes over
+const NO_TIMEOUT: u8 = 255;
+
+const WARLS: u8 = 1;
+const DRGB: u8 = 2;
+const DRGBW: u8 = 3;
+const DNRGB: u8 = 4;
+
+/// What [`RealtimeReceiver::receive`] made of a datagram
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum RealtimeEvent {
+    /// Not realtime data; WLED's own sync packets come to the same port
+    Ignored,
+    /// Pixels were written
+    Data,
+}
+
+/// Takes realtime packets: a protocol byte, a timeout in seconds, then
+/// pixels
+///
+/// - WARLS: index and RGB for each pixel to set, for up to 255 pixels
+/// - DRGB: RGB from the first pixel on, up to 490 pixels a packet
+/// - DRGBW: RGBW, the white dropped, since the strip is RGB
+/// - DNRGB: a 16-bit start pixel, then RGB from there, for longer strips
+///
+/// The sender picks how long the stream keeps the strip after its last
+/// packet: 255 holds it until something else takes over, and 0 means
+/// [`DEFAULT_TIMEOUT_MS`].
+///
+/// ```ignore
+/// if realtime.receive(datagram, now_ms, &host_frame) == RealtimeEvent::Data {
+///     current = host_effect;
+/// }
+/// if current == host_effect && !realtime.is_streaming(now_ms) {
+///     current = local_effect;
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct RealtimeReceiver {
+    last_ms: Option<u32>,
+    /// None holds the stream
+    timeout_ms: Option<u32>,
+}
+
+impl RealtimeReceiver {
+    pub const fn new() -> Self {
+        Self {
+            last_ms: None,
+            timeout_ms: Some(DEFAULT_TIMEOUT_MS),
+        }
+    }
+
+    /// Whether a stream has the strip, within the timeout its last packet
+    /// asked for
+    pub fn is_streaming(&self, now_ms: u32) -> bool {
+        self.last_ms.is_some_and(|ms| {
+            self.timeout_ms
+                .is_none_or(|timeout| now_ms.wrapping_sub(ms) <= timeout)
+        })
+    }
+
+    /// Let go of a stream held with timeout 255, when local control takes
+    /// over
+    pub fn release(&mut self) {
+        self.last_ms = None;
+    }
+
+    /// Take one UDP payload
+    pub fn receive<const N: usize>(
+        &mut self,
+        datagram: &[u8],
+        now_ms: u32,
+        frame: &HostFrame<N>,
+    ) -> RealtimeEvent {
+        let [protocol, timeout, data @ ..] = datagram else {
+            return RealtimeEvent::Ignored;
+        };
+        match *protocol {
+            WARLS => {
+                for pixel in data.chunks_exact(4) {
+                    frame.set(pixel[0] as usize, Rgb::new(pixel[1], pixel[2], pixel[3]));
+                }
+            }
+            DRGB => frame.write_rgb(0, data),
+            DRGBW => {
+                for (i, pixel) in data.chunks_exact(4).enumerate() {
+                    frame.set(i, Rgb::new(pixel[0], pixel[1], pixel[2]));
+                }
+            }
+            DNRGB => {
+                let [high, low, pixels @ ..] = data else {
+                    return RealtimeEvent::Ignored;
+                };
+                frame.write_rgb(u16::from_be_bytes([*high, *low]) as usize, pixels);
+            }
+            _ => return RealtimeEvent::Ignored,
+        }
+        self.last_ms = Some(now_ms);
+        self.timeout_ms = match *timeout {
+            NO_TIMEOUT => None,
+            0 => Some(DEFAULT_TIMEOUT_MS),
+            seconds => Some(seconds as u32 * 1000),
+        };
+        RealtimeEvent::Data
+    }
+}
+
+impl Default for RealtimeReceiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
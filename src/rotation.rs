@@ -0,0 +1,123 @@
+// Rotation timing from a once-a-turn sensor, for spinning (POV) strips
+
+use core::cell::Cell;
+
+/// Marks closer together than this are ignored, 12 000 RPM
+pub const MIN_PERIOD_US: u32 = 5_000;
+
+/// With no mark for this long the strip counts as stopped, 30 RPM
+pub const MAX_PERIOD_US: u32 = 2_000_000;
+
+/// One full turn in [`RotationTimer::angle`] units
+pub const FULL_TURN: u32 = 65_536;
+
+/// Times each turn of a spinning strip from one mark per revolution
+///
+/// A hall sensor (A3144, or any open-collector switch) on the frame with a
+/// magnet on the rotor, or the other way round, marks one point of the
+/// turn; the angle in between is worked out from the last turn's period.
+/// Call [`mark`](Self::mark) from the sensor's GPIO interrupt with a
+/// microsecond timestamp, so the marks are timed to the microsecond
+/// whatever the main loop is doing.
+///
+/// ```ignore
+/// // In the IO_IRQ_BANK0 handler
+/// pin.clear_interrupt(Interrupt::EdgeLow);
+/// timing.mark(timer.get_counter_low());
+/// // In the main loop
+/// rotation.update(&timing, timer.get_counter_low());
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RotationTimer {
+    last_us: Option<u32>,
+    period_us: u32,
+    revolutions: u32,
+}
+
+impl RotationTimer {
+    pub const fn new() -> Self {
+        Self {
+            last_us: None,
+            period_us: 0,
+            revolutions: 0,
+        }
+    }
+
+    /// The sensor saw the magnet at `now_us`; returns whether this counted
+    /// as a new turn rather than a bounce
+    pub fn mark(&mut self, now_us: u32) -> bool {
+        if let Some(last) = self.last_us {
+            let period = now_us.wrapping_sub(last);
+            if period < MIN_PERIOD_US {
+                return false;
+            }
+            // Restarting from a standstill, the first gap isn't a turn
+            self.period_us = if period <= MAX_PERIOD_US { period } else { 0 };
+        }
+        self.last_us = Some(now_us);
+        self.revolutions = self.revolutions.wrapping_add(1);
+        true
+    }
+
+    /// Length of the last turn, `None` when stopped
+    pub fn period_us(&self, now_us: u32) -> Option<u32> {
+        let last = self.last_us?;
+        if self.period_us == 0 || now_us.wrapping_sub(last) > MAX_PERIOD_US {
+            return None;
+        }
+        Some(self.period_us)
+    }
+
+    /// Revolutions per minute, 0 when stopped
+    pub fn rpm(&self, now_us: u32) -> u16 {
+        self.period_us(now_us).map_or(0, |period| {
+            (60_000_000 / period).min(u16::MAX as u32) as u16
+        })
+    }
+
+    /// How far round the strip is since the mark, out of [`FULL_TURN`];
+    /// holds just short of a full turn if the next mark is late
+    pub fn angle(&self, now_us: u32) -> Option<u16> {
+        let period = self.period_us(now_us)?;
+        let since = now_us.wrapping_sub(self.last_us?) as u64;
+        Some((since * FULL_TURN as u64 / period as u64).min(FULL_TURN as u64 - 1) as u16)
+    }
+
+    /// Marks counted so far
+    pub fn revolutions(&self) -> u32 {
+        self.revolutions
+    }
+}
+
+/// Where a spinning strip is in its turn, shared with the effects drawing
+/// on it
+#[derive(Debug, Default)]
+pub struct Rotation {
+    angle: Cell<Option<u16>>,
+    rpm: Cell<u16>,
+}
+
+impl Rotation {
+    pub const fn new() -> Self {
+        Self {
+            angle: Cell::new(None),
+            rpm: Cell::new(0),
+        }
+    }
+
+    /// Position in the turn out of [`FULL_TURN`], `None` when stopped
+    pub fn angle(&self) -> Option<u16> {
+        self.angle.get()
+    }
+
+    /// Revolutions per minute, 0 when stopped
+    pub fn rpm(&self) -> u16 {
+        self.rpm.get()
+    }
+
+    /// Take the position at `now_us` from the timer
+    pub fn update(&self, timing: &RotationTimer, now_us: u32) {
+        self.angle.set(timing.angle(now_us));
+        self.rpm.set(timing.rpm(now_us));
+    }
+}
@@ -0,0 +1,113 @@
+// Splits one strip into zones, each running its own effect
+
+use core::ops::Range;
+
+use crate::effects::{Effect, Param, ParamValue};
+use crate::palette::Palette16;
+use crate::pio_programs::Rgb;
+use crate::strip::LedStrip;
+
+/// Normal speed for [`Segment::speed`]
+pub const REAL_TIME: u16 = 256;
+
+/// A range of the physical strip with its own effect, brightness and speed
+pub struct Segment<'a> {
+    /// Pixels of the strip this segment covers, clipped to its length
+    pub range: Range<usize>,
+    pub effect: &'a mut dyn Effect,
+    pub brightness: u8,
+    /// How fast the segment's clock runs, [`REAL_TIME`] being real time
+    pub speed: u16,
+    last_t: Option<u32>,
+    /// Segment time in 1/256 ms, so slow speeds still advance
+    local_t: u64,
+}
+
+impl<'a> Segment<'a> {
+    pub fn new(range: Range<usize>, effect: &'a mut dyn Effect) -> Self {
+        Self {
+            range,
+            effect,
+            brightness: 255,
+            speed: REAL_TIME,
+            last_t: None,
+            local_t: 0,
+        }
+    }
+
+    pub fn brightness(mut self, brightness: u8) -> Self {
+        self.brightness = brightness;
+        self
+    }
+
+    pub fn speed(mut self, speed: u16) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Give the effect a palette, if it takes one
+    pub fn set_palette(&mut self, palette: &'static Palette16) -> bool {
+        self.effect
+            .set_param(Param::Palette, ParamValue::Palette(palette))
+    }
+
+    /// Advance this segment's clock to engine time `t`
+    fn advance(&mut self, t: u32) -> u32 {
+        let dt = self.last_t.map_or(0, |last| t.wrapping_sub(last));
+        self.last_t = Some(t);
+        self.local_t += dt as u64 * self.speed as u64;
+        (self.local_t >> 8) as u32
+    }
+}
+
+/// `S` segments over an `N`-pixel strip, rendered as one effect
+///
+/// Each segment's effect draws on its own undimmed canvas, so fades and
+/// trails carry on from what the effect drew rather than from the dimmed
+/// output. Later segments are drawn over earlier ones where they overlap;
+/// pixels outside every segment stay dark.
+///
+/// ```ignore
+/// let (mut fire, mut scanner) = (Fire::<20>::default(), Scanner::default());
+/// let mut zones = Segments::<NUM_LEDS, 2>::new([
+///     Segment::new(0..20, &mut fire),
+///     Segment::new(20..30, &mut scanner).brightness(96).speed(REAL_TIME / 2),
+/// ]);
+/// engine.tick(&mut zones, now_ms);
+/// ```
+pub struct Segments<'a, const N: usize, const S: usize> {
+    segments: [Segment<'a>; S],
+    canvas: LedStrip<N>,
+}
+
+impl<'a, const N: usize, const S: usize> Segments<'a, N, S> {
+    pub fn new(segments: [Segment<'a>; S]) -> Self {
+        Self {
+            segments,
+            canvas: LedStrip::new(),
+        }
+    }
+
+    pub fn segments_mut(&mut self) -> &mut [Segment<'a>; S] {
+        &mut self.segments
+    }
+}
+
+impl<const N: usize, const S: usize> Effect for Segments<'_, N, S> {
+    fn render(&mut self, frame: &mut [Rgb], t: u32) {
+        frame.fill(Rgb::BLACK);
+        let len = frame.len().min(N);
+        for segment in self.segments.iter_mut() {
+            let range = segment.range.start.min(len)..segment.range.end.min(len);
+            if range.is_empty() {
+                continue;
+            }
+            let t = segment.advance(t);
+            let canvas = &mut self.canvas[range.clone()];
+            segment.effect.render(canvas, t);
+            for (out, &led) in frame[range].iter_mut().zip(canvas.iter()) {
+                *out = led.scale(segment.brightness);
+            }
+        }
+    }
+}
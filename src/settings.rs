@@ -0,0 +1,97 @@
+// Settings kept in flash across power cycles
+
+use crate::pio_programs::{ColorOrder, Rgb};
+use crate::store::{self, SETTINGS_KEY};
+
+/// Bytes the settings take in the store
+const LEN: usize = 8;
+
+/// Channel orders by their number in a record
+const ORDERS: [ColorOrder; 9] = [
+    ColorOrder::Rgb,
+    ColorOrder::Rbg,
+    ColorOrder::Grb,
+    ColorOrder::Gbr,
+    ColorOrder::Brg,
+    ColorOrder::Bgr,
+    ColorOrder::Rgbw,
+    ColorOrder::Grbw,
+    ColorOrder::Wrgb,
+];
+
+/// What's restored at boot
+///
+/// They're one record in the [`store`], which checks each, so a blank
+/// store or a torn write reads as no settings, and the defaults.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Settings {
+    pub brightness: u8,
+    /// Effect number, counting from 0
+    pub effect: u8,
+    /// The strip's channel order, None for the chipset's
+    pub color_order: Option<ColorOrder>,
+    /// Strip length, which the engine caps at its buffers'
+    pub leds: u16,
+    /// Per-channel multipliers evening out the strip's LEDs
+    pub calibration: Rgb,
+}
+
+impl Settings {
+    /// The settings before anything's saved
+    pub const fn new(leds: u16) -> Self {
+        Self {
+            brightness: 255,
+            effect: 0,
+            color_order: None,
+            leds,
+            calibration: Rgb::WHITE,
+        }
+    }
+
+    /// The settings as they're stored
+    pub fn encode(&self) -> [u8; LEN] {
+        let order = match self.color_order {
+            Some(order) => ORDERS.iter().position(|&o| o == order).unwrap_or(0) as u8,
+            None => 0xFF,
+        };
+        let [leds_low, leds_high] = self.leds.to_le_bytes();
+        let Rgb { r, g, b } = self.calibration;
+        [
+            self.brightness,
+            self.effect,
+            order,
+            leds_low,
+            leds_high,
+            r,
+            g,
+            b,
+        ]
+    }
+
+    /// The settings in a stored value, None if it's too short
+    pub fn decode(value: &[u8]) -> Option<Self> {
+        // A value written by later firmware may be longer; the fields known
+        // here come first
+        let [brightness, effect, order, leds_low, leds_high, r, g, b, ..] = *value else {
+            return None;
+        };
+        Some(Self {
+            brightness,
+            effect,
+            color_order: ORDERS.get(order as usize).copied(),
+            leds: u16::from_le_bytes([leds_low, leds_high]),
+            calibration: Rgb::new(r, g, b),
+        })
+    }
+
+    /// What was last saved, if anything
+    pub fn load() -> Option<Self> {
+        store::read(SETTINGS_KEY).and_then(Self::decode)
+    }
+
+    /// Keep the settings for the next power-up; nothing's written when
+    /// they're unchanged
+    pub fn save(&self) {
+        store::write(SETTINGS_KEY, &self.encode());
+    }
+}
@@ -0,0 +1,116 @@
+// SNTP client: the time of day from a network time server, for the wall
+// clock
+
+use crate::clock::{Date, TimeOfDay, DAY_MS};
+
+/// UDP port time servers answer on
+pub const NTP_PORT: u16 = 123;
+
+/// Length of a request and of the part of a reply that's read
+pub const NTP_PACKET_LEN: usize = 48;
+
+/// How often to sync once synced; the timer drifts far less than a second
+/// in this time
+pub const SYNC_INTERVAL_MS: u32 = 3_600_000;
+
+/// How long to wait for an answer before asking again
+pub const RETRY_MS: u32 = 15_000;
+
+/// Seconds from 1900, where NTP counts from, to 1970
+const UNIX_EPOCH: u64 = 2_208_988_800;
+
+/// Version 4, client mode
+const CLIENT_REQUEST: u8 = 0x23;
+const MODE_SERVER: u8 = 4;
+/// Leap indicator for a server that isn't synchronized itself
+const LEAP_UNSYNCHRONIZED: u8 = 3;
+
+/// Asks a time server for the time, now and then, and reads its answers
+///
+/// The request's transmit timestamp is a count of requests rather than
+/// the time (which isn't known yet); an answer has to carry it back, which
+/// shuts out stray and spoofed packets. Half the round trip is added to
+/// the server's time. Kiss-of-death replies and unsynchronized servers
+/// are ignored, and the request repeated after [`RETRY_MS`].
+///
+/// ```ignore
+/// if sntp.is_due(now_ms) {
+///     send_to(server, NTP_PORT, sntp.request(now_ms, &mut packet));
+/// }
+/// if let Some((date, time)) = sntp.receive(datagram, now_ms) {
+///     clock.set(now_ms, date, time);
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SntpClient {
+    requests: u32,
+    /// When the outstanding request went
+    sent_ms: Option<u32>,
+    synced_ms: Option<u32>,
+}
+
+impl SntpClient {
+    pub const fn new() -> Self {
+        Self {
+            requests: 0,
+            sent_ms: None,
+            synced_ms: None,
+        }
+    }
+
+    /// Whether it's time to send a request: never synced, an hour since
+    /// the last sync, or no answer to the last request
+    pub fn is_due(&self, now_ms: u32) -> bool {
+        match (self.sent_ms, self.synced_ms) {
+            (Some(sent), _) => now_ms.wrapping_sub(sent) >= RETRY_MS,
+            (None, Some(synced)) => now_ms.wrapping_sub(synced) >= SYNC_INTERVAL_MS,
+            (None, None) => true,
+        }
+    }
+
+    pub fn request<'a>(&mut self, now_ms: u32, out: &'a mut [u8; NTP_PACKET_LEN]) -> &'a [u8] {
+        self.requests = self.requests.wrapping_add(1);
+        self.sent_ms = Some(now_ms);
+        out.fill(0);
+        out[0] = CLIENT_REQUEST;
+        out[44..48].copy_from_slice(&self.requests.to_be_bytes());
+        out
+    }
+
+    /// Take one UDP payload: the UTC date and time as of `now_ms`, if it's
+    /// the answer to the last request
+    pub fn receive(&mut self, datagram: &[u8], now_ms: u32) -> Option<(Date, TimeOfDay)> {
+        let sent_ms = self.sent_ms?;
+        let packet = datagram.get(..NTP_PACKET_LEN)?;
+        let stratum = packet[1];
+        if packet[0] & 0x07 != MODE_SERVER
+            || packet[0] >> 6 == LEAP_UNSYNCHRONIZED
+            || !(1..=15).contains(&stratum)
+            || packet[24..28] != [0; 4]
+            || packet[28..32] != self.requests.to_be_bytes()
+        {
+            return None;
+        }
+        let seconds = u32::from_be_bytes(packet[40..44].try_into().unwrap());
+        let fraction = u32::from_be_bytes(packet[44..48].try_into().unwrap());
+        if seconds == 0 {
+            return None;
+        }
+        self.sent_ms = None;
+        self.synced_ms = Some(now_ms);
+
+        // Timestamps wrap in 2036; anything before 1970 is after that
+        let mut seconds = seconds as u64;
+        if seconds < UNIX_EPOCH {
+            seconds += 1 << 32;
+        }
+        let ms = (seconds - UNIX_EPOCH) * 1000
+            + ((fraction as u64 * 1000) >> 32)
+            + now_ms.wrapping_sub(sent_ms) as u64 / 2;
+        let days = ms / DAY_MS as u64;
+        Some((
+            Date::from_unix_days(days as u32),
+            TimeOfDay((ms % DAY_MS as u64) as u32),
+        ))
+    }
+}
@@ -0,0 +1,145 @@
+// HC-SR04 ultrasonic distance sensor, timed by a PIO state machine
+
+use core::cell::Cell;
+
+use crate::platform::hal::{
+    gpio::{Function, Pin, PinId, PullType, ValidFunction},
+    pio::{
+        PIOBuilder, PIOExt, PinDir, Running, Rx, StateMachine, StateMachineIndex, Tx,
+        UninitStateMachine, PIO,
+    },
+};
+
+use crate::driver::{fixed_point_divisor, into_data_pin, DataPin};
+use crate::pio_programs;
+
+/// Time between measurements; the datasheet asks for at least 60 ms so one
+/// ping's echoes die down before the next
+pub const MEASURE_INTERVAL_MS: u32 = 60;
+
+/// Farthest the sensor reliably sees; beyond this it reports nothing
+pub const MAX_RANGE_MM: u16 = 4000;
+
+/// The PIO program's counting loop takes two cycles, one count a microsecond
+const PIO_CLOCK_HZ: u32 = 2_000_000;
+
+/// Configures a state machine to run an HC-SR04
+///
+/// The HC-SR04 is a 5 V part and its echo output swings to 5 V: put a
+/// divider (1k over 2k) on the echo line, or use the 3.3 V HC-SR04P.
+///
+/// ```ignore
+/// let mut sonar = SonarBuilder::new(&mut pio, sm2, pins.gpio6, pins.gpio7, sys_clk_hz).build();
+/// if let Some(Some(mm)) = sonar.poll(now_ms) {
+///     info!("{} mm", mm);
+/// }
+/// ```
+pub struct SonarBuilder<'a, P: PIOExt, SM: StateMachineIndex> {
+    pio: &'a mut PIO<P>,
+    sm: UninitStateMachine<(P, SM)>,
+    trigger: DataPin<P>,
+    echo: DataPin<P>,
+    sys_clk_hz: u32,
+}
+
+impl<'a, P: PIOExt, SM: StateMachineIndex> SonarBuilder<'a, P, SM> {
+    pub fn new<TI, TF, TP, EI, EF, EP>(
+        pio: &'a mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        trigger: Pin<TI, TF, TP>,
+        echo: Pin<EI, EF, EP>,
+        sys_clk_hz: u32,
+    ) -> Self
+    where
+        TI: PinId + ValidFunction<P::PinFunction>,
+        TF: Function,
+        TP: PullType,
+        EI: PinId + ValidFunction<P::PinFunction>,
+        EF: Function,
+        EP: PullType,
+    {
+        Self {
+            pio,
+            sm,
+            trigger: into_data_pin::<P, _, _, _>(trigger),
+            echo: into_data_pin::<P, _, _, _>(echo),
+            sys_clk_hz,
+        }
+    }
+
+    pub fn build(self) -> Sonar<P, SM> {
+        let trigger_id = self.trigger.id().num;
+        let echo_id = self.echo.id().num;
+        let installed = self.pio.install(&pio_programs::hc_sr04()).unwrap();
+        let (div_int, div_frac) = fixed_point_divisor(self.sys_clk_hz, PIO_CLOCK_HZ);
+
+        let (mut sm, rx, tx) = PIOBuilder::from_program(installed)
+            .set_pins(trigger_id, 1)
+            .in_pin_base(echo_id)
+            .jmp_pin(echo_id)
+            .clock_divisor_fixed_point(div_int, div_frac)
+            .build(self.sm);
+        sm.set_pindirs([(trigger_id, PinDir::Output), (echo_id, PinDir::Input)]);
+
+        Sonar {
+            _sm: sm.start(),
+            _pins: [self.trigger, self.echo],
+            rx,
+            tx,
+            next_ms: 0,
+        }
+    }
+}
+
+/// An HC-SR04 pinging every [`MEASURE_INTERVAL_MS`]
+pub struct Sonar<P: PIOExt, SM: StateMachineIndex> {
+    _sm: StateMachine<(P, SM), Running>,
+    _pins: [DataPin<P>; 2],
+    rx: Rx<(P, SM)>,
+    tx: Tx<(P, SM)>,
+    next_ms: u32,
+}
+
+impl<P: PIOExt, SM: StateMachineIndex> Sonar<P, SM> {
+    /// Ping when it's time; returns the last measurement's outcome once it
+    /// arrives, `Some(None)` for nothing in range
+    pub fn poll(&mut self, now_ms: u32) -> Option<Option<u16>> {
+        let echo = self.rx.read().map(echo_to_mm);
+        if (now_ms.wrapping_sub(self.next_ms) as i32) >= 0 {
+            self.next_ms = now_ms.wrapping_add(MEASURE_INTERVAL_MS);
+            // A sensor that never answers leaves the request queued
+            self.tx.write(0);
+        }
+        echo
+    }
+}
+
+/// Echo length in microseconds to distance in millimetres, `None` out of
+/// range; sound goes 0.343 mm/µs, there and back
+fn echo_to_mm(us: u32) -> Option<u16> {
+    let mm = us as u64 * 343 / 2000;
+    (mm <= MAX_RANGE_MM as u64).then_some(mm as u16)
+}
+
+/// The nearest thing the sensor sees, shared with the effects following it
+#[derive(Debug, Default)]
+pub struct Proximity {
+    distance_mm: Cell<Option<u16>>,
+}
+
+impl Proximity {
+    pub const fn new() -> Self {
+        Self {
+            distance_mm: Cell::new(None),
+        }
+    }
+
+    /// Millimetres to the nearest object, `None` if nothing is in range
+    pub fn distance_mm(&self) -> Option<u16> {
+        self.distance_mm.get()
+    }
+
+    pub fn set_distance_mm(&self, mm: Option<u16>) {
+        self.distance_mm.set(mm);
+    }
+}
@@ -0,0 +1,129 @@
+// WS2812 over the hardware SPI, for boards or builds without a free PIO block
+
+use crate::platform::hal::{
+    dma::SingleChannel,
+    fugit::RateExtU32,
+    pac::RESETS,
+    spi::{Disabled, Enabled, Spi, SpiDevice, ValidSpiPinout},
+};
+
+use crate::driver::{Buffer, DmaStream, Drain};
+use crate::output::LedOutput;
+use crate::pio_programs::Rgb;
+
+/// SPI bit rate: three SPI bits per 800 kHz WS2812 bit
+pub const SPI_BIT_RATE_HZ: u32 = 3 * 800_000;
+
+/// SPI symbol for a one bit: high for two thirds of the period
+const ONE: u32 = 0b110;
+
+/// SPI symbol for a zero bit: high for one third of the period
+const ZERO: u32 = 0b100;
+
+/// Encoded bytes per 24-bit pixel
+const BYTES_PER_PIXEL: usize = 9;
+
+/// Zero bytes after the pixels: the 50μs reset plus 20μs of slack
+const RESET_BYTES: usize = (70 * SPI_BIT_RATE_HZ as usize / 1_000_000).div_ceil(8);
+
+/// Frame buffer bytes needed for `leds` pixels
+pub const fn frame_bytes(leds: usize) -> usize {
+    leds * BYTES_PER_PIXEL + RESET_BYTES
+}
+
+/// Spread one color byte over three SPI bytes, MSB first
+fn encode_byte(c: u8) -> [u8; 3] {
+    let bits = (0..8).rev().fold(0, |acc, bit| {
+        acc << 3 | if c >> bit & 1 == 1 { ONE } else { ZERO }
+    });
+    let [_, a, b, c] = u32::to_be_bytes(bits);
+    [a, b, c]
+}
+
+type EnabledSpi<D, P> = Spi<Enabled, D, P, 8>;
+
+impl<D: SpiDevice, P: ValidSpiPinout<D>> Drain for EnabledSpi<D, P> {
+    fn is_drained(&self) -> bool {
+        !self.is_busy()
+    }
+}
+
+/// WS2812 strip on an SPI MOSI pin, fed by one DMA channel
+///
+/// Each WS2812 bit is sent as a 3-bit SPI symbol, so the frame buffer is
+/// nine bytes per pixel plus the reset gap; size it with [`frame_bytes`].
+/// Only MOSI is used, the clock pin can be left unconnected.
+///
+/// ```ignore
+/// let spi = Spi::<_, _, _, 8>::new(pac.SPI0, (pins.gpio19.into_function(), pins.gpio18.into_function()));
+/// let frame = cortex_m::singleton!(: [u8; frame_bytes(8)] = [0; frame_bytes(8)]).unwrap();
+/// let mut ws = SpiWs2812::new(spi, &mut pac.RESETS, sys_clk_hz, dma.ch0, frame);
+/// ```
+pub struct SpiWs2812<D: SpiDevice, P: ValidSpiPinout<D>, CH: SingleChannel, const N: usize> {
+    max_leds: usize,
+    stream: DmaStream<CH, EnabledSpi<D, P>, N>,
+}
+
+impl<D: SpiDevice, P: ValidSpiPinout<D>, CH: SingleChannel, const N: usize> SpiWs2812<D, P, CH, N> {
+    /// Bring up `spi` at the WS2812 symbol rate; `peri_clk_hz` is the
+    /// peripheral clock, the system clock unless reconfigured
+    pub fn new(
+        spi: Spi<Disabled, D, P, 8>,
+        resets: &mut RESETS,
+        peri_clk_hz: u32,
+        ch: CH,
+        frame: Buffer<u8, N>,
+    ) -> Self {
+        let spi = spi.init(
+            resets,
+            peri_clk_hz.Hz(),
+            SPI_BIT_RATE_HZ.Hz(),
+            embedded_hal::spi::MODE_0,
+        );
+        Self {
+            max_leds: N.saturating_sub(RESET_BYTES) / BYTES_PER_PIXEL,
+            stream: DmaStream::new(ch, frame, spi),
+        }
+    }
+
+    /// Whether a frame is still being shifted out
+    pub fn is_busy(&self) -> bool {
+        self.stream.is_busy()
+    }
+
+    /// Start sending `pixels` to the strip
+    ///
+    /// Waits for the previous frame first; the reset gap is part of the
+    /// buffer, so no extra delay is needed. Pixels beyond the buffer are
+    /// ignored and missing ones are sent as black.
+    pub fn write_frame(&mut self, pixels: &[Rgb]) {
+        self.write_pixels(pixels.iter().copied());
+    }
+
+    fn write_pixels(&mut self, mut pixels: impl Iterator<Item = Rgb>) {
+        let max_leds = self.max_leds;
+        self.stream.send(0, |frame| {
+            let (data, reset) = frame.split_at_mut(max_leds * BYTES_PER_PIXEL);
+            for chunk in data.chunks_exact_mut(BYTES_PER_PIXEL) {
+                let led = pixels.next().unwrap_or(Rgb::BLACK);
+                // WS2812 takes green first
+                for (out, c) in chunk.chunks_exact_mut(3).zip([led.g, led.r, led.b]) {
+                    out.copy_from_slice(&encode_byte(c));
+                }
+            }
+            reset.fill(0);
+        });
+    }
+}
+
+impl<D: SpiDevice, P: ValidSpiPinout<D>, CH: SingleChannel, const N: usize> LedOutput
+    for SpiWs2812<D, P, CH, N>
+{
+    fn write_pixels(&mut self, pixels: impl Iterator<Item = Rgb>) {
+        self.write_pixels(pixels);
+    }
+
+    fn is_busy(&self) -> bool {
+        self.is_busy()
+    }
+}
@@ -0,0 +1,239 @@
+// Wear-leveled store: small records appended to a ring of flash sectors,
+// so frequent saves don't keep erasing the same one
+
+use crate::flash::{self, PAGE_SIZE, SECTOR_SIZE, STORE_OFFSET, STORE_SECTORS};
+use crate::packet::crc16;
+
+/// The key [`Settings`](crate::settings::Settings) are kept under
+pub const SETTINGS_KEY: u8 = 0;
+
+/// The first of the presets' keys, one a slot from here
+pub const PRESET_KEYS: u8 = 1;
+
+/// Longest value a record holds
+pub const MAX_VALUE: usize = 64;
+
+/// Starts a sector that's in use, and marks the layout's version; a
+/// sequence number follows, counting the sectors taken
+const MAGIC: [u8; 4] = *b"PDJ1";
+const HEADER_LEN: usize = 8;
+
+/// Key and value length before the value, CRC-16 after it
+const OVERHEAD: usize = 4;
+
+/// Erased flash; no key is 0xFF, so it ends a sector's records
+const BLANK: u8 = 0xFF;
+
+const SECTOR_LEN: usize = SECTOR_SIZE as usize;
+
+/// The latest value under `key`, if it's ever been written
+pub fn read(key: u8) -> Option<&'static [u8]> {
+    let (index, _) = active()?;
+    latest(sector(index), key).map(value)
+}
+
+/// Keep `value` under `key`, unless it's there already
+///
+/// The record goes after the last in the sector in use, programming a page
+/// or two. When that sector is full it moves on to the next, round the
+/// ring: the next is erased and starts with the latest record for every
+/// key, then this one, and the header goes on last, so until it's whole
+/// the old sector is still the one read. Only one save in a few hundred
+/// erases anything, and the erases go round all [`STORE_SECTORS`]. The
+/// latest records of all the keys together must fit in a sector.
+///
+/// # Panics
+///
+/// If `key` is 0xFF or `value` is longer than [`MAX_VALUE`].
+pub fn write(key: u8, value: &[u8]) {
+    assert!(key != BLANK && value.len() <= MAX_VALUE);
+    if read(key) == Some(value) {
+        return;
+    }
+    let mut buf = [BLANK; OVERHEAD + MAX_VALUE];
+    let len = OVERHEAD + value.len();
+    buf[0] = key;
+    buf[1] = value.len() as u8;
+    buf[2..len - 2].copy_from_slice(value);
+    let crc = crc16(&buf[..len - 2]);
+    buf[len - 2..len].copy_from_slice(&crc.to_le_bytes());
+    let record = &buf[..len];
+
+    if let Some((index, _)) = active() {
+        let at = end(sector(index));
+        // A torn write may have left bits programmed past the last record
+        // it can see; only blank flash takes a record as written
+        let blank = |at: usize| sector(index)[at..at + len].iter().all(|&b| b == BLANK);
+        if at + len <= SECTOR_LEN && blank(at) {
+            append(index, at, record);
+            if sector(index)[at..at + len] == *record {
+                return;
+            }
+        }
+    }
+    compact(record);
+}
+
+/// Forget every key, erasing all [`STORE_SECTORS`], for a factory reset
+pub fn clear() {
+    flash::erase(STORE_OFFSET, STORE_SECTORS * SECTOR_SIZE);
+}
+
+fn offset(index: u32) -> u32 {
+    STORE_OFFSET + index * SECTOR_SIZE
+}
+
+fn sector(index: u32) -> &'static [u8] {
+    flash::read(offset(index), SECTOR_LEN)
+}
+
+/// The sector being written, and its sequence number: the latest whole
+/// header
+fn active() -> Option<(u32, u32)> {
+    (0..STORE_SECTORS)
+        .filter_map(|index| {
+            let header = &sector(index)[..HEADER_LEN];
+            let sequence = u32::from_le_bytes(header[4..].try_into().unwrap());
+            (header[..4] == MAGIC).then_some((index, sequence))
+        })
+        .max_by_key(|&(_, sequence)| sequence)
+}
+
+/// The records in a sector, key and value length first and CRC last; None
+/// for one that's damaged
+struct Records {
+    sector: &'static [u8],
+    at: usize,
+}
+
+impl Records {
+    fn new(sector: &'static [u8]) -> Self {
+        Self {
+            sector,
+            at: HEADER_LEN,
+        }
+    }
+}
+
+impl Iterator for Records {
+    type Item = Option<&'static [u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = *self.sector.get(self.at)?;
+        if key == BLANK {
+            return None;
+        }
+        let len = *self.sector.get(self.at + 1)? as usize;
+        let end = self.at + OVERHEAD + len;
+        if len > MAX_VALUE || end > SECTOR_LEN {
+            // No telling where the next record starts, so the sector's
+            // full as far as writing goes
+            self.at = SECTOR_LEN;
+            return None;
+        }
+        let record = &self.sector[self.at..end];
+        self.at = end;
+        let (body, crc) = record.split_at(record.len() - 2);
+        Some((crc16(body).to_le_bytes() == crc).then_some(record))
+    }
+}
+
+fn value(record: &'static [u8]) -> &'static [u8] {
+    &record[2..record.len() - 2]
+}
+
+fn latest(sector: &'static [u8], key: u8) -> Option<&'static [u8]> {
+    Records::new(sector)
+        .flatten()
+        .filter(|record| record[0] == key)
+        .last()
+}
+
+/// Where the next record goes
+fn end(sector: &'static [u8]) -> usize {
+    let mut records = Records::new(sector);
+    records.by_ref().for_each(drop);
+    records.at
+}
+
+/// Program `record` at `at` in a sector, 0xFF around it in its pages
+fn append(index: u32, at: usize, record: &[u8]) {
+    let first = at / PAGE_SIZE * PAGE_SIZE;
+    let mut pages = [BLANK; 2 * PAGE_SIZE];
+    pages[at - first..at - first + record.len()].copy_from_slice(record);
+    let len = (at - first + record.len()).div_ceil(PAGE_SIZE) * PAGE_SIZE;
+    flash::program(offset(index) + first as u32, &pages[..len]);
+}
+
+/// Take the next sector round: the latest of every other key, then
+/// `record`, then the header
+fn compact(record: &[u8]) {
+    let from = active();
+    let (index, sequence) = match from {
+        Some((index, sequence)) => ((index + 1) % STORE_SECTORS, sequence.wrapping_add(1)),
+        None => (0, 0),
+    };
+    flash::erase(offset(index), SECTOR_SIZE);
+
+    let mut out = PageWriter::new(offset(index));
+    out.push(&[BLANK; HEADER_LEN]);
+    if let Some((old, _)) = from {
+        let mut done = [false; 256];
+        done[record[0] as usize] = true;
+        for key in Records::new(sector(old)).flatten().map(|record| record[0]) {
+            if !core::mem::replace(&mut done[key as usize], true) {
+                out.push(latest(sector(old), key).unwrap());
+            }
+        }
+    }
+    out.push(record);
+    out.finish();
+
+    let mut header = [BLANK; PAGE_SIZE];
+    header[..4].copy_from_slice(&MAGIC);
+    header[4..HEADER_LEN].copy_from_slice(&sequence.to_le_bytes());
+    flash::program(offset(index), &header);
+}
+
+/// Programs a sector a page at a time, from its start
+struct PageWriter {
+    offset: u32,
+    page: [u8; PAGE_SIZE],
+    len: usize,
+}
+
+impl PageWriter {
+    fn new(offset: u32) -> Self {
+        Self {
+            offset,
+            page: [BLANK; PAGE_SIZE],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            assert!(self.len < SECTOR_LEN, "store records don't fit a sector");
+            self.page[self.len % PAGE_SIZE] = byte;
+            self.len += 1;
+            if self.len.is_multiple_of(PAGE_SIZE) {
+                self.flush();
+            }
+        }
+    }
+
+    /// Program the page being filled, if any of it is
+    fn finish(mut self) {
+        if !self.len.is_multiple_of(PAGE_SIZE) {
+            self.len = self.len.next_multiple_of(PAGE_SIZE);
+            self.flush();
+        }
+    }
+
+    /// Program the page that ends at `len`
+    fn flush(&mut self) {
+        let start = (self.len - PAGE_SIZE) as u32;
+        flash::program(self.offset + start, &self.page);
+        self.page = [BLANK; PAGE_SIZE];
+    }
+}
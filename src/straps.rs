@@ -0,0 +1,55 @@
+// Strap pins: jumpers read once at boot, so one build can be set up on the
+// bench without a host
+
+use core::convert::Infallible;
+
+use embedded_hal::digital::v2::InputPin;
+
+use crate::pio_programs::ColorOrder;
+
+/// What the jumpers were set to at boot
+///
+/// Each strap is a pin pulled up, and a jumper to ground sets it. Two pins
+/// pick a strip length from a bank of three, none jumpered leaving the
+/// saved length be; one switches the channel order to RGB, for strips
+/// wired that way rather than as the chipset has it; one starts demo mode.
+///
+/// ```ignore
+/// let straps = Straps::read([&bank0, &bank1], &rgb, &demo);
+/// let leds = straps.leds(&[30, 60, 144]).unwrap_or(settings.leds);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, defmt::Format)]
+pub struct Straps {
+    /// 1-3 for a length in the bank, 0 for none
+    pub bank: u8,
+    pub rgb: bool,
+    /// Step through the effects on a timer
+    pub demo: bool,
+}
+
+impl Straps {
+    /// Read the pins, already pulled up and given time to settle
+    pub fn read(
+        bank: [&dyn InputPin<Error = Infallible>; 2],
+        rgb: &dyn InputPin<Error = Infallible>,
+        demo: &dyn InputPin<Error = Infallible>,
+    ) -> Self {
+        let jumpered = |pin: &dyn InputPin<Error = Infallible>| pin.is_low().unwrap();
+        Self {
+            bank: jumpered(bank[0]) as u8 | (jumpered(bank[1]) as u8) << 1,
+            rgb: jumpered(rgb),
+            demo: jumpered(demo),
+        }
+    }
+
+    /// The strip length the bank straps pick from `lengths`, if any
+    pub fn leds(&self, lengths: &[u16; 3]) -> Option<u16> {
+        let index = self.bank.checked_sub(1)?;
+        lengths.get(index as usize).copied()
+    }
+
+    /// The channel order the straps set, if any
+    pub fn color_order(&self) -> Option<ColorOrder> {
+        self.rgb.then_some(ColorOrder::Rgb)
+    }
+}
@@ -0,0 +1,99 @@
+// Fixed-length LED strip pixel storage
+
+use core::ops::{Bound, Deref, DerefMut, RangeBounds};
+
+use crate::pio_programs::Rgb;
+
+/// The pixels of one strip, with its length as a type parameter
+///
+/// Derefs to `[Rgb]`, so slice iterators and indexing work as usual; the
+/// helpers below ignore out-of-range positions instead of panicking.
+#[derive(Copy, Clone, Debug)]
+pub struct LedStrip<const N: usize> {
+    pixels: [Rgb; N],
+}
+
+impl<const N: usize> LedStrip<N> {
+    /// Number of LEDs on the strip
+    pub const LEN: usize = N;
+
+    /// A strip with every LED off
+    pub const fn new() -> Self {
+        Self {
+            pixels: [Rgb::BLACK; N],
+        }
+    }
+
+    /// Set LED `i`; out-of-range indices are ignored
+    pub fn set(&mut self, i: usize, color: Rgb) {
+        if let Some(led) = self.pixels.get_mut(i) {
+            *led = color;
+        }
+    }
+
+    /// Set every LED to `color`
+    pub fn fill(&mut self, color: Rgb) {
+        self.pixels.fill(color);
+    }
+
+    /// Set the LEDs in `range` to `color`, clipped to the strip length
+    pub fn fill_range(&mut self, range: impl RangeBounds<usize>, color: Rgb) {
+        let start = match range.start_bound() {
+            Bound::Included(&s) => s,
+            Bound::Excluded(&s) => s + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&e) => e + 1,
+            Bound::Excluded(&e) => e,
+            Bound::Unbounded => N,
+        };
+        let end = end.min(N);
+        if start < end {
+            self.pixels[start..end].fill(color);
+        }
+    }
+
+    /// Turn every LED off
+    pub fn clear(&mut self) {
+        self.fill(Rgb::BLACK);
+    }
+}
+
+impl<const N: usize> Default for LedStrip<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Deref for LedStrip<N> {
+    type Target = [Rgb];
+
+    fn deref(&self) -> &[Rgb] {
+        &self.pixels
+    }
+}
+
+impl<const N: usize> DerefMut for LedStrip<N> {
+    fn deref_mut(&mut self) -> &mut [Rgb] {
+        &mut self.pixels
+    }
+}
+
+impl<'a, const N: usize> IntoIterator for &'a LedStrip<N> {
+    type Item = &'a Rgb;
+    type IntoIter = core::slice::Iter<'a, Rgb>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.pixels.iter()
+    }
+}
+
+impl<'a, const N: usize> IntoIterator for &'a mut LedStrip<N> {
+    type Item = &'a mut Rgb;
+    type IntoIter = core::slice::IterMut<'a, Rgb>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.pixels.iter_mut()
+    }
+}
@@ -0,0 +1,177 @@
+// Thermal dimming from the RP2040's on-chip temperature sensor
+
+use embedded_hal::adc::{Channel, OneShot};
+
+/// How often [`ThermalLimiter`] takes a reading
+pub const SAMPLE_MS: u32 = 1000;
+
+/// How far the temperature must fall back before the state steps down, in
+/// millidegrees
+const HYSTERESIS_MC: i32 = 2000;
+
+/// Convert a 12-bit reading of the temperature sensor to millidegrees C
+///
+/// From the datasheet: 27 °C reads 0.706 V, falling 1.721 mV per degree.
+/// Assumes the ADC reference is 3.3 V.
+pub fn adc_to_millicelsius(raw: u16) -> i32 {
+    let microvolts = raw as i32 * 3_300_000 / 4096;
+    27_000 - (microvolts - 706_000) * 1000 / 1721
+}
+
+/// The die temperature sensor, on ADC channel 4
+///
+/// ```ignore
+/// let mut adc = Adc::new(pac.ADC, &mut pac.RESETS);
+/// let mut die = DieTemperature::new(adc.take_temp_sensor().unwrap());
+/// let mc = die.read(&mut adc);
+/// ```
+pub struct DieTemperature<S> {
+    sensor: S,
+}
+
+impl<S> DieTemperature<S> {
+    pub fn new(sensor: S) -> Self {
+        Self { sensor }
+    }
+
+    /// Temperature in millidegrees C
+    pub fn read<ADC, A>(&mut self, adc: &mut A) -> Option<i32>
+    where
+        S: Channel<ADC>,
+        A: OneShot<ADC, u16, S>,
+    {
+        nb::block!(adc.read(&mut self.sensor))
+            .ok()
+            .map(adc_to_millicelsius)
+    }
+
+    pub fn free(self) -> S {
+        self.sensor
+    }
+}
+
+/// How hard the limiter is holding brightness back
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, defmt::Format)]
+pub enum ThrottleState {
+    /// Below `start_mc`, no limit
+    Normal,
+    /// Between `start_mc` and `max_mc`, brightness scaled down as it warms
+    Throttling,
+    /// At or past `max_mc`, held at `min_level`
+    Hot,
+}
+
+/// Caps brightness as the enclosure heats up
+///
+/// Dense strips in a closed box get hot enough to shorten LED life, and the
+/// Pico's die temperature follows the box's. Up to `start_mc` nothing
+/// happens; from there the cap falls linearly to `min_level` at `max_mc`.
+/// The die runs a few degrees over the air around it, so set the
+/// thresholds with that in mind. Feed the result to
+/// [`Engine::set_dimmer`](crate::engine::Engine::set_dimmer).
+///
+/// ```ignore
+/// let mut limiter = ThermalLimiter::new(50_000, 70_000);
+/// if limiter.due(now_ms) {
+///     if let Some(state) = die.read(&mut adc).and_then(|mc| limiter.update(mc)) {
+///         info!("Thermal {}", state);
+///     }
+/// }
+/// engine.set_dimmer(limiter.level());
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct ThermalLimiter {
+    pub start_mc: i32,
+    pub max_mc: i32,
+    pub min_level: u8,
+    /// Running average of the readings
+    filtered: Option<i32>,
+    state: ThrottleState,
+    next_ms: u32,
+}
+
+impl ThermalLimiter {
+    pub const fn new(start_mc: i32, max_mc: i32) -> Self {
+        Self {
+            start_mc,
+            max_mc,
+            min_level: 32,
+            filtered: None,
+            state: ThrottleState::Normal,
+            next_ms: 0,
+        }
+    }
+
+    pub fn min_level(mut self, level: u8) -> Self {
+        self.min_level = level;
+        self
+    }
+
+    /// Whether it's time for another reading
+    pub fn due(&mut self, now_ms: u32) -> bool {
+        if (now_ms.wrapping_sub(self.next_ms) as i32) < 0 {
+            return false;
+        }
+        self.next_ms = now_ms.wrapping_add(SAMPLE_MS);
+        true
+    }
+
+    /// Take in a reading in millidegrees; returns the new state if it changed
+    pub fn update(&mut self, mc: i32) -> Option<ThrottleState> {
+        let filtered = match self.filtered {
+            Some(f) => f + (mc - f) / 8,
+            None => mc,
+        };
+        self.filtered = Some(filtered);
+
+        let heated = self.classify(filtered);
+        let cooled = self.classify(filtered + HYSTERESIS_MC);
+        let state = if heated > self.state {
+            heated
+        } else if cooled < self.state {
+            cooled
+        } else {
+            self.state
+        };
+        if state == self.state {
+            return None;
+        }
+        self.state = state;
+        Some(state)
+    }
+
+    fn classify(&self, mc: i32) -> ThrottleState {
+        if mc >= self.max_mc {
+            ThrottleState::Hot
+        } else if mc > self.start_mc {
+            ThrottleState::Throttling
+        } else {
+            ThrottleState::Normal
+        }
+    }
+
+    pub fn state(&self) -> ThrottleState {
+        self.state
+    }
+
+    /// Smoothed temperature in millidegrees, if there's been a reading
+    pub fn temperature(&self) -> Option<i32> {
+        self.filtered
+    }
+
+    /// Brightness cap for the temperature so far
+    pub fn level(&self) -> u8 {
+        let Some(mc) = self.filtered else {
+            return 255;
+        };
+        if mc <= self.start_mc {
+            return 255;
+        }
+        if mc >= self.max_mc {
+            return self.min_level;
+        }
+        let span = (255 - self.min_level) as i32;
+        let over = (mc - self.start_mc) * span / (self.max_mc - self.start_mc).max(1);
+        (255 - over) as u8
+    }
+}
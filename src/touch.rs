@@ -0,0 +1,101 @@
+// Capacitive touch pads by charge-time measurement on a plain GPIO
+
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+
+use crate::button::{ButtonEvent, Presses};
+
+/// Give up counting after this many loops, for a pad that never charges
+pub const MAX_COUNT: u32 = 20_000;
+
+/// Readings averaged per poll
+const SAMPLES: u32 = 4;
+
+/// A bare wire or foil pad on a GPIO, used as a button
+///
+/// The pin is pulled low to empty the pad, then released and pulled up; a
+/// finger adds capacitance, so it takes longer to read high. A baseline
+/// follows the untouched charge time, so slow drift from temperature and
+/// humidity isn't mistaken for a touch.
+///
+/// The pin must both drive low and read back, e.g. the HAL's open-drain
+/// `InOutPin`. The internal pull-up works for short wires; a 1 MΩ resistor
+/// to 3V3 instead makes for longer, easier to measure charge times.
+///
+/// ```ignore
+/// let mut pad = TouchPad::new(InOutPin::new(pins.gpio21.into_pull_up_input()));
+/// if pad.poll(now_ms) == Some(ButtonEvent::Short) {
+///     toggle_strip();
+/// }
+/// ```
+pub struct TouchPad<P> {
+    pin: P,
+    /// Untouched charge time, with 4 fraction bits
+    baseline: Option<u32>,
+    /// How much longer than the baseline counts as a touch, in percent
+    threshold_percent: u32,
+    presses: Presses,
+}
+
+impl<P: InputPin + OutputPin> TouchPad<P> {
+    pub fn new(pin: P) -> Self {
+        Self {
+            pin,
+            baseline: None,
+            threshold_percent: 25,
+            presses: Presses::new(),
+        }
+    }
+
+    /// Raise for noisy setups, lower for pads behind thicker covers
+    pub fn threshold_percent(mut self, percent: u8) -> Self {
+        self.threshold_percent = percent as u32;
+        self
+    }
+
+    /// One charge time, in busy-loop counts
+    ///
+    /// Runs with interrupts off, a few microseconds for an untouched pad.
+    pub fn measure(&mut self) -> u32 {
+        critical_section::with(|_| {
+            let _ = self.pin.set_low();
+            // Long enough to drain the pad through the pin driver
+            cortex_m::asm::delay(200);
+            let _ = self.pin.set_high();
+
+            let mut count = 0;
+            while count < MAX_COUNT && matches!(self.pin.is_low(), Ok(true)) {
+                count += 1;
+            }
+            count
+        })
+    }
+
+    /// Debounced touch state
+    pub fn is_pressed(&self) -> bool {
+        self.presses.is_pressed()
+    }
+
+    /// See [`Presses::is_held`]
+    pub fn is_held(&self) -> bool {
+        self.presses.is_held()
+    }
+
+    /// Measure the pad; touches are reported like button presses
+    pub fn poll(&mut self, now_ms: u32) -> Option<ButtonEvent> {
+        let count = (0..SAMPLES).map(|_| self.measure()).sum::<u32>() / SAMPLES;
+        let baseline = *self.baseline.get_or_insert(count << 4);
+
+        let base = baseline >> 4;
+        // At least a couple of counts, short charge times are mostly noise
+        let touched = count > base + (base * self.threshold_percent / 100).max(2);
+        if !touched {
+            // Track drift slowly while nobody is touching
+            self.baseline = Some(baseline - baseline / 32 + (count << 4) / 32);
+        }
+        self.presses.update(touched, now_ms)
+    }
+
+    pub fn free(self) -> P {
+        self.pin
+    }
+}
@@ -0,0 +1,112 @@
+// TPM2 serial frames, as Jinx!, Glediator and other LED sequencers send them
+
+use crate::host::StreamEvent;
+use crate::pio_programs::Rgb;
+
+/// First byte of every packet
+pub const TPM2_START: u8 = 0xC9;
+/// Last byte of every packet
+pub const TPM2_END: u8 = 0x36;
+
+/// Packet types
+pub const TPM2_DATA: u8 = 0xDA;
+pub const TPM2_COMMAND: u8 = 0xC0;
+pub const TPM2_RESPONSE: u8 = 0xAA;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    Idle,
+    Type,
+    SizeHigh(u8),
+    SizeLow(u8, u8),
+    /// Packet type, and payload bytes still to come
+    Payload(u8, usize),
+    End(u8),
+}
+
+/// Picks TPM2 packets out of a serial stream
+///
+/// A packet is [`TPM2_START`], a type byte, the payload size as a
+/// big-endian 16-bit number, the payload, and [`TPM2_END`]. Data packets
+/// carry three bytes of RGB per LED; those for more LEDs than `N` have the
+/// rest dropped. Command packets are read past and ignored.
+///
+/// TPM2 has no checksum: a packet whose end byte isn't where its size says
+/// is dropped, and that's the only check.
+///
+/// ```ignore
+/// if let StreamEvent::Frame(pixels) = tpm2.push(byte) {
+///     host_frame.copy_from(pixels);
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct Tpm2Reader<const N: usize> {
+    state: State,
+    pixels: [Rgb; N],
+    /// Payload bytes so far
+    received: usize,
+    partial: [u8; 3],
+}
+
+impl<const N: usize> Tpm2Reader<N> {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Idle,
+            pixels: [Rgb::BLACK; N],
+            received: 0,
+            partial: [0; 3],
+        }
+    }
+
+    /// Whether a packet has started and every byte until its end is claimed
+    pub fn in_frame(&self) -> bool {
+        self.state != State::Idle
+    }
+
+    pub fn push(&mut self, byte: u8) -> StreamEvent<'_> {
+        self.state = match self.state {
+            State::Idle if byte == TPM2_START => State::Type,
+            State::Idle => return StreamEvent::Pass,
+            State::Type => State::SizeHigh(byte),
+            State::SizeHigh(kind) => State::SizeLow(kind, byte),
+            State::SizeLow(kind, high) => {
+                self.received = 0;
+                match u16::from_be_bytes([high, byte]) as usize {
+                    0 => State::End(kind),
+                    size => State::Payload(kind, size),
+                }
+            }
+            State::Payload(kind, left) => {
+                if kind == TPM2_DATA {
+                    self.partial[self.received % 3] = byte;
+                    if self.received % 3 == 2 {
+                        if let Some(pixel) = self.pixels.get_mut(self.received / 3) {
+                            let [r, g, b] = self.partial;
+                            *pixel = Rgb::new(r, g, b);
+                        }
+                    }
+                }
+                self.received += 1;
+                match left - 1 {
+                    0 => State::End(kind),
+                    left => State::Payload(kind, left),
+                }
+            }
+            State::End(kind) => {
+                self.state = State::Idle;
+                if byte == TPM2_END && kind == TPM2_DATA {
+                    let count = (self.received / 3).min(N);
+                    return StreamEvent::Frame(&self.pixels[..count]);
+                }
+                return StreamEvent::Taken;
+            }
+        };
+        StreamEvent::Taken
+    }
+}
+
+impl<const N: usize> Default for Tpm2Reader<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
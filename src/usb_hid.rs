@@ -0,0 +1,204 @@
+// Vendor-defined USB HID interface for driverless control from a host
+
+use usb_device::class_prelude::*;
+use usb_device::Result;
+
+use crate::pio_programs::Rgb;
+
+/// Feature report IDs, each with its data bytes after the ID
+///
+/// - [`REPORT_BRIGHTNESS`]: master brightness, 0-255
+/// - [`REPORT_EFFECT`]: running effect, and on reads how many there are
+/// - [`REPORT_COLOR`]: the running effect's primary color as R, G, B
+pub const REPORT_BRIGHTNESS: u8 = 1;
+pub const REPORT_EFFECT: u8 = 2;
+pub const REPORT_COLOR: u8 = 3;
+
+const USB_CLASS_HID: u8 = 0x03;
+const HID_DESCRIPTOR: u8 = 0x21;
+const REPORT_DESCRIPTOR: u8 = 0x22;
+
+const REQ_GET_DESCRIPTOR: u8 = 0x06;
+const REQ_GET_REPORT: u8 = 0x01;
+const REQ_GET_IDLE: u8 = 0x02;
+const REQ_SET_REPORT: u8 = 0x09;
+const REQ_SET_IDLE: u8 = 0x0A;
+const REQ_SET_PROTOCOL: u8 = 0x0B;
+
+/// Report type in the high byte of GET/SET_REPORT's value
+const FEATURE_REPORT: u8 = 3;
+
+/// Vendor page 0xFF00, one application collection of three feature reports
+#[rustfmt::skip]
+const REPORT_DESCRIPTOR_BYTES: &[u8] = &[
+    0x06, 0x00, 0xFF, // Usage Page (Vendor 0xFF00)
+    0x09, 0x01,       // Usage (1)
+    0xA1, 0x01,       // Collection (Application)
+    0x15, 0x00,       //   Logical Minimum (0)
+    0x26, 0xFF, 0x00, //   Logical Maximum (255)
+    0x75, 0x08,       //   Report Size (8)
+    0x85, REPORT_BRIGHTNESS,
+    0x09, 0x02,       //   Usage (2)
+    0x95, 0x01,       //   Report Count (1)
+    0xB1, 0x02,       //   Feature (Data, Var, Abs)
+    0x85, REPORT_EFFECT,
+    0x09, 0x03,       //   Usage (3)
+    0x95, 0x02,       //   Report Count (2)
+    0xB1, 0x02,       //   Feature (Data, Var, Abs)
+    0x85, REPORT_COLOR,
+    0x09, 0x04,       //   Usage (4)
+    0x95, 0x03,       //   Report Count (3)
+    0xB1, 0x02,       //   Feature (Data, Var, Abs)
+    0xC0,             // End Collection
+];
+
+/// What the device reports back when the host reads
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct HidStatus {
+    pub brightness: u8,
+    pub effect: u8,
+    pub effects: u8,
+    pub color: Rgb,
+}
+
+/// A change the host asked for by writing a feature report
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum HidRequest {
+    Brightness(u8),
+    Effect(u8),
+    Color(Rgb),
+}
+
+/// A vendor HID interface taking brightness, effect and color as feature
+/// reports
+///
+/// HID needs no driver on any desktop OS and, unlike a serial port, can be
+/// opened by several programs at once (hidapi's `send_feature_report` and
+/// `get_feature_report`). Reads answer from the last [`HidStatus`] given
+/// to [`set_status`](Self::set_status); writes are kept, the latest of
+/// each kind, until [`take_request`](Self::take_request) hands them over.
+///
+/// ```ignore
+/// usb_dev.poll(&mut [&mut hid]);
+/// while let Some(request) = hid.take_request() {
+///     // apply it
+/// }
+/// hid.set_status(status);
+/// ```
+pub struct HidControl<'a, B: UsbBus> {
+    interface: InterfaceNumber,
+    /// HID requires one; nothing is ever sent on it
+    ep_in: EndpointIn<'a, B>,
+    status: HidStatus,
+    /// Latest write of each report, by ID less one
+    requests: [Option<HidRequest>; 3],
+}
+
+impl<'a, B: UsbBus> HidControl<'a, B> {
+    pub fn new(alloc: &'a UsbBusAllocator<B>) -> Self {
+        Self {
+            interface: alloc.interface(),
+            ep_in: alloc.interrupt(8, 100),
+            status: HidStatus {
+                brightness: 255,
+                effect: 0,
+                effects: 0,
+                color: Rgb::BLACK,
+            },
+            requests: [None; 3],
+        }
+    }
+
+    pub fn set_status(&mut self, status: HidStatus) {
+        self.status = status;
+    }
+
+    /// The next change the host wrote, oldest report ID first
+    pub fn take_request(&mut self) -> Option<HidRequest> {
+        self.requests.iter_mut().find_map(Option::take)
+    }
+
+    fn is_ours(&self, req: &control::Request) -> bool {
+        req.recipient == control::Recipient::Interface
+            && req.index == u8::from(self.interface) as u16
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for HidControl<'_, B> {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> Result<()> {
+        writer.interface(self.interface, USB_CLASS_HID, 0x00, 0x00)?;
+        let [len_low, len_high] = (REPORT_DESCRIPTOR_BYTES.len() as u16).to_le_bytes();
+        writer.write(
+            HID_DESCRIPTOR,
+            // HID 1.11, no country, one report descriptor
+            &[0x11, 0x01, 0x00, 0x01, REPORT_DESCRIPTOR, len_low, len_high],
+        )?;
+        writer.endpoint(&self.ep_in)?;
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.requests = [None; 3];
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        let req = *xfer.request();
+        if !self.is_ours(&req) {
+            return;
+        }
+        let [id, kind] = req.value.to_le_bytes();
+        let _ = match (req.request_type, req.request) {
+            (control::RequestType::Standard, REQ_GET_DESCRIPTOR) if kind == REPORT_DESCRIPTOR => {
+                xfer.accept_with_static(REPORT_DESCRIPTOR_BYTES)
+            }
+            (control::RequestType::Class, REQ_GET_REPORT) if kind == FEATURE_REPORT => {
+                let status = self.status;
+                match id {
+                    REPORT_BRIGHTNESS => xfer.accept_with(&[id, status.brightness]),
+                    REPORT_EFFECT => xfer.accept_with(&[id, status.effect, status.effects]),
+                    REPORT_COLOR => {
+                        xfer.accept_with(&[id, status.color.r, status.color.g, status.color.b])
+                    }
+                    _ => xfer.reject(),
+                }
+            }
+            // Idle rate 0, only report on change; there are no input reports
+            (control::RequestType::Class, REQ_GET_IDLE) => xfer.accept_with(&[0]),
+            (control::RequestType::Class, _) => xfer.reject(),
+            _ => Ok(()),
+        };
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        let req = *xfer.request();
+        if !self.is_ours(&req) || req.request_type != control::RequestType::Class {
+            return;
+        }
+        let [_, kind] = req.value.to_le_bytes();
+        let _ = match req.request {
+            REQ_SET_REPORT if kind == FEATURE_REPORT => {
+                // The report ID leads the data
+                let request = match *xfer.data() {
+                    [REPORT_BRIGHTNESS, level, ..] => Some(HidRequest::Brightness(level)),
+                    [REPORT_EFFECT, effect, ..] => Some(HidRequest::Effect(effect)),
+                    [REPORT_COLOR, r, g, b, ..] => Some(HidRequest::Color(Rgb::new(r, g, b))),
+                    _ => None,
+                };
+                match request {
+                    Some(request) => {
+                        let slot = match request {
+                            HidRequest::Brightness(_) => 0,
+                            HidRequest::Effect(_) => 1,
+                            HidRequest::Color(_) => 2,
+                        };
+                        self.requests[slot] = Some(request);
+                        xfer.accept()
+                    }
+                    None => xfer.reject(),
+                }
+            }
+            REQ_SET_IDLE | REQ_SET_PROTOCOL => xfer.accept(),
+            _ => xfer.reject(),
+        };
+    }
+}
@@ -0,0 +1,148 @@
+// defmt logging out a USB serial port, for devices without a debug probe
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Encoded log bytes kept until a host reads them
+const LOG_BUFFER: usize = 4096;
+
+/// The defmt global logger, in place of defmt-rtt
+///
+/// Log frames are kept in a buffer until [`drain`] hands them on, normally
+/// to a second [`UsbSerial`](crate::usb_serial::UsbSerial) port. When the
+/// buffer fills the oldest whole frames go, so a host that connects late
+/// still sees the most recent logs and `defmt-print` never gets half a
+/// frame.
+#[defmt::global_logger]
+struct UsbLogger;
+
+static LOGGER: LogState = LogState::new();
+
+/// A ring of encoded frames
+struct Ring {
+    buf: [u8; LOG_BUFFER],
+    head: usize,
+    len: usize,
+    /// Where the frame being written starts, as a length from `head`
+    frame_start: usize,
+    /// The frame being written didn't fit and will be taken back
+    overflow: bool,
+}
+
+impl Ring {
+    fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.len == LOG_BUFFER && !self.drop_oldest() {
+                self.overflow = true;
+                return;
+            }
+            self.buf[(self.head + self.len) % LOG_BUFFER] = byte;
+            self.len += 1;
+        }
+    }
+
+    /// Drop the oldest complete frame, up to and including its final 0x00;
+    /// false if there's none before the frame being written
+    fn drop_oldest(&mut self) -> bool {
+        let Some(end) =
+            (0..self.frame_start).find(|&i| self.buf[(self.head + i) % LOG_BUFFER] == 0)
+        else {
+            return false;
+        };
+        self.head = (self.head + end + 1) % LOG_BUFFER;
+        self.len -= end + 1;
+        self.frame_start -= end + 1;
+        true
+    }
+}
+
+struct LogState {
+    /// Set between `acquire` and `release`
+    taken: AtomicBool,
+    cs_restore: UnsafeCell<critical_section::RestoreState>,
+    encoder: UnsafeCell<defmt::Encoder>,
+    ring: UnsafeCell<Ring>,
+}
+
+// Safety: the cells are only touched inside a critical section
+unsafe impl Sync for LogState {}
+
+impl LogState {
+    const fn new() -> Self {
+        Self {
+            taken: AtomicBool::new(false),
+            cs_restore: UnsafeCell::new(critical_section::RestoreState::invalid()),
+            encoder: UnsafeCell::new(defmt::Encoder::new()),
+            ring: UnsafeCell::new(Ring {
+                buf: [0; LOG_BUFFER],
+                head: 0,
+                len: 0,
+                frame_start: 0,
+                overflow: false,
+            }),
+        }
+    }
+}
+
+unsafe impl defmt::Logger for UsbLogger {
+    fn acquire() {
+        // Safety: paired with the release in `release`
+        let restore = unsafe { critical_section::acquire() };
+        if LOGGER.taken.load(Ordering::Relaxed) {
+            panic!("defmt logger taken reentrantly");
+        }
+        LOGGER.taken.store(true, Ordering::Relaxed);
+        // Safety: inside the critical section
+        unsafe {
+            LOGGER.cs_restore.get().write(restore);
+            let ring = &mut *LOGGER.ring.get();
+            ring.frame_start = ring.len;
+            (*LOGGER.encoder.get()).start_frame(|bytes| ring.push(bytes));
+        }
+    }
+
+    unsafe fn flush() {
+        // The host reads when it's ready; nothing to wait for
+    }
+
+    unsafe fn release() {
+        // Safety: `acquire` took the critical section
+        unsafe {
+            let ring = &mut *LOGGER.ring.get();
+            (*LOGGER.encoder.get()).end_frame(|bytes| ring.push(bytes));
+            if core::mem::take(&mut ring.overflow) {
+                ring.len = ring.frame_start;
+            }
+            LOGGER.taken.store(false, Ordering::Relaxed);
+            critical_section::release(LOGGER.cs_restore.get().read());
+        }
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        // Safety: `acquire` took the critical section
+        unsafe {
+            let ring = &mut *LOGGER.ring.get();
+            (*LOGGER.encoder.get()).write(bytes, |bytes| ring.push(bytes));
+        }
+    }
+}
+
+/// Hand the oldest waiting log bytes to `send`, which returns how many it
+/// took; call it until nothing's left or the port is full
+///
+/// ```ignore
+/// if log_port.dtr() {
+///     usb_log::drain(|bytes| log_port.write(bytes));
+/// }
+/// ```
+pub fn drain(send: impl FnOnce(&[u8]) -> usize) {
+    critical_section::with(|_| {
+        // Safety: inside a critical section, and no frame is being written,
+        // as that holds one too
+        let ring = unsafe { &mut *LOGGER.ring.get() };
+        let end = (ring.head + ring.len).min(LOG_BUFFER);
+        let sent = send(&ring.buf[ring.head..end]).min(end - ring.head);
+        ring.head = (ring.head + sent) % LOG_BUFFER;
+        ring.len -= sent;
+    });
+}
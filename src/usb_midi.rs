@@ -0,0 +1,164 @@
+// USB MIDI input, a class-compliant MIDI port with no driver needed
+
+use usb_device::class_prelude::*;
+use usb_device::Result;
+
+use crate::midi::MidiMessage;
+
+/// Bulk packet size: sixteen 4-byte MIDI events
+const MAX_PACKET: usize = 64;
+
+const USB_CLASS_AUDIO: u8 = 0x01;
+const AUDIO_SUBCLASS_CONTROL: u8 = 0x01;
+const AUDIO_SUBCLASS_MIDI_STREAMING: u8 = 0x03;
+
+const CS_INTERFACE: u8 = 0x24;
+const CS_ENDPOINT: u8 = 0x25;
+const HEADER: u8 = 0x01;
+const MIDI_IN_JACK: u8 = 0x02;
+const MIDI_OUT_JACK: u8 = 0x03;
+const MS_GENERAL: u8 = 0x01;
+const JACK_EMBEDDED: u8 = 0x01;
+const JACK_EXTERNAL: u8 = 0x02;
+
+/// The host's notes come in on the embedded IN jack and leave for the
+/// (notional) synth on the external OUT jack
+const IN_JACK_ID: u8 = 1;
+const OUT_JACK_ID: u8 = 2;
+
+/// MIDIStreaming descriptors after the interface: header, the two jacks,
+/// and the endpoint with its class-specific part
+const MS_TOTAL_LENGTH: u16 = 7 + 6 + 9 + 9 + 5;
+
+/// A MIDI input port, one MIDIStreaming interface with an OUT endpoint
+///
+/// Shows up as a MIDI device to DAWs and controllers' software without any
+/// driver. Only what the host sends is read; nothing goes back.
+///
+/// ```ignore
+/// usb_dev.poll(&mut [&mut midi_port]);
+/// while let Some(message) = midi_port.read() {
+///     // note on, note off, control change
+/// }
+/// ```
+pub struct UsbMidi<'a, B: UsbBus> {
+    control_if: InterfaceNumber,
+    streaming_if: InterfaceNumber,
+    read_ep: EndpointOut<'a, B>,
+    buf: [u8; MAX_PACKET],
+    /// Unread events in `buf`, `pos..len`
+    pos: usize,
+    len: usize,
+}
+
+impl<'a, B: UsbBus> UsbMidi<'a, B> {
+    pub fn new(alloc: &'a UsbBusAllocator<B>) -> Self {
+        Self {
+            control_if: alloc.interface(),
+            streaming_if: alloc.interface(),
+            read_ep: alloc.bulk(MAX_PACKET as u16),
+            buf: [0; MAX_PACKET],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// The next message from the host, fetching another packet when the
+    /// last one is used up
+    pub fn read(&mut self) -> Option<MidiMessage> {
+        loop {
+            if self.pos + 4 > self.len {
+                self.len = self.read_ep.read(&mut self.buf).unwrap_or(0);
+                self.pos = 0;
+                if self.len < 4 {
+                    return None;
+                }
+            }
+            let event = [
+                self.buf[self.pos],
+                self.buf[self.pos + 1],
+                self.buf[self.pos + 2],
+                self.buf[self.pos + 3],
+            ];
+            self.pos += 4;
+            if let Some(message) = MidiMessage::from_usb_packet(event) {
+                return Some(message);
+            }
+        }
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for UsbMidi<'_, B> {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> Result<()> {
+        writer.iad(
+            self.control_if,
+            2,
+            USB_CLASS_AUDIO,
+            AUDIO_SUBCLASS_CONTROL,
+            0x00,
+        )?;
+
+        // Audio control, with nothing to control but required to hold the
+        // streaming interface
+        writer.interface(
+            self.control_if,
+            USB_CLASS_AUDIO,
+            AUDIO_SUBCLASS_CONTROL,
+            0x00,
+        )?;
+        writer.write(
+            CS_INTERFACE,
+            // ADC 1.0, 9 bytes long, one streaming interface
+            &[
+                HEADER,
+                0x00,
+                0x01,
+                0x09,
+                0x00,
+                0x01,
+                self.streaming_if.into(),
+            ],
+        )?;
+
+        writer.interface(
+            self.streaming_if,
+            USB_CLASS_AUDIO,
+            AUDIO_SUBCLASS_MIDI_STREAMING,
+            0x00,
+        )?;
+        let [total_low, total_high] = MS_TOTAL_LENGTH.to_le_bytes();
+        writer.write(CS_INTERFACE, &[HEADER, 0x00, 0x01, total_low, total_high])?;
+        writer.write(
+            CS_INTERFACE,
+            &[MIDI_IN_JACK, JACK_EMBEDDED, IN_JACK_ID, 0x00],
+        )?;
+        writer.write(
+            CS_INTERFACE,
+            // One input pin, from pin 1 of the IN jack
+            &[
+                MIDI_OUT_JACK,
+                JACK_EXTERNAL,
+                OUT_JACK_ID,
+                0x01,
+                IN_JACK_ID,
+                0x01,
+                0x00,
+            ],
+        )?;
+        // Audio class endpoints carry bRefresh and bSynchAddress, both 0
+        writer.endpoint_ex(&self.read_ep, |extra| {
+            if extra.len() < 2 {
+                return Err(UsbError::BufferOverflow);
+            }
+            extra[..2].fill(0);
+            Ok(2)
+        })?;
+        writer.write(CS_ENDPOINT, &[MS_GENERAL, 0x01, IN_JACK_ID])?;
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.pos = 0;
+        self.len = 0;
+    }
+}
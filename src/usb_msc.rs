@@ -0,0 +1,382 @@
+// USB mass storage: a drive on the host's desktop, over Bulk-Only
+// Transport and the SCSI commands operating systems send a USB stick
+
+use usb_device::class_prelude::*;
+use usb_device::Result;
+
+/// The one block size there is
+pub const BLOCK_SIZE: usize = 512;
+
+const MAX_PACKET: usize = 64;
+
+const USB_CLASS_MSC: u8 = 0x08;
+const MSC_SUBCLASS_SCSI: u8 = 0x06;
+const MSC_PROTOCOL_BOT: u8 = 0x50;
+
+const REQ_GET_MAX_LUN: u8 = 0xFE;
+const REQ_BOT_RESET: u8 = 0xFF;
+
+/// Command and status wrappers, each with its signature first
+const CBW_SIGNATURE: u32 = 0x4342_5355;
+const CBW_LEN: usize = 31;
+const CSW_SIGNATURE: u32 = 0x5342_5355;
+const CSW_LEN: usize = 13;
+
+const CSW_PASSED: u8 = 0;
+const CSW_FAILED: u8 = 1;
+const CSW_PHASE_ERROR: u8 = 2;
+
+const TEST_UNIT_READY: u8 = 0x00;
+const REQUEST_SENSE: u8 = 0x03;
+const INQUIRY: u8 = 0x12;
+const MODE_SENSE_6: u8 = 0x1A;
+const START_STOP_UNIT: u8 = 0x1B;
+const PREVENT_ALLOW_MEDIUM_REMOVAL: u8 = 0x1E;
+const READ_FORMAT_CAPACITIES: u8 = 0x23;
+const READ_CAPACITY_10: u8 = 0x25;
+const READ_10: u8 = 0x28;
+const WRITE_10: u8 = 0x2A;
+const VERIFY_10: u8 = 0x2F;
+const SYNCHRONIZE_CACHE_10: u8 = 0x35;
+const MODE_SENSE_10: u8 = 0x5A;
+
+/// Sense key, additional sense code and qualifier, for REQUEST SENSE to
+/// explain the last failure
+type Sense = (u8, u8, u8);
+const NO_SENSE: Sense = (0x00, 0x00, 0x00);
+const MEDIUM_NOT_PRESENT: Sense = (0x02, 0x3A, 0x00);
+const INVALID_COMMAND: Sense = (0x05, 0x20, 0x00);
+const LBA_OUT_OF_RANGE: Sense = (0x05, 0x21, 0x00);
+
+/// Direct-access, removable, SPC-2, then the vendor, product and revision
+/// a host shows for the drive
+const INQUIRY_DATA: [u8; 36] = *b"\x00\x80\x04\x02\x1f\x00\x00\x00segfaultpico-display    1.0 ";
+
+/// What the drive reads and writes, a [`BLOCK_SIZE`] block at a time
+pub trait BlockDevice {
+    /// How many blocks there are
+    fn blocks(&self) -> u32;
+
+    fn read_block(&mut self, lba: u32, block: &mut [u8; BLOCK_SIZE]);
+
+    fn write_block(&mut self, lba: u32, block: &[u8; BLOCK_SIZE]);
+
+    /// Whether there's a medium for the host to read
+    fn is_present(&self) -> bool;
+
+    /// The host ejected the medium, or loaded it again; a USB reset loads
+    /// it too, so a replugged drive comes back
+    fn set_present(&mut self, present: bool);
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Stage {
+    /// Waiting for a command wrapper
+    Command,
+    /// Sending the reply in `buf`, then any blocks from `lba`
+    DataIn,
+    /// Taking blocks to write at `lba`, and discarding any beyond them
+    DataOut,
+    /// Sending the status wrapper
+    Status,
+}
+
+/// A single-LUN USB drive over any [`BlockDevice`]
+///
+/// It answers the commands Linux, macOS and Windows need to mount and use
+/// a removable drive: identify, capacity, read, write and eject. A data
+/// stage the host asked for that doesn't match the command's is cut short
+/// or read and discarded rather than stalled, which the Bulk-Only spec
+/// allows and every host copes with.
+///
+/// ```ignore
+/// let mut msc = MassStorage::new(&usb_bus, disk);
+/// usb_dev.poll(&mut [&mut msc]);
+/// msc.disk_mut().poll(now_ms);
+/// ```
+pub struct MassStorage<'a, B: UsbBus, D: BlockDevice> {
+    interface: InterfaceNumber,
+    read_ep: EndpointOut<'a, B>,
+    write_ep: EndpointIn<'a, B>,
+    disk: D,
+    stage: Stage,
+    tag: u32,
+    /// Bytes of the data stage the host asked for still to go
+    residue: u32,
+    status: u8,
+    sense: Sense,
+    /// The block range a read or write still has to cover
+    lba: u32,
+    blocks: u32,
+    buf: [u8; BLOCK_SIZE],
+    buf_len: usize,
+    buf_pos: usize,
+    /// The data stage's packets so far were all full, so a short end
+    /// needs a zero-length one
+    last_full: bool,
+}
+
+impl<'a, B: UsbBus, D: BlockDevice> MassStorage<'a, B, D> {
+    pub fn new(alloc: &'a UsbBusAllocator<B>, disk: D) -> Self {
+        Self {
+            interface: alloc.interface(),
+            read_ep: alloc.bulk(MAX_PACKET as u16),
+            write_ep: alloc.bulk(MAX_PACKET as u16),
+            disk,
+            stage: Stage::Command,
+            tag: 0,
+            residue: 0,
+            status: CSW_PASSED,
+            sense: NO_SENSE,
+            lba: 0,
+            blocks: 0,
+            buf: [0; BLOCK_SIZE],
+            buf_len: 0,
+            buf_pos: 0,
+            last_full: true,
+        }
+    }
+
+    pub fn disk(&self) -> &D {
+        &self.disk
+    }
+
+    pub fn disk_mut(&mut self) -> &mut D {
+        &mut self.disk
+    }
+
+    /// Take a command wrapper, if one's come
+    fn command(&mut self) -> Result<()> {
+        let mut cbw = [0; MAX_PACKET];
+        let len = self.read_ep.read(&mut cbw)?;
+        let word = |at: usize| u32::from_le_bytes(cbw[at..at + 4].try_into().unwrap());
+        if len != CBW_LEN || word(0) != CBW_SIGNATURE {
+            // Not a command; wait for the host to reset and send one
+            return Ok(());
+        }
+        self.tag = word(4);
+        self.residue = word(8);
+        let data_in = cbw[12] & 0x80 != 0;
+        let cb = &cbw[15..31];
+
+        self.buf_len = 0;
+        self.buf_pos = 0;
+        self.blocks = 0;
+        self.last_full = true;
+        let (status, wants_in) = self.scsi(cb);
+        self.status = status;
+        self.stage = match (self.residue, data_in) {
+            (0, _) => Stage::Status,
+            (_, true) => Stage::DataIn,
+            (_, false) => Stage::DataOut,
+        };
+        // The host's data stage goes the other way from the command's
+        if self.residue > 0 && wants_in.is_some_and(|wants_in| wants_in != data_in) {
+            self.status = CSW_PHASE_ERROR;
+            self.blocks = 0;
+            self.buf_len = 0;
+        }
+        Ok(())
+    }
+
+    /// Carry out a command block, leaving any reply in `buf` and any block
+    /// range in `lba` and `blocks`; the status and which way the command's
+    /// data goes, None for no data
+    fn scsi(&mut self, cb: &[u8]) -> (u8, Option<bool>) {
+        let block_count = self.disk.blocks();
+        let present = self.disk.is_present();
+        let lba = u32::from_be_bytes(cb[2..6].try_into().unwrap());
+        let count = u16::from_be_bytes([cb[7], cb[8]]) as u32;
+        let needs_medium = matches!(
+            cb[0],
+            TEST_UNIT_READY | READ_CAPACITY_10 | READ_10 | WRITE_10 | VERIFY_10
+        );
+        if needs_medium && !present {
+            self.sense = MEDIUM_NOT_PRESENT;
+            return (CSW_FAILED, None);
+        }
+        let mut reply = |data: &[u8], allocation: usize| {
+            let len = data.len().min(allocation);
+            self.buf[..len].copy_from_slice(&data[..len]);
+            self.buf_len = len;
+            (CSW_PASSED, Some(true))
+        };
+        match cb[0] {
+            TEST_UNIT_READY | PREVENT_ALLOW_MEDIUM_REMOVAL | VERIFY_10 | SYNCHRONIZE_CACHE_10 => {
+                (CSW_PASSED, None)
+            }
+            REQUEST_SENSE => {
+                let (key, asc, ascq) = core::mem::replace(&mut self.sense, NO_SENSE);
+                let sense = [
+                    0x70, 0, key, 0, 0, 0, 0, 10, 0, 0, 0, 0, asc, ascq, 0, 0, 0, 0,
+                ];
+                reply(&sense, cb[4] as usize)
+            }
+            INQUIRY => reply(&INQUIRY_DATA, u16::from_be_bytes([cb[3], cb[4]]) as usize),
+            // No mode pages, and not write-protected
+            MODE_SENSE_6 => reply(&[3, 0, 0, 0], cb[4] as usize),
+            MODE_SENSE_10 => reply(
+                &[0, 6, 0, 0, 0, 0, 0, 0],
+                u16::from_be_bytes([cb[7], cb[8]]) as usize,
+            ),
+            READ_CAPACITY_10 => {
+                let mut capacity = [0; 8];
+                capacity[..4].copy_from_slice(&(block_count - 1).to_be_bytes());
+                capacity[4..].copy_from_slice(&(BLOCK_SIZE as u32).to_be_bytes());
+                reply(&capacity, capacity.len())
+            }
+            READ_FORMAT_CAPACITIES => {
+                let mut list = [0, 0, 0, 8, 0, 0, 0, 0, 0x02, 0, 0, 0];
+                list[4..8].copy_from_slice(&block_count.to_be_bytes());
+                list[9..].copy_from_slice(&(BLOCK_SIZE as u32).to_be_bytes()[1..]);
+                reply(&list, u16::from_be_bytes([cb[7], cb[8]]) as usize)
+            }
+            START_STOP_UNIT => {
+                let (load_eject, start) = (cb[4] & 0x02 != 0, cb[4] & 0x01 != 0);
+                if load_eject {
+                    self.disk.set_present(start);
+                }
+                (CSW_PASSED, None)
+            }
+            READ_10 | WRITE_10 if lba.checked_add(count).is_none_or(|end| end > block_count) => {
+                self.sense = LBA_OUT_OF_RANGE;
+                (CSW_FAILED, Some(cb[0] == READ_10))
+            }
+            READ_10 | WRITE_10 => {
+                self.lba = lba;
+                self.blocks = count;
+                (CSW_PASSED, Some(cb[0] == READ_10))
+            }
+            _ => {
+                self.sense = INVALID_COMMAND;
+                (CSW_FAILED, None)
+            }
+        }
+    }
+
+    /// Send the reply and blocks, then a zero-length packet if it ended
+    /// short of what the host asked for on a packet boundary
+    fn data_in(&mut self) -> Result<()> {
+        loop {
+            if self.buf_pos == self.buf_len && self.blocks > 0 && self.residue > 0 {
+                self.disk.read_block(self.lba, &mut self.buf);
+                self.lba += 1;
+                self.blocks -= 1;
+                self.buf_len = BLOCK_SIZE;
+                self.buf_pos = 0;
+            }
+            if self.buf_pos == self.buf_len || self.residue == 0 {
+                if self.residue > 0 && self.last_full {
+                    self.write_ep.write(&[])?;
+                }
+                self.stage = Stage::Status;
+                return Ok(());
+            }
+            let len = MAX_PACKET
+                .min(self.buf_len - self.buf_pos)
+                .min(self.residue as usize);
+            self.write_ep
+                .write(&self.buf[self.buf_pos..self.buf_pos + len])?;
+            self.buf_pos += len;
+            self.residue -= len as u32;
+            self.last_full = len == MAX_PACKET;
+        }
+    }
+
+    /// Take what the host sends, writing whole blocks as they fill
+    fn data_out(&mut self) -> Result<()> {
+        while self.residue > 0 {
+            let mut packet = [0; MAX_PACKET];
+            let len = self.read_ep.read(&mut packet)?.min(self.residue as usize);
+            self.residue -= len as u32;
+            if self.blocks == 0 {
+                continue;
+            }
+            let len = len.min(BLOCK_SIZE - self.buf_pos);
+            self.buf[self.buf_pos..self.buf_pos + len].copy_from_slice(&packet[..len]);
+            self.buf_pos += len;
+            if self.buf_pos == BLOCK_SIZE {
+                self.disk.write_block(self.lba, &self.buf);
+                self.lba += 1;
+                self.blocks -= 1;
+                self.buf_pos = 0;
+            }
+        }
+        self.stage = Stage::Status;
+        Ok(())
+    }
+
+    fn send_status(&mut self) -> Result<()> {
+        let mut csw = [0; CSW_LEN];
+        csw[..4].copy_from_slice(&CSW_SIGNATURE.to_le_bytes());
+        csw[4..8].copy_from_slice(&self.tag.to_le_bytes());
+        csw[8..12].copy_from_slice(&self.residue.to_le_bytes());
+        csw[12] = self.status;
+        self.write_ep.write(&csw)?;
+        self.stage = Stage::Command;
+        Ok(())
+    }
+
+    fn is_ours(&self, req: &control::Request) -> bool {
+        req.request_type == control::RequestType::Class
+            && req.recipient == control::Recipient::Interface
+            && req.index == u8::from(self.interface) as u16
+    }
+}
+
+impl<B: UsbBus, D: BlockDevice> UsbClass<B> for MassStorage<'_, B, D> {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> Result<()> {
+        writer.interface(
+            self.interface,
+            USB_CLASS_MSC,
+            MSC_SUBCLASS_SCSI,
+            MSC_PROTOCOL_BOT,
+        )?;
+        writer.endpoint(&self.read_ep)?;
+        writer.endpoint(&self.write_ep)?;
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.stage = Stage::Command;
+        self.sense = NO_SENSE;
+        self.disk.set_present(true);
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        let req = *xfer.request();
+        if self.is_ours(&req) && req.request == REQ_GET_MAX_LUN {
+            let _ = xfer.accept_with(&[0]);
+        }
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        let req = *xfer.request();
+        if self.is_ours(&req) && req.request == REQ_BOT_RESET {
+            self.stage = Stage::Command;
+            let _ = xfer.accept();
+        }
+    }
+
+    fn poll(&mut self) {
+        loop {
+            let result = match self.stage {
+                Stage::Command => {
+                    let result = self.command();
+                    if self.stage == Stage::Command {
+                        return;
+                    }
+                    result
+                }
+                Stage::DataIn => self.data_in(),
+                Stage::DataOut => self.data_out(),
+                Stage::Status => self.send_status(),
+            };
+            // Mostly WouldBlock, waiting on the host; the next packet it
+            // sends or takes polls again
+            if result.is_err() {
+                return;
+            }
+        }
+    }
+}
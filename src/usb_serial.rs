@@ -0,0 +1,215 @@
+// USB CDC-ACM virtual serial port, shows up as /dev/ttyACM* or a COM port
+
+use usb_device::class_prelude::*;
+use usb_device::Result;
+
+/// Bulk packet size, and the least `read` needs to be given
+pub const MAX_PACKET: usize = 64;
+
+/// Bytes queued for the host before `write` starts turning them away; room
+/// for the shell's help
+const TX_BUFFER: usize = 1024;
+
+const USB_CLASS_CDC: u8 = 0x02;
+const USB_CLASS_CDC_DATA: u8 = 0x0A;
+const CDC_SUBCLASS_ACM: u8 = 0x02;
+const CDC_PROTOCOL_NONE: u8 = 0x00;
+
+const CS_INTERFACE: u8 = 0x24;
+const CDC_TYPE_HEADER: u8 = 0x00;
+const CDC_TYPE_CALL_MANAGEMENT: u8 = 0x01;
+const CDC_TYPE_ACM: u8 = 0x02;
+const CDC_TYPE_UNION: u8 = 0x06;
+
+const REQ_SET_LINE_CODING: u8 = 0x20;
+const REQ_GET_LINE_CODING: u8 = 0x21;
+const REQ_SET_CONTROL_LINE_STATE: u8 = 0x22;
+const REQ_SEND_BREAK: u8 = 0x23;
+
+/// 115200 baud, 1 stop bit, no parity, 8 data bits: what the port reports
+/// until the host sets something else. The baud rate means nothing over
+/// USB, but hosts like to read it back.
+const DEFAULT_LINE_CODING: [u8; 7] = [0x00, 0xC2, 0x01, 0x00, 0, 0, 8];
+
+/// A USB serial port: one CDC-ACM function with a byte stream each way
+///
+/// Give it to the `UsbDevice` poll with the rest of the device's classes;
+/// reads come straight from the endpoint, and writes are queued and sent
+/// as the host takes them, a packet per poll. Writes beyond the queue are
+/// dropped, so a host that isn't reading can't hold up the firmware.
+///
+/// ```ignore
+/// let mut serial = UsbSerial::new(&usb_bus);
+/// let mut usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x16c0, 0x27dd))
+///     .composite_with_iads()
+///     .build();
+/// if usb_dev.poll(&mut [&mut serial]) {
+///     let mut packet = [0; MAX_PACKET];
+///     let count = serial.read(&mut packet);
+///     serial.write(&packet[..count]);
+/// }
+/// ```
+pub struct UsbSerial<'a, B: UsbBus> {
+    comm_if: InterfaceNumber,
+    data_if: InterfaceNumber,
+    /// Serial state notifications; never sent, but ACM wants the endpoint
+    comm_ep: EndpointIn<'a, B>,
+    read_ep: EndpointOut<'a, B>,
+    write_ep: EndpointIn<'a, B>,
+    line_coding: [u8; 7],
+    dtr: bool,
+    tx: [u8; TX_BUFFER],
+    tx_len: usize,
+}
+
+impl<'a, B: UsbBus> UsbSerial<'a, B> {
+    pub fn new(alloc: &'a UsbBusAllocator<B>) -> Self {
+        Self {
+            comm_if: alloc.interface(),
+            data_if: alloc.interface(),
+            comm_ep: alloc.interrupt(8, 255),
+            read_ep: alloc.bulk(MAX_PACKET as u16),
+            write_ep: alloc.bulk(MAX_PACKET as u16),
+            line_coding: DEFAULT_LINE_CODING,
+            dtr: false,
+            tx: [0; TX_BUFFER],
+            tx_len: 0,
+        }
+    }
+
+    /// Whether a program on the host has the port open (DTR set)
+    pub fn dtr(&self) -> bool {
+        self.dtr
+    }
+
+    /// Baud rate the host last set, for protocols that agree on one
+    pub fn baud_rate(&self) -> u32 {
+        u32::from_le_bytes([
+            self.line_coding[0],
+            self.line_coding[1],
+            self.line_coding[2],
+            self.line_coding[3],
+        ])
+    }
+
+    /// Read the next packet from the host into `buf`, at least
+    /// [`MAX_PACKET`] long; returns how many bytes came, 0 for none
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        self.read_ep.read(buf).unwrap_or(0)
+    }
+
+    /// Queue bytes for the host; returns how many fit
+    pub fn write(&mut self, data: &[u8]) -> usize {
+        let count = data.len().min(TX_BUFFER - self.tx_len);
+        self.tx[self.tx_len..self.tx_len + count].copy_from_slice(&data[..count]);
+        self.tx_len += count;
+        count
+    }
+
+    /// Send the next packet of queued bytes, if the endpoint is free
+    fn flush(&mut self) {
+        if self.tx_len == 0 {
+            return;
+        }
+        let count = self.tx_len.min(MAX_PACKET);
+        if let Ok(sent) = self.write_ep.write(&self.tx[..count]) {
+            self.tx.copy_within(sent..self.tx_len, 0);
+            self.tx_len -= sent;
+        }
+    }
+
+    /// A class request to the communications interface
+    fn is_ours(&self, req: &control::Request) -> bool {
+        req.request_type == control::RequestType::Class
+            && req.recipient == control::Recipient::Interface
+            && req.index == u8::from(self.comm_if) as u16
+    }
+}
+
+impl<B: UsbBus> core::fmt::Write for UsbSerial<'_, B> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        if self.write(s.as_bytes()) == s.len() {
+            Ok(())
+        } else {
+            Err(core::fmt::Error)
+        }
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for UsbSerial<'_, B> {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> Result<()> {
+        writer.iad(
+            self.comm_if,
+            2,
+            USB_CLASS_CDC,
+            CDC_SUBCLASS_ACM,
+            CDC_PROTOCOL_NONE,
+        )?;
+
+        writer.interface(
+            self.comm_if,
+            USB_CLASS_CDC,
+            CDC_SUBCLASS_ACM,
+            CDC_PROTOCOL_NONE,
+        )?;
+        // CDC 1.10
+        writer.write(CS_INTERFACE, &[CDC_TYPE_HEADER, 0x10, 0x01])?;
+        // No call management, data on the data interface
+        writer.write(
+            CS_INTERFACE,
+            &[CDC_TYPE_CALL_MANAGEMENT, 0x00, self.data_if.into()],
+        )?;
+        // Supports the line coding and control line state requests
+        writer.write(CS_INTERFACE, &[CDC_TYPE_ACM, 0x02])?;
+        writer.write(
+            CS_INTERFACE,
+            &[CDC_TYPE_UNION, self.comm_if.into(), self.data_if.into()],
+        )?;
+        writer.endpoint(&self.comm_ep)?;
+
+        writer.interface(self.data_if, USB_CLASS_CDC_DATA, 0x00, 0x00)?;
+        writer.endpoint(&self.write_ep)?;
+        writer.endpoint(&self.read_ep)?;
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.line_coding = DEFAULT_LINE_CODING;
+        self.dtr = false;
+        self.tx_len = 0;
+    }
+
+    fn poll(&mut self) {
+        self.flush();
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        let req = *xfer.request();
+        if !self.is_ours(&req) {
+            return;
+        }
+        let _ = match req.request {
+            REQ_GET_LINE_CODING => xfer.accept_with(&self.line_coding),
+            _ => xfer.reject(),
+        };
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        let req = *xfer.request();
+        if !self.is_ours(&req) {
+            return;
+        }
+        let _ = match req.request {
+            REQ_SET_LINE_CODING if xfer.data().len() >= 7 => {
+                self.line_coding.copy_from_slice(&xfer.data()[..7]);
+                xfer.accept()
+            }
+            REQ_SET_CONTROL_LINE_STATE => {
+                self.dtr = req.value & 1 != 0;
+                xfer.accept()
+            }
+            REQ_SEND_BREAK => xfer.accept(),
+            _ => xfer.reject(),
+        };
+    }
+}
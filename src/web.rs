@@ -0,0 +1,37 @@
+// The control page, compiled into flash, and the routes of the device's HTTP
+// server
+
+use crate::http::{HttpError, Method, Request};
+use crate::wled::WledRoute;
+
+/// Power, brightness, color and effect, driven through the WLED API so the
+/// page needs nothing of its own on the device
+pub const INDEX_HTML: &str = include_str!("web/index.html");
+
+/// What a request to the server is for
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum WebRoute {
+    /// `GET /`: [`INDEX_HTML`]
+    Page,
+    Wled(WledRoute),
+    NotFound,
+}
+
+impl WebRoute {
+    pub fn find(request: &Request) -> Self {
+        match (request.method, request.path) {
+            (Method::Get, b"/" | b"/index.html") => WebRoute::Page,
+            (method, path) => {
+                WledRoute::find(method, path).map_or(WebRoute::NotFound, WebRoute::Wled)
+            }
+        }
+    }
+}
+
+/// The status to answer a request that couldn't be read with
+pub fn error_status(error: HttpError) -> u16 {
+    match error {
+        HttpError::BadRequest => 400,
+        HttpError::TooLarge => 413,
+    }
+}
@@ -0,0 +1,401 @@
+// WLED's JSON API, the core of it, so WLED apps and Home Assistant's WLED
+// integration can drive the strip
+
+use core::fmt;
+
+use crate::effects::{Effect, Param, ParamValue};
+use crate::http::Method;
+use crate::json::{for_each_element, for_each_member, JsonError, JsonValue};
+use crate::palette::{self, Palette16};
+use crate::pio_programs::Rgb;
+
+/// The WLED release whose API this answers as; Home Assistant won't take
+/// devices older than 0.14
+pub const WLED_VERSION: &str = "0.14.0";
+
+/// UDP port WLED devices sync over, reported in the info though not
+/// listened on
+const WLED_UDP_PORT: u16 = 21324;
+
+/// The palettes `pal` picks from, after 0, which leaves the effect's own
+pub const PALETTES: [(&str, &Palette16); 11] = [
+    ("Heat", &palette::HEAT),
+    ("Ocean", &palette::OCEAN),
+    ("Forest", &palette::FOREST),
+    ("Party", &palette::PARTY),
+    ("Pacifica 1", &palette::PACIFICA_1),
+    ("Pacifica 2", &palette::PACIFICA_2),
+    ("Pacifica 3", &palette::PACIFICA_3),
+    ("VU", &palette::VU),
+    ("Temperature", &palette::TEMPERATURE),
+    ("Storm", &palette::STORM),
+    ("Sunny", &palette::SUNNY),
+];
+
+/// What a request to the API is for
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum WledRoute {
+    /// `GET /json/state`: a [`WledState`]
+    State,
+    /// `POST /json/state` or `/json`: a [`WledUpdate`] to apply, answered
+    /// with the state if it asks for it, `{"success":true}` if not
+    Update,
+    /// `GET /json/info`: a [`WledInfo`]
+    Info,
+    /// `GET /json/eff`: [`EffectNames`]
+    Effects,
+    /// `GET /json/pal`: [`PaletteNames`]
+    Palettes,
+    /// `GET /json`: a [`WledDoc`], all of the above in one
+    All,
+}
+
+impl WledRoute {
+    /// The route for a request, None for paths the API doesn't have
+    pub fn find(method: Method, path: &[u8]) -> Option<Self> {
+        Some(match (method, path) {
+            (Method::Get, b"/json/state") => WledRoute::State,
+            (Method::Post, b"/json/state" | b"/json") => WledRoute::Update,
+            (Method::Get, b"/json/info") => WledRoute::Info,
+            (Method::Get, b"/json/eff") => WledRoute::Effects,
+            (Method::Get, b"/json/pal") => WledRoute::Palettes,
+            (Method::Get, b"/json") => WledRoute::All,
+            _ => return None,
+        })
+    }
+}
+
+/// How an update sets the power
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Power {
+    On,
+    Off,
+    /// `"on": "t"`
+    Toggle,
+}
+
+impl Power {
+    /// Whether the strip ends up on, from whether it was
+    pub fn apply(self, was_on: bool) -> bool {
+        match self {
+            Power::On => true,
+            Power::Off => false,
+            Power::Toggle => !was_on,
+        }
+    }
+}
+
+/// The changes a state update asks for
+///
+/// ```text
+/// {"on":true,"bri":128,"seg":[{"id":0,"fx":3,"sx":200,"col":[[255,0,0],[0,0,255]]}],"v":true}
+/// ```
+///
+/// The strip is one segment, id 0, so its `on` and `bri` are the strip's
+/// and other segments are ignored. Keys this doesn't handle (transitions,
+/// presets, nightlight, sync and so on) are ignored rather than refused, as
+/// apps send them whatever the device.
+///
+/// ```ignore
+/// let update = WledUpdate::parse(request.body)?;
+/// if let Some(effect) = update.effect {
+///     current = effect.min(effects.len() - 1);
+/// }
+/// // Parameters the effect doesn't have are left, as WLED's sliders are
+/// for (param, value) in update.params() {
+///     effects[current].set_param(param, value);
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, defmt::Format)]
+pub struct WledUpdate {
+    pub on: Option<Power>,
+    pub brightness: Option<u8>,
+    pub effect: Option<usize>,
+    pub speed: Option<u8>,
+    pub intensity: Option<u8>,
+    /// Index into [`PALETTES`] plus one; 0 asks for the effect's own, which
+    /// once changed can't be had back and is left as is
+    pub palette: Option<u8>,
+    pub primary: Option<Rgb>,
+    pub secondary: Option<Rgb>,
+    /// `"v": true`: reply with the state after the update
+    pub verbose: bool,
+}
+
+impl WledUpdate {
+    pub fn parse(body: &[u8]) -> Result<Self, JsonError> {
+        let mut update = Self::default();
+        for_each_member(body, |key, value| {
+            match key {
+                b"on" => update.on = Some(power(value)?),
+                b"bri" => update.brightness = Some(value.level()?),
+                b"v" => update.verbose = matches!(value, JsonValue::Bool(true)),
+                b"seg" => match value {
+                    JsonValue::Object(segment) => update.segment(segment, 0)?,
+                    JsonValue::Array(segments) => {
+                        // Without an id, a segment's place in the array is its id
+                        let mut index = 0;
+                        for_each_element(segments, |segment| {
+                            let JsonValue::Object(segment) = segment else {
+                                return Err(JsonError::WrongType);
+                            };
+                            update.segment(segment, index)?;
+                            index += 1;
+                            Ok(())
+                        })?;
+                    }
+                    _ => return Err(JsonError::WrongType),
+                },
+                _ => {}
+            }
+            Ok(())
+        })?;
+        Ok(update)
+    }
+
+    fn segment(&mut self, segment: &[u8], index: i32) -> Result<(), JsonError> {
+        let mut id = index;
+        for_each_member(segment, |key, value| {
+            if key == b"id" {
+                match value {
+                    JsonValue::Number(Some(n)) => id = n,
+                    _ => return Err(JsonError::WrongType),
+                }
+            }
+            Ok(())
+        })?;
+        if id != 0 {
+            return Ok(());
+        }
+        for_each_member(segment, |key, value| {
+            match key {
+                b"on" => self.on = Some(power(value)?),
+                b"bri" => self.brightness = Some(value.level()?),
+                b"fx" => match value {
+                    JsonValue::Number(Some(n)) => {
+                        self.effect = Some(usize::try_from(n).map_err(|_| JsonError::OutOfRange)?)
+                    }
+                    _ => return Err(JsonError::WrongType),
+                },
+                b"sx" => self.speed = Some(value.level()?),
+                b"ix" => self.intensity = Some(value.level()?),
+                b"pal" => {
+                    let id = value.level()?;
+                    if id as usize > PALETTES.len() {
+                        return Err(JsonError::OutOfRange);
+                    }
+                    self.palette = Some(id);
+                }
+                b"col" => {
+                    let JsonValue::Array(colors) = value else {
+                        return Err(JsonError::WrongType);
+                    };
+                    let mut slot = 0;
+                    for_each_element(colors, |color| {
+                        let color = wled_color(color)?;
+                        match slot {
+                            0 => self.primary = Some(color),
+                            1 => self.secondary = Some(color),
+                            _ => {}
+                        }
+                        slot += 1;
+                        Ok(())
+                    })?;
+                }
+                _ => {}
+            }
+            Ok(())
+        })
+    }
+
+    /// The parameter changes, for [`Effect::set_param`]
+    pub fn params(&self) -> impl Iterator<Item = (Param, ParamValue)> {
+        let palette = self
+            .palette
+            .and_then(|id| PALETTES.get((id as usize).checked_sub(1)?));
+        [
+            self.speed.map(|s| (Param::Speed, ParamValue::Level(s))),
+            self.intensity
+                .map(|i| (Param::Intensity, ParamValue::Level(i))),
+            palette.map(|&(_, p)| (Param::Palette, ParamValue::Palette(p))),
+            self.primary.map(|c| (Param::Primary, ParamValue::Color(c))),
+            self.secondary
+                .map(|c| (Param::Secondary, ParamValue::Color(c))),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+fn power(value: JsonValue) -> Result<Power, JsonError> {
+    match value {
+        JsonValue::Bool(true) => Ok(Power::On),
+        JsonValue::Bool(false) => Ok(Power::Off),
+        JsonValue::String(b"t") => Ok(Power::Toggle),
+        _ => Err(JsonError::WrongType),
+    }
+}
+
+/// A color as `[r,g,b]`, `[r,g,b,w]` with the white dropped, or hex
+fn wled_color(value: JsonValue) -> Result<Rgb, JsonError> {
+    let JsonValue::Array(channels) = value else {
+        return value.color();
+    };
+    let mut rgb = [0; 3];
+    let mut count = 0;
+    for_each_element(channels, |channel| {
+        let level = channel.level()?;
+        if let Some(c) = rgb.get_mut(count) {
+            *c = level;
+        }
+        count += 1;
+        Ok(())
+    })?;
+    if !(3..=4).contains(&count) {
+        return Err(JsonError::WrongType);
+    }
+    Ok(Rgb::new(rgb[0], rgb[1], rgb[2]))
+}
+
+fn write_rgb(f: &mut fmt::Formatter, color: Option<ParamValue>) -> fmt::Result {
+    let Rgb { r, g, b } = match color {
+        Some(ParamValue::Color(c)) => c,
+        _ => Rgb::BLACK,
+    };
+    write!(f, "[{r},{g},{b}]")
+}
+
+/// The state, written with `{}` as WLED's `/json/state`
+///
+/// Off is brightness 0 here, so `on` and the brightness to come back to
+/// are kept by whoever switches the strip off. Speed and intensity the
+/// running effect doesn't have read as WLED's default, 128.
+pub struct WledState<'a> {
+    pub on: bool,
+    /// The brightness when on, whether or not it is
+    pub brightness: u8,
+    pub effect: usize,
+    pub leds: usize,
+    pub running: &'a dyn Effect,
+}
+
+impl fmt::Display for WledState<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = |param| match self.running.param(param) {
+            Some(ParamValue::Level(level)) => level,
+            _ => 128,
+        };
+        let palette = match self.running.param(Param::Palette) {
+            Some(ParamValue::Palette(p)) => PALETTES
+                .iter()
+                .position(|&(_, candidate)| candidate == p)
+                .map_or(0, |i| i + 1),
+            _ => 0,
+        };
+        write!(
+            f,
+            "{{\"on\":{on},\"bri\":{bri},\"transition\":0,\"ps\":-1,\"pl\":-1,\
+             \"nl\":{{\"on\":false,\"dur\":60,\"mode\":1,\"tbri\":0}},\
+             \"udpn\":{{\"send\":false,\"recv\":false}},\"lor\":0,\"mainseg\":0,\
+             \"seg\":[{{\"id\":0,\"start\":0,\"stop\":{leds},\"len\":{leds},\"grp\":1,\
+             \"spc\":0,\"of\":0,\"on\":{on},\"frz\":false,\"bri\":{bri},\"cct\":127,\"col\":[",
+            on = self.on,
+            bri = self.brightness,
+            leds = self.leds,
+        )?;
+        write_rgb(f, self.running.param(Param::Primary))?;
+        f.write_str(",")?;
+        write_rgb(f, self.running.param(Param::Secondary))?;
+        write!(
+            f,
+            ",[0,0,0]],\"fx\":{},\"sx\":{},\"ix\":{},\"pal\":{palette},\"sel\":true,\
+             \"rev\":false,\"mi\":false}}]}}",
+            self.effect,
+            level(Param::Speed),
+            level(Param::Intensity),
+        )
+    }
+}
+
+/// The device, written with `{}` as WLED's `/json/info`
+pub struct WledInfo<'a> {
+    pub name: &'a str,
+    pub leds: usize,
+    pub fps: u32,
+    pub effects: usize,
+    pub uptime_s: u32,
+    pub ip: [u8; 4],
+    pub mac: [u8; 6],
+}
+
+impl fmt::Display for WledInfo<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [a, b, c, d] = self.ip;
+        write!(
+            f,
+            "{{\"ver\":\"{WLED_VERSION}\",\"vid\":2310130,\"leds\":{{\"count\":{},\
+             \"pwr\":0,\"fps\":{},\"maxpwr\":0,\"maxseg\":1,\"rgbw\":false,\"wv\":0,\"cct\":0}},\
+             \"str\":false,\"name\":\"{}\",\"udpport\":{WLED_UDP_PORT},\"live\":false,\
+             \"ws\":-1,\"fxcount\":{},\"palcount\":{},\
+             \"wifi\":{{\"bssid\":\"\",\"rssi\":0,\"signal\":0,\"channel\":0}},\
+             \"arch\":\"rp2040\",\"core\":\"\",\"freeheap\":0,\"uptime\":{},\"opt\":0,\
+             \"brand\":\"WLED\",\"product\":\"pico-display\",\"mac\":\"",
+            self.leds,
+            self.fps,
+            self.name,
+            self.effects,
+            PALETTES.len() + 1,
+            self.uptime_s,
+        )?;
+        for byte in self.mac {
+            write!(f, "{byte:02x}")?;
+        }
+        write!(f, "\",\"ip\":\"{a}.{b}.{c}.{d}\"}}")
+    }
+}
+
+/// `/json/eff`: effects have no names of their own, so they're numbered
+pub struct EffectNames(pub usize);
+
+impl fmt::Display for EffectNames {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("[")?;
+        for i in 0..self.0 {
+            let separator = if i == 0 { "" } else { "," };
+            write!(f, "{separator}\"Effect {i}\"")?;
+        }
+        f.write_str("]")
+    }
+}
+
+/// `/json/pal`, "Default" for 0 then [`PALETTES`]
+pub struct PaletteNames;
+
+impl fmt::Display for PaletteNames {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("[\"Default\"")?;
+        for (name, _) in PALETTES {
+            write!(f, ",\"{name}\"")?;
+        }
+        f.write_str("]")
+    }
+}
+
+/// `/json`: state, info, effects and palettes together
+pub struct WledDoc<'a> {
+    pub state: WledState<'a>,
+    pub info: WledInfo<'a>,
+}
+
+impl fmt::Display for WledDoc<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{{\"state\":{},\"info\":{},\"effects\":{},\"palettes\":{}}}",
+            self.state,
+            self.info,
+            EffectNames(self.info.effects),
+            PaletteNames
+        )
+    }
+}